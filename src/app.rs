@@ -1,29 +1,35 @@
 use crate::assets::ICON;
-use crate::render::RenderState;
+use crate::i18n::{t, tf};
+use crate::render::{NoCompatibleAdapter, RenderState};
 #[cfg(feature = "startup_animation")]
 use crate::state::StartupAnimation;
 use crate::state::{
-    AppState, CanvasObject, CanvasObjectOps, CanvasTool, PointerInteraction, PointerState,
+    AppState, CanvasObject, CanvasTool, Gesture, MOUSE_POINTER_ID, PersistentState,
+    PointerInteraction, PointerState, touch_pointer_id,
 };
 use crate::ui;
+use crate::utils::measure::{measure_drag_end, measure_drag_move, measure_drag_start};
+use crate::utils::select::{select_drag_end, select_drag_move, select_drag_start};
+use crate::utils::shape::{shape_drag_end, shape_drag_move, shape_drag_start};
 use crate::utils::stroke::{brush_stroke_add_point, brush_stroke_end, brush_stroke_start};
-use crate::utils::ui::{apply_theme_mode_and_canvas_color, apply_window_mode};
+use crate::utils::ui::{
+    apply_tessellation_quality, apply_theme_mode_and_canvas_color, apply_window_mode,
+};
 use crate::utils::{self, cursor_pos};
 use core::f32;
-use egui::{Pos2, Vec2};
+use egui::Pos2;
 use egui_wgpu::{ScreenDescriptor, wgpu};
 use image::GenericImageView;
+use std::collections::HashSet;
 use std::sync::Arc;
-use wgpu::{
-    BackendOptions, CurrentSurfaceTexture, InstanceDescriptor, TexelCopyBufferInfo,
-    TexelCopyBufferLayout,
-};
-use wgpu::{InstanceFlags, TexelCopyTextureInfo};
+use std::time::{Duration, Instant};
+use wgpu::InstanceFlags;
+use wgpu::{BackendOptions, CurrentSurfaceTexture, InstanceDescriptor};
 use winit::application::ApplicationHandler;
 use winit::dpi::PhysicalPosition;
 use winit::event::{KeyEvent, Touch, TouchPhase, WindowEvent};
-use winit::event_loop::ActiveEventLoop;
-use winit::keyboard::{Key, NamedKey};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoopProxy};
+use winit::keyboard::{Key, KeyCode, NamedKey, PhysicalKey};
 use winit::window::{Window, WindowId};
 
 pub struct App {
@@ -31,11 +37,21 @@ pub struct App {
     render_state: Option<RenderState>,
     window: Option<Arc<Window>>,
     state: AppState,
+    last_redraw: Instant,
+    redraw_deferred: bool,      // 因帧率上限推迟的重绘
+    palm_touches: HashSet<u64>, // 被手掌拒绝忽略的触点
+    surface_failures: u32,      // 连续获取交换链纹理失败的次数
 }
 
+// 连续获取交换链纹理失败达到此次数时重建显卡设备
+const MAX_SURFACE_FAILURES: u32 = 3;
+
 impl App {
-    pub fn new() -> Self {
+    pub fn new(event_loop_proxy: EventLoopProxy<()>) -> Self {
         let mut state = AppState::default();
+        state.board_transfer.set_waker(event_loop_proxy.clone());
+        state.collab.set_waker(event_loop_proxy.clone());
+        state.spectator.set_waker(event_loop_proxy);
         let gpu_instance = wgpu::Instance::new(InstanceDescriptor {
             backends: state.persistent.graphics_api.to_backends(),
             flags: InstanceFlags::empty(),
@@ -66,10 +82,14 @@ impl App {
             render_state: None,
             window: None,
             state,
+            last_redraw: Instant::now(),
+            redraw_deferred: false,
+            palm_touches: HashSet::new(),
+            surface_failures: 0,
         }
     }
 
-    pub async fn set_window(&mut self, window: Window) {
+    pub async fn set_window(&mut self, window: Window) -> Result<(), Box<dyn std::error::Error>> {
         let window = Arc::new(window);
 
         // title
@@ -121,7 +141,16 @@ error: failed to enable premultiplied alpha for window: {:?}
             }
         };
 
-        // prepare renderer
+        self.create_render_state(&window).await?;
+        self.window.get_or_insert(window);
+        Ok(())
+    }
+
+    // 创建交换链、显卡设备和 egui 渲染器
+    async fn create_render_state(
+        &mut self,
+        window: &Arc<Window>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let size = window.inner_size();
         let initial_width = size.width;
         let initial_height = size.height;
@@ -129,46 +158,80 @@ error: failed to enable premultiplied alpha for window: {:?}
         let surface = self
             .gpu_instance
             .create_surface(window.clone())
-            .expect("failed to create surface");
+            .map_err(|err| format!("failed to create surface: {err}"))?;
 
         let state = RenderState::new(
             &self.gpu_instance,
             surface,
-            &window,
+            window,
             initial_width,
             initial_height,
             self.state.persistent.optimization_policy,
             self.state.persistent.present_mode,
+            self.state.persistent.msaa_samples,
         )
-        .await;
+        .await?;
 
         self.state.active_backend = Some(state.device.adapter_info().backend);
+        // 保存的呈现模式可能不受当前设备支持
+        self.state.persistent.present_mode = state.surface_config.present_mode;
+        self.state.persistent.msaa_samples = state.msaa_samples();
 
-        let ctx = state.egui_renderer.context();
+        configure_egui_context(state.egui_renderer.context(), &self.state.persistent);
 
-        // colors
-        apply_theme_mode_and_canvas_color(
-            ctx,
-            self.state.persistent.theme_mode,
-            self.state.persistent.canvas_color,
-        );
+        self.render_state = Some(state);
+        Ok(())
+    }
 
-        self.window.get_or_insert(window);
-        self.render_state.get_or_insert(state);
+    // 显卡设备丢失（例如驱动重置）时重建渲染状态，并为新的 egui 上下文重新上传所有纹理
+    fn recreate_render_state(&mut self) {
+        let Some(window) = self.window.clone() else {
+            return;
+        };
+        // 同一窗口同时只能有一个交换链，先释放旧的渲染状态
+        self.render_state = None;
+        if let Err(err) = pollster::block_on(self.create_render_state(&window)) {
+            // 无法继续绘制：立即写入自动保存并保留恢复文件，下次启动时可以恢复
+            utils::ui::write_autosave(&mut self.state);
+            self.state.autosave.pending_recovery = true;
+            self.state.should_quit = true;
+            utils::crash::report_fatal_error(
+                &render_init_error_message(err.as_ref()),
+                &format!("{err:?}"),
+            );
+            return;
+        }
+
+        let ctx = self
+            .render_state
+            .as_ref()
+            .unwrap()
+            .egui_renderer
+            .context()
+            .clone();
+        self.state.reload_textures(&ctx);
+        self.surface_failures = 0;
+        self.state
+            .toasts
+            .warning(t("显卡设备已重置, 已重新初始化渲染器!"));
+        window.request_redraw();
     }
 
     fn exit(&mut self, event_loop: &ActiveEventLoop) {
-        if let Err(err) = self.state.persistent.save_to_file() {
+        if let Err(err) = self.state.save_settings() {
             eprintln!("failed to save settings: {}", err);
         }
+        // 正常退出时不需要恢复，但仍保留尚未处理的恢复文件
+        if !self.state.autosave.pending_recovery {
+            self.state.autosave.discard();
+        }
         event_loop.exit();
     }
 
     fn handle_resized(&mut self, width: u32, height: u32) {
-        self.render_state
-            .as_mut()
-            .unwrap()
-            .resize_surface(width, height);
+        if let Some(render_state) = self.render_state.as_mut() {
+            render_state.resize_surface(width, height);
+        }
     }
 
     #[cfg_attr(feature = "profiling", profiling::function)]
@@ -176,20 +239,61 @@ error: failed to enable premultiplied alpha for window: {:?}
         #[cfg(feature = "profiling")]
         profiling::scope!("handle_redraw::setup");
 
-        let render_state = self.render_state.as_mut().unwrap();
+        let Some(window) = self.window.clone() else {
+            return;
+        };
+        if self
+            .render_state
+            .as_ref()
+            .is_some_and(|render_state| render_state.is_device_lost())
+        {
+            self.recreate_render_state();
+            return;
+        }
+
+        let Some(render_state) = self.render_state.as_mut() else {
+            return;
+        };
 
         if self.state.present_mode_changed {
-            render_state.set_present_mode(self.state.persistent.present_mode);
+            let requested = self.state.persistent.present_mode;
+            let applied = render_state.set_present_mode(requested);
+            if applied != requested {
+                self.state.persistent.present_mode = applied;
+                self.state.toasts.warning(tf(
+                    "当前设备不支持 {}, 已回退到 {}!",
+                    &[&format!("{requested:?}"), &format!("{applied:?}")],
+                ));
+            }
             self.state.present_mode_changed = false;
         }
 
+        if self.state.msaa_samples_changed {
+            let requested = self.state.persistent.msaa_samples;
+            let previous = render_state.msaa_samples();
+            let applied = render_state.set_msaa_samples(&window, requested);
+            if applied != requested {
+                self.state.persistent.msaa_samples = applied;
+                self.state.toasts.warning(tf(
+                    "当前设备不支持 {}x 多重采样, 已回退到 {}x!",
+                    &[&requested, &applied],
+                ));
+            }
+            // 重建的 egui 渲染器使用新的上下文，需重新应用设置并上传纹理
+            if applied != previous {
+                let ctx = render_state.egui_renderer.context().clone();
+                configure_egui_context(&ctx, &self.state.persistent);
+                self.state.reload_textures(&ctx);
+            }
+            self.state.msaa_samples_changed = false;
+        }
+
         let screen_descriptor = ScreenDescriptor {
             size_in_pixels: [
                 render_state.surface_config.width,
                 render_state.surface_config.height,
             ],
-            pixels_per_point: self.window.as_ref().unwrap().scale_factor() as f32
-                * render_state.scale_factor,
+            pixels_per_point: window.scale_factor() as f32 * render_state.scale_factor,
         };
 
         let surface_texture = render_state.surface.get_current_texture();
@@ -200,11 +304,36 @@ error: failed to enable premultiplied alpha for window: {:?}
                 println!("warning: wgpu surface suboptimal");
                 surface
             }
+            // 窗口被遮挡或获取超时，跳过这一帧
+            CurrentSurfaceTexture::Timeout | CurrentSurfaceTexture::Occluded => return,
+            // 交换链过期或丢失时重新配置并在下一帧重试，连续失败时重建显卡设备
+            val @ (CurrentSurfaceTexture::Outdated | CurrentSurfaceTexture::Lost) => {
+                println!("warning: wgpu surface {:?}, retrying", val);
+                self.surface_failures += 1;
+                let recovered = self.surface_failures < MAX_SURFACE_FAILURES
+                    && match val {
+                        CurrentSurfaceTexture::Lost => render_state
+                            .recreate_surface(&self.gpu_instance, window.clone())
+                            .inspect_err(|err| eprintln!("failed to recreate surface: {err}"))
+                            .is_ok(),
+                        _ => {
+                            render_state.reconfigure_surface();
+                            true
+                        }
+                    };
+                if recovered {
+                    window.request_redraw();
+                } else {
+                    self.recreate_render_state();
+                }
+                return;
+            }
             val => {
                 println!("warning: wgpu surface {:?}", val);
                 return;
             }
         };
+        self.surface_failures = 0;
 
         let surface_view = surface_texture
             .texture
@@ -214,16 +343,28 @@ error: failed to enable premultiplied alpha for window: {:?}
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-        let window = self.window.as_ref().unwrap();
-
-        render_state.egui_renderer.begin_frame(window);
+        let window = &window;
 
         // access this value in next redraw before ui to ensure that all ui has become invisible
         let screenshot_path = self.state.screenshot_path.clone();
+        let fill_pos = self.state.pending_fill;
 
         // fixes a borrow checker error
         let ctx = &(render_state.egui_renderer.context().clone());
 
+        // 观看者只看到画布：隐藏界面绘制一帧并截取，拖动、输入或按下指针时推迟
+        let capture_for_spectators = screenshot_path.is_none()
+            && fill_pos.is_none()
+            && self.state.spectator.prepare_capture(
+                utils::ui::current_interaction(ctx).is_some()
+                    || ctx.input(|input| input.pointer.any_down()),
+            );
+        if capture_for_spectators {
+            render_state.egui_renderer.begin_capture_frame(window);
+        } else {
+            render_state.egui_renderer.begin_frame(window);
+        }
+
         // --- ui ---
         let toolbar_rect = {
             #[cfg(feature = "profiling")]
@@ -238,21 +379,52 @@ error: failed to enable premultiplied alpha for window: {:?}
                 }
             }
 
-            self.state.toasts.show(ctx);
+            utils::ui::import_received_boards(&mut self.state, ctx);
+            utils::ui::apply_collab_updates(&mut self.state);
+            utils::ui::update_session_recording(&mut self.state, ctx);
+            utils::ui::update_autosave(&mut self.state, ctx);
+            utils::ui::update_presentation(&mut self.state, ctx);
+
+            // only the canvas itself is drawn in the exported image
+            let is_exporting = self.state.is_capturing_canvas();
+
+            if !is_exporting {
+                self.state.toasts.show(ctx);
+            }
 
             #[cfg(feature = "profiling")]
             puffin_egui::profiler_window(ctx);
 
-            if self.state.show_welcome_window {
+            if self.state.show_welcome_window && !is_exporting {
                 ui::ui_welcome(&mut self.state, ctx);
             }
 
+            // 松开滑块或离开输入框后，下一次修改另起一条历史记录
+            self.state
+                .history
+                .end_coalescing_unless(utils::ui::current_interaction(ctx));
+
             let toolbar_rect = ui::ui_toolbar(&mut self.state, ctx, window);
 
             ui::ui_pages_nav(&mut self.state, ctx);
 
-            if self.state.show_page_management_window {
-                ui::ui_pages_manager(&mut self.state, ctx);
+            if self.state.presentation.is_some() {
+                if !is_exporting {
+                    ui::ui_presentation_controls(&mut self.state, ctx, window);
+                }
+            } else if !is_exporting {
+                if self.state.show_page_management_window {
+                    ui::ui_pages_manager(&mut self.state, ctx);
+                }
+                if self.state.show_layers_window {
+                    ui::ui_layers_manager(&mut self.state, ctx);
+                }
+
+                ui::ui_session_player(&mut self.state, ctx);
+                ui::ui_text_editor(&mut self.state, ctx);
+                ui::ui_shape_recognition_prompt(&mut self.state, ctx);
+                ui::ui_object_context_menu(&mut self.state, ctx);
+                ui::ui_autosave_recovery_prompt(&mut self.state, ctx);
             }
 
             ui::ui_canvas(&mut self.state, ctx);
@@ -272,107 +444,37 @@ error: failed to enable premultiplied alpha for window: {:?}
                 &mut encoder,
                 window,
                 &surface_view,
+                render_state.msaa_view.as_ref(),
                 screen_descriptor,
             );
         }
 
         // submit & present texture
-        if let Some(path) = screenshot_path {
+        if screenshot_path.is_some() || fill_pos.is_some() || capture_for_spectators {
             #[cfg(feature = "profiling")]
             profiling::scope!("handle_redraw::screenshot");
 
             let width = render_state.surface_config.width;
             let height = render_state.surface_config.height;
+            let pixels = render_state.read_surface_pixels(encoder, &surface_texture.texture);
 
-            let bytes_per_pixel = 4;
-            let unpadded_bytes_per_row = width * bytes_per_pixel;
-
-            // wgpu requires 256-byte alignment
-            const ALIGN: u32 = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
-            let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(ALIGN) * ALIGN;
-
-            let buffer_size = (padded_bytes_per_row * height) as u64;
-
-            let output_buffer = render_state.device.create_buffer(&wgpu::BufferDescriptor {
-                label: Some("screenshot buffer"),
-                size: buffer_size,
-                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-                mapped_at_creation: false,
-            });
-
-            encoder.copy_texture_to_buffer(
-                TexelCopyTextureInfo {
-                    texture: &surface_texture.texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All,
-                },
-                TexelCopyBufferInfo {
-                    buffer: &output_buffer,
-                    layout: TexelCopyBufferLayout {
-                        offset: 0,
-                        bytes_per_row: Some(padded_bytes_per_row),
-                        rows_per_image: Some(height),
-                    },
-                },
-                wgpu::Extent3d {
-                    width,
-                    height,
-                    depth_or_array_layers: 1,
-                },
-            );
-
-            render_state.queue.submit(Some(encoder.finish()));
-
-            let buffer_slice = output_buffer.slice(..);
-
-            buffer_slice.map_async(wgpu::MapMode::Read, |_| {});
-
-            // ensure gpu work is done
-            let _ = render_state.device.poll(wgpu::wgt::PollType::Wait {
-                submission_index: None,
-                timeout: None,
-            });
-
-            let data = buffer_slice.get_mapped_range();
-
-            let mut pixels = vec![0u8; (width * height * 4) as usize];
-
-            for y in 0..height as usize {
-                let src_offset = y * padded_bytes_per_row as usize;
-                let dst_offset = y * unpadded_bytes_per_row as usize;
-
-                pixels[dst_offset..dst_offset + unpadded_bytes_per_row as usize].copy_from_slice(
-                    &data[src_offset..src_offset + unpadded_bytes_per_row as usize],
-                );
-            }
-
-            // pixels
-            //     .chunks_exact(width as usize * 4)
-            //     .collect::<Vec<_>>()
-            //     .into_iter()
-            //     .rev()
-            //     .flatten()
-            //     .copied()
-            //     .collect::<Vec<u8>>();
-
-            for chunk in pixels.chunks_exact_mut(4) {
-                chunk.swap(0, 2); // B ↔ R
-            }
-
-            match image::save_buffer(path, &pixels, width, height, image::ColorType::Rgba8) {
-                Ok(_) => {
-                    self.state.toasts.success("成功导出为图片!");
-                }
-                Err(err) => {
-                    self.state.toasts.error(format!("画布导出失败: {}!", err));
+            if let Some(path) = screenshot_path {
+                match image::save_buffer(path, &pixels, width, height, image::ColorType::Rgba8) {
+                    Ok(_) => {
+                        self.state.toasts.success(t("成功导出为图片!"));
+                    }
+                    Err(err) => {
+                        self.state.toasts.error(tf("画布导出失败: {}!", &[&err]));
+                    }
                 }
-            }
-
-            drop(data);
-            output_buffer.unmap();
 
-            self.state.screenshot_path = None;
+                self.state.screenshot_path = None;
+            } else if let Some(pos) = fill_pos {
+                utils::ui::apply_flood_fill(&mut self.state, ctx, pos, width, height, &pixels);
+                self.state.pending_fill = None;
+            } else {
+                self.state.spectator.submit_snapshot(width, height, pixels);
+            }
         } else {
             render_state.queue.submit(Some(encoder.finish()));
         }
@@ -390,7 +492,13 @@ error: failed to enable premultiplied alpha for window: {:?}
             });
         }
 
-        surface_texture.present();
+        // 给观看者截取的一帧不显示，丢弃后立即重绘带界面的一帧
+        if capture_for_spectators {
+            drop(surface_texture);
+            window.request_redraw();
+        } else {
+            surface_texture.present();
+        }
 
         // update window passthrough state only once if disabled
         if self.state.overlay_mode_changed && !self.state.is_overlay_mode {
@@ -440,17 +548,32 @@ error: failed to enable premultiplied alpha for window: {:?}
 
 impl ApplicationHandler<()> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let window = event_loop
+        let result = event_loop
             // support transparency
             .create_window(Window::default_attributes().with_transparent(true))
-            .unwrap();
-        pollster::block_on(self.set_window(window));
+            .map_err(Into::into)
+            .and_then(|window| pollster::block_on(self.set_window(window)));
+        if let Err(err) = result {
+            utils::crash::report_fatal_error(
+                &render_init_error_message(err.as_ref()),
+                &format!("{err:?}"),
+            );
+            event_loop.exit();
+            return;
+        }
         // redraw on window creation
         self.window.as_ref().unwrap().request_redraw();
     }
 
+    // woken up by background threads (e.g. network transfers)
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, _event: ()) {
+        if let Some(window) = self.window.as_ref() {
+            window.request_redraw();
+        }
+    }
+
     // redraw if egui requests repaint
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
         if self.state.should_quit {
             return;
         }
@@ -460,6 +583,26 @@ impl ApplicationHandler<()> for App {
                 self.window.as_ref().unwrap().request_redraw();
             }
         }
+
+        // 帧率上限：推迟的重绘在下一帧的时间点到达时再请求，期间让事件循环休眠
+        let deadline = self
+            .state
+            .persistent
+            .fps_cap
+            .frame_interval()
+            .map(|interval| self.last_redraw + interval);
+        match deadline {
+            Some(deadline) if self.redraw_deferred && Instant::now() < deadline => {
+                event_loop.set_control_flow(ControlFlow::WaitUntil(deadline));
+            }
+            _ => {
+                if self.redraw_deferred {
+                    self.redraw_deferred = false;
+                    self.window.as_ref().unwrap().request_redraw();
+                }
+                event_loop.set_control_flow(ControlFlow::Wait);
+            }
+        }
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
@@ -469,6 +612,31 @@ impl ApplicationHandler<()> for App {
             return;
         }
 
+        // 启动动画播放时，任意按键、点击或触摸跳过动画
+        #[cfg(feature = "startup_animation")]
+        if matches!(
+            event,
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    state: winit::event::ElementState::Pressed,
+                    ..
+                },
+                ..
+            } | WindowEvent::MouseInput {
+                state: winit::event::ElementState::Pressed,
+                ..
+            } | WindowEvent::Touch(Touch {
+                phase: TouchPhase::Started,
+                ..
+            })
+        ) && let Some(anim) = &mut self.state.startup_animation
+            && !anim.is_finished()
+        {
+            anim.skip();
+            self.window.as_ref().unwrap().request_redraw();
+            return;
+        }
+
         // redraw only on input
         // don't pass RedrawRequested to egui's input handler,
         // it's not input and would make egui request a repaint, causing an infinite redraw loop
@@ -492,6 +660,42 @@ impl ApplicationHandler<()> for App {
             WindowEvent::CloseRequested => {
                 self.exit(event_loop);
             }
+            // 演示时 Esc 退出演示，方向键与翻页键切换页面，方便使用翻页笔
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(key),
+                        state: winit::event::ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } if self.state.presentation.is_some()
+                && matches!(
+                    key,
+                    NamedKey::Escape
+                        | NamedKey::ArrowLeft
+                        | NamedKey::ArrowUp
+                        | NamedKey::PageUp
+                        | NamedKey::ArrowRight
+                        | NamedKey::ArrowDown
+                        | NamedKey::PageDown
+                        | NamedKey::Space
+                ) =>
+            {
+                let current = self.state.current_page;
+                match key {
+                    NamedKey::Escape => {
+                        utils::ui::stop_presentation(&mut self.state, self.window.as_ref().unwrap())
+                    }
+                    NamedKey::ArrowLeft | NamedKey::ArrowUp | NamedKey::PageUp => {
+                        if current > 0 {
+                            utils::ui::present_page(&mut self.state, current - 1);
+                        }
+                    }
+                    _ => utils::ui::present_page(&mut self.state, current + 1),
+                }
+                self.window.as_ref().unwrap().request_redraw();
+            }
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
@@ -503,19 +707,86 @@ impl ApplicationHandler<()> for App {
             } => {
                 self.exit(event_loop);
             }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.state.modifiers = modifiers.state();
+            }
+            // 撤销/重做、复制/剪切/粘贴/创建副本快捷键, 文本输入框获得焦点时交给 egui 处理
+            WindowEvent::KeyboardInput { event, .. }
+                if event.state == winit::event::ElementState::Pressed
+                    && self.state.modifiers.control_key()
+                    && shortcut_letter(&event).is_some_and(|letter| "zycxvd".contains(letter))
+                    && !self
+                        .render_state
+                        .as_ref()
+                        .unwrap()
+                        .egui_renderer
+                        .context()
+                        .wants_keyboard_input() =>
+            {
+                match shortcut_letter(&event) {
+                    Some('z') if self.state.modifiers.shift_key() => {
+                        utils::ui::redo(&mut self.state)
+                    }
+                    Some('z') => utils::ui::undo(&mut self.state),
+                    Some('y') => utils::ui::redo(&mut self.state),
+                    Some('c') => utils::ui::copy_selected_object(&mut self.state),
+                    Some('x') => utils::ui::cut_selected_object(&mut self.state),
+                    Some('v') => utils::ui::paste_clipboard(
+                        &mut self.state,
+                        self.render_state.as_ref().unwrap().egui_renderer.context(),
+                    ),
+                    Some('d') => utils::ui::duplicate_selected_object(&mut self.state),
+                    _ => unreachable!(),
+                }
+                self.window.as_ref().unwrap().request_redraw();
+            }
+            // 删除选中的对象
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::Delete | NamedKey::Backspace),
+                        state: winit::event::ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } if (self.state.selected_object_index.is_some()
+                || !self.state.selected_objects.is_empty())
+                && !self
+                    .render_state
+                    .as_ref()
+                    .unwrap()
+                    .egui_renderer
+                    .context()
+                    .wants_keyboard_input() =>
+            {
+                utils::ui::delete_selected_object(&mut self.state);
+                self.window.as_ref().unwrap().request_redraw();
+            }
             WindowEvent::RedrawRequested => {
-                self.handle_redraw();
+                if let Some(interval) = self.state.persistent.fps_cap.frame_interval()
+                    && self.last_redraw.elapsed() < interval
+                {
+                    self.redraw_deferred = true;
+                } else {
+                    self.last_redraw = Instant::now();
+                    self.handle_redraw();
+                }
             }
             WindowEvent::Resized(new_size) if new_size.width > 0 && new_size.height > 0 => {
                 self.handle_resized(new_size.width, new_size.height);
                 self.window.as_ref().unwrap().request_redraw();
             }
+            // 演示时触摸只用于翻页，由 egui 作为点击处理
+            WindowEvent::Touch(_) if self.state.presentation.is_some() => {}
             WindowEvent::Touch(Touch {
                 phase,
                 location,
                 id,
+                force,
                 ..
             }) => {
+                let id = touch_pointer_id(id);
+
                 // Convert touch location to logical coordinates
                 let window = self.window.as_ref().unwrap();
                 let scale_factor = window.scale_factor() as f32;
@@ -523,51 +794,98 @@ impl ApplicationHandler<()> for App {
                     location.x as f32 / scale_factor,
                     location.y as f32 / scale_factor,
                 );
+                let screen_pos = pos;
+                let pos = self.state.viewport.to_canvas(pos);
+
+                // 手掌拒绝：被忽略的触点直到抬起都不参与书写和手势
+                if phase == TouchPhase::Started
+                    && self.state.persistent.palm_rejection
+                    && is_palm_touch(&self.state, screen_pos)
+                {
+                    self.palm_touches.insert(id);
+                }
+                if self.palm_touches.contains(&id) {
+                    if matches!(phase, TouchPhase::Ended | TouchPhase::Cancelled) {
+                        self.palm_touches.remove(&id);
+                    }
+                    return;
+                }
+
+                // 已有触点在书写时 (例如多人同时书写)，新触点同样开始自己的操作，不构成手势，
+                // 也不打断正在进行的书写
+                let joins_gestures = phase != TouchPhase::Started
+                    || !self.state.pointers.values().any(|pointer| {
+                        matches!(pointer.interaction, PointerInteraction::Drawing { .. })
+                    });
 
                 match phase {
+                    TouchPhase::Started if !joins_gestures => {}
+                    TouchPhase::Started => {
+                        // 多根手指按下时不再书写：轻触时放弃已开始的笔画，避免留下误触的痕迹，否则正常结束
+                        if self.state.gestures.touch_down(id, screen_pos) {
+                            let ids: Vec<u64> = self.state.gestures.touch_ids().collect();
+                            for id in ids {
+                                self.state.pointers.remove(&id);
+                            }
+                        } else if self.state.gestures.touch_count() >= 2 {
+                            let ids: Vec<u64> = self.state.gestures.touch_ids().collect();
+                            for id in ids {
+                                end_touch_interaction(&mut self.state, id);
+                            }
+                        }
+                    }
+                    TouchPhase::Moved => {
+                        // 双指捏合缩放，双指拖动平移
+                        if let Some(pinch) = self.state.gestures.touch_moved(id, screen_pos) {
+                            self.state.viewport.offset += pinch.translation;
+                            self.state.viewport.zoom_at(pinch.center, pinch.zoom);
+                        }
+                    }
+                    TouchPhase::Ended | TouchPhase::Cancelled => {
+                        let cancelled = phase == TouchPhase::Cancelled;
+                        match self.state.gestures.touch_up(id, cancelled) {
+                            Some(Gesture::Undo) => utils::ui::undo(&mut self.state),
+                            Some(Gesture::Redo) => utils::ui::redo(&mut self.state),
+                            None => {}
+                        }
+                    }
+                }
+                // 其他手指已按下时新触点不开始操作，已结束操作的触点在抬起前也不会再参与
+                let in_gesture = phase == TouchPhase::Started
+                    && joins_gestures
+                    && self.state.gestures.touch_count() >= 2;
+
+                match phase {
+                    _ if in_gesture => {}
                     TouchPhase::Started => match self.state.current_tool {
-                        CanvasTool::Brush => {
-                            brush_stroke_start(&mut self.state, id, pos);
+                        CanvasTool::Brush | CanvasTool::Highlighter => {
+                            brush_stroke_start(&mut self.state, id, pos, force);
                         }
                         CanvasTool::Select
                             if !self.state.pointers.values().any(|p| {
-                                matches!(p.interaction, PointerInteraction::Selecting { .. })
+                                matches!(
+                                    p.interaction,
+                                    PointerInteraction::Selecting { .. }
+                                        | PointerInteraction::Marquee { .. }
+                                        | PointerInteraction::Cropping { .. }
+                                )
                             }) =>
                         {
-                            // Hit-test objects (last to first for z-order)
-                            for (i, object) in self.state.canvas.objects.iter().enumerate().rev() {
-                                if object.bounding_box().contains(pos) {
-                                    self.state.selected_object_index = Some(i);
-                                    break;
-                                }
-                            }
-
-                            let (dragged_handle, drag_original_transform) = if let Some(idx) =
-                                self.state.selected_object_index
-                                && idx < self.state.canvas.objects.len()
-                            {
-                                let object = &self.state.canvas.objects[idx];
-                                let bbox = object.bounding_box();
-                                let handle = utils::get_transform_handle_at_pos(bbox, pos);
-                                let transform = handle.is_some().then(|| object.get_transform());
-                                (handle, transform)
-                            } else {
-                                (None, None)
-                            };
-
-                            self.state.pointers.insert(
-                                id,
-                                PointerState {
-                                    id,
-                                    pos,
-                                    interaction: PointerInteraction::Selecting {
-                                        drag_start: pos,
-                                        dragged_handle,
-                                        drag_original_transform,
-                                        drag_accumulated_delta: Vec2::ZERO,
-                                    },
-                                },
-                            );
+                            select_drag_start(&mut self.state, id, pos);
+                        }
+                        CanvasTool::Insert
+                            if !self.state.pointers.values().any(|p| {
+                                matches!(p.interaction, PointerInteraction::PlacingShape { .. })
+                            }) =>
+                        {
+                            shape_drag_start(&mut self.state, id, pos);
+                        }
+                        CanvasTool::Measure
+                            if !self.state.pointers.values().any(|p| {
+                                matches!(p.interaction, PointerInteraction::Measuring { .. })
+                            }) =>
+                        {
+                            measure_drag_start(&mut self.state, id, pos);
                         }
                         CanvasTool::ObjectEraser | CanvasTool::PixelEraser => {
                             self.state.pointers.insert(
@@ -582,45 +900,15 @@ impl ApplicationHandler<()> for App {
                         _ => {}
                     },
                     TouchPhase::Moved => match self.state.current_tool {
-                        CanvasTool::Brush => {
-                            brush_stroke_add_point(&mut self.state, id, pos, false);
+                        CanvasTool::Brush | CanvasTool::Highlighter => {
+                            brush_stroke_add_point(&mut self.state, id, pos, force, false);
                         }
                         CanvasTool::Select => {
-                            if let Some(pointer) = self.state.pointers.get_mut(&id) {
-                                pointer.pos = pos;
-
-                                if let PointerInteraction::Selecting {
-                                    ref mut drag_start,
-                                    dragged_handle,
-                                    ref mut drag_accumulated_delta,
-                                    ..
-                                } = pointer.interaction
-                                {
-                                    let delta = pos - *drag_start;
-
-                                    if let Some(idx) = self.state.selected_object_index
-                                        && idx < self.state.canvas.objects.len()
-                                    {
-                                        if let Some(handle) = dragged_handle {
-                                            if let Some(object) =
-                                                self.state.canvas.objects.get_mut(idx)
-                                            {
-                                                object.transform(handle, delta, *drag_start, pos);
-                                            }
-                                        } else {
-                                            if let Some(object) =
-                                                self.state.canvas.objects.get_mut(idx)
-                                            {
-                                                CanvasObject::move_object(object, delta);
-                                            }
-                                            *drag_accumulated_delta += delta;
-                                        }
-                                    }
-
-                                    *drag_start = pos;
-                                }
-                            }
+                            let keep_aspect_ratio = !self.state.modifiers.shift_key();
+                            select_drag_move(&mut self.state, id, pos, keep_aspect_ratio);
                         }
+                        CanvasTool::Insert => shape_drag_move(&mut self.state, id, pos),
+                        CanvasTool::Measure => measure_drag_move(&mut self.state, id, pos),
                         CanvasTool::ObjectEraser | CanvasTool::PixelEraser => {
                             if let Some(pointer) = self.state.pointers.get_mut(&id) {
                                 pointer.pos = pos;
@@ -628,53 +916,33 @@ impl ApplicationHandler<()> for App {
                         }
                         _ => {}
                     },
-                    TouchPhase::Ended | TouchPhase::Cancelled => match self.state.current_tool {
-                        CanvasTool::Brush => {
-                            brush_stroke_end(&mut self.state, id);
-                        }
-                        CanvasTool::Select => {
-                            if let Some(pointer) = self.state.pointers.get(&id) {
-                                if let PointerInteraction::Selecting {
-                                    drag_accumulated_delta,
-                                    drag_original_transform,
-                                    ..
-                                } = &pointer.interaction
-                                {
-                                    if let Some(sel_idx) = self.state.selected_object_index {
-                                        if *drag_accumulated_delta != Vec2::ZERO {
-                                            self.state.history.save_move_object(
-                                                sel_idx,
-                                                -*drag_accumulated_delta,
-                                                *drag_accumulated_delta,
-                                            );
-                                        }
-                                    }
-                                    if let Some(original) = drag_original_transform.clone() {
-                                        if let Some(sel_idx) = self.state.selected_object_index
-                                            && sel_idx < self.state.canvas.objects.len()
-                                        {
-                                            let new_transform =
-                                                self.state.canvas.objects[sel_idx].get_transform();
-                                            self.state.history.save_transform_object(
-                                                sel_idx,
-                                                original,
-                                                new_transform,
-                                            );
-                                        }
-                                    }
-                                }
-                            }
-                            self.state.pointers.remove(&id);
-                        }
-                        CanvasTool::ObjectEraser | CanvasTool::PixelEraser => {
-                            self.state.pointers.remove(&id);
-                        }
-                        _ => {}
-                    },
+                    TouchPhase::Ended | TouchPhase::Cancelled => {
+                        end_touch_interaction(&mut self.state, id);
+                    }
                 }
 
                 self.window.as_ref().unwrap().request_redraw();
             }
+            // 拖入图片文件时插入到松开鼠标的位置
+            WindowEvent::DroppedFile(path) => {
+                let window = self.window.as_ref().unwrap();
+                let scale_factor = window.scale_factor() as f32;
+                let pos = self.state.viewport.to_canvas(Pos2::new(
+                    self.state.cursor_position.x as f32 / scale_factor,
+                    self.state.cursor_position.y as f32 / scale_factor,
+                ));
+                let ctx = self
+                    .render_state
+                    .as_ref()
+                    .unwrap()
+                    .egui_renderer
+                    .context()
+                    .clone();
+                if utils::ui::insert_image_file(&mut self.state, &ctx, &path, pos) {
+                    self.state.current_tool = CanvasTool::Select;
+                }
+                window.request_redraw();
+            }
             WindowEvent::CursorMoved {
                 device_id: _,
                 position,
@@ -686,3 +954,80 @@ impl ApplicationHandler<()> for App {
         }
     }
 }
+
+// 快捷键对应的字母：按键盘布局上标注的字母匹配，例如 AZERTY 上 Ctrl+Z 是标有 Z 的键
+// 非拉丁字母布局 (如俄文) 上改用按键的物理位置
+fn shortcut_letter(event: &KeyEvent) -> Option<char> {
+    if let Key::Character(text) = &event.logical_key {
+        let mut chars = text.chars();
+        if let (Some(letter), None) = (chars.next(), chars.next())
+            && letter.is_ascii_alphabetic()
+        {
+            return Some(letter.to_ascii_lowercase());
+        }
+    }
+    let PhysicalKey::Code(code) = event.physical_key else {
+        return None;
+    };
+    Some(match code {
+        KeyCode::KeyZ => 'z',
+        KeyCode::KeyY => 'y',
+        KeyCode::KeyC => 'c',
+        KeyCode::KeyX => 'x',
+        KeyCode::KeyV => 'v',
+        KeyCode::KeyD => 'd',
+        _ => return None,
+    })
+}
+
+// 为新建的 egui 上下文应用外观与绘制质量设置
+fn configure_egui_context(ctx: &egui::Context, persistent: &PersistentState) {
+    apply_theme_mode_and_canvas_color(ctx, persistent.theme_mode, persistent.canvas_color);
+    apply_tessellation_quality(ctx, persistent);
+}
+
+// 渲染器无法初始化时展示给用户的说明，找不到显卡时给出排查建议
+fn render_init_error_message(err: &(dyn std::error::Error + 'static)) -> String {
+    if err.is::<NoCompatibleAdapter>() {
+        t("未找到兼容的显卡, 请更新显卡驱动后重试").to_string()
+    } else {
+        tf("无法初始化渲染器: {}", &[&err])
+    }
+}
+
+// 手掌拒绝：书写已持续此时间后同时出现的新触点视为手掌
+const PALM_REJECTION_DELAY: Duration = Duration::from_millis(300);
+// 手掌拒绝：离正在书写的笔尖不超过此距离（逻辑像素）的新触点才可能是手掌
+const PALM_REJECTION_RADIUS: f32 = 250.0;
+// 手掌拒绝：高于笔尖超过此距离的新触点不是手掌，书写时手掌总是搭在笔尖附近或下方
+const PALM_REJECTION_ABOVE_TIP: f32 = 40.0;
+
+// winit 不提供触点的接触面积，只能根据正在进行的书写判断新触点是否为误触的手掌
+// 只忽略笔尖附近及下方的触点，离得较远的触点多半是另一个人在书写
+fn is_palm_touch(state: &AppState, screen_pos: Pos2) -> bool {
+    state.pointers.values().any(|pointer| {
+        let PointerInteraction::Drawing { active_stroke } = &pointer.interaction else {
+            return false;
+        };
+        pointer.id != MOUSE_POINTER_ID
+            && active_stroke.start_time.elapsed() >= PALM_REJECTION_DELAY
+            && active_stroke.points.last().is_some_and(|&tip| {
+                let offset = screen_pos - state.viewport.to_screen(tip);
+                offset.y >= -PALM_REJECTION_ABOVE_TIP && offset.length() <= PALM_REJECTION_RADIUS
+            })
+    })
+}
+
+// 结束触点正在进行的书写、选择、形状绘制或擦除操作
+fn end_touch_interaction(state: &mut AppState, id: u64) {
+    match state.current_tool {
+        CanvasTool::Brush | CanvasTool::Highlighter => brush_stroke_end(state, id),
+        CanvasTool::Select => select_drag_end(state, id),
+        CanvasTool::Insert => shape_drag_end(state, id),
+        CanvasTool::Measure => measure_drag_end(state, id),
+        CanvasTool::ObjectEraser | CanvasTool::PixelEraser => {
+            state.pointers.remove(&id);
+        }
+        _ => {}
+    }
+}
@@ -11,13 +11,18 @@ pub const STARTUP_AUDIO: &[u8] = include_bytes!("../assets/startup_animation/aud
 pub const EMBEDDED_FONT: &[u8] =
     include_bytes!("../assets/fonts/maple-mono-normal-noligatures-nerdfont-cn-regular.ttf");
 
-pub fn font_bytes() -> &'static [u8] {
-    static FONT: OnceLock<Vec<u8>> = OnceLock::new();
+/// Used when no CJK font is installed, e.g. on a fresh Linux system
+#[cfg(feature = "fallback_font")]
+pub const FALLBACK_FONT: &[u8] = include_bytes!("../assets/fonts/noto-sans-cjk-sc-regular.otf");
+
+/// CJK font for the UI and for rasterizing text, `None` if no suitable font was found
+pub fn font_bytes() -> Option<&'static [u8]> {
+    static FONT: OnceLock<Option<Vec<u8>>> = OnceLock::new();
 
     FONT.get_or_init(|| {
         #[cfg(feature = "embedded_font")]
         {
-            EMBEDDED_FONT.to_vec()
+            Some(EMBEDDED_FONT.to_vec())
         }
 
         #[cfg(feature = "system_font")]
@@ -90,14 +95,23 @@ pub fn font_bytes() -> &'static [u8] {
                         font_db.with_face_data(face_id, |data, _| Some(data.to_vec()))
                         && let Some(font_bytes) = font_data
                     {
-                        return font_bytes;
+                        return Some(font_bytes);
                     }
                 }
             }
 
-            panic!("cannot find cjk font")
+            #[cfg(feature = "fallback_font")]
+            {
+                Some(FALLBACK_FONT.to_vec())
+            }
+            #[cfg(not(feature = "fallback_font"))]
+            {
+                eprintln!("cannot find cjk font, falling back to the default font");
+                None
+            }
         }
     })
+    .as_deref()
 }
 
 #[cfg(all(feature = "embedded_font", feature = "system_font"))]
@@ -0,0 +1,567 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+/// Language of the user interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum Lang {
+    #[default]
+    Chinese,
+    English,
+}
+
+impl Lang {
+    pub const ALL: [Lang; 2] = [Lang::Chinese, Lang::English];
+
+    /// Name of the language in the language itself, shown in the language selector
+    pub fn native_name(self) -> &'static str {
+        match self {
+            Lang::Chinese => "简体中文",
+            Lang::English => "English",
+        }
+    }
+}
+
+// 界面语言存放在全局变量中，以便在任意位置翻译文本而无需传递状态
+static CURRENT_LANG: AtomicU8 = AtomicU8::new(Lang::Chinese as u8);
+
+pub fn set_language(lang: Lang) {
+    CURRENT_LANG.store(lang as u8, Ordering::Relaxed);
+}
+
+pub fn language() -> Lang {
+    match CURRENT_LANG.load(Ordering::Relaxed) {
+        1 => Lang::English,
+        _ => Lang::Chinese,
+    }
+}
+
+/// Translates a UI string into the current language.
+/// Keys are the Chinese source strings, so strings without a translation stay in Chinese.
+pub fn t(key: &'static str) -> &'static str {
+    match language() {
+        Lang::Chinese => key,
+        Lang::English => english(key).unwrap_or(key),
+    }
+}
+
+/// Translates a UI string and fills its `{}` placeholders with `args` in order
+pub fn tf(key: &'static str, args: &[&dyn Display]) -> String {
+    let template = t(key);
+    let mut result = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut rest = template;
+    while let Some(start) = rest.find("{}") {
+        result.push_str(&rest[..start]);
+        if let Some(arg) = args.next() {
+            result.push_str(&arg.to_string());
+        }
+        rest = &rest[start + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+fn english(key: &str) -> Option<&'static str> {
+    Some(match key {
+        // 欢迎窗口
+        "欢迎" => "Welcome",
+        "欢迎使用 uwu" => "Welcome to uwu",
+        "这是一个功能强大的数字画板应用，您可以：" => {
+            "A powerful digital whiteboard, where you can:"
+        }
+        "• 绘制和涂鸦" => "• Draw and doodle",
+        "• 使用各种工具进行编辑" => "• Edit with a variety of tools",
+        "• 插入图片、文本和形状" => "• Insert images, text and shapes",
+        "• 自定义画板设置" => "• Customize the board",
+        "• 保存与加载画布以保存你的工作" => {
+            "• Save and load canvases to keep your work"
+        }
+        "• 导出画布为图片或 SVG 矢量图" => "• Export the canvas as an image or an SVG",
+        "• 享受超快的启动速度与超高的流畅度" => {
+            "• Enjoy a fast startup and smooth drawing"
+        }
+        "新建画布" => "New canvas",
+        "加载画布" => "Load canvas",
+        "启动时显示欢迎" => "Show welcome on startup",
+
+        // 设置：外观
+        "外观" => "Appearance",
+        "语言:" => "Language:",
+        "画布颜色:" => "Canvas color:",
+        "重置" => "Reset",
+        "背景图案:" => "Background pattern:",
+        "空白" => "Blank",
+        "方格" => "Grid",
+        "点阵" => "Dots",
+        "横线" => "Lines",
+        "间距" => "Spacing",
+        "颜色:" => "Color:",
+        "网格:" => "Grid:",
+        "显示并吸附" => "Show and snap",
+        "主题模式:" => "Theme:",
+        "跟随系统" => "System",
+        "浅色模式" => "Light",
+        "深色模式" => "Dark",
+        "启动时显示欢迎:" => "Show welcome on startup:",
+        "显示启动动画:" => "Show startup animation:",
+        "窗口透明度" => "Window opacity",
+
+        // 设置：绘制
+        "绘制" => "Drawing",
+        "画布持久化:" => "Canvas file:",
+        "加载" => "Load",
+        "保存" => "Save",
+        "画布转换:" => "Canvas export:",
+        "导出为图片" => "Export as image",
+        "图片" => "Image",
+        "导出为 SVG" => "Export as SVG",
+        "录制:" => "Recording:",
+        "停止并保存" => "Stop and save",
+        "开始" => "Start",
+        "回放" => "Replay",
+        "{} 秒, {} 个事件" => "{} s, {} events",
+        "动态画笔宽度微调:" => "Dynamic brush width:",
+        "禁用" => "Off",
+        "模拟笔锋" => "Simulated taper",
+        "基于速度" => "Speed based",
+        "基于压感" => "Pressure based",
+        "笔锋起始位置:" => "Taper start:",
+        "笔锋末端宽度:" => "Taper end width:",
+        "最细时的速度:" => "Speed at thinnest:",
+        " 像素/秒" => " px/s",
+        "宽度倍数:" => "Width multiplier:",
+        "至" => "to",
+        "笔迹平滑:" => "Stroke smoothing:",
+        "移动平均" => "Moving average",
+        "样条曲线" => "Spline",
+        "形状识别:" => "Shape recognition:",
+        "直线停留拉直:" => "Hold to straighten:",
+        "启用" => "Enable",
+        "灵敏度" => "Sensitivity",
+        "采样间距:" => "Sample spacing:",
+        "书写和像素擦除时相邻采样点的最小距离" => {
+            "Minimum distance between samples when writing and pixel erasing"
+        }
+        "插值频率:" => "Interpolation frequency:",
+        "擦除范围:" => "Erase on:",
+        "当前图层" => "Active layer",
+        "所有图层" => "All layers",
+        "(按住 Alt 擦除所有图层)" => "(hold Alt to erase on all layers)",
+        "平滑强度:" => "Smoothing strength:",
+        "移动平均平滑的窗口大小, 越大笔迹越平滑" => {
+            "Window size of the moving-average smoothing, larger values give smoother strokes"
+        }
+        "笔画简化容差:" => "Stroke simplification tolerance:",
+        "完成书写时去掉偏离不超过该距离 (像素) 的冗余点, 0 为保留所有点" => {
+            "Redundant points deviating by at most this many pixels are dropped when a stroke is finished, 0 keeps every point"
+        }
+        "画笔宽度范围:" => "Brush width range:",
+        "橡皮擦大小范围:" => "Eraser size range:",
+        "低延迟模式:" => "Low latency mode:",
+        "手掌拒绝:" => "Palm rejection:",
+        "书写时忽略笔尖附近及下方同时出现的触点; 紧挨着书写者的另一人的触点也会被忽略" => {
+            "Ignore touches near and below the pen tip while writing; \
+             another person writing right next to the writer is ignored as well"
+        }
+        "编辑快捷颜色:" => "Quick colors:",
+        "编辑快捷颜色" => "Edit quick colors",
+        "当前快捷颜色:" => "Current quick colors:",
+        "删除" => "Delete",
+        "新颜色:" => "New color:",
+        "添加" => "Add",
+        "完成" => "Done",
+
+        // 设置：性能
+        "性能" => "Performance",
+        "窗口模式:" => "Window mode:",
+        "窗口化" => "Windowed",
+        "独占全屏" => "Exclusive fullscreen",
+        "无边框全屏" => "Borderless fullscreen",
+        "显示模式:" => "Video mode:",
+        "(仅在独占全屏模式下可切换)" => "(only available in exclusive fullscreen)",
+        "垂直同步:" => "VSync:",
+        "开 (自动) | AutoVsync" => "On (auto) | AutoVsync",
+        "关 (自动) | AutoNoVsync" => "Off (auto) | AutoNoVsync",
+        "开 | Fifo" => "On | Fifo",
+        "自适应 | FifoRelaxed" => "Adaptive | FifoRelaxed",
+        "关 | Immediate" => "Off | Immediate",
+        "开 (快速) | Mailbox" => "On (fast) | Mailbox",
+        "优化策略 [需重启以应用]:" => "Optimization [needs restart]:",
+        "资源用量" => "Resource usage",
+        "帧率上限:" => "FPS cap:",
+        "不限" => "Unlimited",
+        "图形 API [需重启以应用]:" => "Graphics API [needs restart]:",
+        "自动" => "Auto",
+        "Vulkan (当前)" => "Vulkan (current)",
+        "Dx12 (当前)" => "Dx12 (current)",
+        "Metal (当前)" => "Metal (current)",
+        "WebGPU (当前)" => "WebGPU (current)",
+        "Gl (当前)" => "Gl (current)",
+        "强制每帧重绘:" => "Redraw every frame:",
+        "多重采样抗锯齿:" => "MSAA:",
+        "关" => "Off",
+        "当前设备不支持 {}x 多重采样, 已回退到 {}x!" => {
+            "{}x MSAA is not supported on this device, using {}x instead!"
+        }
+        "曲线误差:" => "Curve tolerance:",
+        "抗锯齿宽度:" => "Antialiasing width:",
+
+        // 设置：网络
+        "网络" => "Network",
+        "接收画布:" => "Receive canvas:",
+        "停止" => "Stop",
+        "发送画布:" => "Send canvas:",
+        "发送" => "Send",
+        "状态:" => "Status:",
+        "观看链接:" => "Viewer link:",
+        "观看服务启动失败: {}!" => "Failed to start the viewer server: {}!",
+        "地址:" => "Address:",
+        "空闲" => "Idle",
+        "正在监听 {}" => "Listening on {}",
+        "正在发送至 {}..." => "Sending to {}...",
+        "已发送至 {}" => "Sent to {}",
+        "已接收来自 {} 的画布" => "Received a canvas from {}",
+        "失败: {}" => "Failed: {}",
+        "无法监听端口 {}: {}" => "Cannot listen on port {}: {}",
+        "接收来自 {} 的画布失败: {}" => "Failed to receive a canvas from {}: {}",
+        "监听中断: {}" => "Stopped listening: {}",
+        "发送至 {} 失败: {}" => "Failed to send to {}: {}",
+        "无法解析地址 {}" => "Cannot resolve the address {}",
+        "加入 {} 失败: {}" => "Failed to join {}: {}",
+        "地址:端口" => "address:port",
+        "实时协作:" => "Live collaboration:",
+        "主持中" => "Hosting",
+        "已加入" => "Joined",
+        "结束" => "End",
+        "会话代码:" => "Session code:",
+        "会话代码" => "Session code",
+        "连接中..." => "Connecting...",
+        "已连接设备:" => "Connected devices:",
+        "主持协作:" => "Host a session:",
+        "加入协作:" => "Join a session:",
+        "加入" => "Join",
+
+        // 设置：调试与关于
+        "调试" => "Debug",
+        "引发异常:" => "Trigger a panic:",
+        "显示 FPS:" => "Show FPS:",
+        "显示触控点:" => "Show touch points:",
+        "压力测试:" => "Stress test:",
+        "立即保存设置:" => "Save settings now:",
+        "设置保存失败: {}!" => "Failed to save settings: {}!",
+        "重置设置:" => "Reset settings:",
+        "关于" => "About",
+        "版本: {}" => "Version: {}",
+        "作者: {}" => "Authors: {}",
+        "FPS: {} (当前 {})" => "FPS: {} (current {})",
+
+        // 历史记录与窗口控制
+        "历史记录:" => "History:",
+        "撤销" => "Undo",
+        "重做" => "Redo",
+        "退出" => "Quit",
+        "最小化" => "Minimize",
+        "悬浮窗模式:" => "Overlay mode:",
+        "屏幕键盘" => "On-screen keyboard",
+
+        // 回放
+        "继续" => "Resume",
+        "暂停" => "Pause",
+        "重新开始" => "Restart",
+        "关闭" => "Close",
+        "速度:" => "Speed:",
+        "工具: {}" => "Tool: {}",
+
+        // 页面与图层
+        "页面管理 (共 {} 页)" => "Pages ({} in total)",
+        "第 {} 页" => "Page {}",
+        "✓ 保存" => "✓ Save",
+        "X 删除" => "X Delete",
+        "→ 跳转" => "→ Go to",
+        "⊙ 当前" => "⊙ Current",
+        "+ 新页" => "+ New page",
+        "O 加载" => "O Load",
+        "X 关闭" => "X Close",
+        "图层" => "Layers",
+        "图层:" => "Layer:",
+        "只能删除空图层!" => "Only empty layers can be deleted!",
+        "+ 新图层" => "+ New layer",
+        "设为当前图层" => "Make active",
+        "移入选中对象" => "Move selection here",
+        "将选中的对象移到当前图层" => "Move the selected object to the active layer",
+
+        // 文本窗口
+        "编辑文本" => "Edit text",
+        "插入文本" => "Insert text",
+        "文本内容:" => "Text:",
+        "字号:" => "Font size:",
+        "确认" => "OK",
+        "取消" => "Cancel",
+
+        // 工具栏
+        "工具栏" => "Toolbar",
+        "工具:" => "Tool:",
+        "穿透" => "Passthrough",
+        "选择" => "Select",
+        "平移" => "Pan",
+        "画笔" => "Brush",
+        "荧光笔" => "Highlighter",
+        "取色" => "Eyedropper",
+        "填充" => "Fill",
+        "对象擦" => "Object eraser",
+        "像素擦" => "Pixel eraser",
+        "插入" => "Insert",
+        "设置" => "Settings",
+        "(当前处于穿透模式, 输入将穿透画布)" => {
+            "(Passthrough mode, input goes to the windows below)"
+        }
+
+        // 选择工具
+        "对象操作:" => "Object:",
+        "复制" => "Duplicate",
+        "导出所选" => "Export selection",
+        "置顶" => "Bring to front",
+        "对象已移至顶部!" => "Moved to the front!",
+        "置底" => "Send to back",
+        "对象已移至底部!" => "Moved to the back!",
+        "锁定" => "Lock",
+        "已锁定!" => "Locked!",
+        "解锁" => "Unlock",
+        "已解锁!" => "Unlocked!",
+        "栅格化" => "Rasterize",
+        "已转换为笔画!" => "Converted to strokes!",
+        "文本样式:" => "Text style:",
+        "不透明度:" => "Opacity:",
+        "裁剪:" => "Crop:",
+        "开始裁剪" => "Crop",
+        "显示全部" => "Show all",
+        "方向:" => "Orientation:",
+        "水平翻转" => "Flip horizontally",
+        "垂直翻转" => "Flip vertically",
+        "旋转90°" => "Rotate 90°",
+        "灰度" => "Grayscale",
+        "便签" => "Sticky note",
+        "测量" => "Measure",
+        "直尺" => "Ruler",
+        "量角器" => "Protractor",
+        "移到视图中央" => "Move to center of view",
+        "长度: {} 厘米 ({} 像素), 角度: {}°" => "Length: {} cm ({} px), angle: {}°",
+        "角度: {}°" => "Angle: {}°",
+        "切换工具后保持显示" => "Keep showing with other tools",
+        "(拖动移动, 拖动圆形手柄改变长度或角度)" => {
+            "(Drag to move, drag the round handles to change length or angle)"
+        }
+        "便签颜色:" => "Note color:",
+        "文字颜色:" => "Text color:",
+        "便签内容" => "Note text",
+        "亮度:" => "Brightness:",
+        "对比度:" => "Contrast:",
+        "(在图片上拖动以选择保留的区域)" => {
+            "(Drag over the image to choose the part to keep)"
+        }
+        "宽:" => "W:",
+        "高:" => "H:",
+        "旋转:" => "Rotation:",
+        "已选中 {} 个对象" => "{} objects selected",
+        "组合" => "Group",
+        "取消组合" => "Ungroup",
+        "对齐:" => "Align:",
+        "左对齐" => "Left",
+        "水平居中" => "Center",
+        "右对齐" => "Right",
+        "顶对齐" => "Top",
+        "垂直居中" => "Middle",
+        "底对齐" => "Bottom",
+        "分布:" => "Distribute:",
+        "水平均匀分布" => "Horizontally",
+        "垂直均匀分布" => "Vertically",
+        "取消选择" => "Deselect",
+        "(未选中对象, 按住 Shift 点击可多选)" => {
+            "(Nothing selected, Shift-click to select several objects)"
+        }
+
+        // 平移、取色与填充工具
+        "缩放: {}%" => "Zoom: {}%",
+        "重置视图" => "Reset view",
+        "(拖动平移画布, 滚轮或双指捏合缩放)" => {
+            "(Drag to pan, scroll or pinch to zoom)"
+        }
+        "画笔颜色:" => "Brush color:",
+        "(点击画布吸取颜色)" => "(Click the canvas to pick a color)",
+        "填充颜色:" => "Fill color:",
+        "(点击封闭区域进行填充)" => "(Click an enclosed area to fill it)",
+
+        // 画笔工具
+        "快捷颜色:" => "Quick colors:",
+        "黑" => "Black",
+        "白" => "White",
+        "蓝" => "Blue",
+        "红" => "Red",
+        "绿" => "Green",
+        "橙" => "Orange",
+        "自定义" => "Custom",
+        "最近颜色:" => "Recent:",
+        "收藏颜色:" => "Pinned:",
+        "收藏当前" => "Pin current",
+        "点击使用, 右键收藏" => "Click to use, right-click to pin",
+        "点击使用, 右键取消收藏" => "Click to use, right-click to unpin",
+        "宽度:" => "Width:",
+        "线型:" => "Line style:",
+        "风格:" => "Style:",
+        "马克笔" => "Marker",
+        "铅笔" => "Pencil",
+        "纹理:" => "Texture:",
+        "无" => "None",
+        "粉笔" => "Chalk",
+        "颗粒" => "Grain",
+        "布纹" => "Canvas",
+        "加载纹理..." => "Load texture...",
+        "对称:" => "Symmetry:",
+        "左右" => "Left-right",
+        "上下" => "Top-bottom",
+        "旋转" => "Radial",
+        " 份" => " folds",
+        "快捷宽度:" => "Quick widths:",
+        "小" => "S",
+        "中" => "M",
+        "大" => "L",
+        "大小:" => "Size:",
+        "清空:" => "Clear:",
+
+        // 插入工具
+        "设为背景" => "Set as background",
+        "文本" => "Text",
+        "形状" => "Shape",
+        "(在画布上拖动以绘制形状)" => "(Drag on the canvas to draw the shape)",
+        "背景:" => "Background:",
+        "拉伸" => "Stretch",
+        "适应" => "Fit",
+        "居中" => "Center",
+        "移除" => "Remove",
+        "插入形状" => "Insert shape",
+        "选择要插入的形状:" => "Choose a shape to insert:",
+        "线" => "Line",
+        "箭头" => "Arrow",
+        "矩形" => "Rectangle",
+        "三角形" => "Triangle",
+        "圆形" => "Circle",
+        "椭圆" => "Ellipse",
+        "多边形" => "Polygon",
+        "星形" => "Star",
+        "三角形类型:" => "Triangle type:",
+        "等边" => "Equilateral",
+        "直角" => "Right",
+        "等腰" => "Isosceles",
+        "多边形边数" => "Polygon sides",
+        "星形角数" => "Star points",
+        "箭头:" => "Arrowheads:",
+        "大小" => "Size",
+        "起点" => "Start",
+        "终点" => "End",
+        "保持窗口开启" => "Keep window open",
+        "在画布上拖动绘制" => "Drag on the canvas",
+        "线条角度吸附" => "Snap line angles",
+        "拖动绘制线与箭头时吸附到 15° 的倍数, 也可按住 Shift" => {
+            "Snap lines and arrows to multiples of 15° while dragging, or hold Shift"
+        }
+
+        // 对话框
+        "恢复画板?" => "Restore boards?",
+        "上次运行未正常退出, 发现自动保存的画板." => {
+            "The last session did not exit cleanly, autosaved boards were found."
+        }
+        "恢复" => "Restore",
+        "丢弃" => "Discard",
+        "转换为形状?" => "Convert to a shape?",
+        "识别为: {}" => "Recognized as: {}",
+        "转换" => "Convert",
+        "忽略" => "Ignore",
+        "实线" => "Solid",
+        "虚线" => "Dashed",
+        "点线" => "Dotted",
+
+        // 提示消息
+        "成功加载画布!" => "Canvas loaded!",
+        "画布加载失败: {}!" => "Failed to load the canvas: {}!",
+        "成功撤销操作!" => "Undone!",
+        "无法撤销，没有更多历史记录!" => "Nothing left to undo!",
+        "成功重做操作!" => "Redone!",
+        "无法重做，没有更多历史记录!" => "Nothing left to redo!",
+        "对象已复制到剪贴板!" => "Copied to the clipboard!",
+        "对象已删除!" => "Deleted!",
+        "已组合!" => "Grouped!",
+        "已取消组合!" => "Ungrouped!",
+        "对象已剪切!" => "Cut!",
+        "无法访问系统剪贴板: {}!" => "Cannot access the system clipboard: {}!",
+        "剪贴板中的图片无效!" => "The image in the clipboard is invalid!",
+        "已粘贴图片!" => "Image pasted!",
+        "已粘贴文本!" => "Text pasted!",
+        "对象已复制!" => "Duplicated!",
+        "无法打开图片: {}!" => "Cannot open the image: {}!",
+        "动图过大，只保留了前 {} 帧!" => {
+            "The animation is too large, only its first {} frames were kept!"
+        }
+        "该区域未封闭, 无法填充!" => "This area is not enclosed and cannot be filled!",
+        "成功保存画布!" => "Canvas saved!",
+        "画布保存失败: {}!" => "Failed to save the canvas: {}!",
+        "成功导出为图片!" => "Exported as an image!",
+        "画布导出失败: {}!" => "Failed to export the canvas: {}!",
+        "SVG 矢量图" => "SVG image",
+        "成功导出为 SVG!" => "Exported as SVG!",
+        "导出失败: {}!" => "Export failed: {}!",
+        "未选中对象!" => "Nothing selected!",
+        "成功导出所选对象!" => "Selection exported!",
+        "没有可导出的对象" => "There is nothing to export",
+        "画布编码失败: {}!" => "Failed to encode the canvas: {}!",
+        "已接收来自 {} 的画布!" => "Received a canvas from {}!",
+        "画布接收失败: {}!" => "Failed to receive the canvas: {}!",
+        "成功发送画布至 {}!" => "Canvas sent to {}!",
+        "与协作主机的连接已断开!" => "Disconnected from the collaboration host!",
+        "开始录制!" => "Recording started!",
+        "录制文件" => "Recording",
+        "成功保存录制!" => "Recording saved!",
+        "录制保存失败: {}!" => "Failed to save the recording: {}!",
+        "录制加载失败: {}!" => "Failed to load the recording: {}!",
+        "自动保存失败: {}!" => "Autosave failed: {}!",
+        "成功恢复自动保存的画板!" => "Autosaved boards restored!",
+        "恢复自动保存失败: {}!" => "Failed to restore the autosave: {}!",
+        "显卡设备已重置, 已重新初始化渲染器!" => {
+            "The graphics device was reset, the renderer has been reinitialized!"
+        }
+        "当前设备不支持 {}, 已回退到 {}!" => {
+            "{} is not supported on this device, using {} instead!"
+        }
+        "画布文件" => "Canvas file",
+        "画板文件" => "Board file",
+        "保存所有页面" => "Save all pages",
+        "✓ 全部保存" => "✓ Save all",
+        "成功保存所有页面!" => "All pages saved!",
+        "演示" => "Present",
+        "X 退出演示" => "X Exit presentation",
+        "演示自动翻页间隔:" => "Presentation auto-advance:",
+        "(0 为仅点击翻页)" => "(0 to advance on tap only)",
+        " 秒" => " s",
+        "预设:" => "Presets:",
+        "黑板" => "Blackboard",
+        "白板" => "Whiteboard",
+        "深色" => "Dark",
+        "浅色" => "Light",
+        "⚠ 画笔颜色与画布颜色对比度过低, 笔迹可能难以看清" => {
+            "⚠ The brush color has low contrast against the canvas and may be hard to see"
+        }
+        "已取消" => "Cancelled",
+        "应用崩溃" => "The app crashed",
+        "{}\n\n详细信息已保存到: {}" => "{}\n\nDetails were saved to: {}",
+        "复制详细信息" => "Copy details",
+        "详细信息已复制, 请在粘贴后再关闭此窗口" => {
+            "The details were copied, close this window only after pasting them"
+        }
+        "未找到兼容的显卡, 请更新显卡驱动后重试" => {
+            "No compatible GPU found, please update the graphics driver and try again"
+        }
+        "无法初始化渲染器: {}" => "Failed to initialize the renderer: {}",
+        _ => return None,
+    })
+}
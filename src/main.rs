@@ -1,6 +1,9 @@
 mod app;
 mod assets;
+mod i18n;
+mod net;
 mod render;
+mod spatial;
 mod state;
 mod ui;
 mod utils;
@@ -18,15 +21,8 @@ fn main() {
     puffin::set_scopes_on(true);
 
     std::panic::set_hook(Box::new(|info| {
-        eprintln!("panic: {info}");
-        eprintln!("backtrace:\n{}", Backtrace::force_capture());
-
-        rfd::MessageDialog::new()
-            .set_title("应用崩溃")
-            .set_level(rfd::MessageLevel::Error)
-            .set_description(info.to_string())
-            .set_buttons(rfd::MessageButtons::Ok)
-            .show();
+        let details = format!("panic: {info}\nbacktrace:\n{}", Backtrace::force_capture());
+        utils::crash::report_fatal_error(&info.to_string(), &details);
     }));
 
     println!(
@@ -50,6 +46,6 @@ fn main() {
 async fn run_desktop() {
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Wait);
-    let mut app = app::App::new();
+    let mut app = app::App::new(event_loop.create_proxy());
     event_loop.run_app(&mut app).expect("failed to run app");
 }
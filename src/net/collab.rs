@@ -0,0 +1,419 @@
+use std::io;
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use rkyv::Archive;
+use winit::event_loop::EventLoopProxy;
+
+use crate::i18n::tf;
+use crate::state::flat::StrokeFlat;
+use crate::state::{CanvasObject, CanvasStroke, StrokeId};
+
+pub const DEFAULT_PORT: u16 = 38339;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Changes broadcast between the members of a session, tagged with the index of the page they apply to.
+/// Only strokes are synchronized for now. They are addressed by their [`StrokeId`], so changes apply
+/// to the same stroke on every board whatever order the objects are in; moves are sent as offsets,
+/// so concurrent moves of one stroke add up to the same position everywhere.
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub enum CollabMessage {
+    StrokeAdded { page: u32, stroke: StrokeFlat },
+    StrokeRemoved { page: u32, id: u64 },
+    StrokeMoved { page: u32, id: u64, delta: [f32; 2] },
+    CanvasCleared { page: u32 },
+}
+
+/// Remote changes and session notifications, produced by [`CollabSession::poll`]
+pub enum CollabUpdate {
+    StrokeAdded {
+        page: usize,
+        stroke: CanvasStroke,
+    },
+    StrokeRemoved {
+        page: usize,
+        id: StrokeId,
+    },
+    StrokeMoved {
+        page: usize,
+        id: StrokeId,
+        delta: egui::Vec2,
+    },
+    CanvasCleared {
+        page: usize,
+    },
+    Failed(String),
+    Disconnected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollabRole {
+    Host,
+    Guest,
+}
+
+enum CollabEvent {
+    Started(String),
+    PeersChanged(usize),
+    Message(Vec<u8>),
+    Failed(String),
+    Disconnected,
+}
+
+/// Live drawing session in a star topology: guests connect to the host, which relays every message to the other guests
+pub struct CollabSession {
+    pub port: u16,
+    pub host_address: String,
+    /// Address guests join with, known once the session is up
+    pub session_code: Option<String>,
+    pub peer_count: usize,
+    waker: Option<EventLoopProxy<()>>,
+    events_tx: Sender<CollabEvent>,
+    events_rx: Receiver<CollabEvent>,
+    shared: Option<Arc<Shared>>,
+}
+
+impl Default for CollabSession {
+    fn default() -> Self {
+        let (events_tx, events_rx) = mpsc::channel();
+        Self {
+            port: DEFAULT_PORT,
+            host_address: String::new(),
+            session_code: None,
+            peer_count: 0,
+            waker: None,
+            events_tx,
+            events_rx,
+            shared: None,
+        }
+    }
+}
+
+impl CollabSession {
+    /// Sets the proxy used to wake up the event loop when a remote change arrives
+    pub fn set_waker(&mut self, waker: EventLoopProxy<()>) {
+        self.waker = Some(waker);
+    }
+
+    pub fn role(&self) -> Option<CollabRole> {
+        self.shared.as_ref().map(|shared| shared.role)
+    }
+
+    /// Hosts a new session on `port`
+    pub fn host(&mut self) {
+        let shared = self.start(CollabRole::Host);
+        let port = self.port;
+        thread::spawn(move || {
+            let listener = match TcpListener::bind(("0.0.0.0", port))
+                .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            {
+                Ok(listener) => listener,
+                Err(err) => {
+                    shared.notify(CollabEvent::Failed(tf(
+                        "无法监听端口 {}: {}",
+                        &[&port, &err],
+                    )));
+                    return;
+                }
+            };
+
+            let ip = super::local_ip()
+                .map(|ip| ip.to_string())
+                .unwrap_or_else(|| "127.0.0.1".to_string());
+            shared.notify(CollabEvent::Started(format!("{ip}:{port}")));
+
+            while !shared.stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        if let Err(err) = shared.add_peer(stream) {
+                            eprintln!("failed to accept collaboration peer: {err}");
+                        }
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_POLL_INTERVAL);
+                    }
+                    Err(err) => {
+                        shared.notify(CollabEvent::Failed(tf("监听中断: {}", &[&err])));
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Joins the session hosted at `host_address`
+    pub fn join(&mut self) {
+        let shared = self.start(CollabRole::Guest);
+        let address = normalize_address(&self.host_address);
+        thread::spawn(move || {
+            let result = address
+                .to_socket_addrs()
+                .and_then(|mut addrs| {
+                    addrs.next().ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::NotFound, tf("无法解析地址 {}", &[&address]))
+                    })
+                })
+                .and_then(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT))
+                .and_then(|stream| shared.add_peer(stream));
+
+            match result {
+                Ok(()) => shared.notify(CollabEvent::Started(address)),
+                Err(err) => {
+                    shared.notify(CollabEvent::Failed(tf(
+                        "加入 {} 失败: {}",
+                        &[&address, &err],
+                    )));
+                }
+            }
+        });
+    }
+
+    /// Leaves the session, disconnecting every peer
+    pub fn stop(&mut self) {
+        if let Some(shared) = self.shared.take() {
+            shared.stop.store(true, Ordering::Relaxed);
+            for peer in shared.peers().drain(..) {
+                let _ = peer.stream.shutdown(Shutdown::Both);
+            }
+        }
+        self.session_code = None;
+        self.peer_count = 0;
+    }
+
+    /// Sends a stroke added to page `page` to the other members
+    pub fn broadcast_stroke(&self, page: usize, stroke: &CanvasStroke) {
+        self.broadcast(&CollabMessage::StrokeAdded {
+            page: page as u32,
+            stroke: StrokeFlat::from(stroke),
+        });
+    }
+
+    /// Tells the other members that `object` was removed from page `page`, if it is a stroke
+    pub fn broadcast_removal(&self, page: usize, object: &CanvasObject) {
+        if let CanvasObject::Stroke(stroke) = object {
+            self.broadcast(&CollabMessage::StrokeRemoved {
+                page: page as u32,
+                id: stroke.id,
+            });
+        }
+    }
+
+    /// Tells the other members that `object` on page `page` was moved by `delta`, if it is a stroke
+    pub fn broadcast_move(&self, page: usize, object: &CanvasObject, delta: egui::Vec2) {
+        if let CanvasObject::Stroke(stroke) = object {
+            self.broadcast(&CollabMessage::StrokeMoved {
+                page: page as u32,
+                id: stroke.id,
+                delta: [delta.x, delta.y],
+            });
+        }
+    }
+
+    pub fn broadcast_clear(&self, page: usize) {
+        self.broadcast(&CollabMessage::CanvasCleared { page: page as u32 });
+    }
+
+    fn broadcast(&self, message: &CollabMessage) {
+        let Some(shared) = &self.shared else {
+            return;
+        };
+        match rkyv::to_bytes::<rkyv::rancor::Error>(message) {
+            Ok(bytes) => shared.broadcast(&Arc::from(bytes.as_slice()), None),
+            Err(err) => eprintln!("failed to encode collaboration message: {err}"),
+        }
+    }
+
+    /// Drains pending events, updating the session status and decoding remote changes
+    pub fn poll(&mut self) -> Vec<CollabUpdate> {
+        let mut updates = Vec::new();
+        while let Ok(event) = self.events_rx.try_recv() {
+            match event {
+                CollabEvent::Started(code) => self.session_code = Some(code),
+                CollabEvent::PeersChanged(count) => self.peer_count = count,
+                CollabEvent::Message(payload) => match decode_message(&payload) {
+                    Ok(update) => updates.push(update),
+                    Err(err) => eprintln!("failed to decode collaboration message: {err}"),
+                },
+                CollabEvent::Failed(err) => {
+                    self.stop();
+                    updates.push(CollabUpdate::Failed(err));
+                }
+                CollabEvent::Disconnected => {
+                    self.stop();
+                    updates.push(CollabUpdate::Disconnected);
+                }
+            }
+        }
+        updates
+    }
+
+    fn start(&mut self, role: CollabRole) -> Arc<Shared> {
+        self.stop();
+        let shared = Arc::new(Shared {
+            role,
+            peers: Mutex::new(Vec::new()),
+            next_peer_id: AtomicU32::new(0),
+            stop: AtomicBool::new(false),
+            tx: self.events_tx.clone(),
+            waker: self.waker.clone(),
+        });
+        self.shared = Some(shared.clone());
+        shared
+    }
+}
+
+impl Drop for CollabSession {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct Peer {
+    id: u32,
+    stream: TcpStream,
+    // 发给该成员的消息由其写线程发送，慢速成员不会阻塞 UI 线程和其他成员
+    outgoing: Sender<Arc<[u8]>>,
+}
+
+// 后台线程与 UI 线程共享的会话状态
+struct Shared {
+    role: CollabRole,
+    peers: Mutex<Vec<Peer>>,
+    next_peer_id: AtomicU32,
+    stop: AtomicBool,
+    tx: Sender<CollabEvent>,
+    waker: Option<EventLoopProxy<()>>,
+}
+
+impl Shared {
+    fn peers(&self) -> MutexGuard<'_, Vec<Peer>> {
+        self.peers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn notify(&self, event: CollabEvent) {
+        // events from a session that has already been stopped are stale
+        if self.stop.load(Ordering::Relaxed) {
+            return;
+        }
+        let _ = self.tx.send(event);
+        if let Some(waker) = &self.waker {
+            let _ = waker.send_event(());
+        }
+    }
+
+    fn add_peer(self: &Arc<Self>, stream: TcpStream) -> io::Result<()> {
+        // accepted sockets inherit the listener's non-blocking mode on some platforms
+        stream.set_nonblocking(false)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        let reader = stream.try_clone()?;
+        let writer = stream.try_clone()?;
+        let (outgoing, queue) = mpsc::channel();
+
+        let id = self.next_peer_id.fetch_add(1, Ordering::Relaxed);
+        let count = {
+            let mut peers = self.peers();
+            peers.push(Peer {
+                id,
+                stream,
+                outgoing,
+            });
+            peers.len()
+        };
+        self.notify(CollabEvent::PeersChanged(count));
+
+        let shared = self.clone();
+        thread::spawn(move || shared.read_loop(id, reader));
+        let shared = self.clone();
+        thread::spawn(move || shared.write_loop(id, writer, queue));
+        Ok(())
+    }
+
+    // 移除成员后发送端随之释放，队列关闭时线程退出
+    fn write_loop(&self, id: u32, mut stream: TcpStream, queue: Receiver<Arc<[u8]>>) {
+        while let Ok(payload) = queue.recv() {
+            if super::write_frame(&mut stream, &payload).is_err() {
+                self.remove_peer(id);
+                return;
+            }
+        }
+    }
+
+    fn read_loop(&self, id: u32, mut stream: TcpStream) {
+        while let Ok(payload) = super::read_frame(&mut stream) {
+            if self.role == CollabRole::Host {
+                self.broadcast(&Arc::from(payload.as_slice()), Some(id));
+            }
+            self.notify(CollabEvent::Message(payload));
+        }
+        self.remove_peer(id);
+    }
+
+    // 只把消息放入各成员的发送队列，不在调用线程上写入网络
+    fn broadcast(&self, payload: &Arc<[u8]>, except: Option<u32>) {
+        for peer in self.peers().iter() {
+            if Some(peer.id) != except {
+                // 发送失败说明写线程已退出，成员会由它移除
+                let _ = peer.outgoing.send(payload.clone());
+            }
+        }
+    }
+
+    fn remove_peer(&self, id: u32) {
+        let count = {
+            let mut peers = self.peers();
+            let Some(index) = peers.iter().position(|peer| peer.id == id) else {
+                return;
+            };
+            let peer = peers.remove(index);
+            let _ = peer.stream.shutdown(Shutdown::Both);
+            peers.len()
+        };
+
+        if self.role == CollabRole::Guest {
+            self.notify(CollabEvent::Disconnected);
+        } else {
+            self.notify(CollabEvent::PeersChanged(count));
+        }
+    }
+}
+
+fn decode_message(payload: &[u8]) -> Result<CollabUpdate, rkyv::rancor::Error> {
+    let archived = rkyv::access::<ArchivedCollabMessage, rkyv::rancor::Error>(payload)?;
+    Ok(match archived {
+        ArchivedCollabMessage::StrokeAdded { page, stroke } => CollabUpdate::StrokeAdded {
+            page: page.to_native() as usize,
+            stroke: CanvasStroke::from(stroke),
+        },
+        ArchivedCollabMessage::StrokeRemoved { page, id } => CollabUpdate::StrokeRemoved {
+            page: page.to_native() as usize,
+            id: id.to_native(),
+        },
+        ArchivedCollabMessage::StrokeMoved { page, id, delta } => CollabUpdate::StrokeMoved {
+            page: page.to_native() as usize,
+            id: id.to_native(),
+            delta: egui::vec2(delta[0].into(), delta[1].into()),
+        },
+        ArchivedCollabMessage::CanvasCleared { page } => CollabUpdate::CanvasCleared {
+            page: page.to_native() as usize,
+        },
+    })
+}
+
+// 未指定端口时使用默认端口
+fn normalize_address(address: &str) -> String {
+    let address = address.trim();
+    if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{address}:{DEFAULT_PORT}")
+    }
+}
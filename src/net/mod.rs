@@ -0,0 +1,45 @@
+pub mod collab;
+pub mod spectator;
+pub mod transfer;
+
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, UdpSocket};
+
+/// Upper bound for a single frame, guards against garbage length prefixes
+const MAX_FRAME_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Writes a length-prefixed frame (u64 little-endian length followed by the payload)
+pub fn write_frame(stream: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    stream.write_all(&(payload.len() as u64).to_le_bytes())?;
+    stream.write_all(payload)?;
+    stream.flush()
+}
+
+/// Reads a frame written by [`write_frame`]
+pub fn read_frame(stream: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 8];
+    stream.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame too large ({len} bytes)"),
+        ));
+    }
+
+    // 长度前缀不可信，按实际收到的数据增长缓冲区，而不是预先分配
+    let mut payload = Vec::new();
+    stream.take(len).read_to_end(&mut payload)?;
+    if payload.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(payload)
+}
+
+/// Best-effort LAN address of this machine, used to show peers where to connect.
+/// Connecting a UDP socket only selects a route, no packets are sent.
+pub fn local_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).ok()?;
+    socket.connect(("8.8.8.8", 80)).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
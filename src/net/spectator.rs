@@ -0,0 +1,325 @@
+use std::io::{self, BufRead, BufReader, Cursor, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use winit::event_loop::EventLoopProxy;
+
+pub const DEFAULT_PORT: u16 = 38340;
+
+/// How often a new snapshot is captured while someone is watching
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+/// Snapshots stop being captured when no viewer has asked for one for this long
+const VIEWER_TIMEOUT: Duration = Duration::from_secs(5);
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_REQUEST_HEAD_LINES: usize = 64;
+
+const VIEWER_PAGE: &str = r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>uwu</title>
+<style>
+html, body { margin: 0; height: 100%; background: #202020; }
+img { display: block; width: 100%; height: 100%; object-fit: contain; }
+</style>
+</head>
+<body>
+<img id="board" src="/snapshot.png" alt="">
+<script>
+const board = document.getElementById("board");
+setInterval(() => {
+    const next = new Image();
+    next.onload = () => { board.src = next.src; };
+    next.src = "/snapshot.png?t=" + Date.now();
+}, 1000);
+</script>
+</body>
+</html>
+"#;
+
+/// Read-only view of the board for other devices on the LAN, served as periodically refreshed PNG snapshots
+pub struct SpectatorServer {
+    pub port: u16,
+    url: Option<String>,
+    waker: Option<EventLoopProxy<()>>,
+    shared: Option<Arc<Shared>>,
+    last_capture: Option<Instant>,
+    capturing: bool,
+}
+
+impl Default for SpectatorServer {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT,
+            url: None,
+            waker: None,
+            shared: None,
+            last_capture: None,
+            capturing: false,
+        }
+    }
+}
+
+impl SpectatorServer {
+    /// Sets the proxy used to request a redraw when viewers are waiting for a fresh snapshot
+    pub fn set_waker(&mut self, waker: EventLoopProxy<()>) {
+        self.waker = Some(waker);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.shared.is_some()
+    }
+
+    /// LAN address viewers should open, while the server is running
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    pub fn start(&mut self) -> io::Result<()> {
+        self.stop();
+
+        let listener = TcpListener::bind(("0.0.0.0", self.port))?;
+        listener.set_nonblocking(true)?;
+
+        let shared = Arc::new(Shared {
+            stop: AtomicBool::new(false),
+            snapshot: Mutex::new(None),
+            last_request: Mutex::new(None),
+            waker: self.waker.clone(),
+        });
+        self.shared = Some(shared.clone());
+        self.last_capture = None;
+
+        let ip = super::local_ip()
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "127.0.0.1".to_string());
+        self.url = Some(format!("http://{ip}:{}/", self.port));
+
+        thread::spawn(move || {
+            while !shared.stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let shared = shared.clone();
+                        thread::spawn(move || {
+                            if let Err(err) = shared.serve(stream) {
+                                eprintln!("spectator request failed: {err}");
+                            }
+                        });
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_POLL_INTERVAL);
+                    }
+                    Err(err) => {
+                        eprintln!("spectator server stopped: {err}");
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(shared) = self.shared.take() {
+            shared.stop.store(true, Ordering::Relaxed);
+        }
+        self.url = None;
+    }
+
+    /// Whether the next presented frame should be captured for viewers
+    pub fn wants_snapshot(&self) -> bool {
+        let Some(shared) = &self.shared else {
+            return false;
+        };
+        let has_viewers = shared
+            .last_request()
+            .is_some_and(|at| at.elapsed() < VIEWER_TIMEOUT);
+        has_viewers
+            && self
+                .last_capture
+                .is_none_or(|at| at.elapsed() >= SNAPSHOT_INTERVAL)
+    }
+
+    /// Decides whether the coming frame is drawn for viewers, with the UI hidden; postponed while
+    /// the user is `busy`, so that a hidden frame never interrupts a drag or an edit
+    pub fn prepare_capture(&mut self, busy: bool) -> bool {
+        self.capturing = !busy && self.wants_snapshot();
+        self.capturing
+    }
+
+    /// Whether the current frame is drawn for viewers
+    pub fn is_capturing(&self) -> bool {
+        self.capturing
+    }
+
+    /// Hands a captured RGBA8 frame to the server; encoding happens lazily on the request threads
+    pub fn submit_snapshot(&mut self, width: u32, height: u32, pixels: Vec<u8>) {
+        self.capturing = false;
+        let Some(shared) = &self.shared else {
+            return;
+        };
+        self.last_capture = Some(Instant::now());
+        *shared.snapshot() = Some(Snapshot {
+            width,
+            height,
+            captured_at: Instant::now(),
+            pixels: Arc::new(pixels),
+            png: None,
+        });
+    }
+}
+
+impl Drop for SpectatorServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct Snapshot {
+    width: u32,
+    height: u32,
+    captured_at: Instant,
+    pixels: Arc<Vec<u8>>,
+    png: Option<Arc<Vec<u8>>>,
+}
+
+struct Shared {
+    stop: AtomicBool,
+    snapshot: Mutex<Option<Snapshot>>,
+    last_request: Mutex<Option<Instant>>,
+    waker: Option<EventLoopProxy<()>>,
+}
+
+impl Shared {
+    fn snapshot(&self) -> MutexGuard<'_, Option<Snapshot>> {
+        self.snapshot.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn last_request(&self) -> Option<Instant> {
+        *self
+            .last_request
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn serve(&self, mut stream: TcpStream) -> io::Result<()> {
+        // accepted sockets inherit the listener's non-blocking mode on some platforms
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+        let (method, path) = {
+            let mut reader = BufReader::new(&mut stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line)?;
+
+            // 跳过剩余的请求头
+            let mut line = String::new();
+            for _ in 0..MAX_REQUEST_HEAD_LINES {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                    break;
+                }
+            }
+
+            let mut parts = request_line.split_whitespace();
+            let method = parts.next().unwrap_or_default().to_string();
+            let path = parts.next().unwrap_or_default().to_string();
+            (method, path)
+        };
+
+        if method != "GET" {
+            return respond(
+                &mut stream,
+                "405 Method Not Allowed",
+                "text/plain",
+                b"read only",
+            );
+        }
+
+        match path.split('?').next().unwrap_or_default() {
+            "/" | "/index.html" => respond(
+                &mut stream,
+                "200 OK",
+                "text/html; charset=utf-8",
+                VIEWER_PAGE.as_bytes(),
+            ),
+            "/snapshot.png" => match self.snapshot_png() {
+                Some(png) => respond(&mut stream, "200 OK", "image/png", &png),
+                None => respond(
+                    &mut stream,
+                    "503 Service Unavailable",
+                    "text/plain",
+                    b"no snapshot yet",
+                ),
+            },
+            _ => respond(&mut stream, "404 Not Found", "text/plain", b"not found"),
+        }
+    }
+
+    // 记录观看请求, 快照过期时唤醒 UI 线程重新截取
+    fn snapshot_png(&self) -> Option<Arc<Vec<u8>>> {
+        *self
+            .last_request
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Instant::now());
+
+        let mut snapshot = self.snapshot();
+        let is_stale = snapshot
+            .as_ref()
+            .is_none_or(|s| s.captured_at.elapsed() >= SNAPSHOT_INTERVAL);
+        if is_stale && let Some(waker) = &self.waker {
+            let _ = waker.send_event(());
+        }
+
+        let current = snapshot.as_ref()?;
+        if let Some(png) = &current.png {
+            return Some(png.clone());
+        }
+        let (width, height, captured_at) = (current.width, current.height, current.captured_at);
+        let pixels = current.pixels.clone();
+        // 编码较慢, 不持有锁以免阻塞 UI 线程提交新快照
+        drop(snapshot);
+
+        let mut png = Vec::new();
+        if let Err(err) = image::write_buffer_with_format(
+            &mut Cursor::new(&mut png),
+            &pixels,
+            width,
+            height,
+            image::ColorType::Rgba8,
+            image::ImageFormat::Png,
+        ) {
+            eprintln!("failed to encode spectator snapshot: {err}");
+            return None;
+        }
+        let png = Arc::new(png);
+
+        if let Some(current) = self.snapshot().as_mut()
+            && current.captured_at == captured_at
+        {
+            current.png = Some(png.clone());
+        }
+        Some(png)
+    }
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
@@ -0,0 +1,287 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use winit::event_loop::EventLoopProxy;
+
+use crate::i18n::{t, tf};
+
+pub const DEFAULT_PORT: u16 = 38338;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const ACK: &[u8; 2] = b"OK";
+
+/// Events reported by the background transfer threads
+pub enum TransferEvent {
+    Listening(SocketAddr),
+    ListenFailed(String),
+    Received { from: SocketAddr, payload: Vec<u8> },
+    Sent(String),
+    Failed(String),
+}
+
+// 监听线程的事件带有其代数，停止或重新开始监听后旧线程的事件不再影响状态
+struct TaggedEvent {
+    listener: Option<u64>,
+    event: TransferEvent,
+}
+
+/// Last known state of the board transfer, shown in settings
+#[derive(Debug, Clone, Default)]
+pub enum TransferStatus {
+    #[default]
+    Idle,
+    Listening(SocketAddr),
+    Sending(String),
+    Sent(String),
+    Received(SocketAddr),
+    Failed(String),
+}
+
+impl fmt::Display for TransferStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferStatus::Idle => write!(f, "{}", t("空闲")),
+            TransferStatus::Listening(addr) => write!(f, "{}", tf("正在监听 {}", &[addr])),
+            TransferStatus::Sending(addr) => write!(f, "{}", tf("正在发送至 {}...", &[addr])),
+            TransferStatus::Sent(addr) => write!(f, "{}", tf("已发送至 {}", &[addr])),
+            TransferStatus::Received(addr) => {
+                write!(f, "{}", tf("已接收来自 {} 的画布", &[addr]))
+            }
+            TransferStatus::Failed(err) => write!(f, "{}", tf("失败: {}", &[err])),
+        }
+    }
+}
+
+/// One-shot snapshot transfer of a board to another instance over TCP.
+/// The payload is the regular canvas file format, so no separate protocol versioning is needed.
+pub struct BoardTransfer {
+    pub listen_port: u16,
+    pub peer_address: String,
+    pub status: TransferStatus,
+    waker: Option<EventLoopProxy<()>>,
+    events_tx: Sender<TaggedEvent>,
+    events_rx: Receiver<TaggedEvent>,
+    listener_stop: Option<Arc<AtomicBool>>,
+    listener_generation: u64,
+}
+
+impl Default for BoardTransfer {
+    fn default() -> Self {
+        let (events_tx, events_rx) = mpsc::channel();
+        Self {
+            listen_port: DEFAULT_PORT,
+            peer_address: String::new(),
+            status: TransferStatus::default(),
+            waker: None,
+            events_tx,
+            events_rx,
+            listener_stop: None,
+            listener_generation: 0,
+        }
+    }
+}
+
+impl BoardTransfer {
+    /// Sets the proxy used to wake up the event loop when a background event arrives
+    pub fn set_waker(&mut self, waker: EventLoopProxy<()>) {
+        self.waker = Some(waker);
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.listener_stop.is_some()
+    }
+
+    /// Starts accepting boards from peers on `listen_port`
+    pub fn start_listening(&mut self) {
+        self.stop_listening();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        self.listener_stop = Some(stop.clone());
+        self.listener_generation += 1;
+
+        let port = self.listen_port;
+        let notifier = Notifier {
+            listener: Some(self.listener_generation),
+            ..self.notifier()
+        };
+        thread::spawn(move || {
+            let listener = match TcpListener::bind(("0.0.0.0", port))
+                .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            {
+                Ok(listener) => listener,
+                Err(err) => {
+                    notifier.notify(TransferEvent::ListenFailed(tf(
+                        "无法监听端口 {}: {}",
+                        &[&port, &err],
+                    )));
+                    return;
+                }
+            };
+
+            if let Ok(addr) = listener.local_addr() {
+                notifier.notify(TransferEvent::Listening(addr));
+            }
+
+            while !stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((mut stream, from)) => match receive_board(&mut stream) {
+                        Ok(payload) => notifier.notify(TransferEvent::Received { from, payload }),
+                        Err(err) => notifier.notify(TransferEvent::Failed(tf(
+                            "接收来自 {} 的画布失败: {}",
+                            &[&from, &err],
+                        ))),
+                    },
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_POLL_INTERVAL);
+                    }
+                    Err(err) => {
+                        notifier.notify(TransferEvent::ListenFailed(tf("监听中断: {}", &[&err])));
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    pub fn stop_listening(&mut self) {
+        if let Some(stop) = self.listener_stop.take() {
+            stop.store(true, Ordering::Relaxed);
+            self.status = TransferStatus::Idle;
+        }
+    }
+
+    /// Sends an encoded board to `peer_address` in the background
+    pub fn send(&mut self, payload: Vec<u8>) {
+        let address = normalize_address(&self.peer_address);
+        self.status = TransferStatus::Sending(address.clone());
+
+        let notifier = self.notifier();
+        thread::spawn(move || match send_board(&address, &payload) {
+            Ok(()) => notifier.notify(TransferEvent::Sent(address)),
+            Err(err) => {
+                notifier.notify(TransferEvent::Failed(tf(
+                    "发送至 {} 失败: {}",
+                    &[&address, &err],
+                )));
+            }
+        });
+    }
+
+    /// Drains pending events, updating `status` accordingly
+    pub fn poll(&mut self) -> Vec<TransferEvent> {
+        let current_listener = self
+            .listener_stop
+            .is_some()
+            .then_some(self.listener_generation);
+        let events: Vec<TransferEvent> = self
+            .events_rx
+            .try_iter()
+            .filter(|tagged| {
+                // 旧监听线程收到的画布仍然有效，只忽略其监听状态
+                tagged.listener.is_none()
+                    || tagged.listener == current_listener
+                    || !matches!(
+                        tagged.event,
+                        TransferEvent::Listening(_) | TransferEvent::ListenFailed(_)
+                    )
+            })
+            .map(|tagged| tagged.event)
+            .collect();
+        for event in &events {
+            self.status = match event {
+                TransferEvent::Listening(addr) => TransferStatus::Listening(*addr),
+                TransferEvent::ListenFailed(err) => {
+                    self.listener_stop = None;
+                    TransferStatus::Failed(err.clone())
+                }
+                TransferEvent::Received { from, .. } => TransferStatus::Received(*from),
+                TransferEvent::Sent(addr) => TransferStatus::Sent(addr.clone()),
+                TransferEvent::Failed(err) => TransferStatus::Failed(err.clone()),
+            };
+        }
+        events
+    }
+
+    fn notifier(&self) -> Notifier {
+        Notifier {
+            tx: self.events_tx.clone(),
+            waker: self.waker.clone(),
+            listener: None,
+        }
+    }
+}
+
+impl Drop for BoardTransfer {
+    fn drop(&mut self) {
+        self.stop_listening();
+    }
+}
+
+struct Notifier {
+    tx: Sender<TaggedEvent>,
+    waker: Option<EventLoopProxy<()>>,
+    listener: Option<u64>,
+}
+
+impl Notifier {
+    fn notify(&self, event: TransferEvent) {
+        let _ = self.tx.send(TaggedEvent {
+            listener: self.listener,
+            event,
+        });
+        if let Some(waker) = &self.waker {
+            let _ = waker.send_event(());
+        }
+    }
+}
+
+// 未指定端口时使用默认端口
+fn normalize_address(address: &str) -> String {
+    let address = address.trim();
+    if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{address}:{DEFAULT_PORT}")
+    }
+}
+
+fn send_board(address: &str, payload: &[u8]) -> io::Result<()> {
+    let addr = address.to_socket_addrs()?.next().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, tf("无法解析地址 {}", &[&address]))
+    })?;
+
+    let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    super::write_frame(&mut stream, payload)?;
+
+    let mut ack = [0u8; 2];
+    stream.read_exact(&mut ack)?;
+    if &ack != ACK {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "peer did not acknowledge the board",
+        ));
+    }
+    Ok(())
+}
+
+fn receive_board(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    // accepted sockets inherit the listener's non-blocking mode on some platforms
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    let payload = super::read_frame(stream)?;
+    stream.write_all(ACK)?;
+    Ok(payload)
+}
@@ -1,21 +1,88 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use egui::Context;
 use egui_wgpu::wgpu;
 use egui_wgpu::wgpu::ExperimentalFeatures;
 use egui_wgpu::wgpu::{CommandEncoder, Device, Queue, StoreOp, TextureFormat, TextureView};
 use egui_wgpu::{Renderer, RendererOptions, ScreenDescriptor};
 use egui_winit::State;
-use wgpu::TextureUsages;
+use wgpu::{TexelCopyBufferInfo, TexelCopyBufferLayout, TexelCopyTextureInfo, TextureUsages};
 use winit::event::WindowEvent;
 use winit::window::Window;
 
 use crate::state::OptimizationPolicy;
 use crate::utils;
 
+// 自动模式总是可用，其他模式不受支持时回退到垂直同步
+fn resolve_present_mode(
+    present_mode: wgpu::PresentMode,
+    supported: &[wgpu::PresentMode],
+) -> wgpu::PresentMode {
+    match present_mode {
+        wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync => present_mode,
+        _ if supported.contains(&present_mode) => present_mode,
+        _ => wgpu::PresentMode::AutoVsync,
+    }
+}
+
+// 不支持请求的采样数时使用支持的最大的较小采样数，1 总是可用
+fn resolve_msaa_samples(requested: u32, supported: &[u32]) -> u32 {
+    supported
+        .iter()
+        .copied()
+        .filter(|&samples| samples <= requested)
+        .max()
+        .unwrap_or(1)
+}
+
+fn create_msaa_view(
+    device: &Device,
+    config: &wgpu::SurfaceConfiguration,
+    samples: u32,
+) -> Option<TextureView> {
+    if samples <= 1 || config.width == 0 || config.height == 0 {
+        return None;
+    }
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("msaa color target"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: samples,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
+/// No GPU adapter, not even the software fallback, can render to the window
+#[derive(Debug)]
+pub struct NoCompatibleAdapter;
+
+impl std::fmt::Display for NoCompatibleAdapter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("no compatible gpu adapter found")
+    }
+}
+
+impl std::error::Error for NoCompatibleAdapter {}
+
 pub struct RenderState {
+    device_lost: Arc<AtomicBool>, // 由设备丢失回调设置（例如显卡驱动重置）
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub surface_config: wgpu::SurfaceConfiguration,
     pub surface: wgpu::Surface<'static>,
+    pub supported_present_modes: Vec<wgpu::PresentMode>,
+    supported_msaa_samples: Vec<u32>,
+    msaa_samples: u32,
+    pub msaa_view: Option<TextureView>, // 多重采样时绘制到此纹理，再解析到交换链纹理
     pub scale_factor: f32,
     pub egui_renderer: EguiRenderer,
 }
@@ -29,15 +96,25 @@ impl RenderState {
         height: u32,
         optimization_policy: OptimizationPolicy,
         present_mode: wgpu::PresentMode,
-    ) -> Self {
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .expect("failed to find an appropriate adapter");
+        msaa_samples: u32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut options = wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            compatible_surface: Some(&surface),
+        };
+        // 没有可用的硬件显卡时尝试软件渲染
+        let adapter = match instance.request_adapter(&options).await {
+            Ok(adapter) => adapter,
+            Err(err) => {
+                eprintln!("failed to find a hardware adapter ({err}), trying the fallback adapter");
+                options.force_fallback_adapter = true;
+                instance.request_adapter(&options).await.map_err(|err| {
+                    eprintln!("failed to find a fallback adapter: {err}");
+                    NoCompatibleAdapter
+                })?
+            }
+        };
 
         let info = adapter.get_info();
         println!("using gpu device: {}", info.name);
@@ -56,7 +133,19 @@ impl RenderState {
                 experimental_features: ExperimentalFeatures::default(),
             })
             .await
-            .expect("failed to create device");
+            .map_err(|err| format!("failed to create device: {err}"))?;
+
+        let device_lost = Arc::new(AtomicBool::new(false));
+        {
+            let device_lost = device_lost.clone();
+            device.set_device_lost_callback(move |reason, message| {
+                eprintln!("gpu device lost ({reason:?}): {message}");
+                device_lost.store(true, Ordering::Relaxed);
+            });
+        }
+
+        let supported_present_modes = surface.get_capabilities(&adapter).present_modes;
+        let present_mode = resolve_present_mode(present_mode, &supported_present_modes);
 
         let surface_config = wgpu::SurfaceConfiguration {
             usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
@@ -71,36 +160,182 @@ impl RenderState {
 
         surface.configure(&device, &surface_config);
 
+        let format_flags = adapter
+            .get_texture_format_features(surface_config.format)
+            .flags;
+        let supported_msaa_samples: Vec<u32> = [1, 2, 4, 8]
+            .into_iter()
+            .filter(|&samples| format_flags.sample_count_supported(samples))
+            .collect();
+        let msaa_samples = resolve_msaa_samples(msaa_samples, &supported_msaa_samples);
+        let msaa_view = create_msaa_view(&device, &surface_config, msaa_samples);
+
         const SCALE_FACTOR: f32 = 1.0;
 
         let egui_renderer = EguiRenderer::new(
             &device,
             surface_config.format,
             None,
-            1,
+            msaa_samples,
             window,
             SCALE_FACTOR,
         );
 
-        Self {
+        Ok(Self {
+            device_lost,
             device,
             queue,
             surface,
             surface_config,
+            supported_present_modes,
+            supported_msaa_samples,
+            msaa_samples,
+            msaa_view,
             egui_renderer,
             scale_factor: SCALE_FACTOR,
-        }
+        })
     }
 
     pub fn resize_surface(&mut self, width: u32, height: u32) {
         self.surface_config.width = width;
         self.surface_config.height = height;
+        self.reconfigure_surface();
+        self.msaa_view = create_msaa_view(&self.device, &self.surface_config, self.msaa_samples);
+    }
+
+    /// Configures the surface again with the current settings, e.g. after it became outdated
+    pub fn reconfigure_surface(&mut self) {
         self.surface.configure(&self.device, &self.surface_config);
     }
 
-    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+    /// Replaces a lost surface with a new one for `window`
+    pub fn recreate_surface(
+        &mut self,
+        instance: &wgpu::Instance,
+        window: Arc<Window>,
+    ) -> Result<(), wgpu::CreateSurfaceError> {
+        self.surface = instance.create_surface(window)?;
+        self.reconfigure_surface();
+        Ok(())
+    }
+
+    /// Whether the device has been lost and the render state has to be recreated
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Switches to `present_mode`, falling back to a supported mode; returns the mode actually used
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) -> wgpu::PresentMode {
+        let present_mode = resolve_present_mode(present_mode, &self.supported_present_modes);
         self.surface_config.present_mode = present_mode;
         self.surface.configure(&self.device, &self.surface_config);
+        present_mode
+    }
+
+    /// Number of samples per pixel used for multisample antialiasing, 1 when it is off
+    pub fn msaa_samples(&self) -> u32 {
+        self.msaa_samples
+    }
+
+    /// Switches to `samples` per pixel, falling back to a supported count; returns the count actually used.
+    ///
+    /// The egui renderer is rebuilt for a new count, which loses its textures and creates a new egui context.
+    pub fn set_msaa_samples(&mut self, window: &Window, samples: u32) -> u32 {
+        let samples = resolve_msaa_samples(samples, &self.supported_msaa_samples);
+        if samples != self.msaa_samples {
+            self.msaa_samples = samples;
+            self.msaa_view = create_msaa_view(&self.device, &self.surface_config, samples);
+            self.egui_renderer = EguiRenderer::new(
+                &self.device,
+                self.surface_config.format,
+                None,
+                samples,
+                window,
+                self.scale_factor,
+            );
+        }
+        samples
+    }
+
+    /// Submits `encoder` and reads `texture` back as tightly packed RGBA8 pixels, blocking until the gpu is done
+    pub fn read_surface_pixels(
+        &self,
+        mut encoder: CommandEncoder,
+        texture: &wgpu::Texture,
+    ) -> Vec<u8> {
+        let width = self.surface_config.width;
+        let height = self.surface_config.height;
+
+        let bytes_per_pixel = 4;
+        let unpadded_bytes_per_row = width * bytes_per_pixel;
+
+        // wgpu requires 256-byte alignment
+        const ALIGN: u32 = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(ALIGN) * ALIGN;
+
+        let buffer_size = (padded_bytes_per_row * height) as u64;
+
+        let output_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("screenshot buffer"),
+            size: buffer_size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            TexelCopyBufferInfo {
+                buffer: &output_buffer,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        self.queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = output_buffer.slice(..);
+
+        buffer_slice.map_async(wgpu::MapMode::Read, |_| {});
+
+        // ensure gpu work is done
+        let _ = self.device.poll(wgpu::wgt::PollType::Wait {
+            submission_index: None,
+            timeout: None,
+        });
+
+        let data = buffer_slice.get_mapped_range();
+
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+
+        for y in 0..height as usize {
+            let src_offset = y * padded_bytes_per_row as usize;
+            let dst_offset = y * unpadded_bytes_per_row as usize;
+
+            pixels[dst_offset..dst_offset + unpadded_bytes_per_row as usize]
+                .copy_from_slice(&data[src_offset..src_offset + unpadded_bytes_per_row as usize]);
+        }
+
+        for chunk in pixels.chunks_exact_mut(4) {
+            chunk.swap(0, 2); // B ↔ R
+        }
+
+        drop(data);
+        output_buffer.unmap();
+
+        pixels
     }
 }
 
@@ -171,6 +406,17 @@ impl EguiRenderer {
         self.frame_started = true;
     }
 
+    /// Starts a frame that is only read back, never shown; its input is kept for the next frame
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn begin_capture_frame(&mut self, window: &Window) {
+        let mut raw_input = self.state.take_egui_input(window);
+        // 截取的这一帧不响应输入，事件留到下一帧按原顺序处理
+        let events = std::mem::take(&mut raw_input.events);
+        self.state.egui_input_mut().events.splice(0..0, events);
+        self.state.egui_ctx().begin_pass(raw_input);
+        self.frame_started = true;
+    }
+
     #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn end_frame_and_draw(
         &mut self,
@@ -179,6 +425,7 @@ impl EguiRenderer {
         encoder: &mut CommandEncoder,
         window: &Window,
         window_surface_view: &TextureView,
+        msaa_view: Option<&TextureView>,
         screen_descriptor: ScreenDescriptor,
     ) {
         if !self.frame_started {
@@ -232,9 +479,10 @@ impl EguiRenderer {
         }
         let rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("egui main render pass"),
+            // 多重采样时绘制到多重采样纹理并解析到交换链，多重采样纹理本身无需保留
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: window_surface_view,
-                resolve_target: None,
+                view: msaa_view.unwrap_or(window_surface_view),
+                resolve_target: msaa_view.map(|_| window_surface_view),
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(wgpu::Color {
                         r: 0.0_f64,
@@ -242,7 +490,11 @@ impl EguiRenderer {
                         b: 0.0_f64,
                         a: 0.0_f64,
                     }),
-                    store: StoreOp::Store,
+                    store: if msaa_view.is_some() {
+                        StoreOp::Discard
+                    } else {
+                        StoreOp::Store
+                    },
                 },
                 depth_slice: None,
             })],
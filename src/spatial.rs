@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+use std::ops::{Index, IndexMut, Range};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use egui::{Pos2, Rect};
+
+use crate::state::{CanvasObject, CanvasObjectOps};
+
+/// Side length of a chunk, in canvas units
+const CHUNK_SIZE: f32 = 512.0;
+/// Hit tests accept points slightly outside an object's bounding box, so queries are widened by this much
+const QUERY_MARGIN: f32 = 16.0;
+/// Longest journal kept before it is collapsed into [`ObjectChange::Replaced`], so that a board
+/// nobody collects the changes of doesn't grow without bound
+const MAX_JOURNAL_LEN: usize = 4096;
+
+/// Identifies an object for as long as it stays in the same [`ChunkedObjects`]
+pub type ObjectId = u64;
+
+type ChunkKey = (i32, i32);
+
+// 存储实例的编号，画布整体被替换时编号随之改变
+static NEXT_STORE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A change recorded by [`ChunkedObjects`] while its journal is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectChange {
+    Added(ObjectId),
+    Removed(ObjectId),
+    /// The object was borrowed mutably, it may or may not have changed
+    Modified(ObjectId),
+    /// Too much changed to list one by one, e.g. the board was cleared or reordered
+    Replaced,
+}
+
+/// The objects of a canvas in drawing order, stored in square chunks of the board by where they
+/// are, so that finding the objects in a region only looks at the chunks around it.
+/// Objects are addressed by their drawing-order index like in a `Vec`; borrowing one mutably
+/// moves it to the chunk it now belongs to before the next query.
+#[derive(Debug)]
+pub struct ChunkedObjects {
+    chunks: HashMap<ChunkKey, HashMap<ObjectId, CanvasObject>>,
+    // 大于一个块的对象单独存放，每次查询都会检查
+    large: HashMap<ObjectId, CanvasObject>,
+    order: Vec<ObjectId>,
+    home: HashMap<ObjectId, Option<ChunkKey>>,
+    // 对象在 order 中的下标；在中间插入或删除后失效，下次查询时重建
+    positions: HashMap<ObjectId, usize>,
+    positions_valid: bool,
+    dirty: Vec<ObjectId>, // 被可变借用过、可能需要换块的对象
+    next_id: ObjectId,
+    store_id: u64,
+    journal: Option<Vec<ObjectChange>>,
+}
+
+impl Default for ChunkedObjects {
+    fn default() -> Self {
+        Self {
+            chunks: HashMap::new(),
+            large: HashMap::new(),
+            order: Vec::new(),
+            home: HashMap::new(),
+            positions: HashMap::new(),
+            positions_valid: true,
+            dirty: Vec::new(),
+            next_id: 0,
+            store_id: NEXT_STORE_ID.fetch_add(1, Ordering::Relaxed),
+            journal: None,
+        }
+    }
+}
+
+// 副本是独立的存储，有自己的编号且不记录修改
+impl Clone for ChunkedObjects {
+    fn clone(&self) -> Self {
+        Self {
+            chunks: self.chunks.clone(),
+            large: self.large.clone(),
+            order: self.order.clone(),
+            home: self.home.clone(),
+            positions: self.positions.clone(),
+            positions_valid: self.positions_valid,
+            dirty: self.dirty.clone(),
+            next_id: self.next_id,
+            ..Default::default()
+        }
+    }
+}
+
+impl ChunkedObjects {
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&CanvasObject> {
+        self.order.get(index).map(|&id| self.object(id))
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut CanvasObject> {
+        let id = *self.order.get(index)?;
+        Some(self.object_mut(id))
+    }
+
+    /// Iterates the objects in drawing order
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &CanvasObject> + DoubleEndedIterator + '_ {
+        self.order.iter().map(|&id| self.object(id))
+    }
+
+    /// Calls `f` on every object, in no particular order
+    pub fn for_each_mut(&mut self, mut f: impl FnMut(&mut CanvasObject)) {
+        for (&id, object) in self
+            .chunks
+            .values_mut()
+            .flat_map(|chunk| chunk.iter_mut())
+            .chain(self.large.iter_mut())
+        {
+            f(object);
+            self.dirty.push(id);
+        }
+        for &id in &self.order {
+            note(&mut self.journal, ObjectChange::Modified(id));
+        }
+    }
+
+    pub fn push(&mut self, object: CanvasObject) {
+        let id = self.store(object);
+        self.positions.insert(id, self.order.len());
+        self.order.push(id);
+    }
+
+    pub fn insert(&mut self, index: usize, object: CanvasObject) {
+        assert!(index <= self.order.len(), "insertion index out of bounds");
+        if index == self.order.len() {
+            self.push(object);
+            return;
+        }
+        let id = self.store(object);
+        self.order.insert(index, id);
+        self.positions_valid = false;
+    }
+
+    pub fn remove(&mut self, index: usize) -> CanvasObject {
+        let id = self.order.remove(index);
+        if index != self.order.len() {
+            self.positions_valid = false;
+        }
+        self.take(id)
+    }
+
+    /// Replaces the objects in `range` with `replacement` and returns the removed ones
+    pub fn splice(
+        &mut self,
+        range: Range<usize>,
+        replacement: impl IntoIterator<Item = CanvasObject>,
+    ) -> Vec<CanvasObject> {
+        let removed: Vec<CanvasObject> = range.clone().map(|_| self.remove(range.start)).collect();
+        for (offset, object) in replacement.into_iter().enumerate() {
+            self.insert(range.start + offset, object);
+        }
+        removed
+    }
+
+    /// Removes the objects in `range` and returns them
+    pub fn drain(&mut self, range: Range<usize>) -> Vec<CanvasObject> {
+        self.splice(range, [])
+    }
+
+    /// Keeps only the objects for which `keep` returns true
+    pub fn retain(&mut self, mut keep: impl FnMut(&CanvasObject) -> bool) {
+        let mut index = 0;
+        while index < self.order.len() {
+            if keep(self.object(self.order[index])) {
+                index += 1;
+            } else {
+                self.remove(index);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.take_all();
+    }
+
+    /// Removes every object and returns them in drawing order
+    pub fn take_all(&mut self) -> Vec<CanvasObject> {
+        self.replace_all(Vec::new())
+    }
+
+    /// Replaces every object with `objects` and returns the previous ones in drawing order
+    pub fn replace_all(&mut self, objects: Vec<CanvasObject>) -> Vec<CanvasObject> {
+        let old = std::mem::take(&mut self.order)
+            .into_iter()
+            .map(|id| self.take(id))
+            .collect();
+        self.chunks.clear();
+        self.large.clear();
+        self.positions.clear();
+        self.positions_valid = true;
+        self.dirty.clear();
+        self.extend(objects);
+        note(&mut self.journal, ObjectChange::Replaced);
+        old
+    }
+
+    /// Indices of the objects whose bounding box may intersect `rect`, in ascending (drawing) order.
+    /// Only the chunks around `rect` are looked at.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn query(&mut self, rect: Rect) -> Vec<usize> {
+        self.flush();
+        let rect = rect.expand(QUERY_MARGIN);
+        // 块内对象的中心在块内且不大于块，最多超出块边界半个块
+        let (min, max) = chunk_range(rect.expand(CHUNK_SIZE / 2.0));
+        let chunk_count = (max.0 as i64 - min.0 as i64 + 1) * (max.1 as i64 - min.1 as i64 + 1);
+
+        let mut result = Vec::new();
+        let mut collect = |objects: &HashMap<ObjectId, CanvasObject>| {
+            for (id, object) in objects {
+                if object.bounding_box().intersects(rect) {
+                    result.push(self.positions[id]);
+                }
+            }
+        };
+        collect(&self.large);
+        if chunk_count > self.chunks.len() as i64 {
+            // 缩得很小时查询范围覆盖大量空块，改为遍历已有对象的块
+            for (&(x, y), chunk) in &self.chunks {
+                if (min.0..=max.0).contains(&x) && (min.1..=max.1).contains(&y) {
+                    collect(chunk);
+                }
+            }
+        } else {
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    if let Some(chunk) = self.chunks.get(&(x, y)) {
+                        collect(chunk);
+                    }
+                }
+            }
+        }
+        result.sort_unstable();
+        result
+    }
+
+    /// Identifies this store; a board that is swapped out as a whole, e.g. by switching pages,
+    /// comes with a different one
+    pub fn store_id(&self) -> u64 {
+        self.store_id
+    }
+
+    /// Starts (or restarts) recording changes for [`Self::take_changes`]
+    pub fn start_journal(&mut self) {
+        self.journal = Some(Vec::new());
+    }
+
+    /// The changes made since the journal was started or last taken
+    pub fn take_changes(&mut self) -> Vec<ObjectChange> {
+        self.journal
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    pub fn get_by_id(&self, id: ObjectId) -> Option<&CanvasObject> {
+        let home = self.home.get(&id)?;
+        self.bucket(*home).get(&id)
+    }
+
+    /// Ids of the objects in drawing order
+    pub fn ids(&self) -> &[ObjectId] {
+        &self.order
+    }
+
+    /// Drawing-order index of the object `id`
+    pub fn index_of(&mut self, id: ObjectId) -> Option<usize> {
+        self.rebuild_positions();
+        self.positions.get(&id).copied()
+    }
+
+    fn object(&self, id: ObjectId) -> &CanvasObject {
+        &self.bucket(self.home[&id])[&id]
+    }
+
+    fn object_mut(&mut self, id: ObjectId) -> &mut CanvasObject {
+        self.dirty.push(id);
+        note(&mut self.journal, ObjectChange::Modified(id));
+        let home = self.home[&id];
+        self.bucket_mut(home)
+            .get_mut(&id)
+            .expect("object is stored in its home chunk")
+    }
+
+    fn bucket(&self, key: Option<ChunkKey>) -> &HashMap<ObjectId, CanvasObject> {
+        match key {
+            Some(key) => &self.chunks[&key],
+            None => &self.large,
+        }
+    }
+
+    fn bucket_mut(&mut self, key: Option<ChunkKey>) -> &mut HashMap<ObjectId, CanvasObject> {
+        match key {
+            Some(key) => self.chunks.entry(key).or_default(),
+            None => &mut self.large,
+        }
+    }
+
+    // 将对象放入其所在的块并分配编号，不修改绘制顺序
+    fn store(&mut self, object: CanvasObject) -> ObjectId {
+        let id = self.next_id;
+        self.next_id += 1;
+        let key = chunk_of(object.bounding_box());
+        self.bucket_mut(key).insert(id, object);
+        self.home.insert(id, key);
+        note(&mut self.journal, ObjectChange::Added(id));
+        id
+    }
+
+    // 从所在的块中取出对象，调用方负责从绘制顺序中移除
+    fn take(&mut self, id: ObjectId) -> CanvasObject {
+        let key = self.home.remove(&id).expect("object has a home chunk");
+        let object = self
+            .bucket_mut(key)
+            .remove(&id)
+            .expect("object is stored in its home chunk");
+        if let Some(key) = key
+            && self.chunks.get(&key).is_some_and(HashMap::is_empty)
+        {
+            self.chunks.remove(&key);
+        }
+        self.positions.remove(&id);
+        note(&mut self.journal, ObjectChange::Removed(id));
+        object
+    }
+
+    // 把被修改过的对象移到新的块，并在需要时重建下标表
+    fn flush(&mut self) {
+        for id in std::mem::take(&mut self.dirty) {
+            let Some(&home) = self.home.get(&id) else {
+                continue; // 之后已被删除
+            };
+            let key = chunk_of(self.bucket(home)[&id].bounding_box());
+            if key == home {
+                continue;
+            }
+            let object = self.bucket_mut(home).remove(&id).expect("object is stored");
+            if let Some(home) = home
+                && self.chunks.get(&home).is_some_and(HashMap::is_empty)
+            {
+                self.chunks.remove(&home);
+            }
+            self.bucket_mut(key).insert(id, object);
+            self.home.insert(id, key);
+        }
+        self.rebuild_positions();
+    }
+
+    fn rebuild_positions(&mut self) {
+        if self.positions_valid {
+            return;
+        }
+        self.positions.clear();
+        self.positions.extend(
+            self.order
+                .iter()
+                .enumerate()
+                .map(|(index, &id)| (id, index)),
+        );
+        self.positions_valid = true;
+    }
+}
+
+impl Index<usize> for ChunkedObjects {
+    type Output = CanvasObject;
+
+    fn index(&self, index: usize) -> &CanvasObject {
+        self.object(self.order[index])
+    }
+}
+
+impl IndexMut<usize> for ChunkedObjects {
+    fn index_mut(&mut self, index: usize) -> &mut CanvasObject {
+        self.object_mut(self.order[index])
+    }
+}
+
+impl Extend<CanvasObject> for ChunkedObjects {
+    fn extend<I: IntoIterator<Item = CanvasObject>>(&mut self, objects: I) {
+        for object in objects {
+            self.push(object);
+        }
+    }
+}
+
+impl FromIterator<CanvasObject> for ChunkedObjects {
+    fn from_iter<I: IntoIterator<Item = CanvasObject>>(objects: I) -> Self {
+        let mut store = Self::default();
+        store.extend(objects);
+        store
+    }
+}
+
+impl From<Vec<CanvasObject>> for ChunkedObjects {
+    fn from(objects: Vec<CanvasObject>) -> Self {
+        objects.into_iter().collect()
+    }
+}
+
+fn note(journal: &mut Option<Vec<ObjectChange>>, change: ObjectChange) {
+    let Some(journal) = journal else {
+        return;
+    };
+    // 已需要整体替换时不必再记录单个修改
+    if journal.first() == Some(&ObjectChange::Replaced) {
+        return;
+    }
+    if change == ObjectChange::Replaced || journal.len() >= MAX_JOURNAL_LEN {
+        journal.clear();
+        journal.push(ObjectChange::Replaced);
+    } else {
+        journal.push(change);
+    }
+}
+
+// 对象按包围盒中心归入块，大于一个块的对象返回 None
+fn chunk_of(bounds: Rect) -> Option<ChunkKey> {
+    if bounds.width() > CHUNK_SIZE || bounds.height() > CHUNK_SIZE {
+        return None;
+    }
+    Some(chunk_key(bounds.center()))
+}
+
+fn chunk_key(pos: Pos2) -> ChunkKey {
+    (
+        (pos.x / CHUNK_SIZE).floor() as i32,
+        (pos.y / CHUNK_SIZE).floor() as i32,
+    )
+}
+
+fn chunk_range(rect: Rect) -> (ChunkKey, ChunkKey) {
+    (chunk_key(rect.min), chunk_key(rect.max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{BrushStyle, CanvasStroke, LineStyle, StrokeWidth};
+
+    const STROKE_COUNT: usize = 1000;
+    const SPACING: f32 = 100.0;
+
+    fn stroke_at(origin: Pos2, extent: egui::Vec2) -> CanvasObject {
+        CanvasObject::Stroke(
+            CanvasStroke {
+                id: CanvasStroke::new_id(),
+                points: vec![origin, origin + extent],
+                width: StrokeWidth::Fixed(4.0),
+                color: egui::Color32::BLACK,
+                base_width: 4.0,
+                rot: 0.0,
+                is_highlighter: false,
+                line_style: LineStyle::Solid,
+                texture: None,
+                style: BrushStyle::Marker,
+                bounds: Rect::NOTHING,
+                layer: 0,
+                group: None,
+                locked: false,
+            }
+            .with_bounds(),
+        )
+    }
+
+    // 1000 条短笔画，按 40 列排成网格
+    fn stroke_grid() -> ChunkedObjects {
+        (0..STROKE_COUNT)
+            .map(|i| {
+                let origin = Pos2::new((i % 40) as f32 * SPACING, (i / 40) as f32 * SPACING);
+                stroke_at(origin, egui::vec2(20.0, 20.0))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn query_returns_far_fewer_candidates_than_a_full_scan() {
+        let mut objects = stroke_grid();
+
+        let target = 41; // 第二行第二列的笔画
+        let rect = Rect::from_center_size(
+            objects[target].bounding_box().center(),
+            egui::vec2(10.0, 10.0),
+        );
+        let candidates = objects.query(rect);
+
+        assert!(candidates.contains(&target));
+        // 只有指针附近的笔画是候选，全量遍历则要比较全部 1000 条
+        assert!(
+            candidates.len() * 50 < objects.len(),
+            "{} candidates for {} strokes",
+            candidates.len(),
+            objects.len()
+        );
+    }
+
+    #[test]
+    fn query_finds_every_intersecting_object() {
+        let mut objects = stroke_grid();
+        objects.push(stroke_at(
+            Pos2::new(-2000.0, 300.0),
+            egui::vec2(6000.0, 0.0),
+        ));
+
+        let rect = Rect::from_min_max(Pos2::new(250.0, 130.0), Pos2::new(720.0, 480.0));
+        let candidates = objects.query(rect);
+        for (i, object) in objects.iter().enumerate() {
+            if object.bounding_box().intersects(rect) {
+                assert!(candidates.contains(&i), "object {i} is missing");
+            }
+        }
+        assert!(
+            candidates.contains(&STROKE_COUNT),
+            "the long stroke is missing"
+        );
+        assert!(candidates.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn moved_objects_are_found_at_their_new_position() {
+        let mut objects = stroke_grid();
+
+        let far_away = Pos2::new(-10_000.0, -10_000.0);
+        CanvasObject::move_object(&mut objects[0], far_away.to_vec2());
+        let rect = Rect::from_center_size(far_away, egui::vec2(100.0, 100.0));
+        assert_eq!(objects.query(rect), vec![0]);
+        assert!(
+            !objects
+                .query(Rect::from_min_size(Pos2::ZERO, egui::vec2(30.0, 30.0)))
+                .contains(&0)
+        );
+    }
+
+    #[test]
+    fn indices_follow_insertions_and_removals() {
+        let mut objects = stroke_grid();
+        let last = objects[STROKE_COUNT - 1].bounding_box();
+
+        objects.insert(0, stroke_at(last.center(), egui::vec2(1.0, 1.0)));
+        objects.remove(500);
+        let candidates = objects.query(last);
+        assert!(candidates.contains(&0));
+        assert!(candidates.contains(&(STROKE_COUNT - 1)));
+        assert_eq!(objects.len(), STROKE_COUNT);
+    }
+
+    #[test]
+    fn journal_lists_changes_by_id() {
+        let mut objects = stroke_grid();
+        objects.start_journal();
+
+        let first = objects.ids()[0];
+        objects.push(stroke_at(Pos2::ZERO, egui::vec2(5.0, 5.0)));
+        let added = objects.ids()[STROKE_COUNT];
+        let _ = objects.get_mut(0);
+        objects.remove(STROKE_COUNT);
+        assert_eq!(
+            objects.take_changes(),
+            [
+                ObjectChange::Added(added),
+                ObjectChange::Modified(first),
+                ObjectChange::Removed(added),
+            ]
+        );
+
+        objects.clear();
+        assert_eq!(objects.take_changes(), [ObjectChange::Replaced]);
+        assert!(objects.take_changes().is_empty());
+    }
+}
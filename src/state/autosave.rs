@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use egui::Context;
+
+use super::CanvasState;
+use super::board::{pages_from_bytes, pages_to_bytes};
+
+/// Magic header for autosave files: `b"UWA"` followed by the canvas format version byte
+const AUTOSAVE_FILE_MAGIC: &[u8; 3] = b"UWA";
+
+/// How long changes may stay unsaved before the boards are written to the recovery file
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Periodically writes every page to a recovery file, which is offered for restoring after a crash
+pub struct Autosaver {
+    pub last_save: Instant,
+    saved_revisions: Vec<u64>, // 上次保存时各页面的历史记录版本
+    /// A recovery file left by a previous run, waiting to be restored or discarded
+    pub pending_recovery: bool,
+}
+
+impl Default for Autosaver {
+    fn default() -> Self {
+        Self {
+            last_save: Instant::now(),
+            // 初始的空白页面无需保存
+            saved_revisions: vec![0],
+            pending_recovery: autosave_path().exists(),
+        }
+    }
+}
+
+impl Autosaver {
+    /// Whether the pages changed since the last save, judged by their history revisions
+    pub fn is_dirty(&self, revisions: &[u64]) -> bool {
+        self.saved_revisions != revisions
+    }
+
+    /// Marks the pages with `revisions` as saved without writing them
+    pub fn mark_saved(&mut self, revisions: Vec<u64>) {
+        self.saved_revisions = revisions;
+        self.last_save = Instant::now();
+    }
+
+    /// Writes `pages` to the recovery file.
+    /// The data goes to a temporary file first and is then renamed, so a crash mid-write keeps the previous recovery file intact.
+    pub fn save(
+        &mut self,
+        pages: &[&CanvasState],
+        current_page: usize,
+        revisions: Vec<u64>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // 无论成功与否都等到下个周期再尝试，避免每帧重复写入
+        self.last_save = Instant::now();
+
+        let out = pages_to_bytes(AUTOSAVE_FILE_MAGIC, pages, current_page)?;
+
+        let path = autosave_path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, out)?;
+        std::fs::rename(&temp_path, &path)?;
+
+        self.saved_revisions = revisions;
+        Ok(())
+    }
+
+    /// Reads the pages and the current page index back from the recovery file
+    pub fn load(ctx: &Context) -> Result<(Vec<CanvasState>, usize), Box<dyn std::error::Error>> {
+        let bytes = std::fs::read(autosave_path())?;
+        pages_from_bytes(AUTOSAVE_FILE_MAGIC, &bytes, ctx)
+    }
+
+    /// Deletes the recovery file, e.g. on a clean exit or when the user declines to restore it
+    pub fn discard(&mut self) {
+        self.pending_recovery = false;
+        let path = autosave_path();
+        if path.exists()
+            && let Err(err) = std::fs::remove_file(&path)
+        {
+            eprintln!("failed to remove autosave file: {err}");
+        }
+    }
+}
+
+// 自动保存文件路径
+fn autosave_path() -> PathBuf {
+    let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("uwu");
+    path.push("autosave.sba");
+    path
+}
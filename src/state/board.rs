@@ -0,0 +1,114 @@
+use egui::Context;
+use rkyv::Archive;
+
+use super::flat::CanvasStateFlat;
+use super::{CANVAS_FILE_VERSION, CanvasState};
+use crate::i18n::t;
+
+/// Magic header for board files holding every page: `b"UWB"` followed by the canvas format version byte
+pub const BOARD_FILE_MAGIC: &[u8; 3] = b"UWB";
+const BOARD_HEADER_SIZE: usize = 4;
+/// File extension of board files, next to `sb` for single canvases
+pub const BOARD_FILE_EXT: &str = "sbb";
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub struct BoardFlat {
+    pub pages: Vec<CanvasStateFlat>,
+    pub current_page: u32,
+}
+
+/// Encodes `pages` and the current page index, prefixed by `magic` and the canvas format version
+pub fn pages_to_bytes(
+    magic: &[u8; 3],
+    pages: &[&CanvasState],
+    current_page: usize,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let flat = BoardFlat {
+        pages: pages
+            .iter()
+            .map(|&canvas| CanvasStateFlat::from(canvas))
+            .collect(),
+        current_page: current_page as u32,
+    };
+    let payload =
+        rkyv::to_bytes::<rkyv::rancor::Error>(&flat).map_err(|e| format!("rkyv error: {e}"))?;
+
+    let mut out = Vec::with_capacity(BOARD_HEADER_SIZE + payload.len());
+    out.extend_from_slice(magic);
+    out.push(CANVAS_FILE_VERSION);
+    out.extend_from_slice(payload.as_slice());
+    Ok(out)
+}
+
+/// Whether `bytes` start with the header of a multi-page file using `magic`
+pub fn has_pages_header(magic: &[u8; 3], bytes: &[u8]) -> bool {
+    bytes.len() >= BOARD_HEADER_SIZE && bytes[..3] == *magic
+}
+
+/// Decodes the pages and the current page index written by [`pages_to_bytes`]
+pub fn pages_from_bytes(
+    magic: &[u8; 3],
+    bytes: &[u8],
+    ctx: &Context,
+) -> Result<(Vec<CanvasState>, usize), Box<dyn std::error::Error>> {
+    if !has_pages_header(magic, bytes) || bytes[3] != CANVAS_FILE_VERSION {
+        let magic = String::from_utf8_lossy(magic);
+        return Err(format!(
+            "unsupported board file format: expected magic={magic}, version={CANVAS_FILE_VERSION}"
+        )
+        .into());
+    }
+
+    let archived =
+        rkyv::access::<ArchivedBoardFlat, rkyv::rancor::Error>(&bytes[BOARD_HEADER_SIZE..])
+            .map_err(|e| format!("rkyv error: {e}"))?;
+    let pages: Vec<CanvasState> = archived
+        .pages
+        .iter()
+        .map(|page| CanvasState::from_flat(page, ctx))
+        .collect();
+    if pages.is_empty() {
+        return Err("board file contains no pages".into());
+    }
+    let current_page = (archived.current_page.to_native() as usize).min(pages.len() - 1);
+    Ok((pages, current_page))
+}
+
+/// Opens a file dialog and saves every page to the user-selected board file
+pub fn save_board_with_dialog(
+    pages: &[&CanvasState],
+    current_page: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = rfd::FileDialog::new()
+        .add_filter(t("画板文件"), &[BOARD_FILE_EXT])
+        .set_file_name(format!("board.{BOARD_FILE_EXT}"))
+        .save_file()
+        .ok_or(std::io::Error::new(
+            std::io::ErrorKind::InvalidFilename,
+            t("已取消"),
+        ))?;
+
+    std::fs::write(path, pages_to_bytes(BOARD_FILE_MAGIC, pages, current_page)?)?;
+    Ok(())
+}
+
+/// Opens a file dialog and loads the pages of the user-selected file.
+/// Single canvas files are accepted too and yield one page.
+pub fn load_pages_with_dialog(
+    ctx: &Context,
+) -> Result<(Vec<CanvasState>, usize), Box<dyn std::error::Error>> {
+    let path = rfd::FileDialog::new()
+        .add_filter(t("画板文件"), &[BOARD_FILE_EXT, "sb"])
+        .pick_file()
+        .ok_or(std::io::Error::new(
+            std::io::ErrorKind::InvalidFilename,
+            t("已取消"),
+        ))?;
+    let bytes = std::fs::read(path)?;
+    if has_pages_header(BOARD_FILE_MAGIC, &bytes) {
+        pages_from_bytes(BOARD_FILE_MAGIC, &bytes, ctx)
+    } else {
+        Ok((vec![CanvasState::from_bytes(&bytes, ctx)?], 0))
+    }
+}
@@ -1,8 +1,15 @@
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+use egui::Context;
 use rkyv::Archive;
 
 use super::{
-    CanvasObject, CanvasShape, CanvasShapeType, CanvasState, CanvasStroke, CanvasText, Color32,
-    Pos2, StrokeWidth,
+    ArrowHeads, BackgroundFit, BackgroundImage, BrushStyle, CanvasImage, CanvasObject, CanvasShape,
+    CanvasShapeType, CanvasState, CanvasStickyNote, CanvasStroke, CanvasText, Color32,
+    CustomStrokeTexture, ImageAdjustments, ImageAnimation, Layer, LineStyle, Pos2, StrokeTexture,
+    StrokeWidth, TriangleKind,
 };
 
 // ===== Flat data types for rkyv canvas serialization =====
@@ -11,6 +18,34 @@ use super::{
 #[rkyv(bytecheck())]
 pub struct CanvasStateFlat {
     pub objects: Vec<CanvasObjectFlat>,
+    pub layers: Vec<LayerFlat>,
+    pub active_layer: u32,
+    pub background_image: Option<BackgroundImageFlat>,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub struct BackgroundImageFlat {
+    /// PNG-encoded pixels, like [`ImageFlat::png`]
+    pub png: Vec<u8>,
+    pub fit: BackgroundFitFlat,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone, Copy)]
+#[rkyv(bytecheck())]
+pub enum BackgroundFitFlat {
+    Stretch,
+    Fit,
+    Fill,
+    Center,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub struct LayerFlat {
+    pub id: u32,
+    pub name: String,
+    pub visible: bool,
 }
 
 #[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
@@ -19,16 +54,36 @@ pub enum CanvasObjectFlat {
     Stroke(StrokeFlat),
     Text(TextFlat),
     Shape(ShapeFlat),
+    Image(ImageFlat),
+    StickyNote(StickyNoteFlat),
 }
 
 #[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
 #[rkyv(bytecheck())]
 pub struct StrokeFlat {
+    pub id: u64,
     pub points: Vec<[f32; 2]>,
     pub width: StrokeWidthFlat,
     pub color: [u8; 4],
     pub base_width: f32,
     pub rot: f32,
+    pub is_highlighter: bool,
+    pub line_style: LineStyleFlat,
+    pub texture: Option<StrokeTextureFlat>,
+    pub style: BrushStyleFlat,
+    pub layer: u32,
+    pub group: Option<u32>,
+    pub locked: bool,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub enum StrokeTextureFlat {
+    Chalk,
+    Grain,
+    Canvas,
+    /// PNG-encoded mask of a user-loaded texture
+    Custom(Vec<u8>),
 }
 
 #[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
@@ -46,6 +101,46 @@ pub struct TextFlat {
     pub color: [u8; 4],
     pub font_size: f32,
     pub rot: f32,
+    pub layer: u32,
+    pub group: Option<u32>,
+    pub locked: bool,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub struct StickyNoteFlat {
+    pub text: String,
+    pub pos: [f32; 2],
+    pub size: [f32; 2],
+    pub color: [u8; 4],
+    pub text_color: [u8; 4],
+    pub font_size: f32,
+    pub layer: u32,
+    pub group: Option<u32>,
+    pub locked: bool,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub struct ImageFlat {
+    /// PNG-encoded pixels, so canvas files stay self-contained
+    pub png: Vec<u8>,
+    pub pos: [f32; 2],
+    pub size: [f32; 2],
+    pub aspect_ratio: f32,
+    pub rot: f32,
+    pub crop: [f32; 4], // [min_x, min_y, max_x, max_y]，纹理坐标
+    pub grayscale: bool,
+    pub brightness: f32,
+    pub contrast: f32,
+    /// How long each frame of an animated image is shown, empty for still images
+    pub frame_delays_ms: Vec<u32>,
+    /// PNG-encoded frames after the first one, which is [`ImageFlat::png`]
+    pub frames: Vec<Vec<u8>>,
+    pub opacity: u8,
+    pub layer: u32,
+    pub group: Option<u32>,
+    pub locked: bool,
 }
 
 #[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
@@ -53,9 +148,24 @@ pub struct TextFlat {
 pub struct ShapeFlat {
     pub shape_type: ShapeTypeFlat,
     pub pos: [f32; 2],
+    pub end: [f32; 2],
     pub size: f32,
     pub color: [u8; 4],
     pub rotation: f32,
+    pub line_style: LineStyleFlat,
+    pub fill: Option<[u8; 4]>,
+    pub arrow_heads: ArrowHeadsFlat,
+    pub layer: u32,
+    pub group: Option<u32>,
+    pub locked: bool,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone, Copy)]
+#[rkyv(bytecheck())]
+pub struct ArrowHeadsFlat {
+    pub head_start: bool,
+    pub head_end: bool,
+    pub head_size: f32,
 }
 
 #[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
@@ -64,108 +174,556 @@ pub enum ShapeTypeFlat {
     Line,
     Arrow,
     Rectangle,
-    Triangle,
+    Triangle(TriangleKindFlat),
     Circle,
+    Ellipse,
+    Polygon { sides: u32 },
+    Star { points: u32 },
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub enum TriangleKindFlat {
+    Equilateral,
+    Right,
+    Isosceles,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone, Copy)]
+#[rkyv(bytecheck())]
+pub enum LineStyleFlat {
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone, Copy)]
+#[rkyv(bytecheck())]
+pub enum BrushStyleFlat {
+    Marker,
+    Chalk,
+    Pencil,
 }
 
 // Conversions between CanvasState and flat types
 
+impl From<ArrowHeads> for ArrowHeadsFlat {
+    fn from(heads: ArrowHeads) -> Self {
+        Self {
+            head_start: heads.head_start,
+            head_end: heads.head_end,
+            head_size: heads.head_size,
+        }
+    }
+}
+
+impl From<&ArchivedArrowHeadsFlat> for ArrowHeads {
+    fn from(heads: &ArchivedArrowHeadsFlat) -> Self {
+        Self {
+            head_start: heads.head_start,
+            head_end: heads.head_end,
+            head_size: heads.head_size.into(),
+        }
+    }
+}
+
+impl From<LineStyle> for LineStyleFlat {
+    fn from(style: LineStyle) -> Self {
+        match style {
+            LineStyle::Solid => LineStyleFlat::Solid,
+            LineStyle::Dashed => LineStyleFlat::Dashed,
+            LineStyle::Dotted => LineStyleFlat::Dotted,
+        }
+    }
+}
+
+impl From<&ArchivedLineStyleFlat> for LineStyle {
+    fn from(style: &ArchivedLineStyleFlat) -> Self {
+        match style {
+            ArchivedLineStyleFlat::Solid => LineStyle::Solid,
+            ArchivedLineStyleFlat::Dashed => LineStyle::Dashed,
+            ArchivedLineStyleFlat::Dotted => LineStyle::Dotted,
+        }
+    }
+}
+
+impl From<BrushStyle> for BrushStyleFlat {
+    fn from(style: BrushStyle) -> Self {
+        match style {
+            BrushStyle::Marker => BrushStyleFlat::Marker,
+            BrushStyle::Chalk => BrushStyleFlat::Chalk,
+            BrushStyle::Pencil => BrushStyleFlat::Pencil,
+        }
+    }
+}
+
+impl From<&ArchivedBrushStyleFlat> for BrushStyle {
+    fn from(style: &ArchivedBrushStyleFlat) -> Self {
+        match style {
+            ArchivedBrushStyleFlat::Marker => BrushStyle::Marker,
+            ArchivedBrushStyleFlat::Chalk => BrushStyle::Chalk,
+            ArchivedBrushStyleFlat::Pencil => BrushStyle::Pencil,
+        }
+    }
+}
+
+impl From<&StrokeTexture> for StrokeTextureFlat {
+    fn from(texture: &StrokeTexture) -> Self {
+        match texture {
+            StrokeTexture::Chalk => StrokeTextureFlat::Chalk,
+            StrokeTexture::Grain => StrokeTextureFlat::Grain,
+            StrokeTexture::Canvas => StrokeTextureFlat::Canvas,
+            StrokeTexture::Custom(custom) => StrokeTextureFlat::Custom(custom.png.clone()),
+        }
+    }
+}
+
+impl From<&ArchivedStrokeTextureFlat> for StrokeTexture {
+    fn from(texture: &ArchivedStrokeTextureFlat) -> Self {
+        match texture {
+            ArchivedStrokeTextureFlat::Chalk => StrokeTexture::Chalk,
+            ArchivedStrokeTextureFlat::Grain => StrokeTexture::Grain,
+            ArchivedStrokeTextureFlat::Canvas => StrokeTexture::Canvas,
+            ArchivedStrokeTextureFlat::Custom(png) => {
+                StrokeTexture::Custom(Arc::new(CustomStrokeTexture::new(png.to_vec())))
+            }
+        }
+    }
+}
+
+impl From<&CanvasStroke> for StrokeFlat {
+    fn from(s: &CanvasStroke) -> Self {
+        StrokeFlat {
+            id: s.id,
+            points: s.points.iter().map(|p| [p.x, p.y]).collect(),
+            width: match &s.width {
+                StrokeWidth::Fixed(w) => StrokeWidthFlat::Fixed(*w),
+                StrokeWidth::Dynamic(v) => StrokeWidthFlat::Dynamic(v.clone()),
+            },
+            color: [s.color.r(), s.color.g(), s.color.b(), s.color.a()],
+            base_width: s.base_width,
+            rot: s.rot,
+            is_highlighter: s.is_highlighter,
+            line_style: s.line_style.into(),
+            texture: s.texture.as_ref().map(StrokeTextureFlat::from),
+            style: s.style.into(),
+            layer: s.layer,
+            group: s.group,
+            locked: s.locked,
+        }
+    }
+}
+
+impl<'a> From<&'a ArchivedStrokeFlat> for CanvasStroke {
+    fn from(s: &'a ArchivedStrokeFlat) -> Self {
+        CanvasStroke {
+            id: s.id.into(),
+            points: s
+                .points
+                .iter()
+                .map(|p| Pos2::new(p[0].into(), p[1].into()))
+                .collect(),
+            width: match &s.width {
+                ArchivedStrokeWidthFlat::Fixed(w) => StrokeWidth::Fixed((*w).into()),
+                ArchivedStrokeWidthFlat::Dynamic(v) => {
+                    StrokeWidth::Dynamic(v.iter().map(|&x| x.into()).collect())
+                }
+            },
+            color: Color32::from_rgba_premultiplied(s.color[0], s.color[1], s.color[2], s.color[3]),
+            base_width: s.base_width.into(),
+            rot: s.rot.into(),
+            is_highlighter: s.is_highlighter,
+            line_style: (&s.line_style).into(),
+            texture: s.texture.as_ref().map(StrokeTexture::from),
+            style: (&s.style).into(),
+            bounds: egui::Rect::NOTHING,
+            layer: s.layer.into(),
+            group: s.group.as_ref().map(|g| g.to_native()),
+            locked: s.locked,
+        }
+        .with_bounds()
+    }
+}
+
+impl CanvasObjectFlat {
+    /// Returns `None` for images whose pixels cannot be encoded
+    pub fn from_object(obj: &CanvasObject) -> Option<Self> {
+        match obj {
+            CanvasObject::Stroke(s) => Some(CanvasObjectFlat::Stroke(StrokeFlat::from(s))),
+            CanvasObject::Text(t) => Some(CanvasObjectFlat::Text(TextFlat {
+                text: t.text.clone(),
+                pos: [t.pos.x, t.pos.y],
+                color: [t.color.r(), t.color.g(), t.color.b(), t.color.a()],
+                font_size: t.font_size,
+                rot: t.rot,
+                layer: t.layer,
+                group: t.group,
+                locked: t.locked,
+            })),
+            CanvasObject::StickyNote(n) => Some(CanvasObjectFlat::StickyNote(StickyNoteFlat {
+                text: n.text.clone(),
+                pos: [n.pos.x, n.pos.y],
+                size: [n.size.x, n.size.y],
+                color: n.color.to_array(),
+                text_color: n.text_color.to_array(),
+                font_size: n.font_size,
+                layer: n.layer,
+                group: n.group,
+                locked: n.locked,
+            })),
+            CanvasObject::Shape(s) => Some(CanvasObjectFlat::Shape(ShapeFlat {
+                shape_type: match s.shape_type {
+                    CanvasShapeType::Line => ShapeTypeFlat::Line,
+                    CanvasShapeType::Arrow => ShapeTypeFlat::Arrow,
+                    CanvasShapeType::Rectangle => ShapeTypeFlat::Rectangle,
+                    CanvasShapeType::Triangle(kind) => ShapeTypeFlat::Triangle(match kind {
+                        TriangleKind::Equilateral => TriangleKindFlat::Equilateral,
+                        TriangleKind::Right => TriangleKindFlat::Right,
+                        TriangleKind::Isosceles => TriangleKindFlat::Isosceles,
+                    }),
+                    CanvasShapeType::Circle => ShapeTypeFlat::Circle,
+                    CanvasShapeType::Ellipse => ShapeTypeFlat::Ellipse,
+                    CanvasShapeType::Polygon { sides } => ShapeTypeFlat::Polygon { sides },
+                    CanvasShapeType::Star { points } => ShapeTypeFlat::Star { points },
+                },
+                pos: [s.pos.x, s.pos.y],
+                end: [s.end.x, s.end.y],
+                size: s.size,
+                color: [s.color.r(), s.color.g(), s.color.b(), s.color.a()],
+                rotation: s.rotation,
+                line_style: s.line_style.into(),
+                fill: s.fill.map(|c| [c.r(), c.g(), c.b(), c.a()]),
+                arrow_heads: s.arrow_heads.into(),
+                layer: s.layer,
+                group: s.group,
+                locked: s.locked,
+            })),
+            CanvasObject::Image(img) => {
+                let encoded = std::iter::once(&img.image_data)
+                    .chain(
+                        img.animation
+                            .iter()
+                            .flat_map(|animation| animation.frames.iter().skip(1))
+                            .map(|frame| &frame.pixels),
+                    )
+                    .map(|pixels| encode_png(pixels, img.image_size))
+                    .collect::<image::ImageResult<Vec<_>>>();
+                let mut frames = match encoded {
+                    Ok(frames) => frames.into_iter(),
+                    Err(err) => {
+                        eprintln!("failed to encode image for saving: {err}");
+                        return None;
+                    }
+                };
+                let png = frames.next()?;
+                Some(CanvasObjectFlat::Image(ImageFlat {
+                    png,
+                    pos: [img.pos.x, img.pos.y],
+                    size: [img.size.x, img.size.y],
+                    aspect_ratio: img.aspect_ratio,
+                    rot: img.rot,
+                    crop: [
+                        img.crop.min.x,
+                        img.crop.min.y,
+                        img.crop.max.x,
+                        img.crop.max.y,
+                    ],
+                    grayscale: img.adjustments.grayscale,
+                    brightness: img.adjustments.brightness,
+                    contrast: img.adjustments.contrast,
+                    frame_delays_ms: img
+                        .animation
+                        .iter()
+                        .flat_map(|animation| &animation.frames)
+                        .map(|frame| frame.delay.as_millis() as u32)
+                        .collect(),
+                    frames: frames.collect(),
+                    opacity: img.opacity,
+                    layer: img.layer,
+                    group: img.group,
+                    locked: img.locked,
+                }))
+            }
+        }
+    }
+}
+
+impl CanvasObject {
+    /// Images are re-uploaded as textures through `ctx`; returns `None` for images that fail to decode
+    pub fn from_flat(obj: &ArchivedCanvasObjectFlat, ctx: &Context) -> Option<Self> {
+        Some(match obj {
+            ArchivedCanvasObjectFlat::Stroke(s) => CanvasObject::Stroke(CanvasStroke::from(s)),
+            ArchivedCanvasObjectFlat::Text(t) => CanvasObject::Text(CanvasText {
+                text: t.text.as_str().to_string(),
+                pos: Pos2::new(t.pos[0].into(), t.pos[1].into()),
+                color: Color32::from_rgba_premultiplied(
+                    t.color[0], t.color[1], t.color[2], t.color[3],
+                ),
+                font_size: t.font_size.into(),
+                rot: t.rot.into(),
+                cached_size: None,
+                layer: t.layer.into(),
+                group: t.group.as_ref().map(|g| g.to_native()),
+                locked: t.locked,
+            }),
+            ArchivedCanvasObjectFlat::StickyNote(n) => CanvasObject::StickyNote(CanvasStickyNote {
+                text: n.text.as_str().to_string(),
+                pos: Pos2::new(n.pos[0].into(), n.pos[1].into()),
+                size: egui::vec2(n.size[0].into(), n.size[1].into()),
+                color: Color32::from_rgba_premultiplied(
+                    n.color[0], n.color[1], n.color[2], n.color[3],
+                ),
+                text_color: Color32::from_rgba_premultiplied(
+                    n.text_color[0],
+                    n.text_color[1],
+                    n.text_color[2],
+                    n.text_color[3],
+                ),
+                font_size: n.font_size.into(),
+                layer: n.layer.into(),
+                group: n.group.as_ref().map(|g| g.to_native()),
+                locked: n.locked,
+            }),
+            ArchivedCanvasObjectFlat::Shape(s) => CanvasObject::Shape(
+                CanvasShape {
+                    shape_type: match &s.shape_type {
+                        ArchivedShapeTypeFlat::Line => CanvasShapeType::Line,
+                        ArchivedShapeTypeFlat::Arrow => CanvasShapeType::Arrow,
+                        ArchivedShapeTypeFlat::Rectangle => CanvasShapeType::Rectangle,
+                        ArchivedShapeTypeFlat::Triangle(kind) => {
+                            CanvasShapeType::Triangle(match kind {
+                                ArchivedTriangleKindFlat::Equilateral => TriangleKind::Equilateral,
+                                ArchivedTriangleKindFlat::Right => TriangleKind::Right,
+                                ArchivedTriangleKindFlat::Isosceles => TriangleKind::Isosceles,
+                            })
+                        }
+                        ArchivedShapeTypeFlat::Circle => CanvasShapeType::Circle,
+                        ArchivedShapeTypeFlat::Ellipse => CanvasShapeType::Ellipse,
+                        ArchivedShapeTypeFlat::Polygon { sides } => CanvasShapeType::Polygon {
+                            sides: sides.to_native(),
+                        },
+                        ArchivedShapeTypeFlat::Star { points } => CanvasShapeType::Star {
+                            points: points.to_native(),
+                        },
+                    },
+                    pos: Pos2::new(s.pos[0].into(), s.pos[1].into()),
+                    end: Pos2::new(s.end[0].into(), s.end[1].into()),
+                    size: s.size.into(),
+                    color: Color32::from_rgba_premultiplied(
+                        s.color[0], s.color[1], s.color[2], s.color[3],
+                    ),
+                    rotation: s.rotation.into(),
+                    line_style: (&s.line_style).into(),
+                    fill: s
+                        .fill
+                        .as_ref()
+                        .map(|c| Color32::from_rgba_premultiplied(c[0], c[1], c[2], c[3])),
+                    arrow_heads: (&s.arrow_heads).into(),
+                    bounds: egui::Rect::NOTHING,
+                    layer: s.layer.into(),
+                    group: s.group.as_ref().map(|g| g.to_native()),
+                    locked: s.locked,
+                }
+                .with_bounds(),
+            ),
+            ArchivedCanvasObjectFlat::Image(img) => {
+                let rgba = match decode_png(&img.png) {
+                    Ok(rgba) => rgba,
+                    Err(err) => {
+                        eprintln!("failed to decode saved image: {err}");
+                        return None;
+                    }
+                };
+                let (width, height) = rgba.dimensions();
+                let adjustments = ImageAdjustments {
+                    grayscale: img.grayscale,
+                    brightness: img.brightness.into(),
+                    contrast: img.contrast.into(),
+                };
+                let texture = ctx.load_texture(
+                    "inserted_image",
+                    egui::ColorImage::from_rgba_unmultiplied(
+                        [width as usize, height as usize],
+                        &adjustments.apply(&rgba),
+                    ),
+                    egui::TextureOptions::LINEAR,
+                );
+                let image_data: Arc<[u8]> = Arc::from(rgba.into_raw());
+                // 解码失败的帧被跳过，只剩一帧时按静态图片处理
+                let later_frames = img.frames.iter().map(|png| match decode_png(png) {
+                    Ok(frame) if frame.dimensions() == (width, height) => {
+                        Some(Arc::from(frame.into_raw()))
+                    }
+                    Ok(_) => None,
+                    Err(err) => {
+                        eprintln!("failed to decode saved animation frame: {err}");
+                        None
+                    }
+                });
+                let frame_limit = ImageAnimation::frame_limit([width, height]);
+                if img.frame_delays_ms.len() > frame_limit {
+                    eprintln!(
+                        "saved animation has {} frames, keeping the first {frame_limit}",
+                        img.frame_delays_ms.len()
+                    );
+                }
+                let frames: Vec<(Arc<[u8]>, Duration)> = std::iter::once(Some(image_data.clone()))
+                    .chain(later_frames)
+                    .zip(img.frame_delays_ms.iter())
+                    .filter_map(|(pixels, delay)| {
+                        Some((pixels?, Duration::from_millis(delay.to_native().into())))
+                    })
+                    // 超出的帧不解码，避免文件中的大动图占满内存
+                    .take(frame_limit)
+                    .collect();
+                let animation = (frames.len() > 1)
+                    .then(|| ImageAnimation::new(ctx, frames, [width, height], adjustments));
+                CanvasObject::Image(CanvasImage {
+                    texture,
+                    pos: Pos2::new(img.pos[0].into(), img.pos[1].into()),
+                    size: egui::vec2(img.size[0].into(), img.size[1].into()),
+                    aspect_ratio: img.aspect_ratio.into(),
+                    rot: img.rot.into(),
+                    marked_for_deletion: false,
+                    image_data,
+                    image_size: [width, height],
+                    crop: egui::Rect::from_min_max(
+                        Pos2::new(img.crop[0].into(), img.crop[1].into()),
+                        Pos2::new(img.crop[2].into(), img.crop[3].into()),
+                    ),
+                    adjustments,
+                    animation,
+                    opacity: img.opacity,
+                    layer: img.layer.into(),
+                    group: img.group.as_ref().map(|g| g.to_native()),
+                    locked: img.locked,
+                })
+            }
+        })
+    }
+}
+
 impl From<&CanvasState> for CanvasStateFlat {
     fn from(state: &CanvasState) -> Self {
         CanvasStateFlat {
             objects: state
                 .objects
                 .iter()
-                .filter_map(|obj| match obj {
-                    CanvasObject::Stroke(s) => Some(CanvasObjectFlat::Stroke(StrokeFlat {
-                        points: s.points.iter().map(|p| [p.x, p.y]).collect(),
-                        width: match &s.width {
-                            StrokeWidth::Fixed(w) => StrokeWidthFlat::Fixed(*w),
-                            StrokeWidth::Dynamic(v) => StrokeWidthFlat::Dynamic(v.clone()),
-                        },
-                        color: [s.color.r(), s.color.g(), s.color.b(), s.color.a()],
-                        base_width: s.base_width,
-                        rot: s.rot,
-                    })),
-                    CanvasObject::Text(t) => Some(CanvasObjectFlat::Text(TextFlat {
-                        text: t.text.clone(),
-                        pos: [t.pos.x, t.pos.y],
-                        color: [t.color.r(), t.color.g(), t.color.b(), t.color.a()],
-                        font_size: t.font_size,
-                        rot: t.rot,
-                    })),
-                    CanvasObject::Shape(s) => Some(CanvasObjectFlat::Shape(ShapeFlat {
-                        shape_type: match s.shape_type {
-                            CanvasShapeType::Line => ShapeTypeFlat::Line,
-                            CanvasShapeType::Arrow => ShapeTypeFlat::Arrow,
-                            CanvasShapeType::Rectangle => ShapeTypeFlat::Rectangle,
-                            CanvasShapeType::Triangle => ShapeTypeFlat::Triangle,
-                            CanvasShapeType::Circle => ShapeTypeFlat::Circle,
-                        },
-                        pos: [s.pos.x, s.pos.y],
-                        size: s.size,
-                        color: [s.color.r(), s.color.g(), s.color.b(), s.color.a()],
-                        rotation: s.rotation,
-                    })),
-                    CanvasObject::Image(_) => None,
+                .filter_map(CanvasObjectFlat::from_object)
+                .collect(),
+            layers: state
+                .layers
+                .iter()
+                .map(|layer| LayerFlat {
+                    id: layer.id,
+                    name: layer.name.clone(),
+                    visible: layer.visible,
                 })
                 .collect(),
+            active_layer: state.active_layer,
+            background_image: state
+                .background_image
+                .as_ref()
+                .and_then(BackgroundImageFlat::from_image),
+        }
+    }
+}
+
+fn encode_png(rgba: &[u8], size: [u32; 2]) -> image::ImageResult<Vec<u8>> {
+    let mut png = Vec::new();
+    image::write_buffer_with_format(
+        &mut Cursor::new(&mut png),
+        rgba,
+        size[0],
+        size[1],
+        image::ColorType::Rgba8,
+        image::ImageFormat::Png,
+    )?;
+    Ok(png)
+}
+
+fn decode_png(png: &[u8]) -> image::ImageResult<image::RgbaImage> {
+    image::load_from_memory_with_format(png, image::ImageFormat::Png)
+        .map(|decoded| decoded.to_rgba8())
+}
+
+impl BackgroundImageFlat {
+    fn from_image(background: &BackgroundImage) -> Option<Self> {
+        let [width, height] = background.image_size;
+        let mut png = Vec::new();
+        if let Err(err) = image::write_buffer_with_format(
+            &mut Cursor::new(&mut png),
+            &background.image_data,
+            width,
+            height,
+            image::ColorType::Rgba8,
+            image::ImageFormat::Png,
+        ) {
+            eprintln!("failed to encode background image for saving: {err}");
+            return None;
         }
+        Some(Self {
+            png,
+            fit: match background.fit {
+                BackgroundFit::Stretch => BackgroundFitFlat::Stretch,
+                BackgroundFit::Fit => BackgroundFitFlat::Fit,
+                BackgroundFit::Fill => BackgroundFitFlat::Fill,
+                BackgroundFit::Center => BackgroundFitFlat::Center,
+            },
+        })
+    }
+}
+
+impl BackgroundImage {
+    /// The image is re-uploaded as a texture through `ctx`; returns `None` if it fails to decode
+    pub fn from_flat(background: &ArchivedBackgroundImageFlat, ctx: &Context) -> Option<Self> {
+        let rgba =
+            match image::load_from_memory_with_format(&background.png, image::ImageFormat::Png) {
+                Ok(decoded) => decoded.to_rgba8(),
+                Err(err) => {
+                    eprintln!("failed to decode saved background image: {err}");
+                    return None;
+                }
+            };
+        Some(Self::new(
+            ctx,
+            rgba,
+            match background.fit {
+                ArchivedBackgroundFitFlat::Stretch => BackgroundFit::Stretch,
+                ArchivedBackgroundFitFlat::Fit => BackgroundFit::Fit,
+                ArchivedBackgroundFitFlat::Fill => BackgroundFit::Fill,
+                ArchivedBackgroundFitFlat::Center => BackgroundFit::Center,
+            },
+        ))
     }
 }
 
-impl<'a> From<&'a ArchivedCanvasStateFlat> for CanvasState {
-    fn from(archived: &'a ArchivedCanvasStateFlat) -> Self {
-        CanvasState {
+impl CanvasState {
+    pub fn from_flat(archived: &ArchivedCanvasStateFlat, ctx: &Context) -> Self {
+        let mut canvas = CanvasState {
             objects: archived
                 .objects
                 .iter()
-                .map(|obj| match obj {
-                    ArchivedCanvasObjectFlat::Stroke(s) => CanvasObject::Stroke(CanvasStroke {
-                        points: s
-                            .points
-                            .iter()
-                            .map(|p| Pos2::new(p[0].into(), p[1].into()))
-                            .collect(),
-                        width: match &s.width {
-                            ArchivedStrokeWidthFlat::Fixed(w) => StrokeWidth::Fixed((*w).into()),
-                            ArchivedStrokeWidthFlat::Dynamic(v) => {
-                                StrokeWidth::Dynamic(v.iter().map(|&x| x.into()).collect())
-                            }
-                        },
-                        color: Color32::from_rgba_unmultiplied(
-                            s.color[0], s.color[1], s.color[2], s.color[3],
-                        ),
-                        base_width: s.base_width.into(),
-                        rot: s.rot.into(),
-                    }),
-                    ArchivedCanvasObjectFlat::Text(t) => CanvasObject::Text(CanvasText {
-                        text: t.text.as_str().to_string(),
-                        pos: Pos2::new(t.pos[0].into(), t.pos[1].into()),
-                        color: Color32::from_rgba_unmultiplied(
-                            t.color[0], t.color[1], t.color[2], t.color[3],
-                        ),
-                        font_size: t.font_size.into(),
-                        rot: t.rot.into(),
-                        cached_size: None,
-                    }),
-                    ArchivedCanvasObjectFlat::Shape(s) => CanvasObject::Shape(CanvasShape {
-                        shape_type: match s.shape_type {
-                            ArchivedShapeTypeFlat::Line => CanvasShapeType::Line,
-                            ArchivedShapeTypeFlat::Arrow => CanvasShapeType::Arrow,
-                            ArchivedShapeTypeFlat::Rectangle => CanvasShapeType::Rectangle,
-                            ArchivedShapeTypeFlat::Triangle => CanvasShapeType::Triangle,
-                            ArchivedShapeTypeFlat::Circle => CanvasShapeType::Circle,
-                        },
-                        pos: Pos2::new(s.pos[0].into(), s.pos[1].into()),
-                        size: s.size.into(),
-                        color: Color32::from_rgba_unmultiplied(
-                            s.color[0], s.color[1], s.color[2], s.color[3],
-                        ),
-                        rotation: s.rotation.into(),
-                    }),
-                })
+                .filter_map(|obj| CanvasObject::from_flat(obj, ctx))
                 .collect(),
+            background_image: archived
+                .background_image
+                .as_ref()
+                .and_then(|background| BackgroundImage::from_flat(background, ctx)),
+            ..Default::default()
+        };
+        if !archived.layers.is_empty() {
+            canvas.layers = archived
+                .layers
+                .iter()
+                .map(|layer| Layer {
+                    id: layer.id.into(),
+                    name: layer.name.as_str().to_string(),
+                    visible: layer.visible,
+                })
+                .collect();
+            canvas.active_layer = archived.active_layer.into();
         }
+        canvas
     }
 }
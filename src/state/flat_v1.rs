@@ -0,0 +1,214 @@
+use rkyv::Archive;
+
+use super::{
+    ArrowHeads, CanvasObject, CanvasShape, CanvasShapeType, CanvasState, CanvasStroke, CanvasText,
+    Color32, LineStyle, Pos2, StrokeWidth, TriangleKind,
+};
+
+// ===== Version 1 flat types, still read so that older canvas files keep opening =====
+// 布局必须与当时保存的文件一致，不能修改
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub struct CanvasStateFlatV1 {
+    pub objects: Vec<CanvasObjectFlatV1>,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub enum CanvasObjectFlatV1 {
+    Stroke(StrokeFlatV1),
+    Text(TextFlatV1),
+    Shape(ShapeFlatV1),
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub struct StrokeFlatV1 {
+    pub points: Vec<[f32; 2]>,
+    pub width: StrokeWidthFlatV1,
+    pub color: [u8; 4],
+    pub base_width: f32,
+    pub rot: f32,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub enum StrokeWidthFlatV1 {
+    Fixed(f32),
+    Dynamic(Vec<f32>),
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub struct TextFlatV1 {
+    pub text: String,
+    pub pos: [f32; 2],
+    pub color: [u8; 4],
+    pub font_size: f32,
+    pub rot: f32,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub struct ShapeFlatV1 {
+    pub shape_type: ShapeTypeFlatV1,
+    pub pos: [f32; 2],
+    pub size: f32,
+    pub color: [u8; 4],
+    pub rotation: f32,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub enum ShapeTypeFlatV1 {
+    Line,
+    Arrow,
+    Rectangle,
+    Triangle,
+    Circle,
+}
+
+// 第 1 版读取颜色时按非预乘处理，这里保持一致，旧文件打开后颜色不变
+fn color(c: &[u8; 4]) -> Color32 {
+    Color32::from_rgba_unmultiplied(c[0], c[1], c[2], c[3])
+}
+
+impl CanvasObject {
+    fn from_flat_v1(obj: &ArchivedCanvasObjectFlatV1) -> Self {
+        match obj {
+            ArchivedCanvasObjectFlatV1::Stroke(s) => CanvasObject::Stroke(
+                CanvasStroke {
+                    id: CanvasStroke::new_id(),
+                    points: s
+                        .points
+                        .iter()
+                        .map(|p| Pos2::new(p[0].into(), p[1].into()))
+                        .collect(),
+                    width: match &s.width {
+                        ArchivedStrokeWidthFlatV1::Fixed(w) => StrokeWidth::Fixed((*w).into()),
+                        ArchivedStrokeWidthFlatV1::Dynamic(v) => {
+                            StrokeWidth::Dynamic(v.iter().map(|&x| x.into()).collect())
+                        }
+                    },
+                    color: color(&s.color),
+                    base_width: s.base_width.into(),
+                    rot: s.rot.into(),
+                    is_highlighter: false,
+                    line_style: LineStyle::Solid,
+                    texture: None,
+                    style: Default::default(),
+                    bounds: egui::Rect::NOTHING,
+                    layer: 0,
+                    group: None,
+                    locked: false,
+                }
+                .with_bounds(),
+            ),
+            ArchivedCanvasObjectFlatV1::Text(t) => CanvasObject::Text(CanvasText {
+                text: t.text.as_str().to_string(),
+                pos: Pos2::new(t.pos[0].into(), t.pos[1].into()),
+                color: color(&t.color),
+                font_size: t.font_size.into(),
+                rot: t.rot.into(),
+                cached_size: None,
+                layer: 0,
+                group: None,
+                locked: false,
+            }),
+            ArchivedCanvasObjectFlatV1::Shape(s) => {
+                let pos = Pos2::new(s.pos[0].into(), s.pos[1].into());
+                let size: f32 = s.size.into();
+                let shape_color = color(&s.color);
+                let shape_type = match s.shape_type {
+                    ArchivedShapeTypeFlatV1::Line => CanvasShapeType::Line,
+                    ArchivedShapeTypeFlatV1::Arrow => CanvasShapeType::Arrow,
+                    ArchivedShapeTypeFlatV1::Rectangle => CanvasShapeType::Rectangle,
+                    ArchivedShapeTypeFlatV1::Triangle => {
+                        CanvasShapeType::Triangle(TriangleKind::default())
+                    }
+                    ArchivedShapeTypeFlatV1::Circle => CanvasShapeType::Circle,
+                };
+                CanvasObject::Shape(
+                    CanvasShape {
+                        // 第 1 版的直线和箭头总是从 pos 水平向右画出 size 的长度
+                        end: pos + egui::vec2(size, 0.0),
+                        // 第 1 版的三角形是实心的
+                        fill: matches!(shape_type, CanvasShapeType::Triangle(_))
+                            .then_some(shape_color),
+                        arrow_heads: ArrowHeads {
+                            head_size: size * 0.1,
+                            ..Default::default()
+                        },
+                        shape_type,
+                        pos,
+                        size,
+                        color: shape_color,
+                        rotation: s.rotation.into(),
+                        line_style: LineStyle::Solid,
+                        bounds: egui::Rect::NOTHING,
+                        layer: 0,
+                        group: None,
+                        locked: false,
+                    }
+                    .with_bounds(),
+                )
+            }
+        }
+    }
+}
+
+impl CanvasState {
+    /// Builds a canvas from a version 1 file; everything lands on the default layer
+    pub fn from_flat_v1(archived: &ArchivedCanvasStateFlatV1) -> Self {
+        CanvasState {
+            objects: archived
+                .objects
+                .iter()
+                .map(CanvasObject::from_flat_v1)
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_1_files_still_load() {
+        let flat = CanvasStateFlatV1 {
+            objects: vec![
+                CanvasObjectFlatV1::Stroke(StrokeFlatV1 {
+                    points: vec![[0.0, 0.0], [10.0, 5.0]],
+                    width: StrokeWidthFlatV1::Fixed(3.0),
+                    color: [255, 0, 0, 255],
+                    base_width: 3.0,
+                    rot: 0.0,
+                }),
+                CanvasObjectFlatV1::Shape(ShapeFlatV1 {
+                    shape_type: ShapeTypeFlatV1::Line,
+                    pos: [20.0, 20.0],
+                    size: 50.0,
+                    color: [0, 0, 255, 255],
+                    rotation: 0.0,
+                }),
+            ],
+        };
+        let mut bytes = b"UWU\x01".to_vec();
+        bytes.extend_from_slice(&rkyv::to_bytes::<rkyv::rancor::Error>(&flat).unwrap());
+
+        let canvas = CanvasState::from_bytes(&bytes, &egui::Context::default()).unwrap();
+        assert_eq!(canvas.objects.len(), 2);
+        let CanvasObject::Stroke(stroke) = &canvas.objects[0] else {
+            panic!("expected a stroke");
+        };
+        assert_eq!(stroke.points, [Pos2::ZERO, Pos2::new(10.0, 5.0)]);
+        assert_eq!(stroke.color, Color32::RED);
+        let CanvasObject::Shape(line) = &canvas.objects[1] else {
+            panic!("expected a shape");
+        };
+        assert_eq!(line.end, Pos2::new(70.0, 20.0));
+    }
+}
@@ -1,18 +1,27 @@
+pub mod autosave;
+pub mod board;
 pub mod flat;
+mod flat_v1;
+pub mod recording;
 
+use autosave::Autosaver;
 use flat::CanvasStateFlat;
+use recording::{SessionPlayer, SessionRecorder};
 
 use egui::{Color32, Pos2, Stroke};
 use egui_notify::Toasts;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
+use std::ops::RangeInclusive;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use wgpu::Backend;
 use wgpu::PresentMode;
 use winit::dpi::PhysicalPosition;
+use winit::event::Force;
+use winit::keyboard::ModifiersState;
 
 #[cfg(feature = "startup_animation")]
 use egui::{ColorImage, Context, TextureHandle, TextureOptions};
@@ -25,12 +34,19 @@ use rodio::Player;
 #[cfg(feature = "startup_animation")]
 use std::io::Cursor;
 
+use crate::i18n::{self, Lang, t};
+use crate::net::collab::CollabSession;
+use crate::net::spectator::SpectatorServer;
+use crate::net::transfer::BoardTransfer;
+use crate::spatial::ChunkedObjects;
 use crate::utils;
 
 /// Magic header for canvas files: `b"UWU"` followed by format version byte.
 /// Must be kept in sync with [`CANVAS_FILE_HEADER`].
 const CANVAS_FILE_MAGIC: &[u8; 3] = b"UWU";
-const CANVAS_FILE_VERSION: u8 = 1;
+const CANVAS_FILE_VERSION: u8 = 2;
+// 仍可读取的旧版本，只有对象的基本属性
+const CANVAS_FILE_VERSION_V1: u8 = 1;
 
 fn make_canvas_file_header() -> [u8; 4] {
     let mut h = [0u8; 4];
@@ -46,6 +62,196 @@ pub enum DynamicBrushWidthMode {
     Disabled, // No dynamic width adjustment
     BrushTip,   // Simulates brush tip pressure for calligraphy effect
     SpeedBased, // Adjusts width based on drawing speed
+    Pressure,   // Scales width by stylus pressure
+}
+
+/// Tunable parameters of the brush tip and speed based width modes, different devices need different mappings
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DynamicWidthCurve {
+    pub taper_start: f32,          // 笔锋开始收窄的位置，占笔画的比例
+    pub taper_end_multiplier: f32, // 笔锋末端的宽度倍数
+    pub speed_normalization: f32,  // 达到最细宽度的速度，像素/秒
+    pub min_multiplier: f32,       // 速度最快时的宽度倍数
+    pub max_multiplier: f32,       // 静止时的宽度倍数
+}
+
+impl Default for DynamicWidthCurve {
+    fn default() -> Self {
+        Self {
+            taper_start: 0.7,
+            taper_end_multiplier: 0.4,
+            speed_normalization: 500.0,
+            min_multiplier: 0.5,
+            max_multiplier: 1.5,
+        }
+    }
+}
+
+/// Brush, background and grid settings of the last session, restored on the next launch
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DrawingDefaults {
+    pub brush_color: Color32,
+    pub brush_opacity: u8,
+    pub brush_width: f32,
+    pub highlighter_color: Color32,
+    pub highlighter_width: f32,
+    pub eraser_size: f32,
+    pub background_pattern: BackgroundPattern,
+    pub background_spacing: f32,
+    pub background_pattern_color: Color32,
+    pub show_grid: bool,
+    pub grid_size: f32,
+}
+
+impl Default for DrawingDefaults {
+    fn default() -> Self {
+        Self {
+            brush_color: Color32::WHITE,
+            brush_opacity: 255,
+            brush_width: 3.0,
+            highlighter_color: Color32::YELLOW,
+            highlighter_width: 20.0,
+            eraser_size: 10.0,
+            background_pattern: BackgroundPattern::default(),
+            background_spacing: 40.0,
+            background_pattern_color: Color32::from_gray(128).gamma_multiply(0.5),
+            show_grid: false,
+            grid_size: 40.0,
+        }
+    }
+}
+
+/// Measuring aid shown by the measure tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeasureKind {
+    #[default]
+    Ruler,
+    Protractor,
+}
+
+/// Part of the ruler or protractor that is dragged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeasureHandle {
+    Body,     // 拖动整体
+    RulerEnd, // 直尺的末端，同时改变长度与方向
+    BaseArm,  // 量角器的 0° 边，旋转整个量角器
+    AngleArm, // 量角器的测量边
+}
+
+/// Ruler and protractor of the measure tool, in canvas coordinates.
+/// They are only measuring aids and never become objects.
+#[derive(Debug, Clone, Copy)]
+pub struct MeasureState {
+    pub kind: MeasureKind,
+    pub pinned: bool,      // 切换到其他工具后仍然显示
+    pub ruler_start: Pos2, // 直尺的 0 刻度
+    pub ruler_end: Pos2,
+    pub protractor_center: Pos2,
+    pub protractor_base: f32,  // 0° 边的方向
+    pub protractor_angle: f32, // 测量边与 0° 边的夹角（逆时针），0..2π
+}
+
+impl Default for MeasureState {
+    fn default() -> Self {
+        Self {
+            kind: MeasureKind::default(),
+            pinned: false,
+            ruler_start: Pos2::new(100.0, 300.0),
+            ruler_end: Pos2::new(500.0, 300.0),
+            protractor_center: Pos2::new(300.0, 300.0),
+            protractor_base: 0.0,
+            protractor_angle: std::f32::consts::FRAC_PI_4,
+        }
+    }
+}
+
+impl MeasureState {
+    /// Moves the ruler and protractor to `center`, keeping their size and direction
+    pub fn move_to(&mut self, center: Pos2) {
+        let ruler_center = self.ruler_start.lerp(self.ruler_end, 0.5);
+        let delta = center - ruler_center;
+        self.ruler_start += delta;
+        self.ruler_end += delta;
+        self.protractor_center = center;
+    }
+}
+
+/// A running presentation, which shows the pages full screen without the toolbar
+#[derive(Debug, Clone, Copy)]
+pub struct Presentation {
+    /// When the current page was shown, auto-advancing counts from here
+    pub page_shown_at: Instant,
+}
+
+/// Context menu opened on an object by right-clicking or long-pressing it
+#[derive(Debug, Clone, Copy)]
+pub struct ObjectContextMenu {
+    pub index: usize,
+    /// Where the menu opens, in screen coordinates
+    pub pos: Pos2,
+    /// History revision when the menu opened; once the canvas changes `index` may point elsewhere
+    pub revision: u64,
+}
+
+/// Mirroring of brush strokes around the center of the view while drawing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymmetryMode {
+    #[default]
+    Off,
+    Vertical,   // 沿竖直轴左右镜像
+    Horizontal, // 沿水平轴上下镜像
+    Radial {
+        folds: u32,
+    }, // 绕中心旋转复制，共 folds 份
+}
+
+impl SymmetryMode {
+    /// Mirrored copies of `points` around `center`, not including the original
+    pub fn mirror(&self, points: &[Pos2], center: Pos2) -> Vec<Vec<Pos2>> {
+        match *self {
+            SymmetryMode::Off => Vec::new(),
+            SymmetryMode::Vertical => vec![
+                points
+                    .iter()
+                    .map(|p| Pos2::new(2.0 * center.x - p.x, p.y))
+                    .collect(),
+            ],
+            SymmetryMode::Horizontal => vec![
+                points
+                    .iter()
+                    .map(|p| Pos2::new(p.x, 2.0 * center.y - p.y))
+                    .collect(),
+            ],
+            SymmetryMode::Radial { folds } => (1..folds)
+                .map(|i| {
+                    let angle = i as f32 / folds as f32 * std::f32::consts::TAU;
+                    points
+                        .iter()
+                        .map(|&p| utils::rotate_point(p, center, angle))
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Which layers the erasers remove objects from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EraserScope {
+    AllLayers,
+    #[default]
+    ActiveLayer,
+}
+
+/// Smoothing applied to brush strokes when they are finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum SmoothingMode {
+    None,
+    #[default]
+    MovingAverage, // Chaikin corner cutting followed by a moving average
+    CatmullRom, // Spline through the original points
 }
 
 /// Stroke width representation
@@ -140,32 +346,73 @@ pub enum TransformHandle {
     Rotate,
 }
 
+/// Decorative pattern drawn over the canvas color, like ruled or dotted paper
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BackgroundPattern {
+    #[default]
+    Blank,
+    Grid,
+    Dots,
+    Lines,
+}
+
 /// Available tools for canvas interaction
 #[derive(Clone, Copy, PartialEq, Eq, Default)]
 pub enum CanvasTool {
     Select, // Select and manipulate objects
+    Pan,    // Pan and zoom the canvas
     #[default]
     Brush, // Draw freehand strokes
+    Highlighter, // Draw translucent, constant-width strokes
+    Eyedropper, // Pick the brush color from the canvas
+    Fill,   // Fill the enclosed region under the cursor
     ObjectEraser, // Delete entire objects
     PixelEraser, // Erase pixel by pixel
     Insert, // Insert images, text, or shapes
+    Measure, // Position a ruler or protractor to measure on the canvas
     Settings, // Open settings panel
     Passthrough, // Only available in passthrough mode; passes clicks through to underlying windows
 }
 
+impl fmt::Display for CanvasTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CanvasTool::Select => t("选择"),
+            CanvasTool::Pan => t("平移"),
+            CanvasTool::Brush => t("画笔"),
+            CanvasTool::Highlighter => t("荧光笔"),
+            CanvasTool::Eyedropper => t("取色"),
+            CanvasTool::Fill => t("填充"),
+            CanvasTool::ObjectEraser => t("对象擦"),
+            CanvasTool::PixelEraser => t("像素擦"),
+            CanvasTool::Insert => t("插入"),
+            CanvasTool::Measure => t("测量"),
+            CanvasTool::Settings => t("设置"),
+            CanvasTool::Passthrough => t("穿透"),
+        };
+        write!(f, "{name}")
+    }
+}
+
 /// Trait for objects that can be rendered on the canvas
 pub trait CanvasObjectOps {
     /// Renders the object using the provided painter
     fn paint(&self, painter: &egui::Painter, selected: bool);
     /// Returns the axis-aligned bounding rectangle of the object
     fn bounding_box(&self) -> egui::Rect;
-    /// Transforms the object using the specified handle and drag parameters
+    /// Returns whether the given position hits the object, used for selection
+    fn hit_test(&self, pos: Pos2) -> bool {
+        self.bounding_box().contains(pos)
+    }
+    /// Transforms the object using the specified handle and drag parameters.
+    /// `keep_aspect_ratio` only affects objects that can be stretched, i.e. images.
     fn transform(
         &mut self,
         handle: TransformHandle,
         delta: egui::Vec2,
         drag_start: Pos2,
         current_pos: Pos2,
+        keep_aspect_ratio: bool,
     );
 }
 
@@ -180,101 +427,276 @@ pub struct CanvasImage {
     pub marked_for_deletion: bool, // Deferred deletion to avoid borrow checker issues
     pub image_data: Arc<[u8]>,     // RGBA pixel data for export
     pub image_size: [u32; 2],      // [width, height] of the original image
+    pub crop: egui::Rect,          // 显示的图片区域（纹理坐标），裁剪不会丢弃像素
+    pub adjustments: ImageAdjustments,
+    pub animation: Option<ImageAnimation>, // 动图的各帧，静态图片为 None
+    pub opacity: u8,
+    pub layer: LayerId,
+    pub group: Option<GroupId>, // 所属的组合，组合内的对象一起选中和移动
+    pub locked: bool,           // 锁定的对象无法被选中、移动或擦除
+}
+
+/// Color adjustments of an image, applied to a copy of its pixels so they can be changed back at any time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageAdjustments {
+    pub grayscale: bool,
+    pub brightness: f32, // -1.0..=1.0，0 表示不调整
+    pub contrast: f32,   // -1.0..=1.0，0 表示不调整
+}
+
+impl Default for ImageAdjustments {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+impl ImageAdjustments {
+    pub const NONE: Self = Self {
+        grayscale: false,
+        brightness: 0.0,
+        contrast: 0.0,
+    };
+
+    pub fn is_none(&self) -> bool {
+        *self == Self::NONE
+    }
+
+    /// Returns the adjusted copy of the RGBA pixels `rgba`, or `rgba` itself when nothing is adjusted
+    pub fn apply<'a>(&self, rgba: &'a [u8]) -> std::borrow::Cow<'a, [u8]> {
+        if self.is_none() {
+            return std::borrow::Cow::Borrowed(rgba);
+        }
+
+        // 提高对比度时放大得更快，使滑块两端的效果相近
+        let contrast = if self.contrast >= 0.0 {
+            1.0 + self.contrast * 3.0
+        } else {
+            1.0 + self.contrast
+        };
+        let lut: [u8; 256] = std::array::from_fn(|v| {
+            let v = (v as f32 / 255.0 - 0.5) * contrast + 0.5 + self.brightness;
+            (v.clamp(0.0, 1.0) * 255.0).round() as u8
+        });
+
+        let mut pixels = rgba.to_vec();
+        for pixel in pixels.chunks_exact_mut(4) {
+            if self.grayscale {
+                let luma =
+                    0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32;
+                let luma = luma.round() as u8;
+                pixel[..3].fill(luma);
+            }
+            for channel in &mut pixel[..3] {
+                *channel = lut[*channel as usize];
+            }
+        }
+        std::borrow::Cow::Owned(pixels)
+    }
+}
+
+/// One frame of an animated image
+#[derive(Clone)]
+pub struct AnimationFrame {
+    pub pixels: Arc<[u8]>, // RGBA 像素，大小与所属图片的 image_size 相同
+    pub delay: Duration,
+    pub texture: egui::TextureHandle,
+}
+
+/// Frames of an animated image such as a GIF, shown in turn while the image is on screen
+#[derive(Clone)]
+pub struct ImageAnimation {
+    pub frames: Vec<AnimationFrame>,
 }
 
+impl ImageAnimation {
+    /// Shortest time a frame is shown; many GIFs use a delay of 0 and rely on viewers to slow them down
+    pub const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+    /// Most frames kept from one animated image, each frame takes up its own texture
+    pub const MAX_FRAMES: usize = 300;
+    /// Total number of pixels that the frames of one animated image may hold
+    pub const MAX_PIXELS: u64 = 64 * 1024 * 1024;
+
+    /// How many frames of `size` fit within [`Self::MAX_FRAMES`] and [`Self::MAX_PIXELS`], at least one
+    pub fn frame_limit(size: [u32; 2]) -> usize {
+        let frame_pixels = (size[0] as u64 * size[1] as u64).max(1);
+        ((Self::MAX_PIXELS / frame_pixels) as usize).clamp(1, Self::MAX_FRAMES)
+    }
+
+    /// Uploads `frames` (RGBA pixels of `size` and how long each is shown) as textures,
+    /// frames beyond [`Self::frame_limit`] are dropped
+    pub fn new(
+        ctx: &egui::Context,
+        frames: Vec<(Arc<[u8]>, Duration)>,
+        size: [u32; 2],
+        adjustments: ImageAdjustments,
+    ) -> Self {
+        let frames = frames
+            .into_iter()
+            .take(Self::frame_limit(size))
+            .map(|(pixels, delay)| AnimationFrame {
+                texture: load_image_texture(ctx, size, &adjustments.apply(&pixels)),
+                pixels,
+                delay: delay.max(Self::MIN_FRAME_DELAY),
+            })
+            .collect();
+        Self { frames }
+    }
+
+    /// Index of the frame shown `time` seconds after the animation started, and how long until the next one
+    fn frame_at(&self, time: f64) -> (usize, Duration) {
+        let total: Duration = self.frames.iter().map(|frame| frame.delay).sum();
+        let mut time = time.rem_euclid(total.as_secs_f64());
+        for (index, frame) in self.frames.iter().enumerate() {
+            let delay = frame.delay.as_secs_f64();
+            if time < delay {
+                return (index, Duration::from_secs_f64(delay - time));
+            }
+            time -= delay;
+        }
+        (self.frames.len() - 1, Duration::ZERO)
+    }
+}
+
+fn load_image_texture(ctx: &egui::Context, size: [u32; 2], rgba: &[u8]) -> egui::TextureHandle {
+    let [width, height] = size;
+    ctx.load_texture(
+        "inserted_image",
+        egui::ColorImage::from_rgba_unmultiplied([width as usize, height as usize], rgba),
+        egui::TextureOptions::LINEAR,
+    )
+}
+
+/// Crop of an image that shows all of it, in texture coordinates
+pub const FULL_IMAGE_CROP: egui::Rect = egui::Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0));
+
 impl CanvasObjectOps for CanvasImage {
-    /// Transforms the image based on the dragged handle
+    /// Transforms the image based on the dragged handle, the opposite side stays in place
     #[cfg_attr(feature = "profiling", profiling::function)]
     fn transform(
         &mut self,
         handle: TransformHandle,
         _delta: egui::Vec2,
-        _drag_start: Pos2,
+        drag_start: Pos2,
         current_pos: Pos2,
+        keep_aspect_ratio: bool,
     ) {
-        let bbox = self.bounding_box();
+        const MIN_SIZE: f32 = 10.0;
+
+        let bbox = self.local_rect();
+        // 旋转使用原始的指针位置，drag_start 每帧都会更新为上一帧的位置
+        if handle == TransformHandle::Rotate {
+            let center = bbox.center();
+            self.rot += (current_pos - center).angle() - (drag_start - center).angle();
+            return;
+        }
+
+        // 在图片自身（未旋转）的坐标系中缩放
+        let current_pos = utils::rotate_point(current_pos, bbox.center(), -self.rot);
+        let (mut min, mut max) = (bbox.min, bbox.max);
 
         match handle {
-            TransformHandle::TopLeft => {
-                let new_min = current_pos;
-                let new_max = bbox.max;
-                let new_size = egui::vec2(
-                    (new_max.x - new_min.x).max(10.0),
-                    (new_max.y - new_min.y).max(10.0),
-                );
-                self.size = new_size;
-                self.pos = new_min;
-            }
-            TransformHandle::Top => {
-                let new_height = (bbox.max.y - current_pos.y).max(10.0);
-                self.size.y = new_height;
-                self.pos.y = current_pos.y;
-            }
+            TransformHandle::TopLeft => min = current_pos,
+            TransformHandle::Top => min.y = current_pos.y,
             TransformHandle::TopRight => {
-                let new_max = Pos2::new(current_pos.x, bbox.max.y);
-                let new_min = Pos2::new(bbox.min.x, current_pos.y);
-                let new_size = egui::vec2(
-                    (new_max.x - new_min.x).max(10.0),
-                    (new_max.y - new_min.y).max(10.0),
-                );
-                self.size = new_size;
-                self.pos.y = new_min.y;
-            }
-            TransformHandle::Left => {
-                let new_width = (bbox.max.x - current_pos.x).max(10.0);
-                self.size.x = new_width;
-                self.pos.x = current_pos.x;
-            }
-            TransformHandle::Right => {
-                let new_width = (current_pos.x - bbox.min.x).max(10.0);
-                self.size.x = new_width;
+                min.y = current_pos.y;
+                max.x = current_pos.x;
             }
+            TransformHandle::Left => min.x = current_pos.x,
+            TransformHandle::Right => max.x = current_pos.x,
             TransformHandle::BottomLeft => {
-                let new_min = Pos2::new(current_pos.x, bbox.min.y);
-                let new_max = Pos2::new(bbox.max.x, current_pos.y);
-                let new_size = egui::vec2(
-                    (new_max.x - new_min.x).max(10.0),
-                    (new_max.y - new_min.y).max(10.0),
-                );
-                self.size = new_size;
-                self.pos.x = new_min.x;
+                min.x = current_pos.x;
+                max.y = current_pos.y;
             }
-            TransformHandle::Bottom => {
-                let new_height = (current_pos.y - bbox.min.y).max(10.0);
-                self.size.y = new_height;
+            TransformHandle::Bottom => max.y = current_pos.y,
+            TransformHandle::BottomRight => max = current_pos,
+            TransformHandle::Rotate => unreachable!(),
+        }
+
+        let mut size = egui::vec2((max.x - min.x).max(MIN_SIZE), (max.y - min.y).max(MIN_SIZE));
+
+        if keep_aspect_ratio && self.aspect_ratio > 0.0 {
+            let ratio = self.aspect_ratio;
+            size = match handle {
+                TransformHandle::Top | TransformHandle::Bottom => {
+                    egui::vec2(size.y * ratio, size.y)
+                }
+                TransformHandle::Left | TransformHandle::Right => {
+                    egui::vec2(size.x, size.x / ratio)
+                }
+                // 角落手柄按拖动幅度较大的方向缩放
+                _ if size.x / size.y > ratio => egui::vec2(size.x, size.x / ratio),
+                _ => egui::vec2(size.y * ratio, size.y),
+            };
+            if size.x < MIN_SIZE || size.y < MIN_SIZE {
+                let scale = (MIN_SIZE / size.x).max(MIN_SIZE / size.y);
+                size *= scale;
             }
-            TransformHandle::BottomRight => {
-                let new_size = egui::vec2(
-                    (current_pos.x - bbox.min.x).max(10.0),
-                    (current_pos.y - bbox.min.y).max(10.0),
-                );
-                self.size = new_size;
+        }
+
+        self.pos.x = match handle {
+            TransformHandle::TopLeft | TransformHandle::Left | TransformHandle::BottomLeft => {
+                bbox.max.x - size.x
             }
-            TransformHandle::Rotate => {
-                // For now, ignore rotation for images
+            _ => bbox.min.x,
+        };
+        self.pos.y = match handle {
+            TransformHandle::TopLeft | TransformHandle::Top | TransformHandle::TopRight => {
+                bbox.max.y - size.y
             }
+            _ => bbox.min.y,
+        };
+        self.size = size;
+
+        // 旋转中心随尺寸移动，平移图片使保持不动的一角在画布上的位置不变
+        if self.rot.abs() > 0.001 {
+            let anchor = Pos2::new(
+                if self.pos.x == bbox.min.x {
+                    bbox.min.x
+                } else {
+                    bbox.max.x
+                },
+                if self.pos.y == bbox.min.y {
+                    bbox.min.y
+                } else {
+                    bbox.max.y
+                },
+            );
+            let before = utils::rotate_point(anchor, bbox.center(), self.rot);
+            let after = utils::rotate_point(anchor, self.local_rect().center(), self.rot);
+            self.pos += before - after;
         }
     }
 
-    /// Returns the bounding rectangle of the image
+    /// Returns the bounding rectangle of the image, including its rotation
     #[cfg_attr(feature = "profiling", profiling::function)]
     fn bounding_box(&self) -> egui::Rect {
-        egui::Rect::from_min_size(self.pos, self.size)
+        utils::rotated_rect_bounds(self.local_rect(), self.rot)
+    }
+
+    /// Tests against the rotated image instead of its bounding box
+    fn hit_test(&self, pos: Pos2) -> bool {
+        let rect = self.local_rect();
+        rect.contains(utils::rotate_point(pos, rect.center(), -self.rot))
     }
 
     /// Renders the image on the canvas, drawing selection UI if selected
     #[cfg_attr(feature = "profiling", profiling::function)]
     fn paint(&self, painter: &egui::Painter, selected: bool) {
-        let img_rect = self.bounding_box();
-        painter.image(
-            self.texture.id(),
-            img_rect,
-            egui::Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
-            Color32::WHITE,
+        let local_rect = self.local_rect();
+        let mut mesh = egui::Mesh::with_texture(self.texture_id(painter.ctx()));
+        mesh.add_rect_with_uv(
+            local_rect,
+            self.crop,
+            Color32::WHITE.gamma_multiply(self.opacity as f32 / 255.0),
         );
+        if self.rot.abs() > 0.001 {
+            mesh.rotate(egui::emath::Rot2::from_angle(self.rot), local_rect.center());
+        }
+        painter.add(egui::Shape::mesh(mesh));
 
         // Draw selection border and resize handles when selected
         if selected {
+            let img_rect = self.bounding_box();
             painter.rect_stroke(
                 img_rect,
                 0.0,
@@ -286,6 +708,138 @@ impl CanvasObjectOps for CanvasImage {
     }
 }
 
+impl CanvasImage {
+    /// Rectangle covered by the image before rotation
+    pub fn local_rect(&self) -> egui::Rect {
+        egui::Rect::from_min_size(self.pos, self.size)
+    }
+
+    /// Rectangle the whole image would cover before rotation, including the cropped away parts
+    pub fn uncropped_rect(&self) -> egui::Rect {
+        let full_size = self.size / self.crop.size();
+        let min = self.pos - self.crop.min.to_vec2() * full_size;
+        egui::Rect::from_min_size(min, full_size)
+    }
+
+    /// Texture coordinates of the point of the whole image under the canvas position `pos`
+    pub fn texture_coords_at(&self, pos: Pos2) -> Pos2 {
+        let pos = utils::rotate_point(pos, self.local_rect().center(), -self.rot);
+        let full = self.uncropped_rect();
+        let uv = (pos - full.min) / full.size();
+        Pos2::new(uv.x.clamp(0.0, 1.0), uv.y.clamp(0.0, 1.0))
+    }
+
+    /// Shows only the `crop` part of the image, keeping the visible pixels where they are on the canvas
+    pub fn set_crop(&mut self, crop: egui::Rect) {
+        let full = self.uncropped_rect();
+        let old_center = self.local_rect().center();
+        let size = crop.size() * full.size();
+        let local_center = full.min + crop.center().to_vec2() * full.size();
+        // 旋转的图片绕其中心旋转，裁剪后中心改变，需要换算到旋转后的位置
+        let center = utils::rotate_point(local_center, old_center, self.rot);
+        self.pos = center - size / 2.0;
+        self.size = size;
+        self.aspect_ratio = size.x / size.y;
+        self.crop = crop;
+    }
+
+    /// Texture to draw now; for animated images this is the current frame, and a repaint is requested for the next one
+    pub fn texture_id(&self, ctx: &egui::Context) -> egui::TextureId {
+        let Some(animation) = &self.animation else {
+            return self.texture.id();
+        };
+        let (index, remaining) = animation.frame_at(ctx.input(|i| i.time));
+        // 只在动图被绘制（即可见）时才持续重绘
+        ctx.request_repaint_after(remaining);
+        animation.frames[index].texture.id()
+    }
+
+    /// Pixels as they are shown, with the adjustments applied
+    pub fn adjusted_pixels(&self) -> std::borrow::Cow<'_, [u8]> {
+        self.adjustments.apply(&self.image_data)
+    }
+
+    /// Changes the adjustments and uploads the adjusted pixels as the new texture
+    pub fn set_adjustments(&mut self, adjustments: ImageAdjustments, ctx: &egui::Context) {
+        if self.adjustments != adjustments {
+            self.adjustments = adjustments;
+            self.reload_texture(ctx);
+        }
+    }
+
+    /// Mirrors the pixels left to right, or top to bottom if `horizontal` is false
+    pub fn flip(&mut self, horizontal: bool, ctx: &egui::Context) {
+        let (min, max) = (self.crop.min, self.crop.max);
+        if horizontal {
+            self.crop = egui::Rect::from_min_max(
+                Pos2::new(1.0 - max.x, min.y),
+                Pos2::new(1.0 - min.x, max.y),
+            );
+            self.transform_pixels(image::imageops::flip_horizontal, ctx);
+        } else {
+            self.crop = egui::Rect::from_min_max(
+                Pos2::new(min.x, 1.0 - max.y),
+                Pos2::new(max.x, 1.0 - min.y),
+            );
+            self.transform_pixels(image::imageops::flip_vertical, ctx);
+        }
+    }
+
+    /// Rotates the pixels 90° clockwise, keeping the center of the image in place
+    pub fn rotate_90(&mut self, ctx: &egui::Context) {
+        let (min, max) = (self.crop.min, self.crop.max);
+        // 顺时针旋转后原来的纹理坐标 (u, v) 变为 (1 - v, u)
+        self.crop =
+            egui::Rect::from_min_max(Pos2::new(1.0 - max.y, min.x), Pos2::new(1.0 - min.y, max.x));
+        let center = self.local_rect().center();
+        self.size = egui::vec2(self.size.y, self.size.x);
+        self.pos = center - self.size / 2.0;
+        self.aspect_ratio = self.size.x / self.size.y;
+        self.transform_pixels(image::imageops::rotate90, ctx);
+    }
+
+    // 对图片（动图的每一帧）做同样的变换后上传新的纹理，
+    // 旧纹理在所有引用（例如撤销记录）释放后才会被回收
+    fn transform_pixels(
+        &mut self,
+        transform: impl Fn(&image::RgbaImage) -> image::RgbaImage,
+        ctx: &egui::Context,
+    ) {
+        let [width, height] = self.image_size;
+        let apply = |pixels: &Arc<[u8]>| {
+            image::RgbaImage::from_raw(width, height, pixels.to_vec())
+                .map(|pixels| transform(&pixels))
+        };
+        let Some(still) = apply(&self.image_data) else {
+            return;
+        };
+        if let Some(animation) = &mut self.animation {
+            for frame in &mut animation.frames {
+                if let Some(pixels) = apply(&frame.pixels) {
+                    frame.pixels = Arc::from(pixels.into_raw());
+                }
+            }
+        }
+        self.image_size = [still.width(), still.height()];
+        self.image_data = Arc::from(still.into_raw());
+        self.reload_texture(ctx);
+    }
+
+    /// Uploads the pixel data again as a texture of `ctx`, after the renderer has been recreated
+    pub fn reload_texture(&mut self, ctx: &egui::Context) {
+        self.texture = load_image_texture(ctx, self.image_size, &self.adjusted_pixels());
+        if let Some(animation) = &mut self.animation {
+            for frame in &mut animation.frames {
+                frame.texture = load_image_texture(
+                    ctx,
+                    self.image_size,
+                    &self.adjustments.apply(&frame.pixels),
+                );
+            }
+        }
+    }
+}
+
 impl fmt::Debug for CanvasImage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("CanvasImage")
@@ -295,10 +849,114 @@ impl fmt::Debug for CanvasImage {
             .field("aspect_ratio", &self.aspect_ratio)
             .field("marked_for_deletion", &self.marked_for_deletion)
             .field("image_size", &self.image_size)
+            .field("crop", &self.crop)
+            .field("adjustments", &self.adjustments)
+            .field(
+                "animation_frames",
+                &self
+                    .animation
+                    .as_ref()
+                    .map(|animation| animation.frames.len()),
+            )
             .finish()
     }
 }
 
+/// How a background image is placed in the area shown by the default view
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BackgroundFit {
+    Stretch, // 拉伸填满，不保持宽高比
+    #[default]
+    Fit, // 完整显示，保持宽高比
+    Fill,    // 裁剪以填满，保持宽高比
+    Center,  // 原始大小居中
+}
+
+/// Image drawn behind every object of a page; it can't be selected or erased
+#[derive(Clone)]
+pub struct BackgroundImage {
+    pub texture: egui::TextureHandle,
+    pub image_data: Arc<[u8]>, // RGBA pixel data for saving
+    pub image_size: [u32; 2],
+    pub fit: BackgroundFit,
+}
+
+impl BackgroundImage {
+    pub fn new(ctx: &egui::Context, rgba: image::RgbaImage, fit: BackgroundFit) -> Self {
+        let (width, height) = rgba.dimensions();
+        let texture = ctx.load_texture(
+            "background_image",
+            egui::ColorImage::from_rgba_unmultiplied([width as usize, height as usize], &rgba),
+            egui::TextureOptions::LINEAR,
+        );
+        Self {
+            texture,
+            image_data: rgba.into_raw().into(),
+            image_size: [width, height],
+            fit,
+        }
+    }
+
+    /// Uploads the pixel data again as a texture of `ctx`, after the renderer has been recreated
+    pub fn reload_texture(&mut self, ctx: &egui::Context) {
+        let [width, height] = self.image_size;
+        self.texture = ctx.load_texture(
+            "background_image",
+            egui::ColorImage::from_rgba_unmultiplied(
+                [width as usize, height as usize],
+                &self.image_data,
+            ),
+            egui::TextureOptions::LINEAR,
+        );
+    }
+
+    /// Paints the image into `frame`, given in canvas coordinates
+    pub fn paint(&self, painter: &egui::Painter, frame: egui::Rect) {
+        let image_size = egui::vec2(self.image_size[0] as f32, self.image_size[1] as f32);
+        let full_uv = egui::Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0));
+        let (rect, uv) = match self.fit {
+            BackgroundFit::Stretch => (frame, full_uv),
+            BackgroundFit::Fit => {
+                let scale = (frame.width() / image_size.x).min(frame.height() / image_size.y);
+                (
+                    egui::Rect::from_center_size(frame.center(), image_size * scale),
+                    full_uv,
+                )
+            }
+            BackgroundFit::Fill => {
+                // 只显示图片中与画面宽高比相同的中间部分
+                let scale = (frame.width() / image_size.x).max(frame.height() / image_size.y);
+                let visible = frame.size() / (image_size * scale);
+                (
+                    frame,
+                    egui::Rect::from_center_size(Pos2::new(0.5, 0.5), visible),
+                )
+            }
+            BackgroundFit::Center => (
+                egui::Rect::from_center_size(frame.center(), image_size),
+                full_uv,
+            ),
+        };
+        painter.image(self.texture.id(), rect, uv, Color32::WHITE);
+    }
+}
+
+impl fmt::Debug for BackgroundImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BackgroundImage")
+            .field("texture", &"<TextureHandle>")
+            .field("image_size", &self.image_size)
+            .field("fit", &self.fit)
+            .finish()
+    }
+}
+
+/// Lines of inserted text wrap once they grow wider than this
+pub const TEXT_WRAP_WIDTH: f32 = 800.0;
+
+/// Angle between an arrow's shaft and each side of its head
+const ARROW_HEAD_ANGLE: f32 = std::f32::consts::PI / 6.0; // 30度
+
 /// Text object that can be placed on the canvas
 #[derive(Debug, Clone)]
 pub struct CanvasText {
@@ -308,6 +966,21 @@ pub struct CanvasText {
     pub font_size: f32,
     pub rot: f32,
     pub cached_size: Option<egui::Vec2>,
+    pub layer: LayerId,
+    pub group: Option<GroupId>,
+    pub locked: bool,
+}
+
+impl CanvasText {
+    /// Lays out the text, keeping its line breaks and wrapping overly long lines
+    pub fn layout(&self, painter: &egui::Painter) -> Arc<egui::Galley> {
+        painter.layout(
+            self.text.clone(),
+            egui::FontId::proportional(self.font_size),
+            self.color,
+            TEXT_WRAP_WIDTH,
+        )
+    }
 }
 
 impl CanvasObjectOps for CanvasText {
@@ -319,6 +992,7 @@ impl CanvasObjectOps for CanvasText {
         delta: egui::Vec2,
         _drag_start: Pos2,
         _current_pos: Pos2,
+        _keep_aspect_ratio: bool,
     ) {
         match handle {
             TransformHandle::TopLeft
@@ -344,8 +1018,14 @@ impl CanvasObjectOps for CanvasText {
             egui::Rect::from_min_size(self.pos, size)
         } else {
             let approx_char_width = self.font_size * 0.6;
-            let approx_width = self.text.len() as f32 * approx_char_width;
-            let approx_height = self.font_size * 1.2;
+            let longest_line = self
+                .text
+                .lines()
+                .map(|line| line.chars().count())
+                .max()
+                .unwrap_or(0);
+            let approx_width = (longest_line as f32 * approx_char_width).min(TEXT_WRAP_WIDTH);
+            let approx_height = self.font_size * 1.2 * self.text.lines().count().max(1) as f32;
             egui::Rect::from_min_size(self.pos, egui::vec2(approx_width, approx_height))
         }
     }
@@ -354,11 +1034,7 @@ impl CanvasObjectOps for CanvasText {
     #[cfg_attr(feature = "profiling", profiling::function)]
     fn paint(&self, painter: &egui::Painter, selected: bool) {
         // Draw text using egui's text rendering
-        let text_galley = painter.layout_no_wrap(
-            self.text.clone(),
-            egui::FontId::proportional(self.font_size),
-            self.color,
-        );
+        let text_galley = self.layout(painter);
         let text_shape = egui::epaint::TextShape {
             pos: self.pos,
             galley: text_galley.clone(),
@@ -383,24 +1059,448 @@ impl CanvasObjectOps for CanvasText {
     }
 }
 
+/// Background of new sticky notes
+pub const STICKY_NOTE_COLOR: Color32 = Color32::from_rgb(255, 235, 120);
+/// Size of new sticky notes
+pub const STICKY_NOTE_SIZE: egui::Vec2 = egui::vec2(200.0, 160.0);
+/// Distance between the text of a sticky note and its edges
+pub const STICKY_NOTE_PADDING: f32 = 12.0;
+/// Corner radius of sticky notes
+pub const STICKY_NOTE_ROUNDING: f32 = 8.0;
+
+/// Sticky note: a colored rounded rectangle with text wrapped inside it
+#[derive(Debug, Clone)]
+pub struct CanvasStickyNote {
+    pub text: String,
+    pub pos: Pos2,
+    pub size: egui::Vec2,
+    pub color: Color32, // 背景色
+    pub text_color: Color32,
+    pub font_size: f32,
+    pub layer: LayerId,
+    pub group: Option<GroupId>,
+    pub locked: bool,
+}
+
+impl CanvasStickyNote {
+    pub fn rect(&self) -> egui::Rect {
+        egui::Rect::from_min_size(self.pos, self.size)
+    }
+
+    /// Lays out the text, wrapping it at the width of the note
+    pub fn layout(&self, painter: &egui::Painter) -> Arc<egui::Galley> {
+        painter.layout(
+            self.text.clone(),
+            egui::FontId::proportional(self.font_size),
+            self.text_color,
+            (self.size.x - 2.0 * STICKY_NOTE_PADDING).max(1.0),
+        )
+    }
+}
+
+impl CanvasObjectOps for CanvasStickyNote {
+    /// Resizes the note, the side opposite to the dragged handle stays in place; notes can't be rotated
+    fn transform(
+        &mut self,
+        handle: TransformHandle,
+        _delta: egui::Vec2,
+        _drag_start: Pos2,
+        current_pos: Pos2,
+        _keep_aspect_ratio: bool,
+    ) {
+        const MIN_SIZE: f32 = 40.0;
+
+        let rect = self.rect();
+        let (mut min, mut max) = (rect.min, rect.max);
+        match handle {
+            TransformHandle::TopLeft => min = current_pos,
+            TransformHandle::Top => min.y = current_pos.y,
+            TransformHandle::TopRight => {
+                min.y = current_pos.y;
+                max.x = current_pos.x;
+            }
+            TransformHandle::Left => min.x = current_pos.x,
+            TransformHandle::Right => max.x = current_pos.x,
+            TransformHandle::BottomLeft => {
+                min.x = current_pos.x;
+                max.y = current_pos.y;
+            }
+            TransformHandle::Bottom => max.y = current_pos.y,
+            TransformHandle::BottomRight => max = current_pos,
+            TransformHandle::Rotate => return,
+        }
+
+        self.size = egui::vec2((max.x - min.x).max(MIN_SIZE), (max.y - min.y).max(MIN_SIZE));
+        self.pos.x = match handle {
+            TransformHandle::TopLeft | TransformHandle::Left | TransformHandle::BottomLeft => {
+                rect.max.x - self.size.x
+            }
+            _ => rect.min.x,
+        };
+        self.pos.y = match handle {
+            TransformHandle::TopLeft | TransformHandle::Top | TransformHandle::TopRight => {
+                rect.max.y - self.size.y
+            }
+            _ => rect.min.y,
+        };
+    }
+
+    fn bounding_box(&self) -> egui::Rect {
+        self.rect()
+    }
+
+    /// Renders the note background and its text, text that doesn't fit is cut off at the edge
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn paint(&self, painter: &egui::Painter, selected: bool) {
+        let rect = self.rect();
+        painter.rect_filled(rect, STICKY_NOTE_ROUNDING, self.color);
+        let galley = self.layout(painter);
+        painter
+            .with_clip_rect(
+                rect.shrink(STICKY_NOTE_PADDING / 2.0)
+                    .intersect(painter.clip_rect()),
+            )
+            .galley(
+                rect.min + egui::Vec2::splat(STICKY_NOTE_PADDING),
+                galley,
+                self.text_color,
+            );
+
+        if selected {
+            painter.rect_stroke(
+                rect,
+                STICKY_NOTE_ROUNDING,
+                Stroke::new(2.0_f32, Color32::BLUE),
+                egui::StrokeKind::Outside,
+            );
+            utils::draw_resize_handles(painter, rect);
+        }
+    }
+}
+
 /// Available shape types for the canvas
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum CanvasShapeType {
     Line,
     Arrow,
     Rectangle,
-    Triangle,
+    Triangle(TriangleKind),
     Circle,
+    Ellipse,
+    Polygon { sides: u32 }, // 正多边形，顶点位于直径为 size 的外接圆上
+    Star { points: u32 },   // 星形，外顶点位于直径为 size 的外接圆上
+}
+
+/// Line style of shape outlines and strokes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LineStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+/// Brush presets that decide the opacity, texture and width of committed strokes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BrushStyle {
+    #[default]
+    Marker, // 马克笔：实心，宽度和纹理按画笔设置
+    Chalk,  // 粉笔：固定宽度，由沿路径散布的半透明颗粒组成
+    Pencil, // 铅笔：细且带颗粒纹理
+}
+
+/// Image repeated along a texture brush stroke, tinted with the stroke color in place of a solid fill
+#[derive(Clone, Debug, PartialEq)]
+pub enum StrokeTexture {
+    Chalk,  // 粉笔：粗颗粒，带有空隙
+    Grain,  // 颗粒：细密的浓淡变化，类似马克笔
+    Canvas, // 布纹：经纬交织
+    Custom(Arc<CustomStrokeTexture>),
+}
+
+/// Stroke texture loaded by the user; bright pixels are painted, dark ones are left out
+#[derive(Debug, PartialEq)]
+pub struct CustomStrokeTexture {
+    /// Hash of the PNG, under which the uploaded texture is cached
+    pub id: u64,
+    /// PNG-encoded mask, stored with every stroke that uses it
+    pub png: Vec<u8>,
+}
+
+impl CustomStrokeTexture {
+    pub fn new(png: Vec<u8>) -> Self {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+        let mut hasher = DefaultHasher::new();
+        png.hash(&mut hasher);
+        Self {
+            id: hasher.finish(),
+            png,
+        }
+    }
+}
+
+/// Triangle variants; all of them point upwards and use `size` as the base width
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TriangleKind {
+    #[default]
+    Equilateral, // 等边三角形
+    Right,     // 直角三角形，直角位于左下角
+    Isosceles, // 等腰三角形，高与底边等长
+}
+
+/// Arrowhead options of arrow shapes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ArrowHeads {
+    pub head_start: bool, // 起点处是否有箭头
+    pub head_end: bool,   // 终点处是否有箭头
+    pub head_size: f32,   // 箭头两翼的长度
+}
+
+impl Default for ArrowHeads {
+    fn default() -> Self {
+        Self {
+            head_start: false,
+            head_end: true,
+            head_size: 15.0,
+        }
+    }
 }
 
 /// Shape object that can be placed on the canvas
 #[derive(Debug, Clone)]
 pub struct CanvasShape {
     pub shape_type: CanvasShapeType,
-    pub pos: Pos2,
-    pub size: f32,
+    pub pos: Pos2, // 直线和箭头的起点，其他形状的左上角（圆形为圆心）
+    pub end: Pos2, // 直线和箭头的终点，椭圆的右下角，其他形状不使用
+    pub size: f32, // 边长或直径，直线和箭头为长度，椭圆为对角线长度
     pub color: Color32,
     pub rotation: f32,
+    pub line_style: LineStyle,
+    pub fill: Option<Color32>,   // 填充颜色，仅对封闭形状有效
+    pub arrow_heads: ArrowHeads, // 箭头设置，仅对箭头有效
+    pub bounds: egui::Rect,      // 缓存的包围盒，修改位置、大小或旋转后需重新计算
+    pub layer: LayerId,
+    pub group: Option<GroupId>,
+    pub locked: bool,
+}
+
+impl CanvasShape {
+    /// Fills in the cached bounding box of a newly built shape
+    pub fn with_bounds(mut self) -> Self {
+        self.recompute_bounds();
+        self
+    }
+
+    /// Recomputes the cached bounding box, must be called after the position, size or rotation changes
+    pub fn recompute_bounds(&mut self) {
+        self.bounds = utils::rotated_rect_bounds(self.local_bounding_box(), self.rotation);
+    }
+
+    /// Places the shape between two points: lines and arrows run from `start` to `end`,
+    /// ellipses fill the rectangle spanned by both points and other closed shapes
+    /// become the largest square with a corner at `start` that reaches towards `end`
+    pub fn place_between(&mut self, start: Pos2, end: Pos2) {
+        self.rotation = 0.0;
+        match self.shape_type {
+            CanvasShapeType::Line | CanvasShapeType::Arrow => {
+                self.pos = start;
+                self.end = end;
+                self.size = start.distance(end);
+            }
+            CanvasShapeType::Ellipse => {
+                let rect = egui::Rect::from_two_pos(start, end);
+                self.pos = rect.min;
+                self.end = rect.max;
+                self.size = rect.min.distance(rect.max);
+            }
+            CanvasShapeType::Rectangle
+            | CanvasShapeType::Triangle(_)
+            | CanvasShapeType::Circle
+            | CanvasShapeType::Polygon { .. }
+            | CanvasShapeType::Star { .. } => {
+                let delta = end - start;
+                let side = delta.x.abs().max(delta.y.abs());
+                let corner = start + egui::vec2(side.copysign(delta.x), side.copysign(delta.y));
+                let rect = egui::Rect::from_two_pos(start, corner);
+                self.pos = if self.is_centered() {
+                    rect.center()
+                } else {
+                    rect.min
+                };
+                self.end = self.pos;
+                self.size = side;
+            }
+        }
+        self.recompute_bounds();
+    }
+
+    /// Lines, arrows and ellipses are defined by `pos` and `end` rather than by `size` alone
+    pub fn uses_end_point(&self) -> bool {
+        matches!(
+            self.shape_type,
+            CanvasShapeType::Line | CanvasShapeType::Arrow | CanvasShapeType::Ellipse
+        )
+    }
+
+    /// Circles, polygons and stars are positioned by their center instead of their top-left corner
+    fn is_centered(&self) -> bool {
+        matches!(
+            self.shape_type,
+            CanvasShapeType::Circle
+                | CanvasShapeType::Polygon { .. }
+                | CanvasShapeType::Star { .. }
+        )
+    }
+
+    /// Vertices of a polygon or star shape, clockwise from the top, before rotation
+    pub fn star_points(&self) -> Vec<Pos2> {
+        let radius = self.size / 2.0;
+        let (count, inner_radius) = match self.shape_type {
+            CanvasShapeType::Polygon { sides } => (sides.max(3) as usize, radius),
+            CanvasShapeType::Star { points } => (points.max(3) as usize * 2, radius * 0.4),
+            _ => return Vec::new(),
+        };
+        (0..count)
+            .map(|i| {
+                // 星形的奇数顶点为内凹点，多边形内外半径相同
+                let r = if i % 2 == 1 { inner_radius } else { radius };
+                let angle =
+                    i as f32 / count as f32 * std::f32::consts::TAU - std::f32::consts::FRAC_PI_2;
+                self.pos + egui::Vec2::angled(angle) * r
+            })
+            .collect()
+    }
+
+    /// Outline of an ellipse shape as a closed polygon, before rotation, with enough vertices to
+    /// stay within `tolerance` of the curve
+    pub fn ellipse_points(&self, tolerance: f32) -> Vec<Pos2> {
+        let rect = egui::Rect::from_two_pos(self.pos, self.end);
+        let radius = rect.size() / 2.0;
+        let segments = utils::curve_segments(radius.max_elem(), tolerance);
+        (0..segments)
+            .map(|i| {
+                let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+                let offset = egui::Vec2::angled(angle);
+                rect.center() + egui::vec2(offset.x * radius.x, offset.y * radius.y)
+            })
+            .collect()
+    }
+
+    /// Outline of a circle shape as a closed polygon, with enough vertices to stay within
+    /// `tolerance` of the curve
+    pub fn circle_points(&self, tolerance: f32) -> Vec<Pos2> {
+        let radius = self.size / 2.0;
+        let segments = utils::curve_segments(radius, tolerance);
+        (0..segments)
+            .map(|i| {
+                let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+                self.pos + egui::Vec2::angled(angle) * radius
+            })
+            .collect()
+    }
+
+    /// Returns the vertices of a triangle shape, starting from the apex
+    pub fn triangle_points(&self, kind: TriangleKind) -> [Pos2; 3] {
+        let Pos2 { x, y } = self.pos;
+        match kind {
+            TriangleKind::Equilateral => {
+                let height = self.size * 3.0_f32.sqrt() / 2.0;
+                [
+                    Pos2::new(x + self.size / 2.0, y),
+                    Pos2::new(x, y + height),
+                    Pos2::new(x + self.size, y + height),
+                ]
+            }
+            TriangleKind::Right => [
+                Pos2::new(x, y),
+                Pos2::new(x, y + self.size),
+                Pos2::new(x + self.size, y + self.size),
+            ],
+            TriangleKind::Isosceles => [
+                Pos2::new(x + self.size / 2.0, y),
+                Pos2::new(x, y + self.size),
+                Pos2::new(x + self.size, y + self.size),
+            ],
+        }
+    }
+
+    /// Whether the shape encloses an area that can be filled
+    pub fn is_closed(&self) -> bool {
+        matches!(
+            self.shape_type,
+            CanvasShapeType::Rectangle
+                | CanvasShapeType::Triangle(_)
+                | CanvasShapeType::Circle
+                | CanvasShapeType::Ellipse
+                | CanvasShapeType::Polygon { .. }
+                | CanvasShapeType::Star { .. }
+        )
+    }
+
+    /// End point of a line or arrow, before rotation
+    pub fn line_end(&self) -> Pos2 {
+        self.end
+    }
+
+    /// Tip and the two outer points of every enabled arrow head, before rotation
+    pub fn arrow_heads(&self) -> Vec<(Pos2, [Pos2; 2])> {
+        let direction = self.line_end() - self.pos;
+        // 起点与终点重合时没有方向，按水平向右处理
+        let forward = if direction.length_sq() > 0.0 {
+            direction.normalized()
+        } else {
+            egui::Vec2::X
+        };
+        let head = |tip: Pos2, back: egui::Vec2| {
+            let wings = [-ARROW_HEAD_ANGLE, ARROW_HEAD_ANGLE].map(|angle| {
+                tip + egui::emath::Rot2::from_angle(angle) * back * self.arrow_heads.head_size
+            });
+            (tip, wings)
+        };
+
+        let mut heads = Vec::with_capacity(2);
+        if self.arrow_heads.head_end {
+            heads.push(head(self.line_end(), -forward));
+        }
+        if self.arrow_heads.head_start {
+            heads.push(head(self.pos, forward));
+        }
+        heads
+    }
+
+    /// Bounding rectangle of the unrotated shape, padded for handles
+    pub fn local_bounding_box(&self) -> egui::Rect {
+        match self.shape_type {
+            CanvasShapeType::Line => {
+                egui::Rect::from_two_pos(self.pos, self.line_end()).expand(5.0)
+            }
+            CanvasShapeType::Arrow => {
+                let mut points = vec![self.pos, self.line_end()];
+                for (_, wings) in self.arrow_heads() {
+                    points.extend(wings);
+                }
+                egui::Rect::from_points(&points).expand(5.0)
+            }
+            CanvasShapeType::Rectangle => {
+                egui::Rect::from_min_size(self.pos, egui::vec2(self.size, self.size))
+            }
+            CanvasShapeType::Triangle(kind) => {
+                egui::Rect::from_points(&self.triangle_points(kind)).expand(5.0)
+            }
+            CanvasShapeType::Circle => {
+                let radius = self.size / 2.0;
+                egui::Rect::from_min_max(
+                    Pos2::new(self.pos.x - radius - 5.0, self.pos.y - radius - 5.0),
+                    Pos2::new(self.pos.x + radius + 5.0, self.pos.y + radius + 5.0),
+                )
+            }
+            CanvasShapeType::Ellipse => egui::Rect::from_two_pos(self.pos, self.end).expand(5.0),
+            // 外接圆的包围盒
+            CanvasShapeType::Polygon { .. } | CanvasShapeType::Star { .. } => {
+                egui::Rect::from_center_size(self.pos, egui::Vec2::splat(self.size)).expand(5.0)
+            }
+        }
+    }
 }
 
 impl CanvasObjectOps for CanvasShape {
@@ -410,8 +1510,9 @@ impl CanvasObjectOps for CanvasShape {
         &mut self,
         handle: TransformHandle,
         delta: egui::Vec2,
-        _drag_start: Pos2,
-        _current_pos: Pos2,
+        drag_start: Pos2,
+        current_pos: Pos2,
+        _keep_aspect_ratio: bool,
     ) {
         match handle {
             TransformHandle::TopLeft
@@ -422,109 +1523,144 @@ impl CanvasObjectOps for CanvasShape {
             | TransformHandle::BottomLeft
             | TransformHandle::Bottom
             | TransformHandle::BottomRight => {
-                // Scale the shape size uniformly
+                // Scale the shape size uniformly, lines and ellipses keep their start point and direction
                 let scale_factor = 1.0 + (delta.x + delta.y) / 200.0;
                 self.size = (self.size * scale_factor).max(10.0);
+                if self.uses_end_point() {
+                    let direction = (self.end - self.pos).normalized();
+                    if direction.is_finite() {
+                        self.end = self.pos + direction * self.size;
+                    }
+                }
             }
             TransformHandle::Rotate => {
-                // Rotation not yet implemented for shapes
+                let center = self.local_bounding_box().center();
+                self.rotation += (current_pos - center).angle() - (drag_start - center).angle();
             }
         }
+        self.recompute_bounds();
     }
 
-    /// Returns the bounding rectangle of the shape with padding for handles
+    /// Returns the bounding rectangle of the shape with padding for handles, including its rotation
     #[cfg_attr(feature = "profiling", profiling::function)]
     fn bounding_box(&self) -> egui::Rect {
-        match self.shape_type {
-            CanvasShapeType::Line => {
-                let end_point = Pos2::new(self.pos.x + self.size, self.pos.y);
-                let min_x = self.pos.x.min(end_point.x) - 5.0;
-                let max_x = self.pos.x.max(end_point.x) + 5.0;
-                let min_y = self.pos.y.min(end_point.y) - 5.0;
-                let max_y = self.pos.y.max(end_point.y) + 5.0;
-                egui::Rect::from_min_max(Pos2::new(min_x, min_y), Pos2::new(max_x, max_y))
-            }
-            CanvasShapeType::Arrow => {
-                let end_point = Pos2::new(self.pos.x + self.size, self.pos.y);
-                let min_x = self.pos.x.min(end_point.x) - 5.0;
-                let max_x = self.pos.x.max(end_point.x) + 5.0;
-                let min_y = self.pos.y.min(end_point.y) - 15.0;
-                let max_y = self.pos.y.max(end_point.y) + 15.0;
-                egui::Rect::from_min_max(Pos2::new(min_x, min_y), Pos2::new(max_x, max_y))
-            }
-            CanvasShapeType::Rectangle => {
-                egui::Rect::from_min_size(self.pos, egui::vec2(self.size, self.size))
-            }
-            CanvasShapeType::Triangle => {
-                let half_size = self.size / 2.0;
-                let min_x = self.pos.x - 5.0;
-                let max_x = self.pos.x + self.size + 5.0;
-                let min_y = self.pos.y - 5.0;
-                let max_y = self.pos.y + half_size + 5.0;
-                egui::Rect::from_min_max(Pos2::new(min_x, min_y), Pos2::new(max_x, max_y))
-            }
-            CanvasShapeType::Circle => {
-                let radius = self.size / 2.0;
-                egui::Rect::from_min_max(
-                    Pos2::new(self.pos.x - radius - 5.0, self.pos.y - radius - 5.0),
-                    Pos2::new(self.pos.x + radius + 5.0, self.pos.y + radius + 5.0),
-                )
-            }
-        }
+        self.bounds
+    }
+
+    /// Tests against the actual outline of the shape; filled shapes are treated as solid
+    fn hit_test(&self, pos: Pos2) -> bool {
+        utils::point_in_shape(self, pos, utils::SHAPE_HIT_TOLERANCE)
     }
 
     /// Renders the shape and optional selection UI
     #[cfg_attr(feature = "profiling", profiling::function)]
     fn paint(&self, painter: &egui::Painter, selected: bool) {
+        let center = self.local_bounding_box().center();
+        let rotate = |p: Pos2| utils::rotate_point(p, center, self.rotation);
+
+        let stroke = Stroke::new(2.0_f32, self.color);
+        // 圆与椭圆自行细分为多边形，线段数随半径增加，避免大图形出现棱角
+        let curve_tolerance = painter
+            .ctx()
+            .options(|options| options.tessellation_options.bezier_tolerance);
+
         // Draw the shape itself
         match self.shape_type {
             CanvasShapeType::Line => {
-                let end_point = Pos2::new(self.pos.x + self.size, self.pos.y);
-                painter.line_segment([self.pos, end_point], Stroke::new(2.0_f32, self.color));
+                let end_point = self.line_end();
+                utils::paint_styled_line(
+                    painter,
+                    &[rotate(self.pos), rotate(end_point)],
+                    stroke,
+                    self.line_style,
+                    false,
+                );
             }
             CanvasShapeType::Arrow => {
-                let end_point = Pos2::new(self.pos.x + self.size, self.pos.y);
-                painter.line_segment([self.pos, end_point], Stroke::new(2.0_f32, self.color));
-
-                // 绘制箭头头部
-                let arrow_size = self.size * 0.1;
-                let arrow_angle = std::f32::consts::PI / 6.0; // 30度
-                let arrow_point1 = Pos2::new(
-                    end_point.x - arrow_size * arrow_angle.cos(),
-                    end_point.y - arrow_size * arrow_angle.sin(),
-                );
-                let arrow_point2 = Pos2::new(
-                    end_point.x - arrow_size * arrow_angle.cos(),
-                    end_point.y + arrow_size * arrow_angle.sin(),
+                // 箭头头部始终为实线
+                utils::paint_styled_line(
+                    painter,
+                    &[rotate(self.pos), rotate(self.line_end())],
+                    stroke,
+                    self.line_style,
+                    false,
                 );
-
-                painter.line_segment([end_point, arrow_point1], Stroke::new(2.0_f32, self.color));
-                painter.line_segment([end_point, arrow_point2], Stroke::new(2.0_f32, self.color));
+                for (tip, wings) in self.arrow_heads() {
+                    for wing in wings {
+                        painter.line_segment([rotate(tip), rotate(wing)], stroke);
+                    }
+                }
             }
             CanvasShapeType::Rectangle => {
                 let rect = egui::Rect::from_min_size(self.pos, egui::vec2(self.size, self.size));
-                painter.rect_stroke(
-                    rect,
-                    0.0,
-                    Stroke::new(2.0_f32, self.color),
-                    egui::StrokeKind::Outside,
-                );
-            }
-            CanvasShapeType::Triangle => {
-                let half_size = self.size / 2.0;
-                let points = [
-                    self.pos,
-                    Pos2::new(self.pos.x + self.size, self.pos.y),
-                    Pos2::new(self.pos.x + half_size, self.pos.y + half_size),
+                let corners = [
+                    rotate(rect.left_top()),
+                    rotate(rect.right_top()),
+                    rotate(rect.right_bottom()),
+                    rotate(rect.left_bottom()),
                 ];
-                painter.add(egui::Shape::convex_polygon(
-                    points.to_vec(),
-                    self.color,
-                    Stroke::new(2.0_f32, self.color),
-                ));
+                if let Some(fill) = self.fill {
+                    painter.add(egui::Shape::convex_polygon(
+                        corners.to_vec(),
+                        fill,
+                        Stroke::NONE,
+                    ));
+                }
+                utils::paint_styled_line(painter, &corners, stroke, self.line_style, true);
+            }
+            CanvasShapeType::Triangle(kind) => {
+                let points = self.triangle_points(kind).map(rotate);
+                if let Some(fill) = self.fill {
+                    painter.add(egui::Shape::convex_polygon(
+                        points.to_vec(),
+                        fill,
+                        Stroke::NONE,
+                    ));
+                }
+                utils::paint_styled_line(painter, &points, stroke, self.line_style, true);
+            }
+            CanvasShapeType::Circle => {
+                let points = self.circle_points(curve_tolerance);
+                if let Some(fill) = self.fill {
+                    painter.add(egui::Shape::convex_polygon(
+                        points.clone(),
+                        fill,
+                        Stroke::NONE,
+                    ));
+                }
+                utils::paint_styled_line(painter, &points, stroke, self.line_style, true);
+            }
+            CanvasShapeType::Ellipse => {
+                let points: Vec<Pos2> = self
+                    .ellipse_points(curve_tolerance)
+                    .into_iter()
+                    .map(rotate)
+                    .collect();
+                if let Some(fill) = self.fill {
+                    painter.add(egui::Shape::convex_polygon(
+                        points.clone(),
+                        fill,
+                        Stroke::NONE,
+                    ));
+                }
+                utils::paint_styled_line(painter, &points, stroke, self.line_style, true);
             }
-            CanvasShapeType::Circle => {
-                painter.circle_stroke(self.pos, self.size / 2.0, Stroke::new(2.0_f32, self.color));
+            CanvasShapeType::Polygon { .. } | CanvasShapeType::Star { .. } => {
+                let points: Vec<Pos2> = self.star_points().into_iter().map(rotate).collect();
+                if let Some(fill) = self.fill {
+                    // 星形是凹多边形，convex_polygon 无法正确填充，改为从中心扇形三角化
+                    let mut mesh = egui::Mesh::default();
+                    mesh.colored_vertex(rotate(self.pos), fill);
+                    for &point in &points {
+                        mesh.colored_vertex(point, fill);
+                    }
+                    let count = points.len() as u32;
+                    for i in 0..count {
+                        mesh.add_triangle(0, i + 1, (i + 1) % count + 1);
+                    }
+                    painter.add(mesh);
+                }
+                utils::paint_styled_line(painter, &points, stroke, self.line_style, true);
             }
         }
 
@@ -549,9 +1685,231 @@ pub enum CanvasObject {
     Image(CanvasImage),
     Text(CanvasText),
     Shape(CanvasShape),
+    StickyNote(CanvasStickyNote),
 }
 
 impl CanvasObject {
+    /// Uploads the texture of images again, after the renderer has been recreated
+    pub fn reload_texture(&mut self, ctx: &egui::Context) {
+        if let CanvasObject::Image(img) = self {
+            img.reload_texture(ctx);
+        }
+    }
+
+    /// Id of the layer the object belongs to
+    pub fn layer(&self) -> LayerId {
+        match self {
+            CanvasObject::Stroke(stroke) => stroke.layer,
+            CanvasObject::Image(img) => img.layer,
+            CanvasObject::Text(text) => text.layer,
+            CanvasObject::Shape(shape) => shape.layer,
+            CanvasObject::StickyNote(note) => note.layer,
+        }
+    }
+
+    pub fn set_layer(&mut self, layer: LayerId) {
+        match self {
+            CanvasObject::Stroke(stroke) => stroke.layer = layer,
+            CanvasObject::Image(img) => img.layer = layer,
+            CanvasObject::Text(text) => text.layer = layer,
+            CanvasObject::Shape(shape) => shape.layer = layer,
+            CanvasObject::StickyNote(note) => note.layer = layer,
+        }
+    }
+
+    pub fn group(&self) -> Option<GroupId> {
+        match self {
+            CanvasObject::Stroke(stroke) => stroke.group,
+            CanvasObject::Image(img) => img.group,
+            CanvasObject::Text(text) => text.group,
+            CanvasObject::Shape(shape) => shape.group,
+            CanvasObject::StickyNote(note) => note.group,
+        }
+    }
+
+    pub fn set_group(&mut self, group: Option<GroupId>) {
+        match self {
+            CanvasObject::Stroke(stroke) => stroke.group = group,
+            CanvasObject::Image(img) => img.group = group,
+            CanvasObject::Text(text) => text.group = group,
+            CanvasObject::Shape(shape) => shape.group = group,
+            CanvasObject::StickyNote(note) => note.group = group,
+        }
+    }
+
+    /// Whether the object is locked against selection, moving and erasing
+    pub fn is_locked(&self) -> bool {
+        match self {
+            CanvasObject::Stroke(stroke) => stroke.locked,
+            CanvasObject::Image(img) => img.locked,
+            CanvasObject::Text(text) => text.locked,
+            CanvasObject::Shape(shape) => shape.locked,
+            CanvasObject::StickyNote(note) => note.locked,
+        }
+    }
+
+    pub fn set_locked(&mut self, locked: bool) {
+        match self {
+            CanvasObject::Stroke(stroke) => stroke.locked = locked,
+            CanvasObject::Image(img) => img.locked = locked,
+            CanvasObject::Text(text) => text.locked = locked,
+            CanvasObject::Shape(shape) => shape.locked = locked,
+            CanvasObject::StickyNote(note) => note.locked = locked,
+        }
+    }
+
+    /// Alpha of the object's color, or of the image tint for images
+    pub fn opacity(&self) -> u8 {
+        match self {
+            CanvasObject::Stroke(stroke) => stroke.color.a(),
+            CanvasObject::Image(img) => img.opacity,
+            CanvasObject::Text(text) => text.color.a(),
+            CanvasObject::Shape(shape) => shape.color.a(),
+            CanvasObject::StickyNote(note) => note.color.a(),
+        }
+    }
+
+    pub fn set_opacity(&mut self, opacity: u8) {
+        let with_alpha = |color: Color32| {
+            let [r, g, b, _] = color.to_srgba_unmultiplied();
+            Color32::from_rgba_unmultiplied(r, g, b, opacity)
+        };
+        match self {
+            CanvasObject::Stroke(stroke) => stroke.color = with_alpha(stroke.color),
+            CanvasObject::Image(img) => img.opacity = opacity,
+            CanvasObject::Text(text) => text.color = with_alpha(text.color),
+            CanvasObject::Shape(shape) => {
+                shape.color = with_alpha(shape.color);
+                shape.fill = shape.fill.map(with_alpha);
+            }
+            CanvasObject::StickyNote(note) => {
+                note.color = with_alpha(note.color);
+                note.text_color = with_alpha(note.text_color);
+            }
+        }
+    }
+
+    /// Top-left corner of the bounding box, as shown in the inspector
+    pub fn position(&self) -> Pos2 {
+        self.bounding_box().min
+    }
+
+    /// Moves the object so that its bounding box starts at `pos`
+    pub fn set_position(&mut self, pos: Pos2) {
+        let delta = pos - self.position();
+        Self::move_object(self, delta);
+    }
+
+    /// Size of the object as shown in the inspector: the unrotated size of images,
+    /// the extent of shapes and the spanned area of texts and strokes
+    pub fn size(&self) -> egui::Vec2 {
+        match self {
+            CanvasObject::Image(img) => img.size,
+            CanvasObject::StickyNote(note) => note.size,
+            CanvasObject::Text(text) => text.bounding_box().size(),
+            CanvasObject::Shape(shape) => {
+                if shape.uses_end_point() {
+                    (shape.end - shape.pos).abs()
+                } else {
+                    egui::Vec2::splat(shape.size)
+                }
+            }
+            CanvasObject::Stroke(stroke) => egui::Rect::from_points(&stroke.points).size(),
+        }
+    }
+
+    /// Resizes the object, keeping its top-left corner in place.
+    /// Texts and most shapes only scale uniformly, so the dimension that changed more wins.
+    pub fn set_size(&mut self, size: egui::Vec2) {
+        const MIN_SIZE: f32 = 1.0;
+
+        let old_size = self.size();
+        let size = size.max(egui::Vec2::splat(MIN_SIZE));
+        // 等比缩放时以变化较大的一边为准
+        let uniform_scale = || {
+            if (size.x - old_size.x).abs() >= (size.y - old_size.y).abs() {
+                size.x / old_size.x.max(MIN_SIZE)
+            } else {
+                size.y / old_size.y.max(MIN_SIZE)
+            }
+        };
+        let old_pos = self.position();
+        match self {
+            CanvasObject::Image(img) => img.size = size,
+            CanvasObject::StickyNote(note) => note.size = size,
+            CanvasObject::Text(text) => {
+                let scale = uniform_scale();
+                text.font_size = (text.font_size * scale).max(6.0);
+                text.cached_size = text.cached_size.map(|cached| cached * scale);
+            }
+            CanvasObject::Shape(shape) => {
+                if shape.uses_end_point() {
+                    // 保持起点到终点的方向
+                    let extent = shape.end - shape.pos;
+                    let signed = |value: f32, sign_of: f32| {
+                        if sign_of < 0.0 { -value } else { value }
+                    };
+                    shape.end =
+                        shape.pos + egui::vec2(signed(size.x, extent.x), signed(size.y, extent.y));
+                    shape.size = (shape.end - shape.pos).length();
+                } else {
+                    shape.size = (shape.size * uniform_scale()).max(MIN_SIZE);
+                    shape.end = shape.pos;
+                }
+                shape.recompute_bounds();
+            }
+            CanvasObject::Stroke(stroke) => {
+                let rect = egui::Rect::from_points(&stroke.points);
+                // 水平或垂直的笔画在该方向上没有大小可供缩放
+                let scale_x = if rect.width() > 0.0 {
+                    size.x / rect.width()
+                } else {
+                    1.0
+                };
+                let scale_y = if rect.height() > 0.0 {
+                    size.y / rect.height()
+                } else {
+                    1.0
+                };
+                for point in &mut stroke.points {
+                    point.x = rect.min.x + (point.x - rect.min.x) * scale_x;
+                    point.y = rect.min.y + (point.y - rect.min.y) * scale_y;
+                }
+                stroke.recompute_bounds();
+            }
+        }
+        self.set_position(old_pos);
+    }
+
+    /// Rotation of the object in radians
+    pub fn rotation(&self) -> f32 {
+        match self {
+            CanvasObject::Stroke(stroke) => stroke.rot,
+            CanvasObject::Image(img) => img.rot,
+            CanvasObject::Text(text) => text.rot,
+            CanvasObject::Shape(shape) => shape.rotation,
+            CanvasObject::StickyNote(_) => 0.0,
+        }
+    }
+
+    pub fn set_rotation(&mut self, rotation: f32) {
+        match self {
+            CanvasObject::Stroke(stroke) => stroke.rot = rotation,
+            CanvasObject::Image(img) => img.rot = rotation,
+            CanvasObject::Text(text) => text.rot = rotation,
+            CanvasObject::Shape(shape) => {
+                shape.rotation = rotation;
+                shape.recompute_bounds();
+            }
+            // 便签不能旋转
+            CanvasObject::StickyNote(_) => {}
+        }
+    }
+
+    fn is_highlighter(&self) -> bool {
+        matches!(self, CanvasObject::Stroke(stroke) if stroke.is_highlighter)
+    }
+
     /// Moves an object by the specified delta vector
     #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn move_object(object: &mut CanvasObject, delta: egui::Vec2) {
@@ -562,14 +1920,20 @@ impl CanvasObject {
             CanvasObject::Text(text) => {
                 text.pos += delta;
             }
+            CanvasObject::StickyNote(note) => {
+                note.pos += delta;
+            }
             CanvasObject::Shape(shape) => {
                 shape.pos += delta;
+                shape.end += delta;
+                shape.bounds = shape.bounds.translate(delta);
             }
             CanvasObject::Stroke(stroke) => {
                 // For strokes, move all points
                 for point in &mut stroke.points {
                     *point += delta;
                 }
+                stroke.bounds = stroke.bounds.translate(delta);
             }
         }
     }
@@ -587,9 +1951,19 @@ impl CanvasObject {
                 size: egui::vec2(text.font_size, text.font_size), // Using font_size for both dimensions
                 rotation: text.rot,
             },
+            CanvasObject::StickyNote(note) => ObjectTransform {
+                pos: note.pos,
+                size: note.size,
+                rotation: 0.0,
+            },
             CanvasObject::Shape(shape) => ObjectTransform {
                 pos: shape.pos,
-                size: egui::vec2(shape.size, shape.size), // Using shape.size for both dimensions
+                // 直线、箭头和椭圆记录从起点到终点的向量，其他形状两个分量均为 size
+                size: if shape.uses_end_point() {
+                    shape.end - shape.pos
+                } else {
+                    egui::vec2(shape.size, shape.size)
+                },
                 rotation: shape.rotation,
             },
             CanvasObject::Stroke(_stroke) => ObjectTransform {
@@ -610,13 +1984,23 @@ impl CanvasObjectOps for CanvasObject {
         delta: egui::Vec2,
         drag_start: Pos2,
         current_pos: Pos2,
+        keep_aspect_ratio: bool,
     ) {
         match self {
-            CanvasObject::Image(img) => img.transform(handle, delta, drag_start, current_pos),
-            CanvasObject::Text(text) => text.transform(handle, delta, drag_start, current_pos),
-            CanvasObject::Shape(shape) => shape.transform(handle, delta, drag_start, current_pos),
+            CanvasObject::Image(img) => {
+                img.transform(handle, delta, drag_start, current_pos, keep_aspect_ratio)
+            }
+            CanvasObject::Text(text) => {
+                text.transform(handle, delta, drag_start, current_pos, keep_aspect_ratio)
+            }
+            CanvasObject::Shape(shape) => {
+                shape.transform(handle, delta, drag_start, current_pos, keep_aspect_ratio)
+            }
             CanvasObject::Stroke(stroke) => {
-                stroke.transform(handle, delta, drag_start, current_pos)
+                stroke.transform(handle, delta, drag_start, current_pos, keep_aspect_ratio)
+            }
+            CanvasObject::StickyNote(note) => {
+                note.transform(handle, delta, drag_start, current_pos, keep_aspect_ratio)
             }
         }
     }
@@ -629,6 +2013,7 @@ impl CanvasObjectOps for CanvasObject {
             CanvasObject::Image(image) => image.paint(painter, selected),
             CanvasObject::Text(text) => text.paint(painter, selected),
             CanvasObject::Shape(shape) => shape.paint(painter, selected),
+            CanvasObject::StickyNote(note) => note.paint(painter, selected),
         }
     }
 
@@ -640,6 +2025,19 @@ impl CanvasObjectOps for CanvasObject {
             CanvasObject::Image(image) => image.bounding_box(),
             CanvasObject::Text(text) => text.bounding_box(),
             CanvasObject::Shape(shape) => shape.bounding_box(),
+            CanvasObject::StickyNote(note) => note.bounding_box(),
+        }
+    }
+
+    /// Delegates hit testing to the inner object type
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn hit_test(&self, pos: Pos2) -> bool {
+        match self {
+            CanvasObject::Stroke(stroke) => stroke.hit_test(pos),
+            CanvasObject::Image(image) => image.hit_test(pos),
+            CanvasObject::Text(text) => text.hit_test(pos),
+            CanvasObject::Shape(shape) => shape.hit_test(pos),
+            CanvasObject::StickyNote(note) => note.hit_test(pos),
         }
     }
 }
@@ -670,6 +2068,26 @@ pub enum OptimizationPolicy {
     ResourceUsage,
 }
 
+/// Upper bound on how often the window is redrawn
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum FpsCap {
+    #[default]
+    Unlimited,
+    Fps60,
+    Fps30,
+}
+
+impl FpsCap {
+    /// Minimum time between two frames, if the frame rate is capped
+    pub fn frame_interval(self) -> Option<Duration> {
+        match self {
+            FpsCap::Unlimited => None,
+            FpsCap::Fps60 => Some(Duration::from_secs_f64(1.0 / 60.0)),
+            FpsCap::Fps30 => Some(Duration::from_secs_f64(1.0 / 30.0)),
+        }
+    }
+}
+
 /// Graphics API backend selection
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum GraphicsApi {
@@ -697,10 +2115,45 @@ impl GraphicsApi {
     }
 }
 
+/// Identifies a [`Layer`] within a canvas, stays the same when layers are reordered
+pub type LayerId = u32;
+
+/// Identifies a group of objects that are selected, moved and deleted together
+pub type GroupId = u32;
+
+/// Identifies a stroke on every board of a collaboration session; copies and eraser pieces get a new one
+pub type StrokeId = u64;
+
+/// Named group of objects that is drawn and hidden together
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub id: LayerId,
+    pub name: String,
+    pub visible: bool,
+}
+
 /// Represents the current state of the canvas including all objects
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct CanvasState {
-    pub objects: Vec<CanvasObject>,
+    pub objects: ChunkedObjects,
+    pub layers: Vec<Layer>,    // 图层，从下到上排列
+    pub active_layer: LayerId, // 新对象所在的图层
+    pub background_image: Option<BackgroundImage>,
+}
+
+impl Default for CanvasState {
+    fn default() -> Self {
+        Self {
+            objects: ChunkedObjects::default(),
+            layers: vec![Layer {
+                id: 0,
+                name: "图层 1".to_string(),
+                visible: true,
+            }],
+            active_layer: 0,
+            background_image: None,
+        }
+    }
 }
 
 /// State for a single page including canvas and undo/redo history
@@ -713,10 +2166,162 @@ pub struct PageState {
 impl CanvasState {
     const HEADER_SIZE: usize = 4;
 
-    /// Loads canvas state from a file using rkyv binary format
-    pub fn load_from_file(path: &std::path::PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
-        let bytes = std::fs::read(path)?;
+    /// Indices of the objects that may lie within `rect`, topmost last
+    pub fn objects_in_rect(&mut self, rect: egui::Rect) -> Vec<usize> {
+        self.objects.query(rect)
+    }
+
+    // 未知图层（例如来自协作会话）视为最底层且可见
+    fn layer_position(&self, id: LayerId) -> usize {
+        self.layers
+            .iter()
+            .position(|layer| layer.id == id)
+            .unwrap_or(0)
+    }
+
+    pub fn is_layer_visible(&self, id: LayerId) -> bool {
+        self.layers
+            .iter()
+            .find(|layer| layer.id == id)
+            .is_none_or(|layer| layer.visible)
+    }
+
+    /// Indices of all objects in the group of the object at `index`, or just `index` when it is not grouped
+    pub fn group_members(&self, index: usize) -> Vec<usize> {
+        match self.objects.get(index).and_then(CanvasObject::group) {
+            Some(group) => (0..self.objects.len())
+                .filter(|&i| self.objects[i].group() == Some(group))
+                .collect(),
+            None => vec![index],
+        }
+    }
+
+    /// Rearranges the objects so that the object at `order[i]` ends up at index `i`
+    pub fn reorder_objects(&mut self, order: &[usize]) {
+        if order.len() != self.objects.len() {
+            return;
+        }
+        let mut objects: Vec<Option<CanvasObject>> =
+            self.objects.take_all().into_iter().map(Some).collect();
+        self.objects
+            .replace_all(order.iter().filter_map(|&i| objects[i].take()).collect());
+    }
+
+    /// Undoes [`CanvasState::reorder_objects`] with the same `order`
+    pub fn restore_object_order(&mut self, order: &[usize]) {
+        if order.len() != self.objects.len() {
+            return;
+        }
+        let mut inverse = vec![0; order.len()];
+        for (new_index, &old_index) in order.iter().enumerate() {
+            inverse[old_index] = new_index;
+        }
+        self.reorder_objects(&inverse);
+    }
+
+    /// A group id that no object uses yet
+    pub fn next_group_id(&self) -> GroupId {
+        self.objects
+            .iter()
+            .filter_map(CanvasObject::group)
+            .map(|group| group + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn is_object_visible(&self, index: usize) -> bool {
+        self.objects
+            .get(index)
+            .is_some_and(|object| self.is_layer_visible(object.layer()))
+    }
+
+    /// Sort key of an object in drawing order: by layer, then highlighter strokes below everything else, then insertion order
+    pub fn z_order_key(&self, index: usize) -> (usize, bool, usize) {
+        let object = &self.objects[index];
+        (
+            self.layer_position(object.layer()),
+            !object.is_highlighter(),
+            index,
+        )
+    }
+
+    /// Indices of the objects on visible layers, bottommost first
+    pub fn draw_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.objects.len())
+            .filter(|&index| self.is_object_visible(index))
+            .collect();
+        order.sort_by_key(|&index| self.z_order_key(index));
+        order
+    }
+
+    /// Like [`Self::draw_order`], but limited to the objects that may intersect `rect`, so
+    /// panning across a large board only paints the chunks that are on screen
+    pub fn draw_order_in_rect(&mut self, rect: egui::Rect) -> Vec<usize> {
+        let mut order = self.objects_in_rect(rect);
+        order.retain(|&index| self.is_object_visible(index));
+        order.sort_by_key(|&index| self.z_order_key(index));
+        order
+    }
+
+    /// Adds an empty layer on top and makes it the active one
+    pub fn add_layer(&mut self) {
+        let id = self
+            .layers
+            .iter()
+            .map(|layer| layer.id + 1)
+            .max()
+            .unwrap_or(0);
+        self.layers.push(Layer {
+            id,
+            name: format!("图层 {}", self.layers.len() + 1),
+            visible: true,
+        });
+        self.active_layer = id;
+    }
+
+    /// Removes a layer that contains no objects, the last remaining layer is kept
+    pub fn remove_layer(&mut self, id: LayerId) -> bool {
+        if self.layers.len() <= 1 || self.objects.iter().any(|object| object.layer() == id) {
+            return false;
+        }
+        self.layers.retain(|layer| layer.id != id);
+        if self.active_layer == id {
+            self.active_layer = self.layers.last().map_or(0, |layer| layer.id);
+        }
+        true
+    }
+
+    /// Moves a layer up (towards the top) or down in the drawing order by one step
+    pub fn move_layer(&mut self, id: LayerId, up: bool) {
+        let Some(position) = self.layers.iter().position(|layer| layer.id == id) else {
+            return;
+        };
+        let target = if up {
+            position + 1
+        } else {
+            position.wrapping_sub(1)
+        };
+        if target < self.layers.len() {
+            self.layers.swap(position, target);
+        }
+    }
 
+    /// Decodes canvas state from the rkyv binary format (including the file header).
+    /// `ctx` is needed to upload embedded images as textures.
+    pub fn from_bytes(
+        bytes: &[u8],
+        ctx: &egui::Context,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if bytes.len() >= Self::HEADER_SIZE
+            && bytes[..3] == *CANVAS_FILE_MAGIC
+            && bytes[3] == CANVAS_FILE_VERSION_V1
+        {
+            let archived = rkyv::access::<flat_v1::ArchivedCanvasStateFlatV1, rkyv::rancor::Error>(
+                &bytes[Self::HEADER_SIZE..],
+            )
+            .map_err(|e| format!("rkyv error: {e}"))?;
+            return Ok(Self::from_flat_v1(archived));
+        }
         if bytes.len() < Self::HEADER_SIZE
             || bytes[..3] != *CANVAS_FILE_MAGIC
             || bytes[3] != CANVAS_FILE_VERSION
@@ -735,14 +2340,11 @@ impl CanvasState {
         let payload = &bytes[Self::HEADER_SIZE..];
         let archived = rkyv::access::<flat::ArchivedCanvasStateFlat, rkyv::rancor::Error>(payload)
             .map_err(|e| format!("rkyv error: {e}"))?;
-        Ok(Self::from(archived))
+        Ok(Self::from_flat(archived, ctx))
     }
 
-    /// Saves canvas state to a file using rkyv binary format
-    pub fn save_to_file(
-        &self,
-        path: &std::path::PathBuf,
-    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    /// Encodes canvas state into the rkyv binary format (including the file header)
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
         let flat = CanvasStateFlat::from(self);
         let payload =
             rkyv::to_bytes::<rkyv::rancor::Error>(&flat).map_err(|e| format!("rkyv error: {e}"))?;
@@ -751,33 +2353,27 @@ impl CanvasState {
         let mut out = Vec::with_capacity(Self::HEADER_SIZE + payload.len());
         out.extend_from_slice(&header);
         out.extend_from_slice(payload.as_slice());
-
-        std::fs::write(path, out)?;
-        Ok(())
+        Ok(out)
     }
 
-    /// Opens a file dialog to load canvas from user-selected file
-    pub fn load_from_file_with_dialog() -> Result<Self, Box<dyn std::error::Error>> {
-        let path = rfd::FileDialog::new()
-            .add_filter("画布文件", &["sb"])
-            .pick_file()
-            .ok_or(std::io::Error::new(
-                std::io::ErrorKind::InvalidFilename,
-                "已取消",
-            ))?;
-        let canvas = CanvasState::load_from_file(&path)?;
-        Ok(canvas)
+    /// Saves canvas state to a file using rkyv binary format
+    pub fn save_to_file(
+        &self,
+        path: &std::path::PathBuf,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(path, self.to_bytes()?)?;
+        Ok(())
     }
 
     /// Opens a file dialog to save canvas to user-selected file
     pub fn save_to_file_with_dialog(&self) -> Result<(), Box<dyn std::error::Error>> {
         let path = rfd::FileDialog::new()
-            .add_filter("画布文件", &["sb"])
+            .add_filter(t("画布文件"), &["sb"])
             .set_file_name("canvas.sb")
             .save_file()
             .ok_or(std::io::Error::new(
                 std::io::ErrorKind::InvalidFilename,
-                "已取消",
+                t("已取消"),
             ))?;
 
         self.save_to_file(&path)?;
@@ -788,6 +2384,8 @@ impl CanvasState {
 // 应用程序设置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PersistentState {
+    #[serde(default)]
+    pub language: Lang,
     #[serde(default)]
     pub theme_mode: ThemeMode,
     #[serde(default)]
@@ -796,15 +2394,30 @@ pub struct PersistentState {
     pub window_opacity: f32,
 
     #[serde(default)]
-    pub stroke_smoothing: bool,
+    pub smoothing_mode: SmoothingMode,
+    #[serde(default)]
+    pub shape_recognition: bool,
     #[serde(default)]
     pub stroke_straightening: bool,
     #[serde(default)]
     pub stroke_straightening_tolerance: f32,
     #[serde(default)]
     pub interpolation_frequency: f32,
+    /// Tolerance in screen pixels for dropping redundant points when a stroke is finished, 0 keeps every point
+    #[serde(default = "default_stroke_simplification_epsilon")]
+    pub stroke_simplification_epsilon: f32,
     #[serde(default)]
     pub quick_colors: Vec<Color32>,
+    #[serde(default)]
+    pub dynamic_width_curve: DynamicWidthCurve,
+    #[serde(default)]
+    pub drawing_defaults: DrawingDefaults,
+    /// Range of the brush width slider, large displays need much thicker strokes
+    #[serde(default = "default_brush_width_range")]
+    pub brush_width_range: RangeInclusive<f32>,
+    /// Range of the eraser size slider
+    #[serde(default = "default_eraser_size_range")]
+    pub eraser_size_range: RangeInclusive<f32>,
 
     #[serde(default)]
     pub show_fps: bool,
@@ -815,14 +2428,35 @@ pub struct PersistentState {
     #[serde(default)]
     pub optimization_policy: OptimizationPolicy,
     #[serde(default)]
+    pub fps_cap: FpsCap,
+    #[serde(default)]
     pub graphics_api: GraphicsApi,
     #[serde(default)]
     pub low_latency_mode: bool,
     #[serde(default)]
+    pub palm_rejection: bool,
+    #[serde(default)]
     pub force_redraw_every_frame: bool,
+    /// Samples per pixel for multisample antialiasing, 1 turns it off
+    #[serde(default = "default_msaa_samples")]
+    pub msaa_samples: u32,
+    /// Largest distance between a curve and the polygon it is drawn as, in points at 100% zoom
+    #[serde(default = "default_curve_tolerance")]
+    pub curve_tolerance: f32,
+    /// Width of the antialiasing edge around shapes in physical pixels, 0 turns antialiasing off
+    #[serde(default = "default_feathering_size")]
+    pub feathering_size: f32,
+
+    /// Seconds each page is shown before a presentation advances to the next one, 0 advances only on tap
+    #[serde(default)]
+    pub slideshow_interval_secs: u32,
 
     #[serde(default)]
     pub keep_insertion_window_open: bool,
+    #[serde(default)]
+    pub drag_to_insert_shapes: bool,
+    #[serde(default)]
+    pub snap_line_angles: bool,
 
     #[serde(default)]
     pub show_welcome_window_on_start: bool,
@@ -836,25 +2470,41 @@ pub struct PersistentState {
 impl Default for PersistentState {
     fn default() -> Self {
         Self {
+            language: Lang::default(),
             theme_mode: ThemeMode::default(),
             canvas_color: utils::get_default_canvas_color(),
             window_opacity: 1.0,
 
-            stroke_smoothing: true,
+            smoothing_mode: SmoothingMode::default(),
+            shape_recognition: false,
             stroke_straightening: true,
             stroke_straightening_tolerance: 20.0,
             interpolation_frequency: 0.1,
+            stroke_simplification_epsilon: default_stroke_simplification_epsilon(),
             quick_colors: utils::get_default_quick_colors(),
+            dynamic_width_curve: DynamicWidthCurve::default(),
+            drawing_defaults: DrawingDefaults::default(),
+            brush_width_range: default_brush_width_range(),
+            eraser_size_range: default_eraser_size_range(),
 
             show_fps: false,
             window_mode: WindowMode::default(),
             present_mode: PresentMode::AutoVsync,
             optimization_policy: OptimizationPolicy::default(),
+            fps_cap: FpsCap::default(),
             graphics_api: GraphicsApi::default(),
             low_latency_mode: false,
+            palm_rejection: false,
             force_redraw_every_frame: false,
+            msaa_samples: default_msaa_samples(),
+            curve_tolerance: default_curve_tolerance(),
+            feathering_size: default_feathering_size(),
+
+            slideshow_interval_secs: 0,
 
             keep_insertion_window_open: true,
+            drag_to_insert_shapes: true,
+            snap_line_angles: false,
 
             show_welcome_window_on_start: true,
             show_startup_animation: true,
@@ -864,6 +2514,30 @@ impl Default for PersistentState {
     }
 }
 
+fn default_stroke_simplification_epsilon() -> f32 {
+    0.5
+}
+
+fn default_msaa_samples() -> u32 {
+    1
+}
+
+fn default_curve_tolerance() -> f32 {
+    0.1
+}
+
+fn default_feathering_size() -> f32 {
+    1.0
+}
+
+fn default_brush_width_range() -> RangeInclusive<f32> {
+    1.0..=20.0
+}
+
+fn default_eraser_size_range() -> RangeInclusive<f32> {
+    5.0..=50.0
+}
+
 impl PersistentState {
     // 获取设置文件路径
     fn get_settings_path() -> std::path::PathBuf {
@@ -897,14 +2571,71 @@ impl PersistentState {
 // 绘图数据结构
 #[derive(Debug, Clone)]
 pub struct CanvasStroke {
+    pub id: StrokeId,
     pub points: Vec<Pos2>,
     pub width: StrokeWidth,
     pub color: Color32,
     pub base_width: f32,
     pub rot: f32,
+    pub is_highlighter: bool, // 荧光笔笔画，半透明且绘制在其他对象下方
+    pub line_style: LineStyle,
+    pub texture: Option<StrokeTexture>, // 纹理画笔的纹理，None 为纯色
+    pub style: BrushStyle,
+    pub bounds: egui::Rect, // 缓存的包围盒，修改点或宽度后需重新计算
+    pub layer: LayerId,
+    pub group: Option<GroupId>,
+    pub locked: bool,
 }
 
 impl CanvasStroke {
+    /// Returns an id no other stroke has, on this board or on the other members' boards
+    pub fn new_id() -> StrokeId {
+        use std::hash::{BuildHasher, RandomState};
+        use std::sync::LazyLock;
+        use std::sync::atomic::{AtomicU64, Ordering};
+        // 起始值在每个进程中随机选取，各成员依次分配的编号几乎不可能重叠
+        static NEXT_ID: LazyLock<AtomicU64> =
+            LazyLock::new(|| AtomicU64::new(RandomState::new().hash_one(0u8)));
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Fills in the cached bounding box of a newly built stroke
+    pub fn with_bounds(mut self) -> Self {
+        self.recompute_bounds();
+        self
+    }
+
+    /// Recomputes the cached bounding box, must be called after the points or widths change
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn recompute_bounds(&mut self) {
+        if self.points.is_empty() {
+            self.bounds = egui::Rect::from_min_max(Pos2::ZERO, Pos2::ZERO);
+            return;
+        }
+
+        // 计算所有点的最小和最大坐标
+        let mut min_x = f32::INFINITY;
+        let mut max_x = f32::NEG_INFINITY;
+        let mut min_y = f32::INFINITY;
+        let mut max_y = f32::NEG_INFINITY;
+
+        for point in &self.points {
+            min_x = min_x.min(point.x);
+            max_x = max_x.max(point.x);
+            min_y = min_y.min(point.y);
+            max_y = max_y.max(point.y);
+        }
+
+        // 考虑笔画宽度，添加一些边距
+        let max_width = self.width.max_width();
+        let padding = max_width / 2.0 + 5.0; // 添加额外的5像素边距
+
+        self.bounds = egui::Rect::from_min_max(
+            Pos2::new(min_x - padding, min_y - padding),
+            Pos2::new(max_x + padding, max_y + padding),
+        );
+    }
+
     #[cfg_attr(feature = "profiling", profiling::function)]
     fn scale_stroke_points(stroke: &mut CanvasStroke, center: Pos2, scale_x: f32, scale_y: f32) {
         for point in &mut stroke.points {
@@ -922,6 +2653,7 @@ impl CanvasStroke {
                 }
             }
         }
+        stroke.recompute_bounds();
     }
 
     fn move_stroke_to_center(stroke: &mut CanvasStroke, new_center: Pos2) {
@@ -930,6 +2662,7 @@ impl CanvasStroke {
         for point in &mut stroke.points {
             *point += offset;
         }
+        stroke.bounds = stroke.bounds.translate(offset);
     }
 }
 
@@ -941,6 +2674,7 @@ impl CanvasObjectOps for CanvasStroke {
         delta: egui::Vec2,
         _drag_start: Pos2,
         _current_pos: Pos2,
+        _keep_aspect_ratio: bool,
     ) {
         let bbox = self.bounding_box();
         let center = bbox.center();
@@ -1016,31 +2750,14 @@ impl CanvasObjectOps for CanvasStroke {
 
     #[cfg_attr(feature = "profiling", profiling::function)]
     fn bounding_box(&self) -> egui::Rect {
-        if self.points.is_empty() {
-            return egui::Rect::from_min_max(Pos2::ZERO, Pos2::ZERO);
-        }
-
-        // 计算所有点的最小和最大坐标
-        let mut min_x = f32::INFINITY;
-        let mut max_x = f32::NEG_INFINITY;
-        let mut min_y = f32::INFINITY;
-        let mut max_y = f32::NEG_INFINITY;
-
-        for point in &self.points {
-            min_x = min_x.min(point.x);
-            max_x = max_x.max(point.x);
-            min_y = min_y.min(point.y);
-            max_y = max_y.max(point.y);
-        }
-
-        // 考虑笔画宽度，添加一些边距
-        let max_width = self.width.max_width();
-        let padding = max_width / 2.0 + 5.0; // 添加额外的5像素边距
+        self.bounds
+    }
 
-        egui::Rect::from_min_max(
-            Pos2::new(min_x - padding, min_y - padding),
-            Pos2::new(max_x + padding, max_y + padding),
-        )
+    /// Tests against the full polyline instead of the (mostly empty) bounding box
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn hit_test(&self, pos: Pos2) -> bool {
+        self.bounding_box().contains(pos)
+            && utils::point_intersects_stroke(pos, self, utils::STROKE_HIT_TOLERANCE)
     }
 
     #[cfg_attr(feature = "profiling", profiling::function)]
@@ -1069,39 +2786,71 @@ impl CanvasObjectOps for CanvasStroke {
             std::borrow::Cow::Borrowed(&self.points)
         };
 
-        painter.add(egui::Shape::Circle(egui::epaint::CircleShape::filled(
-            rotated_points[0],
-            self.width.first() / 2.0,
-            color,
-        )));
-        if rotated_points.len() >= 2 {
+        // 荧光笔整条绘制为一条路径，避免半透明线段重叠处颜色加深
+        if self.is_highlighter && rotated_points.len() >= 2 {
+            painter.add(egui::Shape::line(
+                rotated_points.into_owned(),
+                Stroke::new(self.width.first(), color),
+            ));
+        } else if self.style == BrushStyle::Chalk {
+            // 粉笔忽略线型，整条由颗粒组成
+            let width = match &self.width {
+                StrokeWidth::Fixed(w) => *w,
+                StrokeWidth::Dynamic(_) => self.base_width,
+            };
+            utils::brush_texture::paint_chalk_stroke(painter, &rotated_points, width, color);
+        } else if self.line_style != LineStyle::Solid && rotated_points.len() >= 2 {
+            // 虚线和点线使用固定宽度
+            let width = match &self.width {
+                StrokeWidth::Fixed(w) => *w,
+                StrokeWidth::Dynamic(_) => self.base_width,
+            };
+            utils::paint_styled_line(
+                painter,
+                &rotated_points,
+                Stroke::new(width, color),
+                self.line_style,
+                false,
+            );
+        } else if let Some(texture_id) = self
+            .texture
+            .as_ref()
+            .and_then(|texture| utils::brush_texture::texture_id(painter.ctx(), texture))
+        {
+            let widths = match &self.width {
+                StrokeWidth::Fixed(w) => vec![*w; rotated_points.len()],
+                StrokeWidth::Dynamic(widths) => widths.clone(),
+            };
+            utils::paint_textured_stroke(painter, &rotated_points, &widths, color, texture_id);
+        } else {
             painter.add(egui::Shape::Circle(egui::epaint::CircleShape::filled(
-                rotated_points[rotated_points.len() - 1],
-                self.width.last() / 2.0,
+                rotated_points[0],
+                self.width.first() / 2.0,
                 color,
             )));
-            match &self.width {
-                StrokeWidth::Fixed(w) => {
-                    if rotated_points.len() == 2 {
-                        painter.line_segment(
-                            [rotated_points[0], rotated_points[1]],
-                            Stroke::new(*w, color),
-                        );
-                    } else {
-                        let path = egui::epaint::PathShape::line(
-                            rotated_points.into_owned(),
-                            Stroke::new(*w, color),
-                        );
-                        painter.add(egui::Shape::Path(path));
+            if rotated_points.len() >= 2 {
+                painter.add(egui::Shape::Circle(egui::epaint::CircleShape::filled(
+                    rotated_points[rotated_points.len() - 1],
+                    self.width.last() / 2.0,
+                    color,
+                )));
+                match &self.width {
+                    StrokeWidth::Fixed(w) => {
+                        if rotated_points.len() == 2 {
+                            painter.line_segment(
+                                [rotated_points[0], rotated_points[1]],
+                                Stroke::new(*w, color),
+                            );
+                        } else {
+                            let path = egui::epaint::PathShape::line(
+                                rotated_points.into_owned(),
+                                Stroke::new(*w, color),
+                            );
+                            painter.add(egui::Shape::Path(path));
+                        }
                     }
-                }
-                StrokeWidth::Dynamic(widths) => {
-                    for i in 0..rotated_points.len() - 1 {
-                        let avg_width = (widths[i] + widths[i + 1]) / 2.0;
-                        painter.line_segment(
-                            [rotated_points[i], rotated_points[i + 1]],
-                            Stroke::new(avg_width, color),
-                        );
+                    StrokeWidth::Dynamic(widths) => {
+                        utils::paint_variable_width_stroke(painter, &rotated_points, widths, color);
                     }
                 }
             }
@@ -1122,34 +2871,51 @@ impl CanvasObjectOps for CanvasStroke {
 
 // FPS 计数器
 pub struct FpsCounter {
-    pub frame_count: u32,
-    pub last_time: Instant,
-    pub current_fps: f32,
+    frame_times: VecDeque<Instant>, // 平均窗口内各帧的时间戳
+    pub current_fps: f32,           // 由最近两帧间隔计算的瞬时帧率
+    pub average_fps: f32,           // 最近 FPS_AVERAGE_WINDOW 内的平均帧率
 }
 
+// 平均帧率的统计窗口
+const FPS_AVERAGE_WINDOW: Duration = Duration::from_millis(500);
+
 impl FpsCounter {
     pub fn new() -> Self {
         Self {
-            frame_count: 0,
-            last_time: Instant::now(),
+            frame_times: VecDeque::new(),
             current_fps: 0.0,
+            average_fps: 0.0,
         }
     }
 
+    /// Records a frame and returns the average fps over the last half second
     #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn update(&mut self) -> f32 {
-        self.frame_count += 1;
-
         let now = Instant::now();
-        let elapsed = now.duration_since(self.last_time).as_secs_f32();
+        if let Some(&last) = self.frame_times.back() {
+            let elapsed = now.duration_since(last).as_secs_f32();
+            if elapsed > 0.0 {
+                self.current_fps = 1.0 / elapsed;
+            }
+        }
+
+        self.frame_times.push_back(now);
+        while self
+            .frame_times
+            .front()
+            .is_some_and(|&at| now.duration_since(at) > FPS_AVERAGE_WINDOW)
+        {
+            self.frame_times.pop_front();
+        }
 
-        if elapsed >= 0.05 {
-            self.current_fps = self.frame_count as f32 / elapsed;
-            self.frame_count = 0;
-            self.last_time = now;
+        if let Some(&oldest) = self.frame_times.front() {
+            let elapsed = now.duration_since(oldest).as_secs_f32();
+            if elapsed > 0.0 {
+                self.average_fps = (self.frame_times.len() - 1) as f32 / elapsed;
+            }
         }
 
-        self.current_fps
+        self.average_fps
     }
 }
 
@@ -1158,8 +2924,10 @@ pub struct ActiveStroke {
     pub points: Vec<Pos2>,
     pub width: StrokeWidth,
     pub times: Vec<f64>,             // 每个点的时间戳（用于速度计算）
+    pub forces: Vec<Option<Force>>,  // 每个点的原始压力（鼠标输入时为 None）
     pub start_time: Instant,         // 笔画开始时间
     pub last_movement_time: Instant, // 最后一次移动的时间（用于检测停留）
+    pub is_highlighter: bool,        // 是否为荧光笔笔画（固定宽度）
 }
 
 /// Unified per-pointer interaction state for all tools
@@ -1172,10 +2940,40 @@ pub enum PointerInteraction {
         dragged_handle: Option<TransformHandle>,
         drag_original_transform: Option<ObjectTransform>,
         drag_accumulated_delta: egui::Vec2,
+        drag_origin: Pos2, // 拖动开始时的指针位置
+    },
+    Marquee {
+        start: Pos2, // 框选起点
+    },
+    PlacingShape {
+        start: Pos2, // 拖动绘制形状的起点
+        shape: CanvasShape,
+    },
+    Cropping {
+        start: Pos2, // 拖动裁剪框的起点（纹理坐标）
+    },
+    Measuring {
+        handle: MeasureHandle,
+        last_pos: Pos2, // 上一次移动时的指针位置
     },
     Erasing,
 }
 
+/// Pointer id used for the mouse in [`AppState::pointers`]
+pub const MOUSE_POINTER_ID: u64 = 0;
+// 触控指针 id 的标记位
+const TOUCH_POINTER_BIT: u64 = 1 << 63;
+
+/// Maps a winit touch id to a pointer id, winit may hand out touch id 0 which would collide with the mouse
+pub fn touch_pointer_id(touch_id: u64) -> u64 {
+    touch_id | TOUCH_POINTER_BIT
+}
+
+/// Recovers the winit touch id from a pointer id made by [`touch_pointer_id`]
+pub fn touch_id_of_pointer(pointer_id: u64) -> u64 {
+    pointer_id & !TOUCH_POINTER_BIT
+}
+
 /// Represents a single pointer (touch or mouse) on the canvas
 pub struct PointerState {
     pub id: u64,
@@ -1183,6 +2981,145 @@ pub struct PointerState {
     pub interaction: PointerInteraction,
 }
 
+// 多指轻触：所有手指需在此时间内按下并抬起
+const TAP_MAX_DURATION: Duration = Duration::from_millis(300);
+// 多指轻触：手指移动超过此距离（逻辑像素）时不再视为轻触
+const TAP_MOVE_TOLERANCE: f32 = 20.0;
+// 单指按住不动超过此时间视为长按，长按的移动容差与轻触相同
+const LONG_PRESS_DURATION: Duration = Duration::from_millis(500);
+
+/// Touch gestures triggered by [`GestureRecognizer`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Gesture {
+    Undo, // 双指轻触
+    Redo, // 三指轻触
+}
+
+/// View change produced by a two-finger pinch or drag, in screen coordinates
+#[derive(Clone, Copy, Debug)]
+pub struct PinchUpdate {
+    pub center: Pos2,
+    pub translation: egui::Vec2,
+    pub zoom: f32,
+}
+
+#[derive(Clone, Copy)]
+struct TrackedTouch {
+    start: Pos2, // 按下位置（屏幕坐标）
+    pos: Pos2,   // 当前位置（屏幕坐标）
+}
+
+/// Recognizes multi-finger taps and two-finger pinches on the touchscreen
+#[derive(Default)]
+pub struct GestureRecognizer {
+    touches: HashMap<u64, TrackedTouch>, // 当前按下的手指
+    started_at: Option<Instant>,         // 第一根手指按下的时间
+    max_touches: usize,                  // 本次手势中同时按下的最多手指数
+    cancelled: bool,                     // 本次手势已不可能是轻触
+}
+
+impl GestureRecognizer {
+    /// Records a touch going down, returns whether the touches on screen now look like a multi-finger tap
+    pub fn touch_down(&mut self, id: u64, pos: Pos2) -> bool {
+        if self.touches.is_empty() {
+            self.started_at = Some(Instant::now());
+            self.max_touches = 0;
+            self.cancelled = false;
+        } else if self
+            .started_at
+            .is_none_or(|at| at.elapsed() > TAP_MAX_DURATION)
+        {
+            // 已有手指按住较久（例如正在书写），新手指不构成轻触
+            self.cancelled = true;
+        }
+        self.touches.insert(id, TrackedTouch { start: pos, pos });
+        self.max_touches = self.max_touches.max(self.touches.len());
+        self.is_tap_candidate()
+    }
+
+    /// Records a touch moving, returns how the view should change while exactly two fingers are down
+    pub fn touch_moved(&mut self, id: u64, pos: Pos2) -> Option<PinchUpdate> {
+        let before = self.pinch_span();
+        let touch = self.touches.get_mut(&id)?;
+        touch.pos = pos;
+        if touch.start.distance(pos) > TAP_MOVE_TOLERANCE {
+            self.cancelled = true;
+        }
+
+        let ((old_center, old_distance), (center, distance)) = before.zip(self.pinch_span())?;
+        Some(PinchUpdate {
+            center,
+            translation: center - old_center,
+            zoom: if old_distance > 0.0 {
+                distance / old_distance
+            } else {
+                1.0
+            },
+        })
+    }
+
+    // 双指的中点与间距
+    fn pinch_span(&self) -> Option<(Pos2, f32)> {
+        let mut touches = self.touches.values();
+        let (Some(a), Some(b), None) = (touches.next(), touches.next(), touches.next()) else {
+            return None;
+        };
+        Some((a.pos.lerp(b.pos, 0.5), a.pos.distance(b.pos)))
+    }
+
+    /// Records a touch going up, returns the recognized gesture once the last finger is lifted
+    pub fn touch_up(&mut self, id: u64, cancelled: bool) -> Option<Gesture> {
+        self.touches.remove(&id)?;
+        self.cancelled |= cancelled;
+        if !self.touches.is_empty()
+            || self.cancelled
+            || self
+                .started_at
+                .is_none_or(|at| at.elapsed() > TAP_MAX_DURATION)
+        {
+            return None;
+        }
+        match self.max_touches {
+            2 => Some(Gesture::Undo),
+            3 => Some(Gesture::Redo),
+            _ => None,
+        }
+    }
+
+    /// Time until the finger held down counts as a long press, `None` when it cannot become one
+    pub fn long_press_remaining(&self) -> Option<Duration> {
+        if self.cancelled || self.max_touches != 1 || self.touches.len() != 1 {
+            return None;
+        }
+        Some(LONG_PRESS_DURATION.saturating_sub(self.started_at?.elapsed()))
+    }
+
+    /// Returns the touch and where it went down once a single finger has been held still long
+    /// enough, at most once per touch
+    pub fn take_long_press(&mut self) -> Option<(u64, Pos2)> {
+        if !self.long_press_remaining()?.is_zero() {
+            return None;
+        }
+        self.cancelled = true;
+        let (&id, touch) = self.touches.iter().next()?;
+        Some((id, touch.start))
+    }
+
+    // 多根手指几乎同时按下，且仍可能是一次轻触
+    fn is_tap_candidate(&self) -> bool {
+        !self.cancelled && self.max_touches >= 2
+    }
+
+    /// Ids of the touches currently on screen
+    pub fn touch_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.touches.keys().copied()
+    }
+
+    pub fn touch_count(&self) -> usize {
+        self.touches.len()
+    }
+}
+
 #[cfg(feature = "startup_animation")]
 pub struct StartupAnimation {
     fps: f32,
@@ -1194,7 +3131,7 @@ pub struct StartupAnimation {
     last_frame_index: usize,
 
     // Audio
-    _audio_sink: Option<Player>,
+    audio_player: Option<Player>,
 
     finished: bool,
 }
@@ -1208,7 +3145,7 @@ impl StartupAnimation {
             frames,
             texture: None,
             last_frame_index: usize::MAX,
-            _audio_sink: Some(Self::play_audio(audio)),
+            audio_player: Some(Self::play_audio(audio)),
             finished: false,
         }
     }
@@ -1221,7 +3158,8 @@ impl StartupAnimation {
         let cursor = Cursor::new(audio);
         let source = Decoder::new(cursor).unwrap();
 
-        handle.mixer().add(source);
+        // 通过 player 播放，以便跳过动画时停止音频
+        player.append(source);
 
         // keep stream alive
         std::mem::forget(handle);
@@ -1295,6 +3233,68 @@ impl StartupAnimation {
     pub fn is_finished(&self) -> bool {
         self.finished
     }
+
+    /// Stops playback immediately, including the audio
+    pub fn skip(&mut self) {
+        self.finished = true;
+        self.texture = None;
+        if let Some(player) = self.audio_player.take() {
+            player.stop();
+        }
+    }
+}
+
+/// Maps canvas coordinates to screen coordinates.
+/// Objects are always stored in canvas coordinates; the transform is only applied when painting and handling input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewportTransform {
+    pub offset: egui::Vec2,
+    pub scale: f32,
+}
+
+impl Default for ViewportTransform {
+    fn default() -> Self {
+        Self {
+            offset: egui::Vec2::ZERO,
+            scale: 1.0,
+        }
+    }
+}
+
+impl ViewportTransform {
+    pub const MIN_SCALE: f32 = 0.1;
+    pub const MAX_SCALE: f32 = 10.0;
+
+    pub fn to_screen(&self, pos: Pos2) -> Pos2 {
+        (pos.to_vec2() * self.scale + self.offset).to_pos2()
+    }
+
+    pub fn to_canvas(&self, pos: Pos2) -> Pos2 {
+        ((pos.to_vec2() - self.offset) / self.scale).to_pos2()
+    }
+
+    pub fn to_canvas_rect(&self, rect: egui::Rect) -> egui::Rect {
+        egui::Rect::from_min_max(self.to_canvas(rect.min), self.to_canvas(rect.max))
+    }
+
+    pub fn transform(&self) -> egui::emath::TSTransform {
+        egui::emath::TSTransform::new(self.offset, self.scale)
+    }
+
+    /// Zooms by `factor`, keeping the canvas point under `screen_pos` in place
+    pub fn zoom_at(&mut self, screen_pos: Pos2, factor: f32) {
+        let anchor = self.to_canvas(screen_pos);
+        self.scale = (self.scale * factor).clamp(Self::MIN_SCALE, Self::MAX_SCALE);
+        self.offset = screen_pos.to_vec2() - anchor.to_vec2() * self.scale;
+    }
+}
+
+/// Object copied with Ctrl+C or Ctrl+X.
+/// Images keep sharing the texture and pixel data of the original, so they can be pasted after the original is gone.
+#[derive(Debug, Clone)]
+pub struct ClipboardItem {
+    pub object: CanvasObject,
+    pub paste_count: u32, // 已粘贴次数，用于计算粘贴偏移
 }
 
 // 历史记录命令枚举
@@ -1310,6 +3310,19 @@ pub enum HistoryCommand {
         index: usize,
         object: CanvasObject,
     },
+    // 批量添加对象命令（用于对称绘制等），对象从 index 处起连续排列
+    AddObjects {
+        index: usize,
+        objects: Vec<CanvasObject>,
+    },
+    // 批量删除对象命令，按下标升序记录
+    RemoveObjects {
+        objects: Vec<(usize, CanvasObject)>,
+    },
+    // 修改对象所属组合的命令，记录每个对象的原组合与新组合
+    ChangeGroups {
+        changes: Vec<(usize, Option<GroupId>, Option<GroupId>)>,
+    },
     // 批量操作（用于清空画布等）
     ClearObjects {
         objects: Vec<CanvasObject>,
@@ -1326,6 +3339,29 @@ pub enum HistoryCommand {
         old_transform: ObjectTransform,
         new_transform: ObjectTransform,
     },
+    // 批量移动对象命令（用于对齐与分布等），记录每个对象的位移
+    MoveObjects {
+        moves: Vec<(usize, egui::Vec2)>,
+    },
+    // 替换对象命令（用于编辑文本内容等）
+    ReplaceObject {
+        index: usize,
+        old_object: CanvasObject,
+        new_object: CanvasObject,
+    },
+    // 拆分对象命令（用于像素橡皮擦），按顺序记录一次擦除手势中的每次拆分：
+    // (index, 原对象, 片段)，index 处的对象被替换为若干片段
+    SplitObjects {
+        splits: Vec<(usize, CanvasObject, Vec<CanvasObject>)>,
+    },
+    // 调整叠放顺序命令，新顺序中第 i 个对象原先位于 order[i]
+    ReorderObjects {
+        order: Vec<usize>,
+    },
+    // 锁定或解锁对象命令，记录每个对象新的锁定状态
+    ChangeLocks {
+        changes: Vec<(usize, bool)>,
+    },
 }
 
 // 对象变换信息
@@ -1342,6 +3378,9 @@ pub struct History {
     undo_stack: Vec<HistoryCommand>,
     redo_stack: Vec<HistoryCommand>,
     max_history_size: usize,
+    revision: u64, // 每次记录、撤销或重做时递增
+    // 正在合并的替换记录：对象下标与产生修改的控件
+    coalescing: Option<(usize, egui::Id)>,
 }
 
 impl History {
@@ -1350,21 +3389,101 @@ impl History {
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             max_history_size,
+            revision: 0,
+            coalescing: None,
+        }
+    }
+
+    /// Counter that changes whenever the canvas is modified through the history
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Calls `f` on every object kept by the undo and redo stacks
+    pub fn for_each_object_mut(&mut self, mut f: impl FnMut(&mut CanvasObject)) {
+        for command in self.undo_stack.iter_mut().chain(self.redo_stack.iter_mut()) {
+            match command {
+                HistoryCommand::AddObject { object, .. }
+                | HistoryCommand::RemoveObject { object, .. } => f(object),
+                HistoryCommand::AddObjects { objects, .. }
+                | HistoryCommand::ClearObjects { objects } => objects.iter_mut().for_each(&mut f),
+                HistoryCommand::ReplaceObject {
+                    old_object,
+                    new_object,
+                    ..
+                } => {
+                    f(old_object);
+                    f(new_object);
+                }
+                HistoryCommand::SplitObjects { splits } => {
+                    for (_, object, pieces) in splits {
+                        f(object);
+                        pieces.iter_mut().for_each(&mut f);
+                    }
+                }
+                HistoryCommand::RemoveObjects { objects } => {
+                    objects.iter_mut().for_each(|(_, object)| f(object))
+                }
+                HistoryCommand::MoveObject { .. }
+                | HistoryCommand::TransformObject { .. }
+                | HistoryCommand::MoveObjects { .. }
+                | HistoryCommand::ChangeGroups { .. }
+                | HistoryCommand::ReorderObjects { .. }
+                | HistoryCommand::ChangeLocks { .. } => {}
+            }
         }
     }
 
+    /// Drops every undo and redo step, for when the canvas was replaced outside of the history
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.coalescing = None;
+        self.revision += 1;
+    }
+
     // 保存添加对象的命令
     pub fn save_add_object(&mut self, index: usize, object: CanvasObject) {
         let command = HistoryCommand::AddObject { index, object };
         self.push_command(command);
     }
 
+    // 保存批量添加对象的命令
+    pub fn save_add_objects(&mut self, index: usize, objects: Vec<CanvasObject>) {
+        let command = HistoryCommand::AddObjects { index, objects };
+        self.push_command(command);
+    }
+
     // 保存删除对象的命令
     pub fn save_remove_object(&mut self, index: usize, object: CanvasObject) {
         let command = HistoryCommand::RemoveObject { index, object };
         self.push_command(command);
     }
 
+    // 保存批量删除对象的命令
+    pub fn save_remove_objects(&mut self, objects: Vec<(usize, CanvasObject)>) {
+        let command = HistoryCommand::RemoveObjects { objects };
+        self.push_command(command);
+    }
+
+    // 保存修改组合的命令
+    pub fn save_change_groups(&mut self, changes: Vec<(usize, Option<GroupId>, Option<GroupId>)>) {
+        let command = HistoryCommand::ChangeGroups { changes };
+        self.push_command(command);
+    }
+
+    // 保存调整叠放顺序的命令
+    pub fn save_reorder_objects(&mut self, order: Vec<usize>) {
+        let command = HistoryCommand::ReorderObjects { order };
+        self.push_command(command);
+    }
+
+    // 保存锁定或解锁对象的命令
+    pub fn save_change_locks(&mut self, changes: Vec<(usize, bool)>) {
+        let command = HistoryCommand::ChangeLocks { changes };
+        self.push_command(command);
+    }
+
     // 保存清空对象的命令
     pub fn save_clear_objects(&mut self, objects: Vec<CanvasObject>) {
         let command = HistoryCommand::ClearObjects { objects };
@@ -1386,6 +3505,12 @@ impl History {
         self.push_command(command);
     }
 
+    // 保存批量移动对象的命令
+    pub fn save_move_objects(&mut self, moves: Vec<(usize, egui::Vec2)>) {
+        let command = HistoryCommand::MoveObjects { moves };
+        self.push_command(command);
+    }
+
     // 保存变换对象的命令
     pub fn save_transform_object(
         &mut self,
@@ -1401,10 +3526,84 @@ impl History {
         self.push_command(command);
     }
 
+    // 保存替换对象的命令
+    pub fn save_replace_object(
+        &mut self,
+        index: usize,
+        old_object: CanvasObject,
+        new_object: CanvasObject,
+    ) {
+        let command = HistoryCommand::ReplaceObject {
+            index,
+            old_object,
+            new_object,
+        };
+        self.push_command(command);
+    }
+
+    /// Records that the object at `index` was split into `pieces`.
+    /// With `merge`, the split joins the previous split command, so that one erase gesture is
+    /// undone in one step.
+    pub fn save_split_object(
+        &mut self,
+        index: usize,
+        object: CanvasObject,
+        pieces: Vec<CanvasObject>,
+        merge: bool,
+    ) {
+        if merge && let Some(HistoryCommand::SplitObjects { splits }) = self.undo_stack.last_mut() {
+            splits.push((index, object, pieces));
+            self.revision += 1;
+            return;
+        }
+        let command = HistoryCommand::SplitObjects {
+            splits: vec![(index, object, pieces)],
+        };
+        self.push_command(command);
+    }
+
+    /// Records a replaced object; repeated changes made by the same continuous `interaction`
+    /// (a slider drag, typing into a focused field) are merged into one undo step.
+    /// Changes without an interaction, like clicks, are always recorded separately.
+    pub fn save_replace_object_coalesced(
+        &mut self,
+        index: usize,
+        old_object: CanvasObject,
+        new_object: CanvasObject,
+        interaction: Option<egui::Id>,
+    ) {
+        if let Some(interaction) = interaction
+            && self.coalescing == Some((index, interaction))
+            && self.redo_stack.is_empty()
+            && let Some(HistoryCommand::ReplaceObject {
+                index: last_index,
+                new_object: last_new_object,
+                ..
+            }) = self.undo_stack.last_mut()
+            && *last_index == index
+        {
+            *last_new_object = new_object;
+            self.revision += 1;
+            return;
+        }
+        self.save_replace_object(index, old_object, new_object);
+        self.coalescing = interaction.map(|interaction| (index, interaction));
+    }
+
+    /// Stops merging replacements once `interaction` is no longer the one being coalesced,
+    /// so that the next drag of the same slider becomes its own undo step
+    pub fn end_coalescing_unless(&mut self, interaction: Option<egui::Id>) {
+        if self.coalescing.map(|(_, id)| id) != interaction {
+            self.coalescing = None;
+        }
+    }
+
     // 推送命令并维护历史记录大小
     fn push_command(&mut self, command: HistoryCommand) {
+        self.coalescing = None;
         self.undo_stack.push(command);
         self.redo_stack.clear();
+        self.revision += 1;
 
         // 清理超出限制的历史记录
         if self.undo_stack.len() > self.max_history_size {
@@ -1415,8 +3614,10 @@ impl History {
     // 执行撤销操作
     pub fn undo(&mut self, current_state: &mut CanvasState) -> bool {
         if let Some(command) = self.undo_stack.pop() {
+            self.coalescing = None;
             self.apply_reverse(&command, current_state);
             self.redo_stack.push(command);
+            self.revision += 1;
             true
         } else {
             false
@@ -1426,8 +3627,10 @@ impl History {
     // 执行重做操作
     pub fn redo(&mut self, current_state: &mut CanvasState) -> bool {
         if let Some(command) = self.redo_stack.pop() {
+            self.coalescing = None;
             self.apply_forward(&command, current_state);
             self.undo_stack.push(command);
+            self.revision += 1;
             true
         } else {
             false
@@ -1441,13 +3644,32 @@ impl History {
                     current_state.objects.remove(*index);
                 }
             }
+            HistoryCommand::AddObjects { index, objects } => {
+                if *index + objects.len() <= current_state.objects.len() {
+                    current_state.objects.drain(*index..*index + objects.len());
+                }
+            }
             HistoryCommand::RemoveObject { index, object } => {
                 if *index <= current_state.objects.len() {
                     current_state.objects.insert(*index, object.clone());
                 }
             }
+            HistoryCommand::RemoveObjects { objects } => {
+                for (index, object) in objects {
+                    if *index <= current_state.objects.len() {
+                        current_state.objects.insert(*index, object.clone());
+                    }
+                }
+            }
+            HistoryCommand::ChangeGroups { changes } => {
+                for (index, old_group, _) in changes {
+                    if let Some(object) = current_state.objects.get_mut(*index) {
+                        object.set_group(*old_group);
+                    }
+                }
+            }
             HistoryCommand::ClearObjects { objects } => {
-                current_state.objects = objects.clone();
+                current_state.objects.replace_all(objects.clone());
             }
             HistoryCommand::MoveObject {
                 index,
@@ -1458,6 +3680,13 @@ impl History {
                     CanvasObject::move_object(&mut current_state.objects[*index], *old_position);
                 }
             }
+            HistoryCommand::MoveObjects { moves } => {
+                for (index, delta) in moves {
+                    if let Some(object) = current_state.objects.get_mut(*index) {
+                        CanvasObject::move_object(object, -*delta);
+                    }
+                }
+            }
             HistoryCommand::TransformObject {
                 index,
                 old_transform,
@@ -1467,6 +3696,34 @@ impl History {
                     History::apply_transform(&mut current_state.objects[*index], old_transform);
                 }
             }
+            HistoryCommand::ReplaceObject {
+                index,
+                old_object,
+                new_object: _,
+            } => {
+                if *index < current_state.objects.len() {
+                    current_state.objects[*index] = old_object.clone();
+                }
+            }
+            HistoryCommand::SplitObjects { splits } => {
+                for (index, object, pieces) in splits.iter().rev() {
+                    if *index + pieces.len() <= current_state.objects.len() {
+                        current_state
+                            .objects
+                            .splice(*index..*index + pieces.len(), [object.clone()]);
+                    }
+                }
+            }
+            HistoryCommand::ReorderObjects { order } => {
+                current_state.restore_object_order(order);
+            }
+            HistoryCommand::ChangeLocks { changes } => {
+                for (index, locked) in changes {
+                    if let Some(object) = current_state.objects.get_mut(*index) {
+                        object.set_locked(!*locked);
+                    }
+                }
+            }
         }
     }
 
@@ -1477,11 +3734,32 @@ impl History {
                     current_state.objects.insert(*index, object.clone());
                 }
             }
+            HistoryCommand::AddObjects { index, objects } => {
+                if *index <= current_state.objects.len() {
+                    current_state
+                        .objects
+                        .splice(*index..*index, objects.iter().cloned());
+                }
+            }
             HistoryCommand::RemoveObject { index, object: _ } => {
                 if *index < current_state.objects.len() {
                     current_state.objects.remove(*index);
                 }
             }
+            HistoryCommand::RemoveObjects { objects } => {
+                for (index, _) in objects.iter().rev() {
+                    if *index < current_state.objects.len() {
+                        current_state.objects.remove(*index);
+                    }
+                }
+            }
+            HistoryCommand::ChangeGroups { changes } => {
+                for (index, _, new_group) in changes {
+                    if let Some(object) = current_state.objects.get_mut(*index) {
+                        object.set_group(*new_group);
+                    }
+                }
+            }
             HistoryCommand::ClearObjects { objects: _ } => {
                 current_state.objects.clear();
             }
@@ -1494,6 +3772,13 @@ impl History {
                     CanvasObject::move_object(&mut current_state.objects[*index], *new_position);
                 }
             }
+            HistoryCommand::MoveObjects { moves } => {
+                for (index, delta) in moves {
+                    if let Some(object) = current_state.objects.get_mut(*index) {
+                        CanvasObject::move_object(object, *delta);
+                    }
+                }
+            }
             HistoryCommand::TransformObject {
                 index,
                 old_transform: _,
@@ -1503,6 +3788,34 @@ impl History {
                     History::apply_transform(&mut current_state.objects[*index], new_transform);
                 }
             }
+            HistoryCommand::ReplaceObject {
+                index,
+                old_object: _,
+                new_object,
+            } => {
+                if *index < current_state.objects.len() {
+                    current_state.objects[*index] = new_object.clone();
+                }
+            }
+            HistoryCommand::SplitObjects { splits } => {
+                for (index, _, pieces) in splits {
+                    if *index < current_state.objects.len() {
+                        current_state
+                            .objects
+                            .splice(*index..*index + 1, pieces.iter().cloned());
+                    }
+                }
+            }
+            HistoryCommand::ReorderObjects { order } => {
+                current_state.reorder_objects(order);
+            }
+            HistoryCommand::ChangeLocks { changes } => {
+                for (index, locked) in changes {
+                    if let Some(object) = current_state.objects.get_mut(*index) {
+                        object.set_locked(*locked);
+                    }
+                }
+            }
         }
     }
 
@@ -1511,16 +3824,28 @@ impl History {
             CanvasObject::Image(img) => {
                 img.pos = transform.pos;
                 img.size = transform.size;
+                img.rot = transform.rotation;
             }
             CanvasObject::Text(text) => {
                 text.pos = transform.pos;
                 text.font_size = transform.size.x;
                 text.cached_size = None;
             }
+            CanvasObject::StickyNote(note) => {
+                note.pos = transform.pos;
+                note.size = transform.size;
+            }
             CanvasObject::Shape(shape) => {
                 shape.pos = transform.pos;
-                shape.size = transform.size.x;
+                if shape.uses_end_point() {
+                    shape.end = shape.pos + transform.size;
+                    shape.size = transform.size.length();
+                } else {
+                    shape.end = shape.pos;
+                    shape.size = transform.size.x;
+                }
                 shape.rotation = transform.rotation;
+                shape.recompute_bounds();
             }
             CanvasObject::Stroke(_) => {}
         }
@@ -1529,7 +3854,7 @@ impl History {
 
 impl Default for History {
     fn default() -> Self {
-        Self::new(50)
+        Self::new(100)
     }
 }
 
@@ -1541,12 +3866,45 @@ pub struct AppState {
     pub pages: Vec<PageState>,                           // 分页
     pub current_page: usize,                             // 当前页码
     pub pointers: HashMap<u64, PointerState>, // 统一指针状态表（鼠标 id=0，触控使用 winit touch id）
+    pub gestures: GestureRecognizer,          // 多指轻触手势识别（撤销/重做）
     pub brush_color: Color32,                 // 画笔颜色
+    pub brush_opacity: u8,                    // 画笔不透明度，绘制笔画时应用到画笔颜色
+    pub fill_color: Color32,                  // 填充工具使用的颜色
     pub brush_width: f32,                     // 画笔大小
+    pub brush_line_style: LineStyle,          // 画笔线型
+    pub brush_texture: Option<StrokeTexture>, // 纹理画笔使用的纹理，None 为纯色画笔
+    pub brush_style: BrushStyle,              // 画笔风格，粉笔和铅笔会覆盖纹理与宽度
+    pub custom_brush_texture: Option<StrokeTexture>, // 用户加载的纹理（仅本次运行有效）
+    pub smoothing_strength: usize,            // 移动平均平滑的窗口大小（点数）
+    pub recent_colors: VecDeque<Color32>,     // 最近使用的画笔颜色，最新的在前
+    pub pinned_colors: Vec<Color32>,          // 收藏的颜色（仅本次运行有效）
+    pub highlighter_color: Color32,           // 荧光笔颜色（不含透明度）
+    pub highlighter_width: f32,               // 荧光笔大小
     pub dynamic_brush_width_mode: DynamicBrushWidthMode, // 动态画笔大小微调
     pub current_tool: CanvasTool,             // 当前工具
     pub eraser_size: f32,                     // 橡皮擦大小
+    pub eraser_scope: EraserScope,            // 橡皮擦作用的图层，按住 Alt 时作用于所有图层
+    pub min_point_distance: f32,              // 书写与像素擦除的最小采样间距（画布坐标）
+    pub last_erase_positions: HashMap<u64, Pos2>, // 像素橡皮擦各指针上次擦除的位置
+    pub erase_history_revision: Option<u64>,  // 本次擦除手势最后一次记录后的历史版本，用于合并记录
     pub selected_object_index: Option<usize>, // 选中的对象索引（全局共享）
+    pub selected_objects: Vec<usize>, // 多选的对象索引，多选时 selected_object_index 为 None
+    pub cropping_image: Option<(usize, egui::Rect)>, // 正在裁剪的图片及尚未确认的裁剪区域
+    pub viewport: ViewportTransform,  // 画布视图的平移与缩放
+    pub clipboard: Option<ClipboardItem>, // 复制或剪切的对象（跨页面共享）
+    pub pending_shape: Option<CanvasShapeType>, // 等待在画布上拖动绘制的形状
+    pub pending_shape_recognition: Option<(usize, CanvasShapeType)>, // 等待确认转换为形状的笔画索引
+    pub show_grid: bool,              // 是否显示背景网格并吸附到网格
+    pub grid_size: f32,               // 网格间距
+    pub background_pattern: BackgroundPattern, // 背景图案
+    pub background_spacing: f32,      // 背景图案间距
+    pub background_pattern_color: Color32, // 背景图案颜色
+    pub symmetry: SymmetryMode,       // 对称绘制模式
+    pub symmetry_center: Pos2,        // 对称轴的交点（画布坐标），为当前视图的中心
+    pub alignment_guides: Vec<[Pos2; 2]>, // 拖动对象时显示的对齐参考线（画布坐标），不会保存
+    pub measure: MeasureState,        // 测量工具的直尺与量角器
+    pub presentation: Option<Presentation>, // 正在全屏演示页面
+    pub context_menu: Option<ObjectContextMenu>, // 打开的对象右键菜单
 
     // persistent states
     pub persistent: PersistentState,
@@ -1557,9 +3915,19 @@ pub struct AppState {
     pub show_insert_shape_window: bool,
     pub show_welcome_window: bool,
     pub show_page_management_window: bool,
+    pub show_layers_window: bool,
 
-    pub show_size_preview: bool,
     pub new_text_content: String,
+    pub editing_text_index: Option<usize>, // 正在编辑的文本对象索引，None 表示插入新文本
+    pub new_text_color: Color32,           // 插入文本时使用的颜色
+    pub new_text_font_size: f32,           // 插入文本时使用的字号
+    pub new_triangle_kind: TriangleKind,   // 插入形状时使用的三角形类型
+    pub new_polygon_sides: u32,            // 插入多边形时使用的边数
+    pub new_star_points: u32,              // 插入星形时使用的角数
+    pub new_shape_line_style: LineStyle,   // 插入形状时使用的线型
+    pub new_arrow_heads: ArrowHeads,       // 插入箭头时使用的箭头设置
+    pub new_shape_fill: bool,              // 插入形状时是否填充
+    pub new_shape_fill_color: Color32,     // 插入形状时使用的填充颜色
     pub should_quit: bool,
     pub fullscreen_video_modes: Vec<winit::monitor::VideoModeHandle>,
     pub selected_video_mode_index: Option<usize>, // 选中的视频模式索引
@@ -1569,15 +3937,28 @@ pub struct AppState {
 
     pub is_overlay_mode: bool,
 
+    // network states
+    pub board_transfer: BoardTransfer,
+    pub collab: CollabSession,
+    pub spectator: SpectatorServer,
+
     // screenshot states
     pub screenshot_path: Option<PathBuf>,
+    pub pending_fill: Option<Pos2>, // 等待截取下一帧画布后执行的填充位置，画布坐标
+
+    // session recording states
+    pub session_recorder: Option<SessionRecorder>,
+    pub session_player: Option<SessionPlayer>,
+    pub autosave: Autosaver, // 定期自动保存所有页面，用于崩溃后恢复
 
     // cached states
     pub active_backend: Option<Backend>,
     pub cursor_position: PhysicalPosition<f64>,
+    pub modifiers: ModifiersState, // 当前按下的修饰键
 
     // reactive states
     pub present_mode_changed: bool,
+    pub msaa_samples_changed: bool,
     pub overlay_mode_changed: bool,
 
     #[cfg(feature = "startup_animation")]
@@ -1590,22 +3971,67 @@ pub struct AppState {
 impl Default for AppState {
     fn default() -> Self {
         let default_page = PageState::default();
+        let persistent = PersistentState::load_from_file();
+        i18n::set_language(persistent.language);
+        let defaults = persistent.drawing_defaults;
         Self {
             canvas: default_page.canvas.clone(),
             pages: vec![default_page],
             current_page: 0,
             pointers: HashMap::new(),
-            brush_color: Color32::WHITE,
-            brush_width: 3.0,
+            gestures: GestureRecognizer::default(),
+            brush_color: defaults.brush_color,
+            brush_opacity: defaults.brush_opacity,
+            fill_color: Color32::from_rgb(0, 160, 255),
+            brush_width: defaults.brush_width,
+            brush_line_style: LineStyle::default(),
+            brush_texture: None,
+            brush_style: BrushStyle::default(),
+            custom_brush_texture: None,
+            smoothing_strength: 3,
+            highlighter_color: defaults.highlighter_color,
+            highlighter_width: defaults.highlighter_width,
             dynamic_brush_width_mode: DynamicBrushWidthMode::default(),
             current_tool: CanvasTool::Brush,
-            eraser_size: 10.0,
+            eraser_size: defaults.eraser_size,
+            eraser_scope: EraserScope::default(),
+            min_point_distance: 1.0,
+            last_erase_positions: HashMap::new(),
+            erase_history_revision: None,
             selected_object_index: None,
-            show_size_preview: false,
+            selected_objects: Vec::new(),
+            cropping_image: None,
+            viewport: ViewportTransform::default(),
+            clipboard: None,
+            pending_shape: None,
+            pending_shape_recognition: None,
+            recent_colors: VecDeque::new(),
+            pinned_colors: Vec::new(),
+            show_grid: defaults.show_grid,
+            grid_size: defaults.grid_size,
+            background_pattern: defaults.background_pattern,
+            background_spacing: defaults.background_spacing,
+            background_pattern_color: defaults.background_pattern_color,
+            symmetry: SymmetryMode::default(),
+            symmetry_center: Pos2::ZERO,
+            alignment_guides: Vec::new(),
+            measure: MeasureState::default(),
+            presentation: None,
+            context_menu: None,
             fps_counter: FpsCounter::new(),
             should_quit: false,
             show_insert_text_window: false,
             new_text_content: "".to_string(),
+            editing_text_index: None,
+            new_text_color: Color32::WHITE,
+            new_text_font_size: 16.0,
+            new_triangle_kind: TriangleKind::default(),
+            new_polygon_sides: 6,
+            new_star_points: 5,
+            new_shape_line_style: LineStyle::default(),
+            new_arrow_heads: ArrowHeads::default(),
+            new_shape_fill: false,
+            new_shape_fill_color: Color32::from_gray(128),
             show_insert_shape_window: false,
             fullscreen_video_modes: Vec::new(),
             selected_video_mode_index: None,
@@ -1614,22 +4040,168 @@ impl Default for AppState {
             show_touch_points: false,
             show_welcome_window: true,
             show_page_management_window: false,
-            persistent: PersistentState::load_from_file(),
+            show_layers_window: false,
+            persistent,
             screenshot_path: None,
+            pending_fill: None,
+            session_recorder: None,
+            session_player: None,
+            autosave: Autosaver::default(),
             toasts: Toasts::default()
                 .with_anchor(egui_notify::Anchor::BottomRight)
                 .with_margin(egui::vec2(20.0, 20.0)),
             history: History::default(),
             active_backend: None,
             present_mode_changed: false,
+            msaa_samples_changed: false,
             is_overlay_mode: false,
             overlay_mode_changed: false,
+            board_transfer: BoardTransfer::default(),
+            collab: CollabSession::default(),
+            spectator: SpectatorServer::default(),
             cursor_position: PhysicalPosition {
                 x: 0.0_f64,
                 y: 0.0_f64,
             },
+            modifiers: ModifiersState::default(),
             #[cfg(feature = "startup_animation")]
             startup_animation: None,
         }
     }
 }
+
+/// Number of recently used brush colors kept in [`AppState::recent_colors`]
+pub const RECENT_COLORS_CAP: usize = 8;
+
+impl AppState {
+    /// Whether the erasers may remove the object at `index`: it must be visible and, unless erasing across all layers, on the active layer.
+    /// Holding Alt erases across all layers regardless of the scope.
+    pub fn is_object_erasable(&self, index: usize) -> bool {
+        let canvas = &self.canvas;
+        canvas.is_object_visible(index)
+            && !canvas.objects[index].is_locked()
+            && (self.eraser_scope == EraserScope::AllLayers
+                || self.modifiers.alt_key()
+                || canvas.objects[index].layer() == canvas.active_layer)
+    }
+
+    /// Color new brush strokes are drawn with: the brush color at the brush opacity
+    pub fn ink_color(&self) -> Color32 {
+        let [r, g, b, _] = self.brush_color.to_srgba_unmultiplied();
+        Color32::from_rgba_unmultiplied(r, g, b, self.brush_opacity)
+    }
+
+    /// Recreates every texture with `ctx` after the renderer has been rebuilt, e.g. when the GPU device was lost
+    pub fn reload_textures(&mut self, ctx: &egui::Context) {
+        let boards = std::iter::once((&mut self.canvas, &mut self.history)).chain(
+            self.pages
+                .iter_mut()
+                .map(|page| (&mut page.canvas, &mut page.history)),
+        );
+        for (canvas, history) in boards {
+            canvas
+                .objects
+                .for_each_mut(|object| object.reload_texture(ctx));
+            if let Some(background) = &mut canvas.background_image {
+                background.reload_texture(ctx);
+            }
+            history.for_each_object_mut(|object| object.reload_texture(ctx));
+        }
+        if let Some(item) = &mut self.clipboard {
+            item.object.reload_texture(ctx);
+        }
+        if let Some(player) = &mut self.session_player {
+            player.for_each_object_mut(|object| object.reload_texture(ctx));
+        }
+        // 启动动画在下一帧重新创建纹理
+        #[cfg(feature = "startup_animation")]
+        if let Some(anim) = &mut self.startup_animation {
+            anim.texture = None;
+        }
+    }
+
+    /// Whether the next frame only draws the canvas, because it is captured for an export, a fill
+    /// or the spectators
+    pub fn is_capturing_canvas(&self) -> bool {
+        self.screenshot_path.is_some()
+            || self.pending_fill.is_some()
+            || self.spectator.is_capturing()
+    }
+
+    /// Stores the current brush, background and grid settings in the settings and writes them to disk
+    pub fn save_settings(&mut self) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        self.persistent.drawing_defaults = DrawingDefaults {
+            brush_color: self.brush_color,
+            brush_opacity: self.brush_opacity,
+            brush_width: self.brush_width,
+            highlighter_color: self.highlighter_color,
+            highlighter_width: self.highlighter_width,
+            eraser_size: self.eraser_size,
+            background_pattern: self.background_pattern,
+            background_spacing: self.background_spacing,
+            background_pattern_color: self.background_pattern_color,
+            show_grid: self.show_grid,
+            grid_size: self.grid_size,
+        };
+        self.persistent.save_to_file()
+    }
+
+    /// Restores the brush, background and grid settings from [`PersistentState::drawing_defaults`]
+    pub fn apply_drawing_defaults(&mut self) {
+        let defaults = self.persistent.drawing_defaults;
+        self.brush_color = defaults.brush_color;
+        self.brush_opacity = defaults.brush_opacity;
+        self.brush_width = defaults.brush_width;
+        self.highlighter_color = defaults.highlighter_color;
+        self.highlighter_width = defaults.highlighter_width;
+        self.eraser_size = defaults.eraser_size;
+        self.background_pattern = defaults.background_pattern;
+        self.background_spacing = defaults.background_spacing;
+        self.background_pattern_color = defaults.background_pattern_color;
+        self.show_grid = defaults.show_grid;
+        self.grid_size = defaults.grid_size;
+    }
+
+    /// Grid spacing objects snap to, if the grid is enabled
+    pub fn snap_grid(&self) -> Option<f32> {
+        self.show_grid.then_some(self.grid_size)
+    }
+
+    /// Moves `color` to the front of the recent colors, dropping the oldest beyond [`RECENT_COLORS_CAP`]
+    pub fn remember_recent_color(&mut self, color: Color32) {
+        self.recent_colors.retain(|&c| c != color);
+        self.recent_colors.push_front(color);
+        self.recent_colors.truncate(RECENT_COLORS_CAP);
+    }
+
+    /// Deselects the selected object and the multi-selection
+    pub fn clear_selection(&mut self) {
+        self.selected_object_index = None;
+        self.selected_objects.clear();
+        self.cropping_image = None;
+    }
+
+    /// Selects the object at `index`, together with the other members of its group
+    pub fn select_object(&mut self, index: usize) {
+        self.clear_selection();
+        let members = self.canvas.group_members(index);
+        if members.len() > 1 {
+            self.selected_objects = members;
+        } else {
+            self.selected_object_index = Some(index);
+        }
+    }
+
+    pub fn is_selected(&self, index: usize) -> bool {
+        self.selected_object_index == Some(index) || self.selected_objects.contains(&index)
+    }
+
+    /// Where newly inserted objects are placed: near the top-left of the visible canvas
+    pub fn new_object_pos(&self) -> Pos2 {
+        let pos = self.viewport.to_canvas(Pos2::new(100.0, 100.0));
+        match self.snap_grid() {
+            Some(grid_size) => utils::snap_to_grid(pos, grid_size),
+            None => pos,
+        }
+    }
+}
@@ -0,0 +1,680 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use egui::Context;
+use rkyv::Archive;
+
+use super::flat::CanvasObjectFlat;
+use super::{CanvasObject, CanvasState, CanvasStroke, CanvasTool, Color32, Pos2, StrokeWidth};
+use crate::spatial::{ChunkedObjects, ObjectChange, ObjectId};
+
+/// Magic header for session recordings: `b"UWR"` followed by format version byte
+const RECORDING_FILE_MAGIC: &[u8; 3] = b"UWR";
+const RECORDING_FILE_VERSION: u8 = 1;
+const RECORDING_HEADER_SIZE: usize = 4;
+
+pub const RECORDING_FILE_EXT: &str = "sbr";
+
+// ===== Flat data types for rkyv recording serialization =====
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub struct SessionLogFlat {
+    pub events: Vec<SessionEventFlat>,
+}
+
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub struct SessionEventFlat {
+    /// Milliseconds since the recording started
+    pub at_ms: u64,
+    pub kind: SessionEventKindFlat,
+}
+
+/// Object indices refer to the board without images, which are not recorded
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone)]
+#[rkyv(bytecheck())]
+pub enum SessionEventKindFlat {
+    ObjectAdded(CanvasObjectFlat),
+    /// An object added below the top of the board, e.g. by undoing its removal
+    ObjectInserted {
+        index: u32,
+        object: CanvasObjectFlat,
+    },
+    /// A freshly drawn stroke, with the time each point was drawn relative to the first one.
+    /// The event itself is recorded when the stroke is committed, i.e. after its last point.
+    StrokeDrawn {
+        object: CanvasObjectFlat,
+        point_offsets_ms: Vec<u32>,
+    },
+    ObjectUpdated {
+        index: u32,
+        object: CanvasObjectFlat,
+    },
+    ObjectRemoved {
+        index: u32,
+    },
+    ObjectsReplaced(Vec<CanvasObjectFlat>),
+    ToolChanged(u8),
+    CanvasColorChanged([u8; 4]),
+}
+
+/// Records every change to the board as timestamped events, from the changes the board's
+/// [`ChunkedObjects`] journals. Only the objects that changed are looked at, whichever of the
+/// many places that edit the canvas changed them.
+pub struct SessionRecorder {
+    started_at: Instant,
+    events: Vec<SessionEventFlat>,
+    store: u64,              // 正在录制的对象存储的编号，画布被整体替换后改变
+    recorded: Vec<ObjectId>, // 已录制的对象，按录制中的下标排列
+    contents: HashMap<ObjectId, CanvasObject>, // 已录制对象最后录下的内容
+    tool: CanvasTool,
+    canvas_color: Color32,
+    drawn_strokes: Vec<(Vec<Pos2>, Vec<u32>)>, // 刚提交、尚未录制的笔画的点及其绘制时间
+}
+
+impl SessionRecorder {
+    pub fn start(objects: &mut ChunkedObjects, tool: CanvasTool, canvas_color: Color32) -> Self {
+        let mut recorder = Self {
+            started_at: Instant::now(),
+            events: Vec::new(),
+            store: objects.store_id(),
+            recorded: Vec::new(),
+            contents: HashMap::new(),
+            tool,
+            canvas_color,
+            drawn_strokes: Vec::new(),
+        };
+        recorder.record_all(objects);
+        recorder.push(SessionEventKindFlat::ToolChanged(tool_to_u8(tool)));
+        recorder.push(SessionEventKindFlat::CanvasColorChanged(color_to_array(
+            canvas_color,
+        )));
+        recorder
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    pub fn event_count(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Remembers when each point of a just committed stroke was drawn, so playback can animate it.
+    /// `times` are the seconds since the stroke started for the raw input points, which smoothing may have resampled.
+    pub fn note_drawn_stroke(&mut self, stroke: &CanvasStroke, times: &[f64]) {
+        if times.is_empty() {
+            return;
+        }
+        let count = stroke.points.len();
+        let offsets = (0..count)
+            .map(|i| {
+                // 按点序号的比例对应到原始输入点
+                let raw = if count > 1 {
+                    i * (times.len() - 1) / (count - 1)
+                } else {
+                    times.len() - 1
+                };
+                (times[raw] * 1000.0) as u32
+            })
+            .collect();
+        self.drawn_strokes.push((stroke.points.clone(), offsets));
+    }
+
+    /// Records whatever changed since the last call
+    pub fn capture(
+        &mut self,
+        objects: &mut ChunkedObjects,
+        tool: CanvasTool,
+        canvas_color: Color32,
+    ) {
+        if tool != self.tool {
+            self.tool = tool;
+            self.push(SessionEventKindFlat::ToolChanged(tool_to_u8(tool)));
+        }
+
+        if canvas_color != self.canvas_color {
+            self.canvas_color = canvas_color;
+            self.push(SessionEventKindFlat::CanvasColorChanged(color_to_array(
+                canvas_color,
+            )));
+        }
+
+        // 切换页面或打开文件后画布换了一个存储，重新录下全部对象
+        if objects.store_id() != self.store {
+            self.store = objects.store_id();
+            self.record_all(objects);
+            return;
+        }
+        for change in objects.take_changes() {
+            match change {
+                ObjectChange::Added(id) | ObjectChange::Modified(id) => {
+                    self.record_object(objects, id);
+                }
+                ObjectChange::Removed(id) => self.record_removal(id),
+                // 整体替换总是日志中唯一的一项
+                ObjectChange::Replaced => {
+                    self.record_all(objects);
+                    return;
+                }
+            }
+        }
+        // 未能对应到新增对象的笔画 (如被后续操作替换) 不再保留
+        self.drawn_strokes.clear();
+    }
+
+    // 重新开始记录存储的修改，并把全部对象录为一个事件
+    fn record_all(&mut self, objects: &mut ChunkedObjects) {
+        objects.start_journal();
+        self.recorded.clear();
+        self.contents.clear();
+        let mut flat = Vec::new();
+        for (&id, object) in objects.ids().iter().zip(objects.iter()) {
+            if !is_recorded(object) {
+                continue;
+            }
+            flat.extend(CanvasObjectFlat::from_object(object));
+            self.recorded.push(id);
+            self.contents.insert(id, object.clone());
+        }
+        self.push(SessionEventKindFlat::ObjectsReplaced(flat));
+        self.drawn_strokes.clear();
+    }
+
+    // 录下对象 `id` 的当前状态：新对象录为添加，已录制的对象只在内容变化时录为更新
+    fn record_object(&mut self, objects: &mut ChunkedObjects, id: ObjectId) {
+        let current = objects.get_by_id(id).filter(|object| is_recorded(object));
+        match (self.contents.get_mut(&id), current) {
+            (Some(recorded), Some(object)) => {
+                if same_object(recorded, object) {
+                    return;
+                }
+                *recorded = object.clone();
+                let index = self.recorded_index(id);
+                if let Some(object) = CanvasObjectFlat::from_object(object) {
+                    self.push(SessionEventKindFlat::ObjectUpdated {
+                        index: index as u32,
+                        object,
+                    });
+                }
+            }
+            // 对象被替换成了不录制的图片
+            (Some(_), None) => self.record_removal(id),
+            (None, Some(object)) => {
+                let object = object.clone();
+                let index = self.insertion_index(objects, id);
+                self.recorded.insert(index, id);
+                let Some(flat) = CanvasObjectFlat::from_object(&object) else {
+                    self.contents.insert(id, object);
+                    return;
+                };
+                let timing = match &object {
+                    CanvasObject::Stroke(stroke) => self
+                        .drawn_strokes
+                        .iter()
+                        .position(|(points, _)| *points == stroke.points)
+                        .map(|i| self.drawn_strokes.swap_remove(i).1),
+                    _ => None,
+                };
+                self.contents.insert(id, object);
+                let kind = if index + 1 < self.recorded.len() {
+                    SessionEventKindFlat::ObjectInserted {
+                        index: index as u32,
+                        object: flat,
+                    }
+                } else {
+                    match timing {
+                        Some(point_offsets_ms) => SessionEventKindFlat::StrokeDrawn {
+                            object: flat,
+                            point_offsets_ms,
+                        },
+                        None => SessionEventKindFlat::ObjectAdded(flat),
+                    }
+                };
+                self.push(kind);
+            }
+            (None, None) => {}
+        }
+    }
+
+    fn record_removal(&mut self, id: ObjectId) {
+        if self.contents.remove(&id).is_none() {
+            return;
+        }
+        let index = self.recorded_index(id);
+        self.recorded.remove(index);
+        self.push(SessionEventKindFlat::ObjectRemoved {
+            index: index as u32,
+        });
+    }
+
+    fn recorded_index(&self, id: ObjectId) -> usize {
+        self.recorded
+            .iter()
+            .position(|&recorded| recorded == id)
+            .expect("recorded objects are listed")
+    }
+
+    // 新对象在录制中的下标：紧跟在画布上位于它之前的最近一个已录制对象之后
+    fn insertion_index(&self, objects: &mut ChunkedObjects, id: ObjectId) -> usize {
+        let position = objects.index_of(id).unwrap_or(0);
+        let previous = objects.ids()[..position]
+            .iter()
+            .rev()
+            .find(|previous| self.contents.contains_key(previous));
+        match previous {
+            None => 0,
+            Some(&previous) if self.recorded.last() == Some(&previous) => self.recorded.len(),
+            Some(&previous) => self.recorded_index(previous) + 1,
+        }
+    }
+
+    /// Writes the recording with its file header, in the rkyv binary format
+    pub fn save_to_file(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let log = SessionLogFlat {
+            events: self.events.clone(),
+        };
+        let payload =
+            rkyv::to_bytes::<rkyv::rancor::Error>(&log).map_err(|e| format!("rkyv error: {e}"))?;
+
+        let mut out = Vec::with_capacity(RECORDING_HEADER_SIZE + payload.len());
+        out.extend_from_slice(RECORDING_FILE_MAGIC);
+        out.push(RECORDING_FILE_VERSION);
+        out.extend_from_slice(payload.as_slice());
+        std::fs::write(path, out)?;
+        Ok(())
+    }
+
+    fn push(&mut self, kind: SessionEventKindFlat) {
+        self.events.push(SessionEventFlat {
+            at_ms: self.started_at.elapsed().as_millis() as u64,
+            kind,
+        });
+    }
+}
+
+enum ReplayEventKind {
+    ObjectAdded(CanvasObject),
+    ObjectInserted {
+        index: usize,
+        object: CanvasObject,
+    },
+    StrokeDrawn {
+        stroke: CanvasStroke,
+        offsets: Vec<Duration>,
+    },
+    ObjectUpdated {
+        index: usize,
+        object: CanvasObject,
+    },
+    ObjectRemoved {
+        index: usize,
+    },
+    ObjectsReplaced(Vec<CanvasObject>),
+    ToolChanged(CanvasTool),
+    CanvasColorChanged(Color32),
+}
+
+struct ReplayEvent {
+    at: Duration,
+    kind: ReplayEventKind,
+}
+
+// 回放中正在逐点绘制的笔画
+struct StrokeAnimation {
+    index: usize,
+    stroke: CanvasStroke,
+    start: Duration,
+    offsets: Vec<Duration>,
+}
+
+impl StrokeAnimation {
+    // 截至 `position` 已画出的点数, 至少为一个点
+    fn drawn_points(&self, position: Duration) -> usize {
+        let elapsed = position.saturating_sub(self.start);
+        self.offsets
+            .partition_point(|&offset| offset <= elapsed)
+            .clamp(1, self.stroke.points.len())
+    }
+
+    fn partial_stroke(&self, count: usize) -> CanvasStroke {
+        let mut stroke = self.stroke.clone();
+        stroke.points.truncate(count);
+        if let StrokeWidth::Dynamic(widths) = &mut stroke.width {
+            widths.truncate(count);
+        }
+        stroke.with_bounds()
+    }
+}
+
+/// Plays a recording back by applying its events to a board over time
+pub struct SessionPlayer {
+    events: Vec<ReplayEvent>,
+    next_event: usize,
+    drawing: Vec<StrokeAnimation>,
+    position: Duration,
+    last_tick: Instant,
+    pub speed: f32,
+    pub paused: bool,
+    /// Page the recording is played back on
+    pub page: usize,
+    pub tool: CanvasTool,
+    pub canvas_color: Option<Color32>,
+}
+
+impl SessionPlayer {
+    pub fn load_from_file(path: &Path, ctx: &Context) -> Result<Self, Box<dyn std::error::Error>> {
+        let bytes = std::fs::read(path)?;
+        if bytes.len() < RECORDING_HEADER_SIZE
+            || bytes[..3] != *RECORDING_FILE_MAGIC
+            || bytes[3] != RECORDING_FILE_VERSION
+        {
+            return Err(format!(
+                "unsupported recording format: expected magic=UWR, version={RECORDING_FILE_VERSION}"
+            )
+            .into());
+        }
+
+        let archived = rkyv::access::<ArchivedSessionLogFlat, rkyv::rancor::Error>(
+            &bytes[RECORDING_HEADER_SIZE..],
+        )
+        .map_err(|e| format!("rkyv error: {e}"))?;
+
+        // 录制中不含图片, 解码失败的对象事件直接跳过
+        let mut events: Vec<ReplayEvent> = archived
+            .events
+            .iter()
+            .filter_map(|event| {
+                let kind = match &event.kind {
+                    ArchivedSessionEventKindFlat::ObjectAdded(obj) => {
+                        ReplayEventKind::ObjectAdded(CanvasObject::from_flat(obj, ctx)?)
+                    }
+                    ArchivedSessionEventKindFlat::ObjectInserted { index, object } => {
+                        ReplayEventKind::ObjectInserted {
+                            index: index.to_native() as usize,
+                            object: CanvasObject::from_flat(object, ctx)?,
+                        }
+                    }
+                    ArchivedSessionEventKindFlat::StrokeDrawn {
+                        object,
+                        point_offsets_ms,
+                    } => match CanvasObject::from_flat(object, ctx)? {
+                        CanvasObject::Stroke(stroke) => ReplayEventKind::StrokeDrawn {
+                            stroke,
+                            offsets: point_offsets_ms
+                                .iter()
+                                .map(|ms| Duration::from_millis(ms.to_native().into()))
+                                .collect(),
+                        },
+                        other => ReplayEventKind::ObjectAdded(other),
+                    },
+                    ArchivedSessionEventKindFlat::ObjectUpdated { index, object } => {
+                        ReplayEventKind::ObjectUpdated {
+                            index: index.to_native() as usize,
+                            object: CanvasObject::from_flat(object, ctx)?,
+                        }
+                    }
+                    ArchivedSessionEventKindFlat::ObjectRemoved { index } => {
+                        ReplayEventKind::ObjectRemoved {
+                            index: index.to_native() as usize,
+                        }
+                    }
+                    ArchivedSessionEventKindFlat::ObjectsReplaced(objects) => {
+                        ReplayEventKind::ObjectsReplaced(
+                            objects
+                                .iter()
+                                .filter_map(|obj| CanvasObject::from_flat(obj, ctx))
+                                .collect(),
+                        )
+                    }
+                    ArchivedSessionEventKindFlat::ToolChanged(tool) => {
+                        ReplayEventKind::ToolChanged(tool_from_u8(*tool))
+                    }
+                    ArchivedSessionEventKindFlat::CanvasColorChanged(c) => {
+                        ReplayEventKind::CanvasColorChanged(Color32::from_rgba_unmultiplied(
+                            c[0], c[1], c[2], c[3],
+                        ))
+                    }
+                };
+                Some(ReplayEvent {
+                    at: Duration::from_millis(event.at_ms.into()),
+                    kind,
+                })
+            })
+            .collect();
+
+        // 笔画在提交时才被录制, 回放时提前到落笔时刻开始绘制, 但不早于前一个事件
+        let mut previous_at = Duration::ZERO;
+        for event in &mut events {
+            if let ReplayEventKind::StrokeDrawn { offsets, .. } = &event.kind {
+                let drawing_time = offsets.last().copied().unwrap_or_default();
+                event.at = event.at.saturating_sub(drawing_time).max(previous_at);
+            }
+            previous_at = event.at;
+        }
+
+        Ok(Self {
+            events,
+            next_event: 0,
+            drawing: Vec::new(),
+            position: Duration::ZERO,
+            last_tick: Instant::now(),
+            speed: 1.0,
+            paused: false,
+            page: 0,
+            tool: CanvasTool::default(),
+            canvas_color: None,
+        })
+    }
+
+    pub fn position(&self) -> Duration {
+        self.position
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.events
+            .last()
+            .map(|event| match &event.kind {
+                ReplayEventKind::StrokeDrawn { offsets, .. } => {
+                    event.at + offsets.last().copied().unwrap_or_default()
+                }
+                _ => event.at,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_event >= self.events.len() && self.drawing.is_empty()
+    }
+
+    /// Advances playback by the wall-clock time since the last tick, applying the events that became due to `canvas`.
+    /// Returns whether the board changed.
+    pub fn tick(&mut self, canvas: &mut CanvasState) -> bool {
+        let now = Instant::now();
+        if !self.paused {
+            self.position += (now - self.last_tick).mul_f32(self.speed);
+        }
+        self.last_tick = now;
+
+        let mut changed = false;
+        while let Some(event) = self.events.get(self.next_event)
+            && event.at <= self.position
+        {
+            let objects = &mut canvas.objects;
+            // 其他事件可能移动对象下标, 应用前先画完进行中的笔画
+            if !matches!(event.kind, ReplayEventKind::StrokeDrawn { .. }) {
+                for animation in self.drawing.drain(..) {
+                    if let Some(slot) = objects.get_mut(animation.index) {
+                        *slot = CanvasObject::Stroke(animation.stroke);
+                    }
+                }
+            }
+            match &event.kind {
+                ReplayEventKind::ObjectAdded(obj) => objects.push(obj.clone()),
+                ReplayEventKind::ObjectInserted { index, object } => {
+                    objects.insert((*index).min(objects.len()), object.clone());
+                }
+                ReplayEventKind::StrokeDrawn { stroke, offsets } => {
+                    let animation = StrokeAnimation {
+                        index: objects.len(),
+                        stroke: stroke.clone(),
+                        start: event.at,
+                        offsets: offsets.clone(),
+                    };
+                    objects.push(CanvasObject::Stroke(animation.partial_stroke(1)));
+                    self.drawing.push(animation);
+                }
+                ReplayEventKind::ObjectUpdated { index, object } => {
+                    if let Some(slot) = objects.get_mut(*index) {
+                        *slot = object.clone();
+                    }
+                }
+                ReplayEventKind::ObjectRemoved { index } => {
+                    if *index < objects.len() {
+                        objects.remove(*index);
+                    }
+                }
+                ReplayEventKind::ObjectsReplaced(new_objects) => {
+                    objects.replace_all(new_objects.clone());
+                }
+                ReplayEventKind::ToolChanged(tool) => self.tool = *tool,
+                ReplayEventKind::CanvasColorChanged(color) => self.canvas_color = Some(*color),
+            }
+            self.next_event += 1;
+            changed = true;
+        }
+
+        let position = self.position;
+        self.drawing.retain(|animation| {
+            let count = animation.drawn_points(position);
+            let finished = count >= animation.stroke.points.len();
+            if let Some(CanvasObject::Stroke(current)) = canvas.objects.get_mut(animation.index)
+                && current.points.len() != count
+            {
+                *current = if finished {
+                    animation.stroke.clone()
+                } else {
+                    animation.partial_stroke(count)
+                };
+                changed = true;
+            }
+            !finished
+        });
+        changed
+    }
+
+    /// Calls `f` on every object the recording will apply
+    pub fn for_each_object_mut(&mut self, mut f: impl FnMut(&mut CanvasObject)) {
+        for event in &mut self.events {
+            match &mut event.kind {
+                ReplayEventKind::ObjectAdded(object)
+                | ReplayEventKind::ObjectInserted { object, .. }
+                | ReplayEventKind::ObjectUpdated { object, .. } => f(object),
+                ReplayEventKind::StrokeDrawn { .. } => {}
+                ReplayEventKind::ObjectsReplaced(objects) => objects.iter_mut().for_each(&mut f),
+                ReplayEventKind::ObjectRemoved { .. }
+                | ReplayEventKind::ToolChanged(_)
+                | ReplayEventKind::CanvasColorChanged(_) => {}
+            }
+        }
+    }
+
+    /// Restarts playback from the beginning
+    pub fn rewind(&mut self, canvas: &mut CanvasState) {
+        self.next_event = 0;
+        self.drawing.clear();
+        self.position = Duration::ZERO;
+        canvas.objects.clear();
+    }
+}
+
+// 图片数据太大，不录制
+fn is_recorded(object: &CanvasObject) -> bool {
+    !matches!(object, CanvasObject::Image(_))
+}
+
+// 仅比较会被录制的属性, 忽略文字尺寸缓存等派生数据
+fn same_object(a: &CanvasObject, b: &CanvasObject) -> bool {
+    match (a, b) {
+        (CanvasObject::Stroke(a), CanvasObject::Stroke(b)) => {
+            a.color == b.color
+                && a.base_width == b.base_width
+                && a.rot == b.rot
+                && a.is_highlighter == b.is_highlighter
+                && a.line_style == b.line_style
+                && a.texture == b.texture
+                && a.style == b.style
+                && a.points == b.points
+                && match (&a.width, &b.width) {
+                    (StrokeWidth::Fixed(a), StrokeWidth::Fixed(b)) => a == b,
+                    (StrokeWidth::Dynamic(a), StrokeWidth::Dynamic(b)) => a == b,
+                    _ => false,
+                }
+        }
+        (CanvasObject::Text(a), CanvasObject::Text(b)) => {
+            a.text == b.text
+                && a.pos == b.pos
+                && a.color == b.color
+                && a.font_size == b.font_size
+                && a.rot == b.rot
+        }
+        (CanvasObject::StickyNote(a), CanvasObject::StickyNote(b)) => {
+            a.text == b.text
+                && a.pos == b.pos
+                && a.size == b.size
+                && a.color == b.color
+                && a.text_color == b.text_color
+                && a.font_size == b.font_size
+        }
+        (CanvasObject::Shape(a), CanvasObject::Shape(b)) => {
+            a.shape_type == b.shape_type
+                && a.pos == b.pos
+                && a.size == b.size
+                && a.color == b.color
+                && a.rotation == b.rotation
+                && a.line_style == b.line_style
+                && a.fill == b.fill
+        }
+        _ => false,
+    }
+}
+
+fn tool_to_u8(tool: CanvasTool) -> u8 {
+    match tool {
+        CanvasTool::Select => 0,
+        CanvasTool::Brush => 1,
+        CanvasTool::ObjectEraser => 2,
+        CanvasTool::PixelEraser => 3,
+        CanvasTool::Insert => 4,
+        CanvasTool::Settings => 5,
+        CanvasTool::Passthrough => 6,
+        CanvasTool::Pan => 7,
+        CanvasTool::Highlighter => 8,
+        CanvasTool::Eyedropper => 9,
+        CanvasTool::Fill => 10,
+        CanvasTool::Measure => 11,
+    }
+}
+
+fn tool_from_u8(tool: u8) -> CanvasTool {
+    match tool {
+        0 => CanvasTool::Select,
+        2 => CanvasTool::ObjectEraser,
+        3 => CanvasTool::PixelEraser,
+        4 => CanvasTool::Insert,
+        5 => CanvasTool::Settings,
+        6 => CanvasTool::Passthrough,
+        7 => CanvasTool::Pan,
+        8 => CanvasTool::Highlighter,
+        9 => CanvasTool::Eyedropper,
+        10 => CanvasTool::Fill,
+        11 => CanvasTool::Measure,
+        _ => CanvasTool::Brush,
+    }
+}
+
+fn color_to_array(color: Color32) -> [u8; 4] {
+    [color.r(), color.g(), color.b(), color.a()]
+}
@@ -1,3 +1,4 @@
+use std::ops::RangeInclusive;
 use std::sync::Arc;
 
 use egui::{Button, Color32, Context, Pos2, Rect, Stroke, Ui};
@@ -6,19 +7,37 @@ use winit::window::Window;
 
 use crate::{
     assets,
+    i18n::{self, Lang, t, tf},
+    net::collab::CollabRole,
     state::{
-        AppState, CanvasImage, CanvasObject, CanvasObjectOps, CanvasShape, CanvasShapeType,
-        CanvasStroke, CanvasText, CanvasTool, DynamicBrushWidthMode, GraphicsApi,
-        OptimizationPolicy, PageState, PersistentState, PointerInteraction, PointerState,
-        StrokeWidth, ThemeMode, WindowMode,
+        AppState, BackgroundFit, BackgroundPattern, BrushStyle, CanvasImage, CanvasObject,
+        CanvasObjectOps, CanvasShapeType, CanvasStickyNote, CanvasStroke, CanvasText, CanvasTool,
+        DynamicBrushWidthMode, DynamicWidthCurve, EraserScope, FULL_IMAGE_CROP, FpsCap,
+        GraphicsApi, ImageAdjustments, LineStyle, MOUSE_POINTER_ID, MeasureKind, ObjectContextMenu,
+        OptimizationPolicy, PageState, PersistentState, PointerInteraction, SmoothingMode,
+        StrokeTexture, StrokeWidth, SymmetryMode, ThemeMode, TriangleKind, ViewportTransform,
+        WindowMode, touch_id_of_pointer,
     },
     utils::{
-        self,
-        stroke::{brush_stroke_add_point, brush_stroke_end, brush_stroke_start},
+        self, Alignment, brush_texture,
+        measure::{self, measure_drag_end, measure_drag_move, measure_drag_start},
+        select::{select_drag_end, select_drag_move, select_drag_start},
+        shape::{new_shape, shape_drag_end, shape_drag_move, shape_drag_start},
+        stroke::{
+            brush_stroke_add_point, brush_stroke_end, brush_stroke_start, highlighter_color,
+            new_brush_stroke,
+        },
         ui::{
             PageAction, add_new_page_state, apply_theme_mode_and_canvas_color, apply_window_mode,
-            clear_interaction_state, load_canvas_from_file, save_canvas_to_file,
-            switch_to_page_state,
+            bring_selected_to_front, clear_interaction_state, close_session_replay,
+            confirm_image_crop, current_interaction, delete_selected_object,
+            duplicate_selected_object, export_canvas_to_svg, export_selection_to_svg,
+            group_selected_objects, insert_image_file, insert_sticky_note, load_canvas_from_file,
+            lock_selected_objects, move_selected_object_to_layer, move_selected_objects,
+            open_session_replay, present_page, save_all_pages_to_file, save_canvas_to_file,
+            send_canvas_to_peer, send_selected_to_back, set_background_image, start_image_crop,
+            start_presentation, start_session_recording, stop_presentation, stop_session_recording,
+            switch_to_page_state, toggle_object_selection, ungroup_selected_objects, unlock_object,
         },
     },
 };
@@ -27,7 +46,8 @@ pub fn ui_welcome(state: &mut AppState, ctx: &Context) {
     let content_rect = ctx.content_rect();
     let center_pos = content_rect.center();
 
-    egui::Window::new("欢迎")
+    egui::Window::new(t("欢迎"))
+        .id(egui::Id::new("welcome_window"))
         .resizable(false)
         .collapsible(false)
         .movable(false)
@@ -35,20 +55,20 @@ pub fn ui_welcome(state: &mut AppState, ctx: &Context) {
         .current_pos(center_pos)
         .order(egui::Order::Foreground)
         .show(ctx, |ui| {
-            ui.heading("欢迎使用 uwu");
+            ui.heading(t("欢迎使用 uwu"));
             ui.separator();
 
-            ui.label("这是一个功能强大的数字画板应用，您可以：");
-            ui.label("• 绘制和涂鸦");
-            ui.label("• 使用各种工具进行编辑");
-            ui.label("• 插入图片、文本和形状");
-            ui.label("• 自定义画板设置");
-            ui.label("• 保存与加载画布以保存你的工作");
-            ui.label("• 导出画布为图片");
-            ui.label("• 享受超快的启动速度与超高的流畅度");
+            ui.label(t("这是一个功能强大的数字画板应用，您可以："));
+            ui.label(t("• 绘制和涂鸦"));
+            ui.label(t("• 使用各种工具进行编辑"));
+            ui.label(t("• 插入图片、文本和形状"));
+            ui.label(t("• 自定义画板设置"));
+            ui.label(t("• 保存与加载画布以保存你的工作"));
+            ui.label(t("• 导出画布为图片或 SVG 矢量图"));
+            ui.label(t("• 享受超快的启动速度与超高的流畅度"));
             ui.separator();
 
-            if ui.button("新建画布").clicked() {
+            if ui.button(t("新建画布")).clicked() {
                 let default_page = PageState::default();
                 state.pages = vec![default_page.clone()];
                 state.current_page = 0;
@@ -57,15 +77,15 @@ pub fn ui_welcome(state: &mut AppState, ctx: &Context) {
                 clear_interaction_state(state);
                 state.show_welcome_window = false;
             }
-            if ui.button("加载画布").clicked() {
-                load_canvas_from_file(state);
+            if ui.button(t("加载画布")).clicked() {
+                load_canvas_from_file(state, ctx);
             }
 
             ui.separator();
 
             ui.checkbox(
                 &mut state.persistent.show_welcome_window_on_start,
-                "启动时显示欢迎",
+                t("启动时显示欢迎"),
             );
         });
 }
@@ -155,9 +175,21 @@ fn collapsing(ui: &mut Ui, section_id: &str, label: &str, add_body: impl FnOnce(
 }
 
 pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, window: &Arc<Window>) {
-    collapsing(ui, "appearance", "外观", |ui| {
+    collapsing(ui, "appearance", t("外观"), |ui| {
+        ui.horizontal(|ui| {
+            ui.label(t("语言:"));
+            for lang in Lang::ALL {
+                if ui
+                    .selectable_value(&mut state.persistent.language, lang, lang.native_name())
+                    .changed()
+                {
+                    i18n::set_language(lang);
+                }
+            }
+        });
+
         ui.horizontal(|ui| {
-            ui.label("画布颜色:");
+            ui.label(t("画布颜色:"));
             if ui
                 .color_edit_button_srgba(&mut state.persistent.canvas_color)
                 .changed()
@@ -168,7 +200,7 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
                     state.persistent.canvas_color,
                 );
             }
-            if ui.button("重置").clicked() {
+            if ui.button(t("重置")).clicked() {
                 state.persistent.canvas_color = utils::get_default_canvas_color();
                 apply_theme_mode_and_canvas_color(
                     ctx,
@@ -179,26 +211,72 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
         });
 
         ui.horizontal(|ui| {
-            ui.label("主题模式:");
+            ui.label(t("预设:"));
+            for (label, canvas_color, brush_color) in utils::CANVAS_PRESETS {
+                if ui.button(t(label)).clicked() {
+                    state.persistent.canvas_color = canvas_color;
+                    state.brush_color = brush_color;
+                    apply_theme_mode_and_canvas_color(
+                        ctx,
+                        state.persistent.theme_mode,
+                        state.persistent.canvas_color,
+                    );
+                }
+            }
+        });
+        ink_contrast_warning(state, ui);
+
+        ui.horizontal(|ui| {
+            ui.label(t("背景图案:"));
+            for (pattern, label) in [
+                (BackgroundPattern::Blank, t("空白")),
+                (BackgroundPattern::Grid, t("方格")),
+                (BackgroundPattern::Dots, t("点阵")),
+                (BackgroundPattern::Lines, t("横线")),
+            ] {
+                ui.selectable_value(&mut state.background_pattern, pattern, label);
+            }
+        });
+        ui.add_enabled_ui(state.background_pattern != BackgroundPattern::Blank, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::Slider::new(&mut state.background_spacing, 10.0..=200.0).text(t("间距")),
+                );
+                ui.label(t("颜色:"));
+                ui.color_edit_button_srgba(&mut state.background_pattern_color);
+            });
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(t("网格:"));
+            ui.checkbox(&mut state.show_grid, t("显示并吸附"));
+            ui.add_enabled(
+                state.show_grid,
+                egui::Slider::new(&mut state.grid_size, 10.0..=200.0).text(t("间距")),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(t("主题模式:"));
             if ui
                 .selectable_value(
                     &mut state.persistent.theme_mode,
                     ThemeMode::System,
-                    "跟随系统",
+                    t("跟随系统"),
                 )
                 .clicked()
                 || ui
                     .selectable_value(
                         &mut state.persistent.theme_mode,
                         ThemeMode::Light,
-                        "浅色模式",
+                        t("浅色模式"),
                     )
                     .clicked()
                 || ui
                     .selectable_value(
                         &mut state.persistent.theme_mode,
                         ThemeMode::Dark,
-                        "深色模式",
+                        t("深色模式"),
                     )
                     .clicked()
             {
@@ -211,18 +289,18 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
         });
 
         ui.horizontal(|ui| {
-            ui.label("启动时显示欢迎:");
+            ui.label(t("启动时显示欢迎:"));
             ui.checkbox(&mut state.persistent.show_welcome_window_on_start, "");
         });
 
         #[cfg(feature = "startup_animation")]
         ui.horizontal(|ui| {
-            ui.label("显示启动动画:");
+            ui.label(t("显示启动动画:"));
             ui.checkbox(&mut state.persistent.show_startup_animation, "");
         });
 
         ui.horizontal(|ui| {
-            ui.label("窗口透明度");
+            ui.label(t("窗口透明度"));
             ui.add(egui::Slider::new(
                 &mut state.persistent.window_opacity,
                 0.0..=1.0,
@@ -230,68 +308,180 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
         });
     });
 
-    collapsing(ui, "drawing", "绘制", |ui| {
+    collapsing(ui, "drawing", t("绘制"), |ui| {
         ui.horizontal(|ui| {
-            ui.label("画布持久化:");
-            if ui.button("加载").clicked() {
-                load_canvas_from_file(state);
+            ui.label(t("画布持久化:"));
+            if ui.button(t("加载")).clicked() {
+                load_canvas_from_file(state, ctx);
             }
-            if ui.button("保存").clicked() {
+            if ui.button(t("保存")).clicked() {
                 save_canvas_to_file(&mut state.toasts, &state.canvas);
             }
+            if ui.button(t("保存所有页面")).clicked() {
+                save_all_pages_to_file(state);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(t("演示自动翻页间隔:"));
+            ui.add(
+                egui::DragValue::new(&mut state.persistent.slideshow_interval_secs)
+                    .range(0..=3600)
+                    .suffix(t(" 秒")),
+            );
+            ui.label(egui::RichText::new(t("(0 为仅点击翻页)")).italics());
         });
 
         ui.horizontal(|ui| {
-            ui.label("画布转换:");
-            if ui.button("导出为图片").clicked() {
+            ui.label(t("画布转换:"));
+            if ui.button(t("导出为图片")).clicked() {
                 if let Some(path) = rfd::FileDialog::new()
-                    .add_filter("画布文件", IMAGE_FILE_EXTS)
-                    .set_file_name("canvas.bmp")
+                    .add_filter(t("图片"), IMAGE_FILE_EXTS)
+                    .set_file_name("canvas.png")
                     .save_file()
                 {
                     state.screenshot_path = Some(path);
                 }
             }
+            if ui.button(t("导出为 SVG")).clicked() {
+                export_canvas_to_svg(state);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(t("录制:"));
+            if let Some(recorder) = &state.session_recorder {
+                ui.label(tf(
+                    "{} 秒, {} 个事件",
+                    &[&recorder.elapsed().as_secs(), &recorder.event_count()],
+                ));
+                if ui.button(t("停止并保存")).clicked() {
+                    stop_session_recording(state);
+                }
+            } else if ui.button(t("开始")).clicked() {
+                start_session_recording(state);
+            }
+            if ui.button(t("回放")).clicked() {
+                open_session_replay(state, ctx);
+            }
         });
 
         ui.horizontal(|ui| {
-            ui.label("动态画笔宽度微调:");
+            ui.label(t("动态画笔宽度微调:"));
             ui.selectable_value(
                 &mut state.dynamic_brush_width_mode,
                 DynamicBrushWidthMode::Disabled,
-                "禁用",
+                t("禁用"),
             );
             ui.selectable_value(
                 &mut state.dynamic_brush_width_mode,
                 DynamicBrushWidthMode::BrushTip,
-                "模拟笔锋",
+                t("模拟笔锋"),
             );
             ui.selectable_value(
                 &mut state.dynamic_brush_width_mode,
                 DynamicBrushWidthMode::SpeedBased,
-                "基于速度",
+                t("基于速度"),
+            );
+            ui.selectable_value(
+                &mut state.dynamic_brush_width_mode,
+                DynamicBrushWidthMode::Pressure,
+                t("基于压感"),
+            );
+        });
+
+        let curve = &mut state.persistent.dynamic_width_curve;
+        match state.dynamic_brush_width_mode {
+            DynamicBrushWidthMode::BrushTip => {
+                ui.horizontal(|ui| {
+                    ui.label(t("笔锋起始位置:"));
+                    ui.add(egui::Slider::new(&mut curve.taper_start, 0.0..=0.95));
+                });
+                ui.horizontal(|ui| {
+                    ui.label(t("笔锋末端宽度:"));
+                    ui.add(egui::Slider::new(
+                        &mut curve.taper_end_multiplier,
+                        0.05..=1.0,
+                    ));
+                });
+            }
+            DynamicBrushWidthMode::SpeedBased => {
+                ui.horizontal(|ui| {
+                    ui.label(t("最细时的速度:"));
+                    ui.add(
+                        egui::Slider::new(&mut curve.speed_normalization, 50.0..=3000.0)
+                            .suffix(t(" 像素/秒")),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label(t("宽度倍数:"));
+                    ui.add(
+                        egui::DragValue::new(&mut curve.min_multiplier)
+                            .range(0.05..=curve.max_multiplier)
+                            .speed(0.01),
+                    );
+                    ui.label(t("至"));
+                    ui.add(
+                        egui::DragValue::new(&mut curve.max_multiplier)
+                            .range(curve.min_multiplier..=5.0)
+                            .speed(0.01),
+                    );
+                });
+            }
+            DynamicBrushWidthMode::Disabled | DynamicBrushWidthMode::Pressure => {}
+        }
+        if state.dynamic_brush_width_mode != DynamicBrushWidthMode::Disabled {
+            dynamic_width_preview(
+                ui,
+                state.dynamic_brush_width_mode,
+                &state.persistent.dynamic_width_curve,
+            );
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(t("笔迹平滑:"));
+            ui.selectable_value(
+                &mut state.persistent.smoothing_mode,
+                SmoothingMode::None,
+                t("禁用"),
+            );
+            ui.selectable_value(
+                &mut state.persistent.smoothing_mode,
+                SmoothingMode::MovingAverage,
+                t("移动平均"),
+            );
+            ui.selectable_value(
+                &mut state.persistent.smoothing_mode,
+                SmoothingMode::CatmullRom,
+                t("样条曲线"),
             );
         });
 
         ui.horizontal(|ui| {
-            ui.label("笔迹平滑:");
-            ui.checkbox(&mut state.persistent.stroke_smoothing, "");
+            ui.label(t("形状识别:"));
+            ui.checkbox(&mut state.persistent.shape_recognition, "");
         });
 
         ui.horizontal(|ui| {
-            ui.label("直线停留拉直:");
-            ui.checkbox(&mut state.persistent.stroke_straightening, "启用");
+            ui.label(t("直线停留拉直:"));
+            ui.checkbox(&mut state.persistent.stroke_straightening, t("启用"));
             if state.persistent.stroke_straightening {
                 ui.add(egui::Slider::new(
                     &mut state.persistent.stroke_straightening_tolerance,
                     1.0..=50.0,
                 ));
-                ui.label("灵敏度");
+                ui.label(t("灵敏度"));
             }
         });
 
         ui.horizontal(|ui| {
-            ui.label("插值频率:");
+            ui.label(t("采样间距:"));
+            ui.add(egui::Slider::new(&mut state.min_point_distance, 0.5..=10.0))
+                .on_hover_text(t("书写和像素擦除时相邻采样点的最小距离"));
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(t("插值频率:"));
             ui.add(egui::Slider::new(
                 &mut state.persistent.interpolation_frequency,
                 0.0..=1.0,
@@ -299,12 +489,47 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
         });
 
         ui.horizontal(|ui| {
-            ui.label("低延迟模式:");
+            ui.label(t("笔画简化容差:"));
+            ui.add(egui::Slider::new(
+                &mut state.persistent.stroke_simplification_epsilon,
+                0.0..=5.0,
+            ))
+            .on_hover_text(t(
+                "完成书写时去掉偏离不超过该距离 (像素) 的冗余点, 0 为保留所有点",
+            ));
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(t("画笔宽度范围:"));
+            if range_setting(ui, &mut state.persistent.brush_width_range, 0.5..=500.0) {
+                let range = &state.persistent.brush_width_range;
+                state.brush_width = state.brush_width.clamp(*range.start(), *range.end());
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(t("橡皮擦大小范围:"));
+            if range_setting(ui, &mut state.persistent.eraser_size_range, 1.0..=1000.0) {
+                let range = &state.persistent.eraser_size_range;
+                state.eraser_size = state.eraser_size.clamp(*range.start(), *range.end());
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(t("低延迟模式:"));
             ui.checkbox(&mut state.persistent.low_latency_mode, "");
         });
 
         ui.horizontal(|ui| {
-            ui.label("编辑快捷颜色:");
+            ui.label(t("手掌拒绝:"));
+            ui.checkbox(&mut state.persistent.palm_rejection, "")
+                .on_hover_text(t(
+                    "书写时忽略笔尖附近及下方同时出现的触点; 紧挨着书写者的另一人的触点也会被忽略",
+                ));
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(t("编辑快捷颜色:"));
             if ui.button("OK").clicked() {
                 state.show_quick_color_edit_window = true;
             }
@@ -315,14 +540,15 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
             let content_rect = ctx.content_rect();
             let center_pos = content_rect.center();
 
-            egui::Window::new("编辑快捷颜色")
+            egui::Window::new(t("编辑快捷颜色"))
+                .id(egui::Id::new("quick_color_edit_window"))
                 .collapsible(false)
                 .resizable(false)
                 .movable(false)
                 .pivot(egui::Align2::CENTER_CENTER)
                 .default_pos([center_pos.x, center_pos.y])
                 .show(ctx, |ui| {
-                    ui.label("当前快捷颜色:");
+                    ui.label(t("当前快捷颜色:"));
                     ui.separator();
 
                     // 显示当前快捷颜色列表
@@ -332,7 +558,7 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
                             // 创建一个临时可变副本用于颜色编辑器
                             let mut temp_color = *color;
                             ui.color_edit_button_srgba(&mut temp_color);
-                            if ui.button("删除").clicked() {
+                            if ui.button(t("删除")).clicked() {
                                 color_index_to_remove = Some(index);
                             }
                         });
@@ -347,9 +573,9 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
 
                     // 添加新颜色
                     ui.horizontal(|ui| {
-                        ui.label("新颜色:");
+                        ui.label(t("新颜色:"));
                         ui.color_edit_button_srgba(&mut state.new_quick_color);
-                        if ui.button("添加").clicked() {
+                        if ui.button(t("添加")).clicked() {
                             state.persistent.quick_colors.push(state.new_quick_color);
                             state.new_quick_color = Color32::WHITE;
                         }
@@ -358,10 +584,10 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
                     ui.separator();
 
                     ui.horizontal(|ui| {
-                        if ui.button("完成").clicked() {
+                        if ui.button(t("完成")).clicked() {
                             state.show_quick_color_edit_window = false;
                         }
-                        if ui.button("重置").clicked() {
+                        if ui.button(t("重置")).clicked() {
                             state.show_quick_color_edit_window = false;
                             state.persistent.quick_colors = utils::get_default_quick_colors();
                         }
@@ -370,14 +596,14 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
         }
     });
 
-    collapsing(ui, "performance", "性能", |ui| {
+    collapsing(ui, "performance", t("性能"), |ui| {
         ui.horizontal(|ui| {
-            ui.label("窗口模式:");
+            ui.label(t("窗口模式:"));
             if ui
                 .selectable_value(
                     &mut state.persistent.window_mode,
                     WindowMode::Windowed,
-                    "窗口化",
+                    t("窗口化"),
                 )
                 .changed()
                 || {
@@ -385,7 +611,7 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
                         !state.fullscreen_video_modes.is_empty(),
                         Button::selectable(
                             state.persistent.window_mode == WindowMode::ExclusiveFullscreen,
-                            "独占全屏",
+                            t("独占全屏"),
                         ),
                     );
                     if response.clicked()
@@ -401,7 +627,7 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
                     .selectable_value(
                         &mut state.persistent.window_mode,
                         WindowMode::BorderlessFullscreen,
-                        "无边框全屏",
+                        t("无边框全屏"),
                     )
                     .changed()
             {
@@ -411,7 +637,7 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
 
         // 显示模式选择（仅在全屏模式下可用）
         ui.horizontal(|ui| {
-            ui.label("显示模式:");
+            ui.label(t("显示模式:"));
 
             // 显示当前选择的视频模式
             if state.persistent.window_mode == WindowMode::ExclusiveFullscreen {
@@ -450,53 +676,53 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
                         }
                     });
             } else {
-                ui.label(egui::RichText::new("(仅在独占全屏模式下可切换)").italics());
+                ui.label(egui::RichText::new(t("(仅在独占全屏模式下可切换)")).italics());
             }
         });
 
         // 垂直同步模式选择
         ui.horizontal(|ui| {
-            ui.label("垂直同步:");
+            ui.label(t("垂直同步:"));
             if ui
                 .selectable_value(
                     &mut state.persistent.present_mode,
                     PresentMode::AutoVsync,
-                    "开 (自动) | AutoVsync",
+                    t("开 (自动) | AutoVsync"),
                 )
                 .changed()
                 || ui
                     .selectable_value(
                         &mut state.persistent.present_mode,
                         PresentMode::AutoNoVsync,
-                        "关 (自动) | AutoNoVsync",
+                        t("关 (自动) | AutoNoVsync"),
                     )
                     .changed()
                 || ui
                     .selectable_value(
                         &mut state.persistent.present_mode,
                         PresentMode::Fifo,
-                        "开 | Fifo",
+                        t("开 | Fifo"),
                     )
                     .changed()
                 || ui
                     .selectable_value(
                         &mut state.persistent.present_mode,
                         PresentMode::FifoRelaxed,
-                        "自适应 | FifoRelaxed",
+                        t("自适应 | FifoRelaxed"),
                     )
                     .changed()
                 || ui
                     .selectable_value(
                         &mut state.persistent.present_mode,
                         PresentMode::Immediate,
-                        "关 | Immediate",
+                        t("关 | Immediate"),
                     )
                     .changed()
                 || ui
                     .selectable_value(
                         &mut state.persistent.present_mode,
                         PresentMode::Mailbox,
-                        "开 (快速) | Mailbox",
+                        t("开 (快速) | Mailbox"),
                     )
                     .changed()
             {
@@ -505,32 +731,46 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
         });
 
         ui.horizontal(|ui| {
-            ui.label("优化策略 [需重启以应用]:");
+            ui.label(t("优化策略 [需重启以应用]:"));
             ui.selectable_value(
                 &mut state.persistent.optimization_policy,
                 OptimizationPolicy::Performance,
-                "性能",
-            );
-            ui.selectable_value(
-                &mut state.persistent.optimization_policy,
-                OptimizationPolicy::ResourceUsage,
-                "资源用量",
+                t("性能"),
             );
+            if ui
+                .selectable_value(
+                    &mut state.persistent.optimization_policy,
+                    OptimizationPolicy::ResourceUsage,
+                    t("资源用量"),
+                )
+                .changed()
+                && state.persistent.fps_cap == FpsCap::Unlimited
+            {
+                // 节省资源时默认限制帧率
+                state.persistent.fps_cap = FpsCap::Fps30;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(t("帧率上限:"));
+            ui.selectable_value(&mut state.persistent.fps_cap, FpsCap::Unlimited, t("不限"));
+            ui.selectable_value(&mut state.persistent.fps_cap, FpsCap::Fps60, "60");
+            ui.selectable_value(&mut state.persistent.fps_cap, FpsCap::Fps30, "30");
         });
 
         let current_backend = state.active_backend.unwrap_or(Backend::Noop);
         ui.horizontal(|ui| {
-            ui.label("图形 API [需重启以应用]:");
+            ui.label(t("图形 API [需重启以应用]:"));
             ui.selectable_value(
                 &mut state.persistent.graphics_api,
                 GraphicsApi::Auto,
-                "自动",
+                t("自动"),
             );
             ui.selectable_value(
                 &mut state.persistent.graphics_api,
                 GraphicsApi::Vulkan,
                 if current_backend == Backend::Vulkan {
-                    "Vulkan (当前)"
+                    t("Vulkan (当前)")
                 } else {
                     "Vulkan"
                 },
@@ -539,7 +779,7 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
                 &mut state.persistent.graphics_api,
                 GraphicsApi::Dx12,
                 if current_backend == Backend::Dx12 {
-                    "Dx12 (当前)"
+                    t("Dx12 (当前)")
                 } else {
                     "Dx12"
                 },
@@ -548,7 +788,7 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
                 &mut state.persistent.graphics_api,
                 GraphicsApi::Metal,
                 if current_backend == Backend::Metal {
-                    "Metal (当前)"
+                    t("Metal (当前)")
                 } else {
                     "Metal"
                 },
@@ -557,7 +797,7 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
                 &mut state.persistent.graphics_api,
                 GraphicsApi::WebGpu,
                 if current_backend == Backend::BrowserWebGpu {
-                    "WebGPU (当前)"
+                    t("WebGPU (当前)")
                 } else {
                     "WebGPU"
                 },
@@ -566,7 +806,7 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
                 &mut state.persistent.graphics_api,
                 GraphicsApi::Gl,
                 if current_backend == Backend::Gl {
-                    "Gl (当前)"
+                    t("Gl (当前)")
                 } else {
                     "Gl"
                 },
@@ -574,31 +814,195 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
         });
 
         ui.horizontal(|ui| {
-            ui.label("强制每帧重绘:");
+            ui.label(t("强制每帧重绘:"));
             ui.checkbox(&mut state.persistent.force_redraw_every_frame, "");
         });
+
+        ui.horizontal(|ui| {
+            ui.label(t("多重采样抗锯齿:"));
+            for (samples, label) in [(1, t("关")), (2, "2x"), (4, "4x"), (8, "8x")] {
+                if ui
+                    .selectable_value(&mut state.persistent.msaa_samples, samples, label)
+                    .changed()
+                {
+                    state.msaa_samples_changed = true;
+                }
+            }
+        });
+
+        // 误差越小曲线越平滑，但大图形需要更多顶点
+        ui.horizontal(|ui| {
+            ui.label(t("曲线误差:"));
+            if ui
+                .add(
+                    egui::Slider::new(&mut state.persistent.curve_tolerance, 0.01..=1.0)
+                        .logarithmic(true)
+                        .suffix(" px"),
+                )
+                .changed()
+            {
+                utils::ui::apply_tessellation_quality(ui.ctx(), &state.persistent);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(t("抗锯齿宽度:"));
+            if ui
+                .add(
+                    egui::Slider::new(&mut state.persistent.feathering_size, 0.0..=3.0)
+                        .suffix(" px"),
+                )
+                .changed()
+            {
+                utils::ui::apply_tessellation_quality(ui.ctx(), &state.persistent);
+            }
+        });
+    });
+
+    collapsing(ui, "network", t("网络"), |ui| {
+        ui.horizontal(|ui| {
+            ui.label(t("接收画布:"));
+            ui.add_enabled(
+                !state.board_transfer.is_listening(),
+                egui::DragValue::new(&mut state.board_transfer.listen_port).range(1024..=65535),
+            );
+            if state.board_transfer.is_listening() {
+                if ui.button(t("停止")).clicked() {
+                    state.board_transfer.stop_listening();
+                }
+            } else if ui.button(t("开始")).clicked() {
+                state.board_transfer.start_listening();
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(t("发送画布:"));
+            ui.add(
+                egui::TextEdit::singleline(&mut state.board_transfer.peer_address)
+                    .hint_text(t("地址:端口")),
+            );
+            if ui
+                .add_enabled(
+                    !state.board_transfer.peer_address.trim().is_empty(),
+                    egui::Button::new(t("发送")),
+                )
+                .clicked()
+            {
+                send_canvas_to_peer(state);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(t("状态:"));
+            ui.label(state.board_transfer.status.to_string());
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label(t("观看链接:"));
+            ui.add_enabled(
+                !state.spectator.is_running(),
+                egui::DragValue::new(&mut state.spectator.port).range(1024..=65535),
+            );
+            if state.spectator.is_running() {
+                if ui.button(t("停止")).clicked() {
+                    state.spectator.stop();
+                }
+            } else if ui.button(t("开始")).clicked() {
+                if let Err(err) = state.spectator.start() {
+                    state.toasts.error(tf("观看服务启动失败: {}!", &[&err]));
+                }
+            }
+        });
+
+        if let Some(url) = state.spectator.url() {
+            ui.horizontal(|ui| {
+                ui.label(t("地址:"));
+                ui.hyperlink(url);
+            });
+        }
+
+        ui.separator();
+
+        match state.collab.role() {
+            Some(role) => {
+                ui.horizontal(|ui| {
+                    ui.label(t("实时协作:"));
+                    ui.label(match role {
+                        CollabRole::Host => t("主持中"),
+                        CollabRole::Guest => t("已加入"),
+                    });
+                    if ui.button(t("结束")).clicked() {
+                        state.collab.stop();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label(t("会话代码:"));
+                    match &state.collab.session_code {
+                        Some(code) => {
+                            ui.monospace(code);
+                        }
+                        None => {
+                            ui.label(t("连接中..."));
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label(t("已连接设备:"));
+                    ui.label(state.collab.peer_count.to_string());
+                });
+            }
+            None => {
+                ui.horizontal(|ui| {
+                    ui.label(t("主持协作:"));
+                    ui.add(egui::DragValue::new(&mut state.collab.port).range(1024..=65535));
+                    if ui.button(t("开始")).clicked() {
+                        state.collab.host();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label(t("加入协作:"));
+                    ui.add(
+                        egui::TextEdit::singleline(&mut state.collab.host_address)
+                            .hint_text(t("会话代码")),
+                    );
+                    if ui
+                        .add_enabled(
+                            !state.collab.host_address.trim().is_empty(),
+                            egui::Button::new(t("加入")),
+                        )
+                        .clicked()
+                    {
+                        state.collab.join();
+                    }
+                });
+            }
+        }
     });
 
-    collapsing(ui, "debug", "调试", |ui| {
+    collapsing(ui, "debug", t("调试"), |ui| {
+        // 发布版本中不提供，避免误触导致程序崩溃
+        #[cfg(debug_assertions)]
         ui.horizontal(|ui| {
-            ui.label("引发异常:");
+            ui.label(t("引发异常:"));
             if ui.button("OK").clicked() {
                 panic!("test panic")
             }
         });
 
         ui.horizontal(|ui| {
-            ui.label("显示 FPS:");
+            ui.label(t("显示 FPS:"));
             ui.checkbox(&mut state.persistent.show_fps, "");
         });
 
         ui.horizontal(|ui| {
-            ui.label("显示触控点:");
+            ui.label(t("显示触控点:"));
             ui.checkbox(&mut state.show_touch_points, "");
         });
 
         ui.horizontal(|ui| {
-            ui.label("压力测试:");
+            ui.label(t("压力测试:"));
             if ui.button("OK").clicked() {
                 // 使用固定颜色和宽度
                 const STRESS_COLOR: Color32 = Color32::from_rgb(255, 0, 0); // 红色
@@ -622,12 +1026,22 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
 
                     // 创建笔画对象
                     let stroke = CanvasStroke {
+                        id: CanvasStroke::new_id(),
                         points,
                         width: STRESS_WIDTH.into(),
                         color: STRESS_COLOR,
                         base_width: STRESS_WIDTH,
                         rot: 0.0,
-                    };
+                        is_highlighter: false,
+                        line_style: LineStyle::Solid,
+                        texture: None,
+                        style: BrushStyle::Marker,
+                        bounds: egui::Rect::NOTHING,
+                        layer: state.canvas.active_layer,
+                        group: None,
+                        locked: false,
+                    }
+                    .with_bounds();
 
                     state.canvas.objects.push(CanvasObject::Stroke(stroke));
                 }
@@ -635,19 +1049,21 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
         });
 
         ui.horizontal(|ui| {
-            ui.label("立即保存设置:");
+            ui.label(t("立即保存设置:"));
             if ui.button("OK").clicked() {
-                if let Err(err) = state.persistent.save_to_file() {
-                    state.toasts.error(format!("设置保存失败: {}!", err));
+                if let Err(err) = state.save_settings() {
+                    state.toasts.error(tf("设置保存失败: {}!", &[&err]));
                 }
             }
         });
 
         ui.horizontal(|ui| {
-            ui.label("重置设置:");
+            ui.label(t("重置设置:"));
             if ui.button("OK").clicked() {
                 clear_interaction_state(state);
                 state.persistent = PersistentState::default();
+                state.apply_drawing_defaults();
+                i18n::set_language(state.persistent.language);
                 apply_theme_mode_and_canvas_color(
                     ctx,
                     state.persistent.theme_mode,
@@ -664,54 +1080,48 @@ pub fn ui_toolbar_settings(state: &mut AppState, ctx: &Context, ui: &mut Ui, win
         });
     });
 
-    collapsing(ui, "about", "关于", |ui| {
+    collapsing(ui, "about", t("关于"), |ui| {
         ui.label("uwu (ujhhgtg's whiteboard, unleashed)");
-        ui.label(format!("版本: {}", env!("CARGO_PKG_VERSION")));
-        ui.label(format!("作者: {}", env!("CARGO_PKG_AUTHORS")));
+        ui.label(tf("版本: {}", &[&env!("CARGO_PKG_VERSION")]));
+        ui.label(tf("作者: {}", &[&env!("CARGO_PKG_AUTHORS")]));
     });
 }
 
 pub fn ui_history(state: &mut AppState, ui: &mut Ui) {
     ui.horizontal(|ui| {
-        ui.label("历史记录:");
-        if ui.button("撤销").clicked() {
-            state.selected_object_index = None; // prevent selecting phantom object
-            if state.history.undo(&mut state.canvas) {
-                state.toasts.success("成功撤销操作!");
-            } else {
-                state.toasts.error("无法撤销，没有更多历史记录!");
-            }
+        ui.label(t("历史记录:"));
+        if ui.button(t("撤销")).clicked() {
+            utils::ui::undo(state);
         }
         if ui
             .button(if !state.persistent.easter_egg_redo {
-                "重做"
+                t("重做")
             } else {
                 "Redo!"
             })
             .clicked()
         {
-            state.selected_object_index = None; // prevent selecting phantom object
-            if state.history.redo(&mut state.canvas) {
-                state.toasts.success("成功重做操作!");
-            } else {
-                state.toasts.error("无法重做，没有更多历史记录!");
-            }
+            utils::ui::redo(state);
         }
     });
 }
 
 pub fn ui_window_controls(state: &mut AppState, ui: &mut Ui, window: &Arc<Window>) {
     ui.horizontal(|ui| {
-        if ui.button("退出").clicked() {
+        if ui.button(t("退出")).clicked() {
             state.should_quit = true;
         }
 
-        if ui.button("最小化").clicked() {
+        if ui.button(t("最小化")).clicked() {
             window.set_minimized(true);
         }
 
+        if ui.button(t("演示")).clicked() {
+            start_presentation(state, window);
+        }
+
         ui.horizontal(|ui| {
-            ui.label("悬浮窗模式:");
+            ui.label(t("悬浮窗模式:"));
             if ui.checkbox(&mut state.is_overlay_mode, "").changed() {
                 state.overlay_mode_changed = true;
                 if state.is_overlay_mode {
@@ -733,13 +1143,19 @@ pub fn ui_window_controls(state: &mut AppState, ui: &mut Ui, window: &Arc<Window
         });
 
         if state.persistent.show_fps {
-            ui.label(format!("FPS: {}", state.fps_counter.current_fps));
+            ui.label(tf(
+                "FPS: {} (当前 {})",
+                &[
+                    &format!("{:.0}", state.fps_counter.average_fps),
+                    &format!("{:.0}", state.fps_counter.current_fps),
+                ],
+            ));
         }
 
         #[cfg(target_os = "windows")]
         {
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                if ui.button("屏幕键盘").clicked() {
+                if ui.button(t("屏幕键盘")).clicked() {
                     const TABTIP_REL: &str =
                         r"\Program Files\Common Files\microsoft shared\ink\TabTip.exe";
 
@@ -799,7 +1215,7 @@ pub fn ui_window_controls(state: &mut AppState, ui: &mut Ui, window: &Arc<Window
 }
 
 pub fn ui_pages_nav(state: &mut AppState, ctx: &Context) -> Option<(Rect, Rect)> {
-    if state.screenshot_path.is_some() || state.is_overlay_mode {
+    if state.is_capturing_canvas() || state.is_overlay_mode {
         return None;
     }
 
@@ -807,7 +1223,7 @@ pub fn ui_pages_nav(state: &mut AppState, ctx: &Context) -> Option<(Rect, Rect)>
     let margin = 8.0;
     let total_pages = state.pages.len();
     let current = state.current_page;
-    let enabled = !state.show_welcome_window;
+    let enabled = !state.show_welcome_window && state.presentation.is_none();
 
     if enabled {
         let mut action = PageAction::None;
@@ -916,12 +1332,107 @@ fn apply_page_action(state: &mut AppState, action: PageAction) {
     }
 }
 
+/// Page indicator and exit button shown in a corner while presenting
+pub fn ui_presentation_controls(state: &mut AppState, ctx: &Context, window: &Arc<Window>) {
+    let content_rect = ctx.content_rect();
+    egui::Window::new("##presentation_controls")
+        .resizable(false)
+        .collapsible(false)
+        .movable(false)
+        .title_bar(false)
+        .pivot(egui::Align2::RIGHT_TOP)
+        .current_pos(Pos2::new(
+            content_rect.max.x - 8.0,
+            content_rect.min.y + 8.0,
+        ))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}/{}", state.current_page + 1, state.pages.len()));
+                if ui.button(t("X 退出演示")).clicked() {
+                    stop_presentation(state, window);
+                }
+            });
+        });
+}
+
+pub fn ui_session_player(state: &mut AppState, ctx: &Context) {
+    let Some(player) = &mut state.session_player else {
+        return;
+    };
+
+    let format_time = |d: std::time::Duration| {
+        let secs = d.as_secs();
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    };
+
+    let mut should_close = false;
+    egui::Window::new(t("回放"))
+        .id("session_player".into())
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -80.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "{} / {}",
+                    format_time(player.position().min(player.duration())),
+                    format_time(player.duration())
+                ));
+                ui.label(tf("工具: {}", &[&player.tool]));
+            });
+
+            ui.horizontal(|ui| {
+                if player.paused {
+                    if ui.button(t("继续")).clicked() {
+                        player.paused = false;
+                    }
+                } else if ui.button(t("暂停")).clicked() {
+                    player.paused = true;
+                }
+                if ui.button(t("重新开始")).clicked() {
+                    let canvas = if player.page == state.current_page {
+                        Some(&mut state.canvas)
+                    } else {
+                        state
+                            .pages
+                            .get_mut(player.page)
+                            .map(|page| &mut page.canvas)
+                    };
+                    match canvas {
+                        Some(canvas) => {
+                            player.rewind(canvas);
+                            player.paused = false;
+                        }
+                        // 回放的页面已不存在
+                        None => should_close = true,
+                    }
+                }
+                if ui.button(t("关闭")).clicked() {
+                    should_close = true;
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label(t("速度:"));
+                for speed in [0.5, 1.0, 2.0, 4.0] {
+                    ui.selectable_value(&mut player.speed, speed, format!("{}x", speed));
+                }
+            });
+        });
+
+    if should_close {
+        close_session_replay(state, ctx);
+    }
+}
+
 pub fn ui_pages_manager(state: &mut AppState, ctx: &Context) {
     let content_rect = ctx.content_rect();
     let center_pos = content_rect.center();
     let total_pages = state.pages.len();
 
-    egui::Window::new(format!("页面管理 (共 {} 页)", total_pages))
+    egui::Window::new(tf("页面管理 (共 {} 页)", &[&total_pages]))
         .id("page_man".into())
         .resizable(false)
         .collapsible(false)
@@ -963,14 +1474,14 @@ pub fn ui_pages_manager(state: &mut AppState, ctx: &Context) {
 
                                         if is_current {
                                             ui.label(
-                                                egui::RichText::new(format!("第 {} 页", i + 1))
+                                                egui::RichText::new(tf("第 {} 页", &[&(i + 1)]))
                                                     .strong(),
                                             );
                                         } else {
-                                            ui.label(format!("第 {} 页", i + 1));
+                                            ui.label(tf("第 {} 页", &[&(i + 1)]));
                                         }
 
-                                        if ui.button("✓ 保存").clicked() {
+                                        if ui.button(t("✓ 保存")).clicked() {
                                             save_canvas_to_file(
                                                 &mut state.toasts,
                                                 &state.pages[i].canvas,
@@ -980,7 +1491,7 @@ pub fn ui_pages_manager(state: &mut AppState, ctx: &Context) {
                                         if ui
                                             .add_enabled(
                                                 total_pages > 1,
-                                                egui::Button::new("X 删除"),
+                                                egui::Button::new(t("X 删除")),
                                             )
                                             .clicked()
                                         {
@@ -991,9 +1502,9 @@ pub fn ui_pages_manager(state: &mut AppState, ctx: &Context) {
                                             .add_enabled(
                                                 !is_current,
                                                 egui::Button::new(if !is_current {
-                                                    "→ 跳转"
+                                                    t("→ 跳转")
                                                 } else {
-                                                    "⊙ 当前"
+                                                    t("⊙ 当前")
                                                 }),
                                             )
                                             .clicked()
@@ -1058,15 +1569,22 @@ pub fn ui_pages_manager(state: &mut AppState, ctx: &Context) {
 
                         state.pages.insert(insert_at, page);
 
-                        state.current_page = if old_cp == from_idx {
-                            insert_at
-                        } else if old_cp > from_idx && old_cp <= insert_at {
-                            old_cp - 1
-                        } else if old_cp < from_idx && old_cp >= insert_at {
-                            old_cp + 1
-                        } else {
-                            old_cp
+                        let moved_index = |page: usize| {
+                            if page == from_idx {
+                                insert_at
+                            } else if page > from_idx && page <= insert_at {
+                                page - 1
+                            } else if page < from_idx && page >= insert_at {
+                                page + 1
+                            } else {
+                                page
+                            }
                         };
+                        state.current_page = moved_index(old_cp);
+                        // 回放跟随其页面移动
+                        if let Some(player) = &mut state.session_player {
+                            player.page = moved_index(player.page);
+                        }
 
                         let cur = state.current_page;
                         std::mem::swap(&mut state.canvas, &mut state.pages[cur].canvas);
@@ -1077,13 +1595,16 @@ pub fn ui_pages_manager(state: &mut AppState, ctx: &Context) {
             ui.separator();
 
             ui.horizontal(|ui| {
-                if ui.button("+ 新页").clicked() {
+                if ui.button(t("+ 新页")).clicked() {
                     add_new_page_state(state);
                 }
-                if ui.button("O 加载").clicked() {
-                    load_canvas_from_file(state);
+                if ui.button(t("O 加载")).clicked() {
+                    load_canvas_from_file(state, ctx);
+                }
+                if ui.button(t("✓ 全部保存")).clicked() {
+                    save_all_pages_to_file(state);
                 }
-                if ui.button("X 关闭").clicked() {
+                if ui.button(t("X 关闭")).clicked() {
                     state.show_page_management_window = false;
                 }
             });
@@ -1108,26 +1629,402 @@ pub fn ui_pages_manager(state: &mut AppState, ctx: &Context) {
                 std::mem::swap(&mut state.canvas, &mut state.pages[cur].canvas);
                 std::mem::swap(&mut state.history, &mut state.pages[cur].history);
                 clear_interaction_state(state);
+
+                // 回放所在的页面被删除时结束回放，否则跟随页码前移
+                if let Some(page) = state.session_player.as_ref().map(|player| player.page) {
+                    if pages_to_remove.contains(&page) {
+                        close_session_replay(state, ctx);
+                    } else if let Some(player) = &mut state.session_player {
+                        player.page -= pages_to_remove.iter().filter(|&&i| i < page).count();
+                    }
+                }
             }
         });
 }
 
-pub fn ui_toolbar(state: &mut AppState, ctx: &Context, window: &Arc<Window>) -> Option<Rect> {
-    if state.screenshot_path.is_some() {
-        return None;
-    }
-
-    let content_rect = ctx.content_rect();
-    Some(
-        egui::Window::new("工具栏")
-            .resizable(false)
-            .pivot(egui::Align2::CENTER_BOTTOM)
-            .default_pos([content_rect.center().x, content_rect.max.y - 20.0])
-            .enabled(!state.show_welcome_window)
-            .show(ctx, |ui| {
+pub fn ui_layers_manager(state: &mut AppState, ctx: &Context) {
+    egui::Window::new(t("图层"))
+        .id("layer_man".into())
+        .resizable(false)
+        .collapsible(false)
+        .default_pos(ctx.content_rect().right_top() + egui::vec2(-260.0, 60.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            let mut move_up = None;
+            let mut move_down = None;
+            let mut remove = None;
+            let layer_count = state.canvas.layers.len();
+
+            // 顶层显示在最上面
+            for position in (0..layer_count).rev() {
+                let layer = &mut state.canvas.layers[position];
+                let id = layer.id;
+                ui.horizontal(|ui| {
+                    if ui
+                        .selectable_label(state.canvas.active_layer == id, "●")
+                        .on_hover_text(t("设为当前图层"))
+                        .clicked()
+                    {
+                        state.canvas.active_layer = id;
+                    }
+                    ui.checkbox(&mut layer.visible, "");
+                    ui.add(egui::TextEdit::singleline(&mut layer.name).desired_width(100.0));
+                    if ui
+                        .add_enabled(position + 1 < layer_count, Button::new("↑"))
+                        .clicked()
+                    {
+                        move_up = Some(id);
+                    }
+                    if ui.add_enabled(position > 0, Button::new("↓")).clicked() {
+                        move_down = Some(id);
+                    }
+                    if ui.add_enabled(layer_count > 1, Button::new("X")).clicked() {
+                        remove = Some(id);
+                    }
+                });
+            }
+
+            if let Some(id) = move_up {
+                state.canvas.move_layer(id, true);
+            }
+            if let Some(id) = move_down {
+                state.canvas.move_layer(id, false);
+            }
+            if let Some(id) = remove
+                && !state.canvas.remove_layer(id)
+            {
+                state.toasts.warning(t("只能删除空图层!"));
+            }
+
+            // 选中的对象所在图层被隐藏后取消选中
+            if let Some(index) = state.selected_object_index
+                && !state.canvas.is_object_visible(index)
+            {
+                state.selected_object_index = None;
+            }
+            state
+                .selected_objects
+                .retain(|&index| state.canvas.is_object_visible(index));
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button(t("+ 新图层")).clicked() {
+                    state.canvas.add_layer();
+                }
+                if ui
+                    .add_enabled(
+                        state.selected_object_index.is_some(),
+                        Button::new(t("移入选中对象")),
+                    )
+                    .on_hover_text(t("将选中的对象移到当前图层"))
+                    .clicked()
+                {
+                    move_selected_object_to_layer(state, state.canvas.active_layer);
+                }
+                if ui.button(t("X 关闭")).clicked() {
+                    state.show_layers_window = false;
+                }
+            });
+        });
+}
+
+// 裁剪选中的图片：开始裁剪后在图片上拖动选择要保留的区域
+fn ui_image_crop(state: &mut AppState, ui: &mut Ui) {
+    ui.horizontal(|ui| {
+        ui.label(t("裁剪:"));
+        let Some((_, crop)) = state
+            .cropping_image
+            .filter(|(index, _)| state.selected_object_index == Some(*index))
+        else {
+            if ui.button(t("开始裁剪")).clicked() {
+                start_image_crop(state);
+            }
+            return;
+        };
+        if ui.button(t("确认")).clicked() {
+            confirm_image_crop(state);
+        }
+        if ui.button(t("取消")).clicked() {
+            state.cropping_image = None;
+        }
+        if ui
+            .add_enabled(crop != FULL_IMAGE_CROP, Button::new(t("显示全部")))
+            .clicked()
+            && let Some((_, crop)) = &mut state.cropping_image
+        {
+            *crop = FULL_IMAGE_CROP;
+        }
+        ui.label(egui::RichText::new(t("(在图片上拖动以选择保留的区域)")).italics());
+    });
+}
+
+// 编辑选中便签的文字与颜色
+fn ui_sticky_note_editor(
+    state: &mut AppState,
+    ui: &mut Ui,
+    index: usize,
+    old_note: CanvasStickyNote,
+) {
+    let mut new_note = old_note.clone();
+    let style_changed = ui
+        .horizontal(|ui| {
+            ui.label(t("便签颜色:"));
+            let color_changed = ui.color_edit_button_srgba(&mut new_note.color).changed();
+            ui.label(t("文字颜色:"));
+            let text_color_changed = ui
+                .color_edit_button_srgba(&mut new_note.text_color)
+                .changed();
+            ui.label(t("字号:"));
+            let size_changed = ui
+                .add(egui::Slider::new(&mut new_note.font_size, 8.0..=96.0))
+                .changed();
+            color_changed || text_color_changed || size_changed
+        })
+        .inner;
+    let text_changed = ui
+        .add(
+            egui::TextEdit::multiline(&mut new_note.text)
+                .hint_text(t("便签内容"))
+                .desired_rows(3),
+        )
+        .changed();
+    if !(style_changed || text_changed) {
+        return;
+    }
+
+    state.history.save_replace_object_coalesced(
+        index,
+        CanvasObject::StickyNote(old_note),
+        CanvasObject::StickyNote(new_note.clone()),
+        current_interaction(ui.ctx()),
+    );
+    state.canvas.objects[index] = CanvasObject::StickyNote(new_note);
+}
+
+// 翻转或旋转选中图片的像素
+fn ui_image_orientation(state: &mut AppState, ui: &mut Ui, index: usize, old_image: CanvasImage) {
+    let mut new_image = old_image.clone();
+    let changed = ui
+        .horizontal(|ui| {
+            ui.label(t("方向:"));
+            if ui.button(t("水平翻转")).clicked() {
+                new_image.flip(true, ui.ctx());
+                return true;
+            }
+            if ui.button(t("垂直翻转")).clicked() {
+                new_image.flip(false, ui.ctx());
+                return true;
+            }
+            if ui.button(t("旋转90°")).clicked() {
+                new_image.rotate_90(ui.ctx());
+                return true;
+            }
+            false
+        })
+        .inner;
+    if !changed {
+        return;
+    }
+
+    state.history.save_replace_object(
+        index,
+        CanvasObject::Image(old_image),
+        CanvasObject::Image(new_image.clone()),
+        current_interaction(ui.ctx()),
+    );
+    state.canvas.objects[index] = CanvasObject::Image(new_image);
+}
+
+// 调整选中图片的颜色，原始像素保留，可随时恢复
+fn ui_image_adjustments(state: &mut AppState, ui: &mut Ui, index: usize, old_image: CanvasImage) {
+    let mut adjustments = old_image.adjustments;
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut adjustments.grayscale, t("灰度"));
+        ui.label(t("亮度:"));
+        ui.add(egui::Slider::new(&mut adjustments.brightness, -1.0..=1.0).show_value(false));
+        ui.label(t("对比度:"));
+        ui.add(egui::Slider::new(&mut adjustments.contrast, -1.0..=1.0).show_value(false));
+        if ui
+            .add_enabled(!adjustments.is_none(), Button::new(t("重置")))
+            .clicked()
+        {
+            adjustments = ImageAdjustments::NONE;
+        }
+    });
+    if adjustments == old_image.adjustments {
+        return;
+    }
+
+    let mut new_image = old_image.clone();
+    new_image.set_adjustments(adjustments, ui.ctx());
+    state.history.save_replace_object_coalesced(
+        index,
+        CanvasObject::Image(old_image),
+        CanvasObject::Image(new_image.clone()),
+    );
+    state.canvas.objects[index] = CanvasObject::Image(new_image);
+}
+
+// 以数值显示并编辑选中对象的位置、大小与旋转角度
+fn ui_object_inspector(state: &mut AppState, ui: &mut Ui, index: usize, old_object: CanvasObject) {
+    let mut pos = old_object.position();
+    let mut size = old_object.size();
+    let mut rotation = old_object.rotation().to_degrees();
+    let (pos_changed, size_changed, rotation_changed) = ui
+        .horizontal(|ui| {
+            ui.label("X:");
+            let x = ui.add(egui::DragValue::new(&mut pos.x).speed(1.0));
+            ui.label("Y:");
+            let y = ui.add(egui::DragValue::new(&mut pos.y).speed(1.0));
+            ui.label(t("宽:"));
+            let width = ui.add(
+                egui::DragValue::new(&mut size.x)
+                    .speed(1.0)
+                    .range(1.0..=f32::MAX),
+            );
+            ui.label(t("高:"));
+            let height = ui.add(
+                egui::DragValue::new(&mut size.y)
+                    .speed(1.0)
+                    .range(1.0..=f32::MAX),
+            );
+            ui.label(t("旋转:"));
+            let rotation = ui.add(egui::DragValue::new(&mut rotation).speed(1.0).suffix("°"));
+            (
+                x.changed() || y.changed(),
+                width.changed() || height.changed(),
+                rotation.changed(),
+            )
+        })
+        .inner;
+    if !(pos_changed || size_changed || rotation_changed) {
+        return;
+    }
+
+    let mut new_object = old_object.clone();
+    if size_changed {
+        new_object.set_size(size);
+    }
+    if rotation_changed {
+        new_object.set_rotation(rotation.to_radians());
+    }
+    if pos_changed {
+        new_object.set_position(pos);
+    }
+    state.history.save_replace_object_coalesced(
+        index,
+        old_object,
+        new_object.clone(),
+        current_interaction(ui.ctx()),
+    );
+    state.canvas.objects[index] = new_object;
+}
+
+pub fn ui_text_editor(state: &mut AppState, ctx: &Context) {
+    if !state.show_insert_text_window {
+        return;
+    }
+
+    // 计算屏幕中心位置
+    let content_rect = ctx.content_rect();
+    let center_pos = content_rect.center();
+
+    let title = if state.editing_text_index.is_some() {
+        t("编辑文本")
+    } else {
+        t("插入文本")
+    };
+    egui::Window::new(title)
+        .id(egui::Id::new("text_editor_window"))
+        .collapsible(false)
+        .resizable(false)
+        .pivot(egui::Align2::CENTER_CENTER)
+        .default_pos([center_pos.x, center_pos.y])
+        .show(ctx, |ui| {
+            ui.label(t("文本内容:"));
+            ui.text_edit_multiline(&mut state.new_text_content);
+
+            // 编辑已有文本时通过选择工具面板调整样式
+            if state.editing_text_index.is_none() {
+                ui.horizontal(|ui| {
+                    ui.label(t("颜色:"));
+                    ui.color_edit_button_srgba(&mut state.new_text_color);
+                    ui.label(t("字号:"));
+                    ui.add(egui::Slider::new(&mut state.new_text_font_size, 8.0..=96.0));
+                });
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button(t("确认")).clicked() {
+                    let editing = state.editing_text_index.and_then(|index| {
+                        match state.canvas.objects.get(index) {
+                            Some(CanvasObject::Text(text)) => Some((index, text.clone())),
+                            _ => None,
+                        }
+                    });
+
+                    if let Some((index, old_text)) = editing {
+                        let mut new_text = old_text.clone();
+                        new_text.text = state.new_text_content.clone();
+                        new_text.cached_size = Some(new_text.layout(ui.painter()).size());
+                        state.history.save_replace_object(
+                            index,
+                            CanvasObject::Text(old_text),
+                            CanvasObject::Text(new_text.clone()),
+                        );
+                        state.canvas.objects[index] = CanvasObject::Text(new_text);
+                    } else {
+                        let mut new_text = CanvasText {
+                            text: state.new_text_content.clone(),
+                            pos: state.new_object_pos(),
+                            color: state.new_text_color,
+                            font_size: state.new_text_font_size,
+                            rot: 0.0,
+                            cached_size: None,
+                            layer: state.canvas.active_layer,
+                            group: None,
+                            locked: false,
+                        };
+                        new_text.cached_size = Some(new_text.layout(ui.painter()).size());
+                        let index = state.canvas.objects.len();
+                        state
+                            .history
+                            .save_add_object(index, CanvasObject::Text(new_text.clone()));
+                        state.canvas.objects.push(CanvasObject::Text(new_text));
+                        state.current_tool = CanvasTool::Select;
+                    }
+                    state.show_insert_text_window = false;
+                    state.editing_text_index = None;
+                    state.new_text_content.clear();
+                }
+
+                if ui.button(t("取消")).clicked() {
+                    state.show_insert_text_window = false;
+                    state.editing_text_index = None;
+                    state.new_text_content.clear();
+                }
+            });
+        });
+}
+
+pub fn ui_toolbar(state: &mut AppState, ctx: &Context, window: &Arc<Window>) -> Option<Rect> {
+    if state.is_capturing_canvas() || state.presentation.is_some() {
+        return None;
+    }
+
+    let content_rect = ctx.content_rect();
+    Some(
+        egui::Window::new(t("工具栏"))
+            .id(egui::Id::new("toolbar_window")) // 标题随界面语言变化，使用固定 id 以保留窗口位置
+            .resizable(false)
+            .pivot(egui::Align2::CENTER_BOTTOM)
+            .default_pos([content_rect.center().x, content_rect.max.y - 20.0])
+            .enabled(!state.show_welcome_window)
+            .show(ctx, |ui| {
                 // 工具选择
                 ui.horizontal(|ui| {
-                    ui.label("工具:");
+                    ui.label(t("工具:"));
                     // TODO: egui doesn't support rendering fonts with colors
                     let old_tool = state.current_tool;
                     if (state.is_overlay_mode
@@ -1135,34 +2032,73 @@ pub fn ui_toolbar(state: &mut AppState, ctx: &Context, window: &Arc<Window>) ->
                             .selectable_value(
                                 &mut state.current_tool,
                                 CanvasTool::Passthrough,
-                                "穿透",
+                                t("穿透"),
                             )
                             .changed())
                         || ui
-                            .selectable_value(&mut state.current_tool, CanvasTool::Select, "选择")
+                            .selectable_value(
+                                &mut state.current_tool,
+                                CanvasTool::Select,
+                                t("选择"),
+                            )
+                            .changed()
+                        || ui
+                            .selectable_value(&mut state.current_tool, CanvasTool::Pan, t("平移"))
+                            .changed()
+                        || ui
+                            .selectable_value(&mut state.current_tool, CanvasTool::Brush, t("画笔"))
+                            .changed()
+                        || ui
+                            .selectable_value(
+                                &mut state.current_tool,
+                                CanvasTool::Highlighter,
+                                t("荧光笔"),
+                            )
+                            .changed()
+                        || ui
+                            .selectable_value(
+                                &mut state.current_tool,
+                                CanvasTool::Eyedropper,
+                                t("取色"),
+                            )
                             .changed()
                         || ui
-                            .selectable_value(&mut state.current_tool, CanvasTool::Brush, "画笔")
+                            .selectable_value(&mut state.current_tool, CanvasTool::Fill, t("填充"))
                             .changed()
                         || ui
                             .selectable_value(
                                 &mut state.current_tool,
                                 CanvasTool::ObjectEraser,
-                                "对象擦",
+                                t("对象擦"),
                             )
                             .changed()
                         || ui
                             .selectable_value(
                                 &mut state.current_tool,
                                 CanvasTool::PixelEraser,
-                                "像素擦",
+                                t("像素擦"),
                             )
                             .changed()
                         || ui
-                            .selectable_value(&mut state.current_tool, CanvasTool::Insert, "插入")
+                            .selectable_value(
+                                &mut state.current_tool,
+                                CanvasTool::Insert,
+                                t("插入"),
+                            )
                             .changed()
                         || ui
-                            .selectable_value(&mut state.current_tool, CanvasTool::Settings, "设置")
+                            .selectable_value(
+                                &mut state.current_tool,
+                                CanvasTool::Measure,
+                                t("测量"),
+                            )
+                            .changed()
+                        || ui
+                            .selectable_value(
+                                &mut state.current_tool,
+                                CanvasTool::Settings,
+                                t("设置"),
+                            )
                             .changed()
                     {
                         if state.current_tool != old_tool {
@@ -1174,71 +2110,40 @@ pub fn ui_toolbar(state: &mut AppState, ctx: &Context, window: &Arc<Window>) ->
                 ui.separator();
 
                 if state.current_tool == CanvasTool::Passthrough {
-                    ui.label(egui::RichText::new("(当前处于穿透模式, 输入将穿透画布)").italics());
+                    ui.label(
+                        egui::RichText::new(t("(当前处于穿透模式, 输入将穿透画布)")).italics(),
+                    );
                 } else if state.current_tool == CanvasTool::Select {
                     if let Some(selected_idx) = state.selected_object_index {
                         ui.horizontal(|ui| {
-                            ui.label("对象操作:");
-                            if ui.button("删除").clicked() {
-                                // Save state to history before modification
-                                let removed_object = state.canvas.objects.remove(selected_idx);
-                                state
-                                    .history
-                                    .save_remove_object(selected_idx, removed_object);
-                                state.selected_object_index = None;
-                                state.toasts.success("对象已删除!");
+                            ui.label(t("对象操作:"));
+                            if ui.button(t("删除")).clicked() {
+                                delete_selected_object(state);
                             }
-                            if ui.button("复制").clicked() {
-                                // FIXME: CanvasImage duplication not implemented
-                                if !matches!(
-                                    state.canvas.objects[selected_idx],
-                                    CanvasObject::Image(_)
-                                ) {
-                                    let mut clone = state.canvas.objects[selected_idx].clone();
-                                    CanvasObject::move_object(&mut clone, egui::vec2(20.0, 20.0));
-                                    let index = state.canvas.objects.len();
-                                    state.history.save_add_object(index, clone.clone());
-                                    state.canvas.objects.push(clone);
-                                    state.selected_object_index = Some(index);
-                                    state.toasts.success("对象已复制!");
-                                }
+                            if ui.button(t("复制")).clicked() {
+                                duplicate_selected_object(state);
                             }
-                            if ui.button("置顶").clicked() {
-                                if selected_idx < state.canvas.objects.len() - 1 {
-                                    // Save state to history before modification
-                                    let object = state.canvas.objects.remove(selected_idx);
-                                    // Actually move the object to the top (end of the array)
-                                    state.canvas.objects.push(object);
-                                    state.history.save_add_object(
-                                        state.canvas.objects.len() - 1,
-                                        state.canvas.objects.last().unwrap().clone(),
-                                    );
-                                    state.selected_object_index =
-                                        Some(state.canvas.objects.len() - 1);
-                                    state.toasts.success("对象已移至顶部!");
-                                }
+                            if ui.button(t("导出所选")).clicked() {
+                                export_selection_to_svg(state);
                             }
-                            if ui.button("置底").clicked() {
-                                if selected_idx > 0 {
-                                    // Save state to history before modification
-                                    let object = state.canvas.objects.remove(selected_idx);
-                                    // Actually move the object to the bottom (beginning of the array)
-                                    state.canvas.objects.insert(0, object);
-                                    state.history.save_add_object(
-                                        0,
-                                        state.canvas.objects.first().unwrap().clone(),
-                                    );
-                                    state.selected_object_index = Some(0);
-                                    state.toasts.success("对象已移至底部!");
-                                }
+                            if ui.button(t("置顶")).clicked() {
+                                bring_selected_to_front(state);
+                            }
+                            if ui.button(t("置底")).clicked() {
+                                send_selected_to_back(state);
+                            }
+                            if ui.button(t("锁定")).clicked() {
+                                lock_selected_objects(state);
                             }
 
                             if let Some(CanvasObject::Text(text)) =
                                 state.canvas.objects.get(selected_idx).cloned()
                             {
-                                if ui.button("栅格化").clicked() {
-                                    let strokes =
-                                        utils::rasterize_text(&text, assets::font_bytes());
+                                // 找不到 CJK 字体时无法栅格化
+                                if let Some(font) = assets::font_bytes()
+                                    && ui.button(t("栅格化")).clicked()
+                                {
+                                    let strokes = utils::rasterize_text(&text, font);
 
                                     state.canvas.objects.remove(selected_idx);
 
@@ -1257,17 +2162,213 @@ pub fn ui_toolbar(state: &mut AppState, ctx: &Context, window: &Arc<Window>) ->
                                         .save_remove_object(selected_idx, CanvasObject::Text(text));
 
                                     state.selected_object_index = None;
-                                    state.toasts.success("已转换为笔画!");
+                                    state.toasts.success(t("已转换为笔画!"));
                                 }
                             }
                         });
+
+                        if let Some(CanvasObject::Image(image)) =
+                            state.canvas.objects.get(selected_idx)
+                        {
+                            let old_image = image.clone();
+                            ui_image_crop(state, ui);
+                            if state.cropping_image.is_none() {
+                                ui_image_orientation(state, ui, selected_idx, old_image.clone());
+                            }
+                            ui_image_adjustments(state, ui, selected_idx, old_image);
+                        }
+
+                        if let Some(CanvasObject::StickyNote(note)) =
+                            state.canvas.objects.get(selected_idx)
+                        {
+                            ui_sticky_note_editor(state, ui, selected_idx, note.clone());
+                        }
+
+                        if let Some(CanvasObject::Text(text)) =
+                            state.canvas.objects.get(selected_idx)
+                        {
+                            let old_text = text.clone();
+                            let mut new_text = old_text.clone();
+                            let changed = ui
+                                .horizontal(|ui| {
+                                    ui.label(t("文本样式:"));
+                                    let color_changed =
+                                        ui.color_edit_button_srgba(&mut new_text.color).changed();
+                                    ui.label(t("字号:"));
+                                    let size_changed = ui
+                                        .add(egui::Slider::new(&mut new_text.font_size, 8.0..=96.0))
+                                        .changed();
+                                    color_changed || size_changed
+                                })
+                                .inner;
+                            if changed {
+                                new_text.cached_size = Some(new_text.layout(ui.painter()).size());
+                                state.history.save_replace_object_coalesced(
+                                    selected_idx,
+                                    CanvasObject::Text(old_text),
+                                    CanvasObject::Text(new_text.clone()),
+                                    current_interaction(ui.ctx()),
+                                );
+                                state.canvas.objects[selected_idx] = CanvasObject::Text(new_text);
+                            }
+                        }
+
+                        if let Some(object) = state.canvas.objects.get(selected_idx) {
+                            let old_object = object.clone();
+                            let mut opacity = old_object.opacity();
+                            let changed = ui
+                                .horizontal(|ui| {
+                                    ui.label(t("不透明度:"));
+                                    ui.add(egui::Slider::new(&mut opacity, 0..=255)).changed()
+                                })
+                                .inner;
+                            if changed {
+                                let mut new_object = old_object.clone();
+                                new_object.set_opacity(opacity);
+                                state.history.save_replace_object_coalesced(
+                                    selected_idx,
+                                    old_object,
+                                    new_object.clone(),
+                                    current_interaction(ui.ctx()),
+                                );
+                                state.canvas.objects[selected_idx] = new_object;
+                            }
+                        }
+
+                        if let Some(object) = state.canvas.objects.get(selected_idx) {
+                            ui_object_inspector(state, ui, selected_idx, object.clone());
+                        }
+                    } else if !state.selected_objects.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label(tf("已选中 {} 个对象", &[&state.selected_objects.len()]));
+                            // 选中的对象已同属一个组合时无需再组合
+                            let first_group = state
+                                .selected_objects
+                                .first()
+                                .and_then(|&index| state.canvas.objects.get(index))
+                                .and_then(CanvasObject::group);
+                            let is_one_group = first_group.is_some()
+                                && state.selected_objects.iter().all(|&index| {
+                                    state
+                                        .canvas
+                                        .objects
+                                        .get(index)
+                                        .and_then(CanvasObject::group)
+                                        == first_group
+                                });
+                            let has_group = state.selected_objects.iter().any(|&index| {
+                                state
+                                    .canvas
+                                    .objects
+                                    .get(index)
+                                    .is_some_and(|object| object.group().is_some())
+                            });
+                            if ui
+                                .add_enabled(!is_one_group, Button::new(t("组合")))
+                                .clicked()
+                            {
+                                group_selected_objects(state);
+                            }
+                            if ui
+                                .add_enabled(has_group, Button::new(t("取消组合")))
+                                .clicked()
+                            {
+                                ungroup_selected_objects(state);
+                            }
+                        });
+                        let mut alignment = None;
+                        ui.horizontal(|ui| {
+                            ui.label(t("对齐:"));
+                            for (label, value) in [
+                                (t("左对齐"), Alignment::Left),
+                                (t("水平居中"), Alignment::HorizontalCenter),
+                                (t("右对齐"), Alignment::Right),
+                                (t("顶对齐"), Alignment::Top),
+                                (t("垂直居中"), Alignment::VerticalCenter),
+                                (t("底对齐"), Alignment::Bottom),
+                            ] {
+                                if ui.button(label).clicked() {
+                                    alignment = Some(value);
+                                }
+                            }
+                        });
+                        if let Some(alignment) = alignment {
+                            let moves = utils::align_objects(
+                                &state.canvas.objects,
+                                &state.selected_objects,
+                                alignment,
+                            );
+                            move_selected_objects(state, moves);
+                        }
+
+                        let mut distribute = None;
+                        ui.horizontal(|ui| {
+                            ui.label(t("分布:"));
+                            let enabled = state.selected_objects.len() >= 3;
+                            if ui
+                                .add_enabled(enabled, Button::new(t("水平均匀分布")))
+                                .clicked()
+                            {
+                                distribute = Some(true);
+                            }
+                            if ui
+                                .add_enabled(enabled, Button::new(t("垂直均匀分布")))
+                                .clicked()
+                            {
+                                distribute = Some(false);
+                            }
+                            if ui.button(t("取消选择")).clicked() {
+                                state.clear_selection();
+                            }
+                            if ui.button(t("删除")).clicked() {
+                                delete_selected_object(state);
+                            }
+                            if ui.button(t("导出所选")).clicked() {
+                                export_selection_to_svg(state);
+                            }
+                        });
+                        if let Some(horizontal) = distribute {
+                            let moves = utils::distribute_objects(
+                                &state.canvas.objects,
+                                &state.selected_objects,
+                                horizontal,
+                            );
+                            move_selected_objects(state, moves);
+                        }
                     } else {
-                        ui.label(egui::RichText::new("(未选中对象)").italics());
+                        ui.label(
+                            egui::RichText::new(t("(未选中对象, 按住 Shift 点击可多选)")).italics(),
+                        );
                     }
+                } else if state.current_tool == CanvasTool::Pan {
+                    ui.horizontal(|ui| {
+                        ui.label(tf(
+                            "缩放: {}%",
+                            &[&format!("{:.0}", state.viewport.scale * 100.0)],
+                        ));
+                        if ui.button(t("重置视图")).clicked() {
+                            state.viewport = ViewportTransform::default();
+                        }
+                    });
+                    ui.label(
+                        egui::RichText::new(t("(拖动平移画布, 滚轮或双指捏合缩放)")).italics(),
+                    );
+                } else if state.current_tool == CanvasTool::Eyedropper {
+                    ui.horizontal(|ui| {
+                        ui.label(t("画笔颜色:"));
+                        ui.color_edit_button_srgba(&mut state.brush_color);
+                    });
+                    ui.label(egui::RichText::new(t("(点击画布吸取颜色)")).italics());
+                } else if state.current_tool == CanvasTool::Fill {
+                    ui.horizontal(|ui| {
+                        ui.label(t("填充颜色:"));
+                        ui.color_edit_button_srgba(&mut state.fill_color);
+                    });
+                    ui.label(egui::RichText::new(t("(点击封闭区域进行填充)")).italics());
                 } else if state.current_tool == CanvasTool::Brush {
                     ui.horizontal(|ui| {
-                        ui.label("颜色:");
-                        let old_color = state.brush_color;
+                        ui.label(t("颜色:"));
+                        let old_color = state.ink_color();
                         if ui.color_edit_button_srgba(&mut state.brush_color).changed() {
                             // Drain all active drawing pointers when color changes
                             let drawing_ids: Vec<u64> = state
@@ -1288,39 +2389,39 @@ pub fn ui_toolbar(state: &mut AppState, ctx: &Context, window: &Arc<Window>) ->
                                                 continue;
                                             }
                                         }
-                                        state.canvas.objects.push(CanvasObject::Stroke(
-                                            CanvasStroke {
-                                                points: active_stroke.points,
-                                                width: active_stroke.width,
-                                                color: old_color,
-                                                base_width: state.brush_width,
-                                                rot: 0.0,
-                                            },
-                                        ));
+                                        let stroke = new_brush_stroke(
+                                            state,
+                                            active_stroke.points,
+                                            active_stroke.width,
+                                            old_color,
+                                        );
+                                        state.collab.broadcast_stroke(state.current_page, &stroke);
+                                        state.canvas.objects.push(CanvasObject::Stroke(stroke));
                                     }
                                 }
                             }
                         }
                     });
+                    ink_contrast_warning(state, ui);
 
                     // 颜色快捷按钮
                     ui.horizontal(|ui| {
-                        ui.label("快捷颜色:");
+                        ui.label(t("快捷颜色:"));
                         for color in &state.persistent.quick_colors {
                             let color_name = if color.r() == 0 && color.g() == 0 && color.b() == 0 {
-                                "黑"
+                                t("黑")
                             } else if color.r() == 255 && color.g() == 255 && color.b() == 255 {
-                                "白"
+                                t("白")
                             } else if color.r() == 0 && color.g() == 100 && color.b() == 255 {
-                                "蓝"
+                                t("蓝")
                             } else if color.r() == 220 && color.g() == 20 && color.b() == 60 {
-                                "红"
+                                t("红")
                             } else if color.r() == 34 && color.g() == 139 && color.b() == 34 {
-                                "绿"
+                                t("绿")
                             } else if color.r() == 255 && color.g() == 140 && color.b() == 0 {
-                                "橙"
+                                t("橙")
                             } else {
-                                "自定义"
+                                t("自定义")
                             };
                             if ui
                                 .add(egui::Button::new(
@@ -1333,180 +2434,269 @@ pub fn ui_toolbar(state: &mut AppState, ctx: &Context, window: &Arc<Window>) ->
                         }
                     });
 
+                    if !state.recent_colors.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label(t("最近颜色:"));
+                            for &color in &state.recent_colors {
+                                let response =
+                                    color_swatch(ui, color).on_hover_text(t("点击使用, 右键收藏"));
+                                if response.clicked() {
+                                    state.brush_color = color;
+                                }
+                                if response.secondary_clicked()
+                                    && !state.pinned_colors.contains(&color)
+                                {
+                                    state.pinned_colors.push(color);
+                                }
+                            }
+                        });
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label(t("收藏颜色:"));
+                        let mut unpinned = None;
+                        for (index, &color) in state.pinned_colors.iter().enumerate() {
+                            let response =
+                                color_swatch(ui, color).on_hover_text(t("点击使用, 右键取消收藏"));
+                            if response.clicked() {
+                                state.brush_color = color;
+                            }
+                            if response.secondary_clicked() {
+                                unpinned = Some(index);
+                            }
+                        }
+                        if let Some(index) = unpinned {
+                            state.pinned_colors.remove(index);
+                        }
+                        if ui.button(t("收藏当前")).clicked()
+                            && !state.pinned_colors.contains(&state.brush_color)
+                        {
+                            state.pinned_colors.push(state.brush_color);
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label(t("宽度:"));
+                        ui.add(egui::Slider::new(
+                            &mut state.brush_width,
+                            state.persistent.brush_width_range.clone(),
+                        ));
+                    });
+
                     ui.horizontal(|ui| {
-                        ui.label("宽度:");
-                        let slider_response =
-                            ui.add(egui::Slider::new(&mut state.brush_width, 1.0..=20.0));
-
-                        // 显示大小预览
-                        if slider_response.dragged() || slider_response.hovered() {
-                            state.show_size_preview = true;
-                            // 使用屏幕中心位置
-                        } else if !slider_response.dragged() && !slider_response.hovered() {
-                            state.show_size_preview = false;
+                        ui.label(t("风格:"));
+                        for (style, label) in [
+                            (BrushStyle::Marker, t("马克笔")),
+                            (BrushStyle::Chalk, t("粉笔")),
+                            (BrushStyle::Pencil, t("铅笔")),
+                        ] {
+                            ui.selectable_value(&mut state.brush_style, style, label);
                         }
                     });
 
+                    ui.horizontal(|ui| {
+                        ui.label(t("线型:"));
+                        line_style_selector(ui, &mut state.brush_line_style);
+                    });
+
+                    // 粉笔和铅笔自带纹理
+                    ui.add_enabled_ui(state.brush_style == BrushStyle::Marker, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label(t("纹理:"));
+                            ui.selectable_value(&mut state.brush_texture, None, t("无"));
+                            for (texture, label) in [
+                                (StrokeTexture::Chalk, t("粉笔")),
+                                (StrokeTexture::Grain, t("颗粒")),
+                                (StrokeTexture::Canvas, t("布纹")),
+                            ] {
+                                ui.selectable_value(&mut state.brush_texture, Some(texture), label);
+                            }
+                            if let Some(custom) = &state.custom_brush_texture {
+                                ui.selectable_value(
+                                    &mut state.brush_texture,
+                                    Some(custom.clone()),
+                                    t("自定义"),
+                                );
+                            }
+                            if ui.button(t("加载纹理...")).clicked()
+                                && let Some(path) = rfd::FileDialog::new()
+                                    .add_filter(t("图片"), IMAGE_FILE_EXTS)
+                                    .pick_file()
+                            {
+                                match brush_texture::load_custom_texture(&path) {
+                                    Ok(texture) => {
+                                        state.custom_brush_texture = Some(texture.clone());
+                                        state.brush_texture = Some(texture);
+                                    }
+                                    Err(err) => {
+                                        state.toasts.error(tf("无法打开图片: {}!", &[&err]));
+                                    }
+                                }
+                            }
+                        });
+                    });
+
+                    // 只有移动平均平滑使用窗口大小
+                    ui.add_enabled_ui(
+                        state.persistent.smoothing_mode == SmoothingMode::MovingAverage,
+                        |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label(t("平滑强度:"));
+                                if ui
+                                    .add(egui::Slider::new(&mut state.smoothing_strength, 1..=9))
+                                    .on_hover_text(t("移动平均平滑的窗口大小, 越大笔迹越平滑"))
+                                    .changed()
+                                {
+                                    // 窗口以当前点为中心，保持为奇数
+                                    state.smoothing_strength |= 1;
+                                }
+                            });
+                        },
+                    );
+
+                    ui.horizontal(|ui| {
+                        ui.label(t("对称:"));
+                        ui.selectable_value(&mut state.symmetry, SymmetryMode::Off, t("关闭"));
+                        ui.selectable_value(&mut state.symmetry, SymmetryMode::Vertical, t("左右"));
+                        ui.selectable_value(
+                            &mut state.symmetry,
+                            SymmetryMode::Horizontal,
+                            t("上下"),
+                        );
+                        let is_radial = matches!(state.symmetry, SymmetryMode::Radial { .. });
+                        if ui.selectable_label(is_radial, t("旋转")).clicked() && !is_radial {
+                            state.symmetry = SymmetryMode::Radial { folds: 6 };
+                        }
+                        if let SymmetryMode::Radial { folds } = &mut state.symmetry {
+                            ui.add(egui::DragValue::new(folds).range(2..=24).suffix(t(" 份")));
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label(t("不透明度:"));
+                        ui.add(egui::Slider::new(&mut state.brush_opacity, 0..=255));
+                    });
+
                     // 画笔宽度快捷按钮
                     ui.horizontal(|ui| {
-                        ui.label("快捷宽度:");
-                        if ui.button("小").clicked() {
+                        ui.label(t("快捷宽度:"));
+                        if ui.button(t("小")).clicked() {
                             state.brush_width = 1.0;
                         }
-                        if ui.button("中").clicked() {
+                        if ui.button(t("中")).clicked() {
                             state.brush_width = 3.0;
                         }
-                        if ui.button("大").clicked() {
+                        if ui.button(t("大")).clicked() {
                             state.brush_width = 5.0;
                         }
                     });
+                } else if state.current_tool == CanvasTool::Highlighter {
+                    ui.horizontal(|ui| {
+                        ui.label(t("颜色:"));
+                        ui.color_edit_button_srgba(&mut state.highlighter_color);
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label(t("大小:"));
+                        ui.add(egui::Slider::new(&mut state.highlighter_width, 5.0..=60.0));
+                    });
                 } else if state.current_tool == CanvasTool::ObjectEraser
                     || state.current_tool == CanvasTool::PixelEraser
                 {
                     ui.horizontal(|ui| {
-                        ui.label("大小:");
-                        let slider_response =
-                            ui.add(egui::Slider::new(&mut state.eraser_size, 5.0..=50.0));
-
-                        // 显示大小预览
-                        if slider_response.dragged() || slider_response.hovered() {
-                            state.show_size_preview = true;
-                        } else if !slider_response.dragged() && !slider_response.hovered() {
-                            state.show_size_preview = false;
-                        }
+                        ui.label(t("大小:"));
+                        ui.add(egui::Slider::new(
+                            &mut state.eraser_size,
+                            state.persistent.eraser_size_range.clone(),
+                        ));
                     });
 
                     ui.horizontal(|ui| {
-                        ui.label("清空:");
+                        ui.label(t("擦除范围:"));
+                        ui.selectable_value(
+                            &mut state.eraser_scope,
+                            EraserScope::ActiveLayer,
+                            t("当前图层"),
+                        );
+                        ui.selectable_value(
+                            &mut state.eraser_scope,
+                            EraserScope::AllLayers,
+                            t("所有图层"),
+                        );
+                    });
+                    ui.label(egui::RichText::new(t("(按住 Alt 擦除所有图层)")).italics());
+
+                    ui.horizontal(|ui| {
+                        ui.label(t("清空:"));
                         if ui.button("OK").clicked() {
                             // Save state to history before modification
-                            let old_objects = std::mem::take(&mut state.canvas.objects);
+                            let old_objects = state.canvas.objects.take_all();
                             state.history.save_clear_objects(old_objects);
+                            state.collab.broadcast_clear(state.current_page);
                             state.pointers.clear();
-                            state.selected_object_index = None;
+                            state.clear_selection();
                             state.current_tool = CanvasTool::Brush;
                         }
                     });
                 } else if state.current_tool == CanvasTool::Insert {
                     ui.horizontal(|ui| {
-                        if ui.button("图片").clicked() {
+                        if ui.button(t("图片")).clicked() {
                             if let Some(path) = rfd::FileDialog::new()
-                                .add_filter("图片", IMAGE_FILE_EXTS)
+                                .add_filter(t("图片"), IMAGE_FILE_EXTS)
                                 .pick_file()
                             {
-                                if let Ok(img) = image::open(path) {
-                                    // 最大纹理大小限制（通常为 2048x2048）
-                                    const MAX_TEXTURE_SIZE: u32 = 2048;
-
-                                    // 如果图像太大，调整大小以适应纹理限制
-                                    let img = if img.width() > MAX_TEXTURE_SIZE
-                                        || img.height() > MAX_TEXTURE_SIZE
-                                    {
-                                        utils::resize_image_for_texture(img, MAX_TEXTURE_SIZE)
-                                    } else {
-                                        img
-                                    };
-
-                                    let img_rgba = img.to_rgba8();
-                                    let (width, height) = img_rgba.dimensions();
-                                    let aspect_ratio = width as f32 / height as f32;
-
-                                    // 默认大小
-                                    let target_width = 300.0_f32;
-                                    let target_height = target_width / aspect_ratio;
-
-                                    let ctx = ui.ctx();
-                                    let texture = ctx.load_texture(
-                                        "inserted_image",
-                                        egui::ColorImage::from_rgba_unmultiplied(
-                                            [width as usize, height as usize],
-                                            &img_rgba,
-                                        ),
-                                        egui::TextureOptions::LINEAR,
-                                    );
-
-                                    // Save state to history before modification
-                                    let image_data: Arc<[u8]> = img_rgba.into_raw().into();
-                                    let new_image = CanvasImage {
-                                        texture,
-                                        pos: Pos2::new(100.0, 100.0),
-                                        size: egui::vec2(target_width, target_height),
-                                        aspect_ratio,
-                                        marked_for_deletion: false,
-                                        rot: 0.0,
-                                        image_data,
-                                        image_size: [width, height],
-                                    };
-                                    let index = state.canvas.objects.len();
-                                    state.history.save_add_object(
-                                        index,
-                                        CanvasObject::Image(new_image.clone()),
-                                    );
-                                    state.canvas.objects.push(CanvasObject::Image(new_image));
-
+                                let pos = state.new_object_pos();
+                                if insert_image_file(state, ui.ctx(), &path, pos) {
                                     state.current_tool = CanvasTool::Select;
                                 }
                             }
                         }
-                        if ui.button("文本").clicked() {
+                        if ui.button(t("设为背景")).clicked()
+                            && let Some(path) = rfd::FileDialog::new()
+                                .add_filter(t("图片"), IMAGE_FILE_EXTS)
+                                .pick_file()
+                        {
+                            set_background_image(state, ui.ctx(), &path);
+                        }
+                        if ui.button(t("文本")).clicked() {
+                            state.editing_text_index = None;
+                            state.new_text_content.clear();
                             state.show_insert_text_window = true;
                         }
-                        if ui.button("形状").clicked() {
+                        if ui.button(t("形状")).clicked() {
                             state.show_insert_shape_window = true;
                         }
+                        if ui.button(t("便签")).clicked() {
+                            insert_sticky_note(state);
+                        }
                     });
 
-                    if state.show_insert_text_window {
-                        // 计算屏幕中心位置
-                        let content_rect = ctx.content_rect();
-                        let center_pos = content_rect.center();
-
-                        egui::Window::new("插入文本")
-                            .collapsible(false)
-                            .resizable(false)
-                            .pivot(egui::Align2::CENTER_CENTER)
-                            .default_pos([center_pos.x, center_pos.y])
-                            .show(ctx, |ui| {
-                                ui.horizontal(|ui| {
-                                    ui.label("文本内容:");
-                                    ui.text_edit_singleline(&mut state.new_text_content);
-                                });
-
-                                ui.horizontal(|ui| {
-                                    if ui.button("确认").clicked() {
-                                        let text_size = ui
-                                            .painter()
-                                            .layout_no_wrap(
-                                                state.new_text_content.clone(),
-                                                egui::FontId::proportional(16.0),
-                                                Color32::WHITE,
-                                            )
-                                            .size();
-                                        let new_text = CanvasText {
-                                            text: state.new_text_content.clone(),
-                                            pos: Pos2::new(100.0, 100.0),
-                                            color: Color32::WHITE,
-                                            font_size: 16.0,
-                                            rot: 0.0,
-                                            cached_size: Some(text_size),
-                                        };
-                                        let index = state.canvas.objects.len();
-                                        state.history.save_add_object(
-                                            index,
-                                            CanvasObject::Text(new_text.clone()),
-                                        );
-                                        state.canvas.objects.push(CanvasObject::Text(new_text));
-                                        state.current_tool = CanvasTool::Select;
-                                        state.show_insert_text_window = false;
-                                        state.new_text_content.clear();
-                                    }
+                    if state.pending_shape.is_some() {
+                        ui.horizontal(|ui| {
+                            ui.label(egui::RichText::new(t("(在画布上拖动以绘制形状)")).italics());
+                            if ui.button(t("取消")).clicked() {
+                                state.pending_shape = None;
+                            }
+                        });
+                    }
 
-                                    if ui.button("取消").clicked() {
-                                        state.show_insert_text_window = false;
-                                        state.new_text_content.clear();
-                                    }
-                                });
-                            });
+                    if let Some(background) = &mut state.canvas.background_image {
+                        let mut remove = false;
+                        ui.horizontal(|ui| {
+                            ui.label(t("背景:"));
+                            for (fit, label) in [
+                                (BackgroundFit::Stretch, t("拉伸")),
+                                (BackgroundFit::Fit, t("适应")),
+                                (BackgroundFit::Fill, t("填充")),
+                                (BackgroundFit::Center, t("居中")),
+                            ] {
+                                ui.selectable_value(&mut background.fit, fit, label);
+                            }
+                            remove = ui.button(t("移除")).clicked();
+                        });
+                        if remove {
+                            state.canvas.background_image = None;
+                        }
                     }
 
                     if state.show_insert_shape_window {
@@ -1514,127 +2704,184 @@ pub fn ui_toolbar(state: &mut AppState, ctx: &Context, window: &Arc<Window>) ->
                         let content_rect = ctx.content_rect();
                         let center_pos = content_rect.center();
 
-                        egui::Window::new("插入形状")
+                        egui::Window::new(t("插入形状"))
+                            .id(egui::Id::new("insert_shape_window"))
                             .collapsible(false)
                             .resizable(false)
                             .pivot(egui::Align2::CENTER_CENTER)
                             .default_pos([center_pos.x, center_pos.y])
                             .show(ctx, |ui| {
-                                ui.label("选择要插入的形状:");
+                                ui.label(t("选择要插入的形状:"));
 
                                 ui.horizontal(|ui| {
-                                    if ui.button("线").clicked() {
-                                        // Save state to history before modification
-                                        let new_shape = CanvasShape {
-                                            shape_type: CanvasShapeType::Line,
-                                            pos: Pos2::new(100.0, 100.0),
-                                            size: 100.0,
-                                            color: Color32::WHITE,
-                                            rotation: 0.0,
-                                        };
-                                        let index = state.canvas.objects.len();
-                                        state.history.save_add_object(
-                                            index,
-                                            CanvasObject::Shape(new_shape.clone()),
-                                        );
-                                        state.canvas.objects.push(CanvasObject::Shape(new_shape));
-                                        state.show_insert_shape_window =
-                                            state.persistent.keep_insertion_window_open;
+                                    let shape_buttons = [
+                                        (t("线"), CanvasShapeType::Line),
+                                        (t("箭头"), CanvasShapeType::Arrow),
+                                        (t("矩形"), CanvasShapeType::Rectangle),
+                                        (
+                                            t("三角形"),
+                                            CanvasShapeType::Triangle(state.new_triangle_kind),
+                                        ),
+                                        (t("圆形"), CanvasShapeType::Circle),
+                                        (t("椭圆"), CanvasShapeType::Ellipse),
+                                        (
+                                            t("多边形"),
+                                            CanvasShapeType::Polygon {
+                                                sides: state.new_polygon_sides,
+                                            },
+                                        ),
+                                        (
+                                            t("星形"),
+                                            CanvasShapeType::Star {
+                                                points: state.new_star_points,
+                                            },
+                                        ),
+                                    ];
+                                    for (label, shape_type) in shape_buttons {
+                                        if ui.button(label).clicked() {
+                                            insert_shape(state, shape_type);
+                                        }
                                     }
+                                });
 
-                                    if ui.button("箭头").clicked() {
-                                        // Save state to history before modification
-                                        let new_shape = CanvasShape {
-                                            shape_type: CanvasShapeType::Arrow,
-                                            pos: Pos2::new(100.0, 100.0),
-                                            size: 100.0,
-                                            color: Color32::WHITE,
-                                            rotation: 0.0,
-                                        };
-                                        let index = state.canvas.objects.len();
-                                        state.history.save_add_object(
-                                            index,
-                                            CanvasObject::Shape(new_shape.clone()),
-                                        );
-                                        state.canvas.objects.push(CanvasObject::Shape(new_shape));
-                                        state.show_insert_shape_window =
-                                            state.persistent.keep_insertion_window_open;
-                                    }
+                                ui.horizontal(|ui| {
+                                    ui.label(t("三角形类型:"));
+                                    ui.selectable_value(
+                                        &mut state.new_triangle_kind,
+                                        TriangleKind::Equilateral,
+                                        t("等边"),
+                                    );
+                                    ui.selectable_value(
+                                        &mut state.new_triangle_kind,
+                                        TriangleKind::Right,
+                                        t("直角"),
+                                    );
+                                    ui.selectable_value(
+                                        &mut state.new_triangle_kind,
+                                        TriangleKind::Isosceles,
+                                        t("等腰"),
+                                    );
+                                });
 
-                                    if ui.button("矩形").clicked() {
-                                        // Save state to history before modification
-                                        let new_shape = CanvasShape {
-                                            shape_type: CanvasShapeType::Rectangle,
-                                            pos: Pos2::new(100.0, 100.0),
-                                            size: 100.0,
-                                            color: Color32::WHITE,
-                                            rotation: 0.0,
-                                        };
-                                        let index = state.canvas.objects.len();
-                                        state.history.save_add_object(
-                                            index,
-                                            CanvasObject::Shape(new_shape.clone()),
-                                        );
-                                        state.canvas.objects.push(CanvasObject::Shape(new_shape));
-                                        state.show_insert_shape_window =
-                                            state.persistent.keep_insertion_window_open;
-                                    }
-                                    if ui.button("三角形").clicked() {
-                                        // Save state to history before modification
-                                        let new_shape = CanvasShape {
-                                            shape_type: CanvasShapeType::Triangle,
-                                            pos: Pos2::new(100.0, 100.0),
-                                            size: 100.0,
-                                            color: Color32::WHITE,
-                                            rotation: 0.0,
-                                        };
-                                        let index = state.canvas.objects.len();
-                                        state.history.save_add_object(
-                                            index,
-                                            CanvasObject::Shape(new_shape.clone()),
-                                        );
-                                        state.canvas.objects.push(CanvasObject::Shape(new_shape));
-                                        state.show_insert_shape_window =
-                                            state.persistent.keep_insertion_window_open;
-                                    }
+                                ui.horizontal(|ui| {
+                                    ui.add(
+                                        egui::Slider::new(&mut state.new_polygon_sides, 3..=12)
+                                            .text(t("多边形边数")),
+                                    );
+                                    ui.add(
+                                        egui::Slider::new(&mut state.new_star_points, 3..=12)
+                                            .text(t("星形角数")),
+                                    );
+                                });
 
-                                    if ui.button("圆形").clicked() {
-                                        // Save state to history before modification
-                                        let new_shape = CanvasShape {
-                                            shape_type: CanvasShapeType::Circle,
-                                            pos: Pos2::new(100.0, 100.0),
-                                            size: 100.0,
-                                            color: Color32::WHITE,
-                                            rotation: 0.0,
-                                        };
-                                        let index = state.canvas.objects.len();
-                                        state.history.save_add_object(
-                                            index,
-                                            CanvasObject::Shape(new_shape.clone()),
-                                        );
-                                        state.canvas.objects.push(CanvasObject::Shape(new_shape));
-                                        state.show_insert_shape_window =
-                                            state.persistent.keep_insertion_window_open;
-                                    }
+                                ui.horizontal(|ui| {
+                                    ui.label(t("线型:"));
+                                    line_style_selector(ui, &mut state.new_shape_line_style);
                                 });
 
                                 ui.horizontal(|ui| {
-                                    if ui.button("取消").clicked() {
+                                    ui.label(t("箭头:"));
+                                    let heads = &mut state.new_arrow_heads;
+                                    ui.checkbox(&mut heads.head_start, t("起点"));
+                                    ui.checkbox(&mut heads.head_end, t("终点"));
+                                    ui.add(
+                                        egui::Slider::new(&mut heads.head_size, 5.0..=60.0)
+                                            .text(t("大小")),
+                                    );
+                                });
+
+                                ui.horizontal(|ui| {
+                                    ui.checkbox(&mut state.new_shape_fill, t("填充"));
+                                    ui.add_enabled_ui(state.new_shape_fill, |ui| {
+                                        ui.color_edit_button_srgba(&mut state.new_shape_fill_color);
+                                    });
+                                });
+
+                                ui.horizontal(|ui| {
+                                    if ui.button(t("取消")).clicked() {
                                         state.show_insert_shape_window = false;
                                     }
                                     ui.checkbox(
                                         &mut state.persistent.keep_insertion_window_open,
-                                        "保持窗口开启",
+                                        t("保持窗口开启"),
                                     );
+                                    ui.checkbox(
+                                        &mut state.persistent.drag_to_insert_shapes,
+                                        t("在画布上拖动绘制"),
+                                    );
+                                    ui.checkbox(
+                                        &mut state.persistent.snap_line_angles,
+                                        t("线条角度吸附"),
+                                    )
+                                    .on_hover_text(t(
+                                        "拖动绘制线与箭头时吸附到 15° 的倍数, 也可按住 Shift",
+                                    ));
                                 });
                             });
                     }
+                } else if state.current_tool == CanvasTool::Measure {
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut state.measure.kind, MeasureKind::Ruler, t("直尺"));
+                        ui.selectable_value(
+                            &mut state.measure.kind,
+                            MeasureKind::Protractor,
+                            t("量角器"),
+                        );
+                        if ui.button(t("移到视图中央")).clicked() {
+                            let center = state.viewport.to_canvas(ctx.content_rect().center());
+                            state.measure.move_to(center);
+                        }
+                    });
+                    match state.measure.kind {
+                        MeasureKind::Ruler => {
+                            let length = measure::ruler_length(&state.measure);
+                            ui.label(tf(
+                                "长度: {} 厘米 ({} 像素), 角度: {}°",
+                                &[
+                                    &format!("{:.1}", length / measure::UNITS_PER_CM),
+                                    &format!("{length:.0}"),
+                                    &format!("{:.1}", measure::ruler_angle_degrees(&state.measure)),
+                                ],
+                            ));
+                        }
+                        MeasureKind::Protractor => {
+                            ui.label(tf(
+                                "角度: {}°",
+                                &[&format!(
+                                    "{:.1}",
+                                    state.measure.protractor_angle.to_degrees()
+                                )],
+                            ));
+                        }
+                    }
+                    ui.checkbox(&mut state.measure.pinned, t("切换工具后保持显示"));
+                    ui.label(
+                        egui::RichText::new(t("(拖动移动, 拖动圆形手柄改变长度或角度)")).italics(),
+                    );
                 } else if state.current_tool == CanvasTool::Settings {
                     ui_toolbar_settings(state, ctx, ui, window);
                 }
 
                 ui.separator();
 
+                ui.horizontal(|ui| {
+                    ui.label(t("图层:"));
+                    let active_layer = state
+                        .canvas
+                        .layers
+                        .iter()
+                        .find(|layer| layer.id == state.canvas.active_layer)
+                        .map_or("", |layer| layer.name.as_str());
+                    if ui
+                        .selectable_label(state.show_layers_window, active_layer)
+                        .clicked()
+                    {
+                        state.show_layers_window = !state.show_layers_window;
+                    }
+                });
+
+                ui.separator();
+
                 ui_history(state, ui);
 
                 ui.separator();
@@ -1653,7 +2900,8 @@ pub fn ui_canvas(state: &mut AppState, ctx: &Context) {
     egui::CentralPanel::default().show(ctx, |ui| {
         let (rect, response) = ui.allocate_exact_size(
             ui.available_size(),
-            if state.persistent.low_latency_mode {
+            // 演示时需要点击翻页
+            if state.persistent.low_latency_mode && state.presentation.is_none() {
                 egui::Sense::drag()
             } else {
                 egui::Sense::click_and_drag()
@@ -1661,67 +2909,160 @@ pub fn ui_canvas(state: &mut AppState, ctx: &Context) {
         );
 
         let painter = ui.painter();
+        let viewport = state.viewport;
+        let canvas_rect = viewport.to_canvas_rect(rect);
+        state.symmetry_center = canvas_rect.center();
+
+        // 导出图片、为填充截取画布或演示时不绘制网格、选中框、触控点等辅助内容
+        let is_exporting = state.is_capturing_canvas() || state.presentation.is_some();
+
+        // 只绘制视图范围内的块中的对象
+        let draw_order = state.canvas.draw_order_in_rect(canvas_rect);
+
+        // 选择工具下标出指针处将被选中的对象，沿用点击选择的命中顺序
+        let hovered_object = if state.current_tool == CanvasTool::Select
+            && !is_exporting
+            && state.pointers.is_empty()
+            && state.cropping_image.is_none()
+            && state.context_menu.is_none()
+        {
+            response
+                .hover_pos()
+                .and_then(|pos| {
+                    utils::hit_test_selectable_objects(&mut state.canvas, viewport.to_canvas(pos))
+                })
+                .filter(|&index| !state.is_selected(index))
+        } else {
+            None
+        };
 
-        // 绘制所有对象
-        for (i, object) in state.canvas.objects.iter().enumerate() {
-            let selected = state.selected_object_index == Some(i);
-            object.paint(painter, selected);
-        }
+        // 对象以画布坐标绘制，再整体变换到屏幕坐标
+        let canvas_painter = painter.with_clip_rect(canvas_rect);
+        utils::paint_transformed(&canvas_painter, viewport.transform(), |painter| {
+            // 背景图片铺在默认视图显示的区域内，随画布平移缩放
+            if let Some(background) = &state.canvas.background_image {
+                background.paint(painter, rect);
+            }
+            // 背景图案会被导出，但填充时不能作为边界
+            if state.pending_fill.is_none() {
+                utils::draw_background_pattern(
+                    painter,
+                    canvas_rect,
+                    state.background_pattern,
+                    state.background_spacing,
+                    state.background_pattern_color,
+                    viewport.scale,
+                );
+            }
+            if state.show_grid && !is_exporting {
+                utils::draw_grid(painter, canvas_rect, state.grid_size, viewport.scale);
+            }
+            if state.symmetry != SymmetryMode::Off
+                && !is_exporting
+                && matches!(
+                    state.current_tool,
+                    CanvasTool::Brush | CanvasTool::Highlighter
+                )
+            {
+                draw_symmetry_guides(painter, state.symmetry, canvas_rect, viewport.scale);
+            }
 
-        // 绘制当前正在绘制的笔画
-        // TODO: unify with CanvasStroke::paint()
-        for pointer in state.pointers.values() {
-            if let PointerInteraction::Drawing { active_stroke } = &pointer.interaction {
-                if let StrokeWidth::Dynamic(v) = &active_stroke.width {
-                    if v.len() != active_stroke.points.len() {
-                        continue;
-                    }
+            // 按图层顺序绘制可见对象，同一图层中荧光笔笔画位于其他对象下方
+            for i in draw_order {
+                if let Some((index, crop)) = state.cropping_image
+                    && index == i
+                    && let CanvasObject::Image(image) = &state.canvas.objects[i]
+                {
+                    paint_crop_preview(painter, image, crop, viewport.scale);
+                    continue;
+                }
+                let selected = !is_exporting && state.is_selected(i);
+                state.canvas.objects[i].paint(painter, selected);
+            }
+
+            if let Some(index) = hovered_object {
+                painter.rect_stroke(
+                    state.canvas.objects[index]
+                        .bounding_box()
+                        .expand(4.0 / viewport.scale),
+                    2.0 / viewport.scale,
+                    Stroke::new(
+                        1.5 / viewport.scale,
+                        Color32::from_rgba_unmultiplied(0, 120, 255, 110),
+                    ),
+                    egui::StrokeKind::Outside,
+                );
+            }
+
+            if !is_exporting {
+                let guide_stroke =
+                    Stroke::new(1.0 / viewport.scale, Color32::from_rgb(255, 0, 144));
+                for guide in &state.alignment_guides {
+                    painter.line_segment(*guide, guide_stroke);
+                }
+            }
+
+            // 绘制框选矩形与正在拖动绘制的形状，框选线宽不随缩放变化
+            for pointer in state.pointers.values() {
+                if let PointerInteraction::PlacingShape { shape, .. } = &pointer.interaction {
+                    shape.paint(painter, false);
                 }
-                painter.add(egui::Shape::Circle(egui::epaint::CircleShape::filled(
-                    active_stroke.points[0],
-                    active_stroke.width.first() / 2.0,
-                    state.brush_color,
-                )));
-                if active_stroke.points.len() >= 2 {
-                    painter.add(egui::Shape::Circle(egui::epaint::CircleShape::filled(
-                        active_stroke.points[active_stroke.points.len() - 1],
-                        active_stroke.width.last() / 2.0,
-                        state.brush_color,
-                    )));
-                    for i in 0..active_stroke.points.len() - 1 {
-                        let avg_width =
-                            (active_stroke.width.get(i) + active_stroke.width.get(i + 1)) / 2.0;
-                        painter.line_segment(
-                            [active_stroke.points[i], active_stroke.points[i + 1]],
-                            Stroke::new(avg_width, state.brush_color),
+                if let PointerInteraction::Marquee { start } = pointer.interaction {
+                    painter.rect(
+                        Rect::from_two_pos(start, pointer.pos),
+                        0.0,
+                        Color32::from_rgba_unmultiplied(0, 120, 255, 32),
+                        Stroke::new(1.0 / viewport.scale, Color32::from_rgb(0, 120, 255)),
+                        egui::StrokeKind::Inside,
+                    );
+                }
+            }
+
+            // 绘制当前正在绘制的笔画
+            for pointer in state.pointers.values() {
+                if let PointerInteraction::Drawing { active_stroke } = &pointer.interaction {
+                    if let StrokeWidth::Dynamic(v) = &active_stroke.width {
+                        if v.len() != active_stroke.points.len() {
+                            continue;
+                        }
+                    }
+                    paint_active_stroke(
+                        painter,
+                        state,
+                        &active_stroke.points,
+                        &active_stroke.width,
+                        active_stroke.is_highlighter,
+                    );
+                    // 对称模式下同时预览镜像出的笔画
+                    for points in state
+                        .symmetry
+                        .mirror(&active_stroke.points, state.symmetry_center)
+                    {
+                        paint_active_stroke(
+                            painter,
+                            state,
+                            &points,
+                            &active_stroke.width,
+                            active_stroke.is_highlighter,
                         );
                     }
                 }
             }
-        }
 
-        // 绘制大小预览圆圈
-        if state.show_size_preview {
-            let content_rect = ui.ctx().content_rect();
-            let pos = content_rect.center();
-            utils::draw_size_preview(
-                painter,
-                pos,
-                match state.current_tool {
-                    CanvasTool::Brush => state.brush_width,
-                    CanvasTool::ObjectEraser | CanvasTool::PixelEraser => state.eraser_size,
-                    _ => unreachable!(),
-                },
-            );
-        }
+            // 直尺与量角器位于所有对象之上，不会被导出
+            if !is_exporting && (state.current_tool == CanvasTool::Measure || state.measure.pinned)
+            {
+                measure::paint_measure(painter, &state.measure, viewport.scale);
+            }
+        });
 
         // 绘制触控点
-        if state.show_touch_points {
+        if state.show_touch_points && !is_exporting {
             for pointer in state.pointers.values() {
-                if pointer.id == 0 {
+                if pointer.id == MOUSE_POINTER_ID {
                     continue;
                 }
-                let pos = pointer.pos;
+                let pos = viewport.to_screen(pointer.pos);
                 painter.circle_filled(
                     pos,
                     15.0,
@@ -1731,7 +3072,7 @@ pub fn ui_canvas(state: &mut AppState, ctx: &Context) {
 
                 // 绘制触控 ID
                 let text_galley = painter.layout_no_wrap(
-                    format!("{}", pointer.id),
+                    format!("{}", touch_id_of_pointer(pointer.id)),
                     egui::FontId::proportional(14.0),
                     Color32::BLACK,
                 );
@@ -1757,147 +3098,201 @@ pub fn ui_canvas(state: &mut AppState, ctx: &Context) {
             return;
         }
 
+        // 演示时不编辑画布，点击左侧或右侧三分之一处切换页面
+        if state.presentation.is_some() {
+            if response.clicked()
+                && let Some(pos) = response.interact_pointer_pos()
+            {
+                let third = rect.width() / 3.0;
+                if pos.x < rect.left() + third && state.current_page > 0 {
+                    present_page(state, state.current_page - 1);
+                } else if pos.x > rect.right() - third {
+                    present_page(state, state.current_page + 1);
+                }
+            }
+            return;
+        }
+
         // 处理指针输入
-        let has_touch = state.pointers.keys().any(|&k| k != 0);
+        let has_touch = state.pointers.keys().any(|&k| k != MOUSE_POINTER_ID);
+
+        // 在指针处绘制画笔大小圆环或橡皮擦范围，并按工具设置光标
+        if let Some(hover_pos) = response
+            .hover_pos()
+            .or_else(|| response.interact_pointer_pos())
+            && !is_exporting
+            && !has_touch
+        {
+            let brush_size = match state.current_tool {
+                CanvasTool::Brush => Some(state.brush_width),
+                CanvasTool::Highlighter => Some(state.highlighter_width),
+                _ => None,
+            };
+            if let Some(size) = brush_size {
+                utils::draw_cursor_ring(painter, hover_pos, size * viewport.scale);
+            }
+            if matches!(
+                state.current_tool,
+                CanvasTool::ObjectEraser | CanvasTool::PixelEraser
+            ) {
+                utils::draw_size_preview(painter, hover_pos, state.eraser_size * viewport.scale);
+            }
+            ctx.set_cursor_icon(tool_cursor_icon(state, response.dragged()));
+        }
+
         let pointer_pos = if has_touch {
             None
         } else {
-            response.interact_pointer_pos()
+            response
+                .interact_pointer_pos()
+                .map(|pos| viewport.to_canvas(pos))
         };
 
+        // 滚轮、Ctrl+滚轮或触控板捏合缩放视图
+        if response.hovered()
+            && let Some(hover_pos) = response.hover_pos()
+        {
+            let (scroll, zoom) = ui.input(|i| (i.smooth_scroll_delta.y, i.zoom_delta()));
+            let factor = zoom * (scroll / 200.0).exp();
+            if factor != 1.0 {
+                state.viewport.zoom_at(hover_pos, factor);
+            }
+        }
+
         match state.current_tool {
-            CanvasTool::Insert | CanvasTool::Settings | CanvasTool::Passthrough => {}
+            CanvasTool::Measure => {
+                if has_touch {
+                    return;
+                }
+                if response.drag_started()
+                    && let Some(pos) = pointer_pos
+                {
+                    measure_drag_start(state, MOUSE_POINTER_ID, pos);
+                }
+                if response.dragged()
+                    && let Some(pos) = pointer_pos
+                {
+                    measure_drag_move(state, MOUSE_POINTER_ID, pos);
+                }
+                if response.drag_stopped() {
+                    measure_drag_end(state, MOUSE_POINTER_ID);
+                }
+            }
+
+            CanvasTool::Insert => {
+                if has_touch {
+                    return;
+                }
+                if response.drag_started()
+                    && let Some(pos) = pointer_pos
+                {
+                    shape_drag_start(state, MOUSE_POINTER_ID, pos);
+                }
+                if response.dragged()
+                    && let Some(pos) = pointer_pos
+                {
+                    shape_drag_move(state, MOUSE_POINTER_ID, pos);
+                }
+                if response.drag_stopped() {
+                    shape_drag_end(state, MOUSE_POINTER_ID);
+                }
+            }
+
+            CanvasTool::Settings | CanvasTool::Passthrough => {}
+
+            CanvasTool::Eyedropper => {
+                if response.clicked()
+                    && let Some(pos) = pointer_pos
+                {
+                    state.brush_color =
+                        utils::pick_color_at(&mut state.canvas, pos, state.persistent.canvas_color);
+                    // 取色后切换回画笔
+                    state.current_tool = CanvasTool::Brush;
+                    clear_interaction_state(state);
+                }
+            }
+
+            CanvasTool::Fill => {
+                if response.clicked()
+                    && let Some(pos) = pointer_pos
+                {
+                    // 下一帧仅绘制画布，截取后再进行填充
+                    state.pending_fill = Some(pos);
+                    ctx.request_repaint();
+                }
+            }
+
+            CanvasTool::Pan => {
+                // 双指捏合与平移在触摸事件中统一处理
+                if ui.input(|i| i.multi_touch()).is_none() && response.dragged() {
+                    state.viewport.offset += response.drag_delta();
+                }
+            }
 
             CanvasTool::Select => {
                 if !has_touch {
-                    // Handle click: iterate through objects from last to first, check bounding boxes
+                    // Handle click: select the topmost object under the pointer, or deselect on empty space.
+                    // Shift-click adds or removes the object from the multi-selection instead.
                     if response.clicked() {
                         if let Some(click_pos) = pointer_pos {
-                            let mut found_selection = false;
-                            for (i, object) in state.canvas.objects.iter().enumerate().rev() {
-                                if object.bounding_box().contains(click_pos) {
-                                    state.selected_object_index = Some(i);
-                                    found_selection = true;
-                                    break;
+                            let hit =
+                                utils::hit_test_selectable_objects(&mut state.canvas, click_pos);
+                            if state.modifiers.shift_key() {
+                                if let Some(index) = hit {
+                                    toggle_object_selection(state, index);
+                                }
+                            } else {
+                                state.clear_selection();
+                                if let Some(index) = hit {
+                                    state.select_object(index);
                                 }
-                            }
-                            if !found_selection {
-                                state.selected_object_index = None;
                             }
                         }
                     }
 
-                    // Handle drag start: create mouse pointer with Selecting interaction
-                    if response.drag_started() {
-                        if let Some(pos) = pointer_pos {
-                            let (dragged_handle, drag_original_transform) =
-                                if let Some(selected_idx) = state.selected_object_index
-                                    && selected_idx < state.canvas.objects.len()
-                                {
-                                    let object = &state.canvas.objects[selected_idx];
-                                    let bbox = object.bounding_box();
-                                    let handle = utils::get_transform_handle_at_pos(bbox, pos);
-                                    let transform =
-                                        handle.is_some().then(|| object.get_transform());
-                                    (handle, transform)
-                                } else {
-                                    (None, None)
-                                };
-
-                            state.pointers.insert(
-                                0,
-                                PointerState {
-                                    id: 0,
-                                    pos,
-                                    interaction: PointerInteraction::Selecting {
-                                        drag_start: pos,
-                                        dragged_handle,
-                                        drag_original_transform,
-                                        drag_accumulated_delta: egui::Vec2::ZERO,
-                                    },
-                                },
-                            );
-                        }
+                    // Handle double click: reopen the text dialog to edit the text under the pointer
+                    if response.double_clicked()
+                        && let Some(click_pos) = pointer_pos
+                        && let Some(index) =
+                            utils::hit_test_selectable_objects(&mut state.canvas, click_pos)
+                    {
+                        open_text_editor(state, index);
                     }
 
-                    // Handle dragging: move or resize the selected object
-                    if response.dragged() && state.selected_object_index.is_some() {
-                        if let Some(current_pos) = pointer_pos {
-                            if let Some(pointer) = state.pointers.get_mut(&0) {
-                                pointer.pos = current_pos;
-                                if let PointerInteraction::Selecting {
-                                    ref mut drag_start,
-                                    dragged_handle,
-                                    ref mut drag_accumulated_delta,
-                                    ..
-                                } = pointer.interaction
-                                {
-                                    let delta = current_pos - *drag_start;
+                    // Handle right click: open the context menu of the object under the pointer
+                    if response.secondary_clicked()
+                        && let Some(pos) = response.interact_pointer_pos()
+                    {
+                        open_object_context_menu(state, pos);
+                    }
 
-                                    if let Some(selected_idx) = state.selected_object_index
-                                        && selected_idx < state.canvas.objects.len()
-                                    {
-                                        if let Some(handle) = dragged_handle {
-                                            if let Some(object) =
-                                                state.canvas.objects.get_mut(selected_idx)
-                                            {
-                                                object.transform(
-                                                    handle,
-                                                    delta,
-                                                    *drag_start,
-                                                    current_pos,
-                                                );
-                                            }
-                                        } else {
-                                            if let Some(object) =
-                                                state.canvas.objects.get_mut(selected_idx)
-                                            {
-                                                CanvasObject::move_object(object, delta);
-                                            }
-                                            *drag_accumulated_delta += delta;
-                                        }
-                                    }
+                    // Handle drag start: transform or move the selection, or start a marquee on empty space
+                    if response.drag_started()
+                        && let Some(pos) = pointer_pos
+                    {
+                        select_drag_start(state, MOUSE_POINTER_ID, pos);
+                    }
 
-                                    *drag_start = current_pos;
-                                }
-                            }
-                        }
+                    // Handle dragging: move or resize the selected objects
+                    if response.dragged()
+                        && let Some(current_pos) = pointer_pos
+                    {
+                        // 按住 Shift 时自由缩放图片
+                        let keep_aspect_ratio = !state.modifiers.shift_key();
+                        select_drag_move(state, MOUSE_POINTER_ID, current_pos, keep_aspect_ratio);
                     }
 
-                    // Handle drag stop: save move/resize to history and clear state
+                    // Handle drag stop: save move/resize to history, or select the objects inside the marquee
                     if response.drag_stopped() {
-                        if let Some(pointer) = state.pointers.get(&0) {
-                            if let PointerInteraction::Selecting {
-                                drag_accumulated_delta,
-                                drag_original_transform,
-                                ..
-                            } = &pointer.interaction
-                            {
-                                if let Some(selected_idx) = state.selected_object_index {
-                                    if *drag_accumulated_delta != egui::Vec2::ZERO {
-                                        state.history.save_move_object(
-                                            selected_idx,
-                                            -*drag_accumulated_delta,
-                                            *drag_accumulated_delta,
-                                        );
-                                    }
-                                }
-                                if let Some(original) = drag_original_transform.clone() {
-                                    if let Some(selected_idx) = state.selected_object_index
-                                        && selected_idx < state.canvas.objects.len()
-                                    {
-                                        let new_transform =
-                                            state.canvas.objects[selected_idx].get_transform();
-                                        state.history.save_transform_object(
-                                            selected_idx,
-                                            original,
-                                            new_transform,
-                                        );
-                                    }
-                                }
-                            }
-                        }
-                        state.pointers.remove(&0);
+                        select_drag_end(state, MOUSE_POINTER_ID);
+                    }
+                } else if state.cropping_image.is_none() {
+                    // 长按对象打开右键菜单，先结束这根手指已开始的拖动
+                    if let Some((id, pos)) = state.gestures.take_long_press() {
+                        select_drag_end(state, id);
+                        open_object_context_menu(state, pos);
+                    } else if let Some(remaining) = state.gestures.long_press_remaining() {
+                        ctx.request_repaint_after(remaining);
                     }
                 }
             }
@@ -1917,14 +3312,28 @@ pub fn ui_canvas(state: &mut AppState, ctx: &Context) {
                 };
 
                 for pos in eraser_positions {
-                    utils::draw_size_preview(painter, pos, state.eraser_size);
+                    // 鼠标指针处的预览已在悬停时绘制
+                    if has_touch {
+                        utils::draw_size_preview(
+                            painter,
+                            viewport.to_screen(pos),
+                            state.eraser_size * viewport.scale,
+                        );
+                    }
 
+                    // 只检查橡皮擦范围附近的对象
+                    let candidates = state.canvas.objects_in_rect(egui::Rect::from_center_size(
+                        pos,
+                        egui::Vec2::splat(state.eraser_size),
+                    ));
                     let mut to_remove = Vec::new();
-                    for (i, object) in state.canvas.objects.iter().enumerate().rev() {
-                        match object {
+                    for i in candidates.into_iter().rev() {
+                        if !state.is_object_erasable(i) {
+                            continue;
+                        }
+                        match &state.canvas.objects[i] {
                             CanvasObject::Image(img) => {
-                                let img_rect = egui::Rect::from_min_size(img.pos, img.size);
-                                if img_rect.contains(pos) {
+                                if img.hit_test(pos) {
                                     to_remove.push(i);
                                 }
                             }
@@ -1933,9 +3342,13 @@ pub fn ui_canvas(state: &mut AppState, ctx: &Context) {
                                     to_remove.push(i);
                                 }
                             }
+                            CanvasObject::StickyNote(note) => {
+                                if note.hit_test(pos) {
+                                    to_remove.push(i);
+                                }
+                            }
                             CanvasObject::Shape(shape) => {
-                                let shape_rect = shape.bounding_box();
-                                if shape_rect.contains(pos) {
+                                if utils::point_in_shape(shape, pos, state.eraser_size / 2.0) {
                                     to_remove.push(i);
                                 }
                             }
@@ -1948,133 +3361,67 @@ pub fn ui_canvas(state: &mut AppState, ctx: &Context) {
                     }
                     for i in to_remove {
                         let object = state.canvas.objects.remove(i);
+                        state.collab.broadcast_removal(state.current_page, &object);
                         state.history.save_remove_object(i, object);
                     }
                 }
             }
 
             CanvasTool::PixelEraser => {
-                let eraser_positions: Vec<Pos2> = if has_touch {
+                let eraser_positions: Vec<(u64, Pos2)> = if has_touch {
                     state
                         .pointers
                         .values()
                         .filter(|p| matches!(p.interaction, PointerInteraction::Erasing))
-                        .map(|p| p.pos)
+                        .map(|p| (p.id, p.pos))
                         .collect()
                 } else if response.dragged() || response.clicked() {
-                    pointer_pos.into_iter().collect()
+                    pointer_pos
+                        .map(|pos| (MOUSE_POINTER_ID, pos))
+                        .into_iter()
+                        .collect()
                 } else {
                     vec![]
                 };
+                // 已抬起的指针不再保留上次擦除的位置
+                state
+                    .last_erase_positions
+                    .retain(|id, _| eraser_positions.iter().any(|(active, _)| active == id));
+                // 所有指针抬起后擦除手势结束，下次擦除开始新的撤销记录
+                if eraser_positions.is_empty() {
+                    state.erase_history_revision = None;
+                }
 
-                for pos in eraser_positions {
-                    utils::draw_size_preview(painter, pos, state.eraser_size);
-
-                    let eraser_radius = state.eraser_size / 2.0;
-                    let eraser_rect = egui::Rect::from_center_size(
-                        pos,
-                        egui::vec2(state.eraser_size, state.eraser_size),
-                    );
-
-                    let mut new_strokes = Vec::new();
-                    let mut strokes_modified = false;
-
-                    for object in &state.canvas.objects {
-                        if let CanvasObject::Stroke(stroke) = object {
-                            if stroke.points.len() < 2 {
-                                let single_point = stroke.points[0];
-                                let dist = pos.distance(single_point);
-                                if dist > eraser_radius + stroke.width.first() / 2.0 {
-                                    new_strokes.push(stroke.clone());
-                                }
-                                strokes_modified = true;
-                                continue;
-                            }
-
-                            if !stroke.bounding_box().intersects(eraser_rect) {
-                                new_strokes.push(stroke.clone());
-                                continue;
-                            }
-
-                            strokes_modified = true;
-
-                            let mut current_points = Vec::new();
-                            let mut current_widths = Vec::new();
-
-                            current_points.push(stroke.points[0]);
-                            current_widths.push(stroke.width.first());
-
-                            for i in 0..stroke.points.len() - 1 {
-                                let p1 = stroke.points[i];
-                                let p2 = stroke.points[i + 1];
-                                let segment_width = stroke.width.get(i);
-
-                                let dist = utils::point_to_line_segment_distance(pos, p1, p2);
-
-                                if dist > eraser_radius + segment_width / 2.0 {
-                                    current_points.push(p2);
-                                    current_widths.push(stroke.width.get(i + 1));
-                                } else {
-                                    if current_points.len() >= 2 {
-                                        new_strokes.push(CanvasStroke {
-                                            points: current_points.clone(),
-                                            width: current_widths.clone().into(),
-                                            color: stroke.color,
-                                            base_width: stroke.base_width,
-                                            rot: 0.0,
-                                        });
-                                    }
-                                    current_points = Vec::new();
-                                    current_widths = Vec::new();
-                                }
-                            }
-
-                            if current_points.len() >= 2 {
-                                new_strokes.push(CanvasStroke {
-                                    points: current_points,
-                                    width: current_widths.into(),
-                                    color: stroke.color,
-                                    base_width: stroke.base_width,
-                                    rot: 0.0,
-                                });
-                            }
-                        }
+                for (id, pos) in eraser_positions {
+                    // 鼠标指针处的预览已在悬停时绘制
+                    if has_touch {
+                        utils::draw_size_preview(
+                            painter,
+                            viewport.to_screen(pos),
+                            state.eraser_size * viewport.scale,
+                        );
                     }
 
-                    if strokes_modified {
-                        let original_stroke_count = state
-                            .canvas
-                            .objects
-                            .iter()
-                            .filter(|obj| matches!(obj, CanvasObject::Stroke(_)))
-                            .count();
-                        let new_stroke_count = new_strokes.len();
-                        if original_stroke_count != new_stroke_count {
-                            let non_strokes: Vec<_> = state
-                                .canvas
-                                .objects
-                                .iter()
-                                .filter(|obj| !matches!(obj, CanvasObject::Stroke(_)))
-                                .cloned()
-                                .collect();
-                            let old_objects = std::mem::take(&mut state.canvas.objects);
-                            state.history.save_clear_objects(old_objects);
-                            state.canvas.objects = non_strokes;
-                        } else {
-                            state
-                                .canvas
-                                .objects
-                                .retain(|obj| !matches!(obj, CanvasObject::Stroke(_)));
-                        }
-
-                        for stroke in new_strokes {
-                            state.canvas.objects.push(CanvasObject::Stroke(stroke));
-                        }
+                    // 移动不足采样间距时不重复擦除；移动较快时在两次位置之间补充采样点，避免漏擦
+                    let Some(last) = state.last_erase_positions.get(&id).copied() else {
+                        state.last_erase_positions.insert(id, pos);
+                        pixel_erase_at(state, pos);
+                        continue;
+                    };
+                    let distance = last.distance(pos);
+                    if distance <= state.min_point_distance {
+                        continue;
+                    }
+                    let spacing = (state.eraser_size / 2.0).max(state.min_point_distance);
+                    let steps = (distance / spacing).ceil() as usize;
+                    for step in 1..=steps {
+                        pixel_erase_at(state, last.lerp(pos, step as f32 / steps as f32));
                     }
+                    state.last_erase_positions.insert(id, pos);
                 }
             }
 
-            CanvasTool::Brush => {
+            CanvasTool::Brush | CanvasTool::Highlighter => {
                 // Skip mouse handling if touch is active
                 if has_touch {
                     return;
@@ -2082,42 +3429,59 @@ pub fn ui_canvas(state: &mut AppState, ctx: &Context) {
 
                 let is_drawing = state
                     .pointers
-                    .get(&0)
+                    .get(&MOUSE_POINTER_ID)
                     .is_some_and(|p| matches!(p.interaction, PointerInteraction::Drawing { .. }));
 
                 // 画笔工具
                 if response.drag_started() {
                     if let Some(pos) = pointer_pos
-                        && pos.x >= rect.min.x
-                        && pos.x <= rect.max.x
-                        && pos.y >= rect.min.y
-                        && pos.y <= rect.max.y
+                        && canvas_rect.contains(pos)
                     {
-                        brush_stroke_start(state, 0, pos);
+                        brush_stroke_start(state, MOUSE_POINTER_ID, pos, None);
                     }
                 } else if response.dragged() {
                     if is_drawing && let Some(pos) = pointer_pos {
-                        brush_stroke_add_point(state, 0, pos, false);
+                        brush_stroke_add_point(state, MOUSE_POINTER_ID, pos, None, false);
                     }
                 } else if response.drag_stopped() {
                     if is_drawing {
-                        brush_stroke_end(state, 0);
+                        brush_stroke_end(state, MOUSE_POINTER_ID);
                     }
                 } else if response.clicked() {
                     // 处理单击事件 - 绘制单个点
                     if let Some(pos) = pointer_pos
-                        && pos.x >= rect.min.x
-                        && pos.x <= rect.max.x
-                        && pos.y >= rect.min.y
-                        && pos.y <= rect.max.y
+                        && canvas_rect.contains(pos)
                     {
-                        let new_stroke = CanvasStroke {
-                            points: vec![pos],
-                            width: StrokeWidth::Fixed(state.brush_width),
-                            color: state.brush_color,
-                            base_width: state.brush_width,
-                            rot: 0.0,
+                        let new_stroke = if state.current_tool == CanvasTool::Highlighter {
+                            let width = state.highlighter_width;
+                            CanvasStroke {
+                                id: CanvasStroke::new_id(),
+                                points: vec![pos],
+                                width: StrokeWidth::Fixed(width),
+                                color: highlighter_color(state.highlighter_color),
+                                base_width: width,
+                                rot: 0.0,
+                                is_highlighter: true,
+                                line_style: LineStyle::Solid,
+                                texture: None,
+                                style: BrushStyle::Marker,
+                                bounds: egui::Rect::NOTHING,
+                                layer: state.canvas.active_layer,
+                                group: None,
+                                locked: false,
+                            }
+                            .with_bounds()
+                        } else {
+                            new_brush_stroke(
+                                state,
+                                vec![pos],
+                                StrokeWidth::Fixed(state.brush_width),
+                                state.ink_color(),
+                            )
                         };
+                        state
+                            .collab
+                            .broadcast_stroke(state.current_page, &new_stroke);
                         let index = state.canvas.objects.len();
                         state
                             .history
@@ -2131,11 +3495,500 @@ pub fn ui_canvas(state: &mut AppState, ctx: &Context) {
                     && is_drawing
                     && let Some(pos) = pointer_pos
                 {
-                    brush_stroke_add_point(state, 0, pos, true);
+                    brush_stroke_add_point(state, MOUSE_POINTER_ID, pos, None, true);
                 }
             }
         }
     });
 }
 
-const IMAGE_FILE_EXTS: &[&str; 6] = &["png", "jpg", "jpeg", "bmp", "webp", "ico"];
+pub fn ui_autosave_recovery_prompt(state: &mut AppState, ctx: &Context) {
+    if !state.autosave.pending_recovery {
+        return;
+    }
+
+    let mut restore = false;
+    let mut discard = false;
+    egui::Window::new(t("恢复画板?"))
+        .id("autosave_recovery".into())
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            ui.label(t("上次运行未正常退出, 发现自动保存的画板."));
+            ui.horizontal(|ui| {
+                restore = ui.button(t("恢复")).clicked();
+                discard = ui.button(t("丢弃")).clicked();
+            });
+        });
+
+    if restore {
+        utils::ui::restore_autosave(state, ctx);
+    } else if discard {
+        state.autosave.discard();
+    }
+}
+
+pub fn ui_shape_recognition_prompt(state: &mut AppState, ctx: &Context) {
+    let Some((index, shape_type)) = state.pending_shape_recognition else {
+        return;
+    };
+    // 笔画已被撤销或删除时不再提示
+    let Some(CanvasObject::Stroke(stroke)) = state.canvas.objects.get(index) else {
+        state.pending_shape_recognition = None;
+        return;
+    };
+
+    let shape_name = match shape_type {
+        CanvasShapeType::Line => t("线"),
+        CanvasShapeType::Arrow => t("箭头"),
+        CanvasShapeType::Rectangle => t("矩形"),
+        CanvasShapeType::Triangle(_) => t("三角形"),
+        CanvasShapeType::Circle => t("圆形"),
+        CanvasShapeType::Ellipse => t("椭圆"),
+        CanvasShapeType::Polygon { .. } => t("多边形"),
+        CanvasShapeType::Star { .. } => t("星形"),
+    };
+    let mut convert = false;
+    let mut dismiss = false;
+    egui::Window::new(t("转换为形状?"))
+        .id("shape_recognition".into())
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -140.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            ui.label(tf("识别为: {}", &[&shape_name]));
+            ui.horizontal(|ui| {
+                convert = ui.button(t("转换")).clicked();
+                dismiss = ui.button(t("忽略")).clicked();
+            });
+        });
+
+    if convert {
+        let shape = CanvasObject::Shape(utils::shape_from_stroke(stroke, shape_type));
+        let old_object = std::mem::replace(&mut state.canvas.objects[index], shape.clone());
+        state.history.save_replace_object(index, old_object, shape);
+    }
+    if convert || dismiss {
+        state.pending_shape_recognition = None;
+    }
+}
+
+pub fn ui_object_context_menu(state: &mut AppState, ctx: &Context) {
+    let Some(menu) = state.context_menu else {
+        return;
+    };
+    // 画布改变后（例如撤销）下标可能已指向其他对象
+    let Some(object) = state
+        .canvas
+        .objects
+        .get(menu.index)
+        .filter(|_| state.history.revision() == menu.revision)
+    else {
+        state.context_menu = None;
+        return;
+    };
+    let locked = object.is_locked();
+    let is_text = matches!(object, CanvasObject::Text(_));
+
+    let mut clicked = false;
+    let response = egui::Area::new("object_context_menu".into())
+        .order(egui::Order::Foreground)
+        .fixed_pos(menu.pos)
+        .constrain(true)
+        .show(ctx, |ui| {
+            egui::Frame::menu(ui.style()).show(ui, |ui| {
+                ui.set_min_width(120.0);
+                // 锁定的对象只能解锁
+                if locked {
+                    if ui.button(t("解锁")).clicked() {
+                        unlock_object(state, menu.index);
+                        clicked = true;
+                    }
+                    return;
+                }
+                if is_text && ui.button(t("编辑文本")).clicked() {
+                    open_text_editor(state, menu.index);
+                    clicked = true;
+                }
+                if ui.button(t("删除")).clicked() {
+                    delete_selected_object(state);
+                    clicked = true;
+                }
+                // 创建副本仅支持单个对象
+                if state.selected_object_index.is_some() && ui.button(t("复制")).clicked() {
+                    duplicate_selected_object(state);
+                    clicked = true;
+                }
+                if ui.button(t("置顶")).clicked() {
+                    bring_selected_to_front(state);
+                    clicked = true;
+                }
+                if ui.button(t("置底")).clicked() {
+                    send_selected_to_back(state);
+                    clicked = true;
+                }
+                if ui.button(t("锁定")).clicked() {
+                    lock_selected_objects(state);
+                    clicked = true;
+                }
+            });
+        })
+        .response;
+
+    // 在菜单外按下或按 Esc 时关闭菜单
+    let pressed_outside = ctx.input(|i| {
+        i.pointer.any_pressed()
+            && i.pointer
+                .interact_pos()
+                .is_some_and(|pos| !response.rect.contains(pos))
+    });
+    if clicked || pressed_outside || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        state.context_menu = None;
+    }
+}
+
+// 打开指针下对象的右键菜单，未锁定的对象同时被选中，菜单中的操作作用于选中的对象
+fn open_object_context_menu(state: &mut AppState, screen_pos: Pos2) {
+    let pos = state.viewport.to_canvas(screen_pos);
+    let Some(index) = utils::hit_test_objects(&mut state.canvas, pos) else {
+        state.context_menu = None;
+        return;
+    };
+    if state.canvas.objects[index].is_locked() {
+        state.clear_selection();
+    } else if !state.is_selected(index) {
+        state.select_object(index);
+    }
+    state.context_menu = Some(ObjectContextMenu {
+        index,
+        pos: screen_pos,
+        revision: state.history.revision(),
+    });
+}
+
+// 打开文本对话框编辑 index 处的文本，其他对象不做任何事
+fn open_text_editor(state: &mut AppState, index: usize) {
+    let Some(CanvasObject::Text(text)) = state.canvas.objects.get(index) else {
+        return;
+    };
+    state.new_text_content = text.text.clone();
+    state.selected_objects.clear();
+    state.selected_object_index = Some(index);
+    state.editing_text_index = Some(index);
+    state.show_insert_text_window = true;
+}
+
+fn insert_shape(state: &mut AppState, shape_type: CanvasShapeType) {
+    state.show_insert_shape_window = state.persistent.keep_insertion_window_open;
+    if state.persistent.drag_to_insert_shapes {
+        state.pending_shape = Some(shape_type);
+        return;
+    }
+
+    // Save state to history before modification
+    let pos = state.new_object_pos();
+    let end = match shape_type {
+        CanvasShapeType::Line | CanvasShapeType::Arrow => pos + egui::vec2(100.0, 0.0),
+        CanvasShapeType::Ellipse => pos + egui::vec2(150.0, 100.0),
+        _ => pos + egui::vec2(100.0, 100.0),
+    };
+    let new_shape = new_shape(state, shape_type, pos, end);
+    let index = state.canvas.objects.len();
+    state
+        .history
+        .save_add_object(index, CanvasObject::Shape(new_shape.clone()));
+    state.canvas.objects.push(CanvasObject::Shape(new_shape));
+}
+
+// 绘制对称轴，线宽不随缩放变化
+fn draw_symmetry_guides(painter: &egui::Painter, symmetry: SymmetryMode, rect: Rect, scale: f32) {
+    let stroke = Stroke::new(
+        1.0 / scale,
+        Color32::from_rgb(0, 120, 255).gamma_multiply(0.4),
+    );
+    let center = rect.center();
+    match symmetry {
+        SymmetryMode::Off => {}
+        SymmetryMode::Vertical => {
+            painter.vline(center.x, rect.y_range(), stroke);
+        }
+        SymmetryMode::Horizontal => {
+            painter.hline(rect.x_range(), center.y, stroke);
+        }
+        SymmetryMode::Radial { folds } => {
+            let radius = rect.size().length() / 2.0;
+            for i in 0..folds {
+                let angle =
+                    i as f32 / folds as f32 * std::f32::consts::TAU - std::f32::consts::FRAC_PI_2;
+                painter.line_segment(
+                    [center, center + egui::Vec2::angled(angle) * radius],
+                    stroke,
+                );
+            }
+        }
+    }
+}
+
+// 裁剪时显示整张图片，裁剪区域以外的部分变暗
+fn paint_crop_preview(painter: &egui::Painter, image: &CanvasImage, crop: Rect, scale: f32) {
+    let full = image.uncropped_rect();
+    let center = image.local_rect().center();
+    let to_canvas = |uv: Pos2| full.min + uv.to_vec2() * full.size();
+    let crop_rect = Rect::from_min_max(to_canvas(crop.min), to_canvas(crop.max));
+
+    let tint = Color32::WHITE.gamma_multiply(image.opacity as f32 / 255.0);
+    let mut mesh = egui::Mesh::with_texture(image.texture_id(painter.ctx()));
+    mesh.add_rect_with_uv(full, FULL_IMAGE_CROP, tint.gamma_multiply(0.35));
+    mesh.add_rect_with_uv(crop_rect, crop, tint);
+    if image.rot.abs() > 0.001 {
+        mesh.rotate(egui::emath::Rot2::from_angle(image.rot), center);
+    }
+    painter.add(egui::Shape::mesh(mesh));
+
+    let corners = [
+        crop_rect.left_top(),
+        crop_rect.right_top(),
+        crop_rect.right_bottom(),
+        crop_rect.left_bottom(),
+    ]
+    .map(|corner| utils::rotate_point(corner, center, image.rot));
+    painter.add(egui::Shape::closed_line(
+        corners.to_vec(),
+        Stroke::new(2.0 / scale, Color32::from_rgb(0, 120, 255)),
+    ));
+}
+
+// 绘制正在绘制的笔画
+// TODO: unify with CanvasStroke::paint()
+fn paint_active_stroke(
+    painter: &egui::Painter,
+    state: &AppState,
+    points: &[Pos2],
+    width: &StrokeWidth,
+    is_highlighter: bool,
+) {
+    if is_highlighter {
+        let color = highlighter_color(state.highlighter_color);
+        if points.len() >= 2 {
+            painter.add(egui::Shape::line(
+                points.to_vec(),
+                Stroke::new(state.highlighter_width, color),
+            ));
+        } else {
+            painter.circle_filled(points[0], state.highlighter_width / 2.0, color);
+        }
+        return;
+    }
+    // 粉笔和铅笔会调整宽度和颜色，直接按提交后的样子绘制
+    if state.brush_style != BrushStyle::Marker {
+        new_brush_stroke(state, points.to_vec(), width.clone(), state.ink_color())
+            .paint(painter, false);
+        return;
+    }
+    if state.brush_line_style != LineStyle::Solid && points.len() >= 2 {
+        utils::paint_styled_line(
+            painter,
+            points,
+            Stroke::new(state.brush_width, state.ink_color()),
+            state.brush_line_style,
+            false,
+        );
+        return;
+    }
+    if let Some(texture_id) = state
+        .brush_texture
+        .as_ref()
+        .and_then(|texture| brush_texture::texture_id(painter.ctx(), texture))
+    {
+        let widths = match width {
+            StrokeWidth::Fixed(w) => vec![*w; points.len()],
+            StrokeWidth::Dynamic(widths) => widths.clone(),
+        };
+        utils::paint_textured_stroke(painter, points, &widths, state.ink_color(), texture_id);
+        return;
+    }
+    match width {
+        StrokeWidth::Fixed(w) => {
+            painter.circle_filled(points[0], w / 2.0, state.ink_color());
+            if points.len() >= 2 {
+                painter.circle_filled(points[points.len() - 1], w / 2.0, state.ink_color());
+                painter.add(egui::Shape::line(
+                    points.to_vec(),
+                    Stroke::new(*w, state.ink_color()),
+                ));
+            }
+        }
+        StrokeWidth::Dynamic(widths) => {
+            utils::paint_variable_width_stroke(painter, points, widths, state.ink_color());
+        }
+    }
+}
+
+// 画笔颜色与画布颜色过于接近时提示，悬浮窗模式下画布透明，无法判断
+fn ink_contrast_warning(state: &AppState, ui: &mut Ui) {
+    if state.is_overlay_mode
+        || utils::contrast_ratio(state.brush_color, state.persistent.canvas_color)
+            >= utils::MIN_INK_CONTRAST
+    {
+        return;
+    }
+    ui.colored_label(
+        ui.visuals().warn_fg_color,
+        t("⚠ 画笔颜色与画布颜色对比度过低, 笔迹可能难以看清"),
+    );
+}
+
+fn color_swatch(ui: &mut Ui, color: Color32) -> egui::Response {
+    ui.add(
+        egui::Button::new("")
+            .fill(color)
+            .min_size(egui::vec2(20.0, 20.0)),
+    )
+}
+
+// 像素橡皮擦在一个位置擦除
+fn pixel_erase_at(state: &mut AppState, pos: Pos2) {
+    // 只检查橡皮擦范围附近的笔画，倒序处理以便原位替换后前面的索引保持不变
+    let candidates = state.canvas.objects_in_rect(egui::Rect::from_center_size(
+        pos,
+        egui::Vec2::splat(state.eraser_size),
+    ));
+    for i in candidates.into_iter().rev() {
+        if !state.is_object_erasable(i) {
+            continue;
+        }
+        let CanvasObject::Stroke(stroke) = &state.canvas.objects[i] else {
+            continue;
+        };
+        let Some(pieces) = utils::erase_stroke_at(stroke, pos, state.eraser_size) else {
+            continue;
+        };
+        let pieces: Vec<CanvasObject> = pieces.into_iter().map(CanvasObject::Stroke).collect();
+        let object = state
+            .canvas
+            .objects
+            .splice(i..i + 1, pieces.iter().cloned())
+            .pop()
+            .unwrap();
+        // 其他成员删除原笔画并添加各个片段，片段各有新的编号
+        state.collab.broadcast_removal(state.current_page, &object);
+        for piece in &pieces {
+            if let CanvasObject::Stroke(piece) = piece {
+                state.collab.broadcast_stroke(state.current_page, piece);
+            }
+        }
+        // 同一手势中的拆分合并为一条记录，期间有其他修改时另起一条
+        let merge = state.erase_history_revision == Some(state.history.revision());
+        state.history.save_split_object(i, object, pieces, merge);
+        state.erase_history_revision = Some(state.history.revision());
+    }
+}
+
+// 画布上的光标样式，绘制类工具使用十字光标以便精确定位
+fn tool_cursor_icon(state: &AppState, dragging: bool) -> egui::CursorIcon {
+    match state.current_tool {
+        CanvasTool::Brush | CanvasTool::Highlighter | CanvasTool::Eyedropper | CanvasTool::Fill => {
+            egui::CursorIcon::Crosshair
+        }
+        // 橡皮擦范围预览代替了光标
+        CanvasTool::ObjectEraser | CanvasTool::PixelEraser => egui::CursorIcon::None,
+        CanvasTool::Insert if state.pending_shape.is_some() => egui::CursorIcon::Crosshair,
+        CanvasTool::Pan if dragging => egui::CursorIcon::Grabbing,
+        CanvasTool::Pan => egui::CursorIcon::Grab,
+        CanvasTool::Select if dragging => egui::CursorIcon::Move,
+        CanvasTool::Select
+        | CanvasTool::Insert
+        | CanvasTool::Measure
+        | CanvasTool::Settings
+        | CanvasTool::Passthrough => egui::CursorIcon::Default,
+    }
+}
+
+// 绘制宽度倍数随笔画进度、速度或压力变化的曲线
+fn dynamic_width_preview(ui: &mut Ui, mode: DynamicBrushWidthMode, curve: &DynamicWidthCurve) {
+    const SAMPLES: usize = 64;
+    const MAX_MULTIPLIER: f32 = 2.0;
+
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(200.0, 60.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    // 原始宽度的参考线
+    let baseline = rect.bottom() - rect.height() / MAX_MULTIPLIER;
+    painter.hline(
+        rect.x_range(),
+        baseline,
+        Stroke::new(1.0, ui.visuals().weak_text_color()),
+    );
+
+    let points: Vec<Pos2> = (0..=SAMPLES)
+        .map(|i| {
+            let t = i as f32 / SAMPLES as f32;
+            let width = match mode {
+                // 横轴为笔画进度
+                DynamicBrushWidthMode::BrushTip => {
+                    utils::calculate_dynamic_width(1.0, mode, curve, i, SAMPLES, None, None)
+                }
+                // 横轴为 0 到两倍最细速度
+                DynamicBrushWidthMode::SpeedBased => utils::calculate_dynamic_width(
+                    1.0,
+                    mode,
+                    curve,
+                    0,
+                    1,
+                    Some(t * 2.0 * curve.speed_normalization),
+                    None,
+                ),
+                // 横轴为压力
+                DynamicBrushWidthMode::Pressure | DynamicBrushWidthMode::Disabled => {
+                    utils::calculate_dynamic_width(
+                        1.0,
+                        mode,
+                        curve,
+                        0,
+                        1,
+                        None,
+                        Some(winit::event::Force::Normalized(t as f64)),
+                    )
+                }
+            };
+            let y = (width.first() / MAX_MULTIPLIER).clamp(0.0, 1.0);
+            rect.lerp_inside(egui::vec2(t, 1.0 - y))
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        Stroke::new(2.0, ui.visuals().selection.bg_fill),
+    ));
+}
+
+// 编辑滑块的最小值与最大值，返回是否修改
+fn range_setting(
+    ui: &mut Ui,
+    range: &mut RangeInclusive<f32>,
+    limits: RangeInclusive<f32>,
+) -> bool {
+    let (mut min, mut max) = (*range.start(), *range.end());
+    let min_changed = ui
+        .add(egui::DragValue::new(&mut min).range(*limits.start()..=max))
+        .changed();
+    ui.label(t("至"));
+    let max_changed = ui
+        .add(egui::DragValue::new(&mut max).range(min..=*limits.end()))
+        .changed();
+    let changed = min_changed || max_changed;
+    if changed {
+        *range = min..=max;
+    }
+    changed
+}
+
+fn line_style_selector(ui: &mut Ui, line_style: &mut LineStyle) {
+    ui.selectable_value(line_style, LineStyle::Solid, t("实线"));
+    ui.selectable_value(line_style, LineStyle::Dashed, t("虚线"));
+    ui.selectable_value(line_style, LineStyle::Dotted, t("点线"));
+}
+
+const IMAGE_FILE_EXTS: &[&str; 7] = &["png", "jpg", "jpeg", "bmp", "webp", "ico", "gif"];
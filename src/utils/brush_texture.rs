@@ -0,0 +1,218 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use egui::{
+    Color32, ColorImage, Context, Id, Mesh, Painter, Pos2, TextureHandle, TextureId,
+    TextureOptions, Vec2,
+};
+use image::GenericImageView;
+
+use crate::state::{CustomStrokeTexture, StrokeTexture};
+
+/// Size of one texture tile in canvas units
+pub const TEXTURE_TILE_SIZE: f32 = 64.0;
+// 内置纹理的边长，与一个纹理块在画布上的大小一致
+const BUILTIN_SIZE: usize = 64;
+// 自定义纹理缩小到不超过此边长，避免每个笔画都存一张大图
+const MAX_CUSTOM_SIZE: u32 = 256;
+
+/// Distance between two clusters of chalk dots, relative to the stroke width
+const CHALK_STEP: f32 = 0.2;
+const MIN_CHALK_STEP: f32 = 1.0;
+/// Radius of a chalk dot, relative to the stroke width
+const CHALK_DOT_RADIUS: f32 = 0.08;
+const MIN_CHALK_DOT_RADIUS: f32 = 0.6;
+const CHALK_DOTS_PER_STEP: usize = 6;
+const CHALK_ALPHA: std::ops::Range<f32> = 0.25..0.6;
+
+/// Returns the texture `texture` is drawn with, uploading it on first use; `None` if a custom
+/// texture cannot be decoded
+pub fn texture_id(ctx: &Context, texture: &StrokeTexture) -> Option<TextureId> {
+    let id = match texture {
+        StrokeTexture::Chalk => Id::new(("stroke_texture", "chalk")),
+        StrokeTexture::Grain => Id::new(("stroke_texture", "grain")),
+        StrokeTexture::Canvas => Id::new(("stroke_texture", "canvas")),
+        StrokeTexture::Custom(custom) => Id::new(("stroke_texture", custom.id)),
+    };
+    // 纹理缓存在上下文中，重建渲染器时会随新的上下文一起重新上传
+    if let Some(handle) = ctx.data(|data| data.get_temp::<Option<TextureHandle>>(id)) {
+        return handle.map(|handle| handle.id());
+    }
+    let image = match texture {
+        StrokeTexture::Chalk => Some(builtin_mask(chalk_alpha)),
+        StrokeTexture::Grain => Some(builtin_mask(grain_alpha)),
+        StrokeTexture::Canvas => Some(builtin_mask(canvas_alpha)),
+        StrokeTexture::Custom(custom) => decode_custom(&custom.png),
+    };
+    let handle = image.map(|image| {
+        ctx.load_texture(
+            "stroke_texture",
+            image,
+            TextureOptions {
+                wrap_mode: egui::TextureWrapMode::Repeat,
+                ..TextureOptions::LINEAR
+            },
+        )
+    });
+    let texture_id = handle.as_ref().map(TextureHandle::id);
+    ctx.data_mut(|data| data.insert_temp(id, handle));
+    texture_id
+}
+
+/// Draws a chalk stroke as low-alpha dots scattered along the path; the dots only depend on the
+/// distance along the path, so they stay in place while the stroke is being drawn
+#[cfg_attr(feature = "profiling", profiling::function)]
+pub fn paint_chalk_stroke(painter: &Painter, points: &[Pos2], width: f32, color: Color32) {
+    let Some(&first) = points.first() else {
+        return;
+    };
+    let step = (width * CHALK_STEP).max(MIN_CHALK_STEP);
+    let radius = (width * CHALK_DOT_RADIUS).max(MIN_CHALK_DOT_RADIUS);
+    let mut mesh = Mesh::default();
+    let mut scatter = |center: Pos2, along: Vec2, index: usize| {
+        let across = along.rot90();
+        for k in 0..CHALK_DOTS_PER_STEP {
+            let noise = |seed: u32| hash_noise(index, k, seed);
+            // 两个均匀分布相加，颗粒集中在中线附近，边缘逐渐稀疏
+            let offset = along * ((noise(10) - 0.5) * step)
+                + across * ((noise(11) + noise(12) - 1.0) * width / 2.0);
+            let alpha = CHALK_ALPHA.start + (CHALK_ALPHA.end - CHALK_ALPHA.start) * noise(13);
+            let dot_color = color.gamma_multiply(alpha);
+            let dot_radius = radius * (0.5 + noise(14));
+            let angle = noise(15) * std::f32::consts::TAU;
+            let base = mesh.vertices.len() as u32;
+            for corner in 0..4 {
+                let corner_angle = angle + corner as f32 * std::f32::consts::FRAC_PI_2;
+                mesh.colored_vertex(
+                    center + offset + Vec2::angled(corner_angle) * dot_radius,
+                    dot_color,
+                );
+            }
+            mesh.add_triangle(base, base + 1, base + 2);
+            mesh.add_triangle(base, base + 2, base + 3);
+        }
+    };
+
+    let mut index = 0;
+    if points.len() == 1 {
+        scatter(first, Vec2::X, index);
+    }
+    // 到下一簇颗粒的距离，从当前线段的起点算起
+    let mut next = 0.0;
+    for segment in points.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        let length = a.distance(b);
+        if length <= 0.0 {
+            continue;
+        }
+        let along = (b - a) / length;
+        while next <= length {
+            scatter(a + along * next, along, index);
+            index += 1;
+            next += step;
+        }
+        next -= length;
+    }
+    painter.add(mesh);
+}
+
+/// Loads an image as a brush texture; its brightness decides how opaque the stroke is
+pub fn load_custom_texture(path: &Path) -> image::ImageResult<StrokeTexture> {
+    let mut image = image::open(path)?;
+    let (width, height) = image.dimensions();
+    if width > MAX_CUSTOM_SIZE || height > MAX_CUSTOM_SIZE {
+        image = image.resize(
+            MAX_CUSTOM_SIZE,
+            MAX_CUSTOM_SIZE,
+            image::imageops::FilterType::Triangle,
+        );
+    }
+    let rgba = image.to_rgba8();
+    let mut mask = image::RgbaImage::new(rgba.width(), rgba.height());
+    for (source, target) in rgba.pixels().zip(mask.pixels_mut()) {
+        let [r, g, b, a] = source.0;
+        let luma = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) / 255.0;
+        *target = image::Rgba([255, 255, 255, (luma * a as f32).round() as u8]);
+    }
+    let mut png = Vec::new();
+    mask.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(StrokeTexture::Custom(Arc::new(CustomStrokeTexture::new(
+        png,
+    ))))
+}
+
+fn decode_custom(png: &[u8]) -> Option<ColorImage> {
+    let image = image::load_from_memory_with_format(png, image::ImageFormat::Png)
+        .inspect_err(|err| eprintln!("failed to decode stroke texture: {err}"))
+        .ok()?
+        .to_rgba8();
+    Some(ColorImage::from_rgba_unmultiplied(
+        [image.width() as usize, image.height() as usize],
+        image.as_raw(),
+    ))
+}
+
+// 内置纹理只有透明度变化，颜色由笔画颜色决定
+fn builtin_mask(alpha: fn(usize, usize) -> f32) -> ColorImage {
+    let rgba: Vec<u8> = (0..BUILTIN_SIZE * BUILTIN_SIZE)
+        .flat_map(|i| {
+            let a = alpha(i % BUILTIN_SIZE, i / BUILTIN_SIZE).clamp(0.0, 1.0);
+            [255, 255, 255, (a * 255.0).round() as u8]
+        })
+        .collect();
+    ColorImage::from_rgba_unmultiplied([BUILTIN_SIZE, BUILTIN_SIZE], &rgba)
+}
+
+// 确定性的整数哈希噪声，返回 0..1
+fn hash_noise(x: usize, y: usize, seed: u32) -> f32 {
+    let mut h = (x as u32)
+        .wrapping_mul(0x27d4_eb2d)
+        .wrapping_add((y as u32).wrapping_mul(0x1656_67b1))
+        .wrapping_add(seed.wrapping_mul(0x9e37_79b9));
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h as f32 / u32::MAX as f32
+}
+
+// 以 cell 为格距插值的噪声，格点按纹理边长取模，保证纹理能无缝平铺
+fn value_noise(x: usize, y: usize, cell: usize, seed: u32) -> f32 {
+    let cells = BUILTIN_SIZE / cell;
+    let (cx, cy) = (x / cell, y / cell);
+    let fx = (x % cell) as f32 / cell as f32;
+    let fy = (y % cell) as f32 / cell as f32;
+    let (sx, sy) = (fx * fx * (3.0 - 2.0 * fx), fy * fy * (3.0 - 2.0 * fy));
+    let corner = |dx: usize, dy: usize| hash_noise((cx + dx) % cells, (cy + dy) % cells, seed);
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * sx;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * sx;
+    top + (bottom - top) * sy
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn chalk_alpha(x: usize, y: usize) -> f32 {
+    let coarse = value_noise(x, y, 8, 1) * 0.6 + value_noise(x, y, 4, 2) * 0.4;
+    let grit = hash_noise(x, y, 3);
+    // 粉笔在纸面凹处留下空隙
+    smoothstep(0.3, 0.6, coarse * 0.7 + grit * 0.3)
+}
+
+fn grain_alpha(x: usize, y: usize) -> f32 {
+    0.65 + value_noise(x, y, 16, 4) * 0.2 + hash_noise(x, y, 5) * 0.15
+}
+
+fn canvas_alpha(x: usize, y: usize) -> f32 {
+    use std::f32::consts::PI;
+    // 经线和纬线各 16 根，交替压在上面
+    let thread = BUILTIN_SIZE as f32 / 16.0;
+    let warp = ((x as f32 + 0.5) / thread * PI).sin().abs();
+    let weft = ((y as f32 + 0.5) / thread * PI).sin().abs();
+    let over = (x / thread as usize + y / thread as usize) % 2 == 0;
+    let weave = if over { warp } else { weft };
+    0.45 + weave * 0.4 + hash_noise(x, y, 6) * 0.15
+}
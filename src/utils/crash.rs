@@ -0,0 +1,75 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::i18n::{t, tf};
+
+// 崩溃日志目录，与自动保存文件位于同一数据目录下
+fn log_dir() -> PathBuf {
+    let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("uwu");
+    path.push("logs");
+    path
+}
+
+/// Writes `details` to a new log file named after the current time, returning its path
+pub fn write_error_log(details: &str) -> std::io::Result<PathBuf> {
+    let dir = log_dir();
+    std::fs::create_dir_all(&dir)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let path = dir.join(format!("crash-{timestamp}.log"));
+    let mut file = std::fs::File::create(&path)?;
+    writeln!(file, "uwu {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(
+        file,
+        "os: {} ({})",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )?;
+    writeln!(file)?;
+    file.write_all(details.as_bytes())?;
+    Ok(path)
+}
+
+/// Logs a fatal error and shows it in a dialog that can copy the details to the clipboard.
+/// `summary` is shown to the user, `details` (e.g. with a backtrace) only goes to the log and the clipboard.
+pub fn report_fatal_error(summary: &str, details: &str) {
+    eprintln!("{details}");
+    let description = match write_error_log(details) {
+        Ok(path) => tf("{}\n\n详细信息已保存到: {}", &[&summary, &path.display()]),
+        Err(err) => {
+            eprintln!("failed to write error log: {err}");
+            summary.to_string()
+        }
+    };
+
+    let copy = t("复制详细信息");
+    let result = rfd::MessageDialog::new()
+        .set_title(t("应用崩溃"))
+        .set_level(rfd::MessageLevel::Error)
+        .set_description(description)
+        .set_buttons(rfd::MessageButtons::OkCancelCustom(
+            copy.to_string(),
+            t("关闭").to_string(),
+        ))
+        .show();
+    if !matches!(&result, rfd::MessageDialogResult::Custom(label) if label == copy) {
+        return;
+    }
+    match arboard::Clipboard::new().and_then(|mut c| c.set_text(details).map(|_| c)) {
+        // X11 等平台上剪贴板内容由本进程提供，进程退出或剪贴板被释放后就无法粘贴
+        // 因此在用户粘贴完并关闭提示前保持剪贴板存活
+        Ok(clipboard) => {
+            rfd::MessageDialog::new()
+                .set_title(t("应用崩溃"))
+                .set_level(rfd::MessageLevel::Info)
+                .set_description(t("详细信息已复制, 请在粘贴后再关闭此窗口"))
+                .set_buttons(rfd::MessageButtons::Ok)
+                .show();
+            drop(clipboard);
+        }
+        Err(err) => eprintln!("failed to copy error details: {err}"),
+    }
+}
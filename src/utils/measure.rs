@@ -0,0 +1,292 @@
+use std::f32::consts::TAU;
+
+use egui::{Align2, Color32, FontId, Painter, Pos2, Shape, Stroke, Vec2};
+
+use crate::state::{
+    AppState, MeasureHandle, MeasureKind, MeasureState, PointerInteraction, PointerState,
+};
+
+/// Canvas units per centimeter, counting 96 units per inch like CSS pixels
+pub const UNITS_PER_CM: f32 = 96.0 / 2.54;
+
+/// Width of the ruler, measured from its edge with the tick marks
+const RULER_WIDTH: f32 = 60.0;
+/// Radius of the protractor
+const PROTRACTOR_RADIUS: f32 = 160.0;
+/// Radius of the drag handles on screen, independent of the zoom
+const HANDLE_RADIUS: f32 = 10.0;
+// 刻度在屏幕上的间距小于此值时不再绘制，避免缩小后挤成一片
+const MIN_TICK_SPACING: f32 = 4.0;
+
+const FILL_COLOR: Color32 = Color32::from_rgba_premultiplied(150, 147, 121, 150);
+const LINE_COLOR: Color32 = Color32::from_rgb(60, 60, 60);
+const ACCENT_COLOR: Color32 = Color32::from_rgb(0, 120, 255);
+
+/// Length of the ruler in canvas units
+pub fn ruler_length(measure: &MeasureState) -> f32 {
+    measure.ruler_start.distance(measure.ruler_end)
+}
+
+/// Direction of the ruler in degrees, counter-clockwise from the horizontal like in maths
+pub fn ruler_angle_degrees(measure: &MeasureState) -> f32 {
+    let direction = measure.ruler_end - measure.ruler_start;
+    (-direction.angle()).to_degrees().rem_euclid(360.0)
+}
+
+fn angle_arm_direction(measure: &MeasureState) -> f32 {
+    measure.protractor_base - measure.protractor_angle
+}
+
+// 直尺刻度边的法向量，直尺的主体位于刻度边的这一侧
+fn ruler_normal(measure: &MeasureState) -> Vec2 {
+    let direction = (measure.ruler_end - measure.ruler_start).normalized();
+    Vec2::new(-direction.y, direction.x)
+}
+
+fn handle_at(measure: &MeasureState, pos: Pos2, scale: f32) -> Option<MeasureHandle> {
+    let handle_radius = HANDLE_RADIUS / scale;
+    match measure.kind {
+        MeasureKind::Ruler => {
+            if pos.distance(measure.ruler_end) <= handle_radius {
+                return Some(MeasureHandle::RulerEnd);
+            }
+            let length = ruler_length(measure);
+            if length <= 0.0 {
+                return None;
+            }
+            let offset = pos - measure.ruler_start;
+            let along = offset.dot((measure.ruler_end - measure.ruler_start) / length);
+            let across = offset.dot(ruler_normal(measure));
+            ((0.0..=length).contains(&along) && (0.0..=RULER_WIDTH).contains(&across))
+                .then_some(MeasureHandle::Body)
+        }
+        MeasureKind::Protractor => {
+            let center = measure.protractor_center;
+            let arm_end = center + Vec2::angled(angle_arm_direction(measure)) * PROTRACTOR_RADIUS;
+            let base_end = center + Vec2::angled(measure.protractor_base) * PROTRACTOR_RADIUS;
+            // 两条边重合时优先拖动测量边
+            if pos.distance(arm_end) <= handle_radius {
+                Some(MeasureHandle::AngleArm)
+            } else if pos.distance(base_end) <= handle_radius {
+                Some(MeasureHandle::BaseArm)
+            } else if pos.distance(center) <= PROTRACTOR_RADIUS {
+                Some(MeasureHandle::Body)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+// 按住直尺或量角器时开始拖动，按在其他位置时不做任何事
+pub fn measure_drag_start(state: &mut AppState, id: u64, pos: Pos2) {
+    let Some(handle) = handle_at(&state.measure, pos, state.viewport.scale) else {
+        return;
+    };
+    state.pointers.insert(
+        id,
+        PointerState {
+            id,
+            pos,
+            interaction: PointerInteraction::Measuring {
+                handle,
+                last_pos: pos,
+            },
+        },
+    );
+}
+
+pub fn measure_drag_move(state: &mut AppState, id: u64, pos: Pos2) {
+    let Some(pointer) = state.pointers.get_mut(&id) else {
+        return;
+    };
+    let PointerInteraction::Measuring { handle, last_pos } = &mut pointer.interaction else {
+        return;
+    };
+    let delta = pos - *last_pos;
+    *last_pos = pos;
+    pointer.pos = pos;
+
+    let measure = &mut state.measure;
+    match *handle {
+        MeasureHandle::Body => match measure.kind {
+            MeasureKind::Ruler => {
+                measure.ruler_start += delta;
+                measure.ruler_end += delta;
+            }
+            MeasureKind::Protractor => measure.protractor_center += delta,
+        },
+        MeasureHandle::RulerEnd => measure.ruler_end = pos,
+        MeasureHandle::BaseArm => {
+            if pos != measure.protractor_center {
+                measure.protractor_base = (pos - measure.protractor_center).angle();
+            }
+        }
+        MeasureHandle::AngleArm => {
+            if pos != measure.protractor_center {
+                let arm = (pos - measure.protractor_center).angle();
+                measure.protractor_angle = (measure.protractor_base - arm).rem_euclid(TAU);
+            }
+        }
+    }
+}
+
+pub fn measure_drag_end(state: &mut AppState, id: u64) {
+    state.pointers.remove(&id);
+}
+
+/// Draws the ruler or protractor of `measure`; line widths and handles keep their size on screen
+pub fn paint_measure(painter: &Painter, measure: &MeasureState, scale: f32) {
+    match measure.kind {
+        MeasureKind::Ruler => paint_ruler(painter, measure, scale),
+        MeasureKind::Protractor => paint_protractor(painter, measure, scale),
+    }
+}
+
+fn paint_ruler(painter: &Painter, measure: &MeasureState, scale: f32) {
+    let (start, end) = (measure.ruler_start, measure.ruler_end);
+    let length = ruler_length(measure);
+    let line = Stroke::new(1.0 / scale, LINE_COLOR);
+    if length >= 1.0 {
+        let along = (end - start) / length;
+        let normal = ruler_normal(measure);
+        painter.add(Shape::convex_polygon(
+            vec![
+                start,
+                end,
+                end + normal * RULER_WIDTH,
+                start + normal * RULER_WIDTH,
+            ],
+            FILL_COLOR,
+            line,
+        ));
+
+        // 毫米刻度太密时只画半厘米和整厘米刻度
+        let mm = UNITS_PER_CM / 10.0;
+        let step = if mm * scale >= MIN_TICK_SPACING {
+            1
+        } else if mm * 5.0 * scale >= MIN_TICK_SPACING {
+            5
+        } else {
+            10
+        };
+        let font = FontId::proportional(RULER_WIDTH * 0.22);
+        for tick in (0..=(length / mm) as u32).step_by(step) {
+            let base = start + along * (tick as f32 * mm);
+            let tick_length = if tick % 10 == 0 {
+                RULER_WIDTH * 0.35
+            } else if tick % 5 == 0 {
+                RULER_WIDTH * 0.25
+            } else {
+                RULER_WIDTH * 0.15
+            };
+            painter.line_segment([base, base + normal * tick_length], line);
+            if tick % 10 == 0 {
+                painter.text(
+                    base + normal * (RULER_WIDTH * 0.55),
+                    Align2::CENTER_CENTER,
+                    (tick / 10).to_string(),
+                    font.clone(),
+                    LINE_COLOR,
+                );
+            }
+        }
+    }
+
+    painter.line_segment([start, end], Stroke::new(2.0 / scale, ACCENT_COLOR));
+    painter.circle_filled(end, HANDLE_RADIUS / scale, ACCENT_COLOR);
+    paint_readout(
+        painter,
+        end + Vec2::splat(HANDLE_RADIUS * 1.5 / scale),
+        Align2::LEFT_TOP,
+        &format!(
+            "{:.1} cm · {:.0} px · {:.1}°",
+            length / UNITS_PER_CM,
+            length,
+            ruler_angle_degrees(measure)
+        ),
+        scale,
+    );
+}
+
+fn paint_protractor(painter: &Painter, measure: &MeasureState, scale: f32) {
+    let center = measure.protractor_center;
+    let line = Stroke::new(1.0 / scale, LINE_COLOR);
+    painter.circle(center, PROTRACTOR_RADIUS, FILL_COLOR, line);
+
+    // 每度一条刻度，太密时只画 5° 和 10° 的刻度
+    let degree = PROTRACTOR_RADIUS * TAU / 360.0;
+    let step = if degree * scale >= MIN_TICK_SPACING {
+        1
+    } else {
+        5
+    };
+    let font = FontId::proportional(PROTRACTOR_RADIUS * 0.08);
+    for deg in (0..360).step_by(step) {
+        let dir = Vec2::angled(measure.protractor_base - (deg as f32).to_radians());
+        let tick_length = if deg % 10 == 0 {
+            PROTRACTOR_RADIUS * 0.12
+        } else if deg % 5 == 0 {
+            PROTRACTOR_RADIUS * 0.08
+        } else {
+            PROTRACTOR_RADIUS * 0.04
+        };
+        let outer = center + dir * PROTRACTOR_RADIUS;
+        painter.line_segment([outer, outer - dir * tick_length], line);
+        if deg % 30 == 0 {
+            painter.text(
+                center + dir * (PROTRACTOR_RADIUS * 0.78),
+                Align2::CENTER_CENTER,
+                deg.to_string(),
+                font.clone(),
+                LINE_COLOR,
+            );
+        }
+    }
+
+    let base_end = center + Vec2::angled(measure.protractor_base) * PROTRACTOR_RADIUS;
+    let arm_angle = angle_arm_direction(measure);
+    let arm_end = center + Vec2::angled(arm_angle) * PROTRACTOR_RADIUS;
+    painter.line_segment([center, base_end], Stroke::new(2.0 / scale, LINE_COLOR));
+    painter.line_segment([center, arm_end], Stroke::new(2.0 / scale, ACCENT_COLOR));
+
+    // 标出测量的角
+    const ARC_SEGMENTS: usize = 48;
+    let arc_radius = PROTRACTOR_RADIUS * 0.3;
+    let arc: Vec<Pos2> = (0..=ARC_SEGMENTS)
+        .map(|i| {
+            let t = i as f32 / ARC_SEGMENTS as f32;
+            center
+                + Vec2::angled(measure.protractor_base - measure.protractor_angle * t) * arc_radius
+        })
+        .collect();
+    painter.add(Shape::line(arc, Stroke::new(2.0 / scale, ACCENT_COLOR)));
+
+    painter.circle_filled(center, 3.0 / scale, LINE_COLOR);
+    painter.circle_filled(base_end, HANDLE_RADIUS / scale, LINE_COLOR);
+    painter.circle_filled(arm_end, HANDLE_RADIUS / scale, ACCENT_COLOR);
+    let bisector = Vec2::angled(measure.protractor_base - measure.protractor_angle / 2.0);
+    paint_readout(
+        painter,
+        center + bisector * (PROTRACTOR_RADIUS * 0.5),
+        Align2::CENTER_CENTER,
+        &format!("{:.1}°", measure.protractor_angle.to_degrees()),
+        scale,
+    );
+}
+
+// 读数的字号不随缩放变化，始终清晰可读
+fn paint_readout(painter: &Painter, pos: Pos2, anchor: Align2, text: &str, scale: f32) {
+    let galley = painter.layout_no_wrap(
+        text.to_string(),
+        FontId::proportional(14.0 / scale),
+        Color32::WHITE,
+    );
+    let rect = anchor.anchor_size(pos, galley.size());
+    painter.rect_filled(
+        rect.expand(4.0 / scale),
+        4.0 / scale,
+        ACCENT_COLOR.gamma_multiply(0.9),
+    );
+    painter.galley(rect.min, galley, Color32::WHITE);
+}
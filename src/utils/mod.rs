@@ -1,6 +1,12 @@
+pub mod brush_texture;
+pub mod crash;
 pub mod cursor_pos;
 pub mod dark_mode;
+pub mod measure;
+pub mod select;
+pub mod shape;
 pub mod stroke;
+pub mod svg;
 pub mod ui;
 
 #[cfg(target_os = "windows")]
@@ -10,11 +16,293 @@ pub mod windows;
 #[cfg(target_os = "linux")]
 pub mod linux;
 
-use egui::{Color32, Painter, Pos2, Rect, Stroke};
+use egui::{Color32, Painter, Pos2, Rect, Stroke, Vec2};
 use image::{DynamicImage, GenericImageView};
 use ttf_parser::{Face, OutlineBuilder};
+use winit::event::Force;
 
-use crate::state::{CanvasStroke, DynamicBrushWidthMode, StrokeWidth, TransformHandle};
+use crate::spatial::ChunkedObjects;
+use crate::state::{
+    ArrowHeads, BackgroundPattern, BrushStyle, CanvasObject, CanvasObjectOps, CanvasShape,
+    CanvasShapeType, CanvasState, CanvasStroke, DynamicBrushWidthMode, DynamicWidthCurve,
+    ImageAnimation, LineStyle, StrokeWidth, TransformHandle, TriangleKind,
+};
+
+// 选择笔画时的命中容差（直径）
+pub const STROKE_HIT_TOLERANCE: f32 = 10.0;
+
+// 像素橡皮擦外圈半径中收窄笔画而非直接擦除的比例
+pub const ERASER_SOFT_EDGE: f32 = 0.5;
+// 被像素橡皮擦收窄到此宽度以下的点会被删除
+pub const MIN_ERASED_STROKE_WIDTH: f32 = 0.5;
+
+// 选择形状时轮廓的命中容差（到轮廓的距离）
+pub const SHAPE_HIT_TOLERANCE: f32 = 6.0;
+
+// 查找位置下最上层的可见对象（按图层与 z 顺序）
+#[cfg_attr(feature = "profiling", profiling::function)]
+pub fn hit_test_objects(canvas: &mut CanvasState, pos: Pos2) -> Option<usize> {
+    hit_test_objects_where(canvas, pos, |_| true)
+}
+
+// 查找位置下最上层的可选中对象，锁定的对象被跳过，点击可以选中其下方的对象
+pub fn hit_test_selectable_objects(canvas: &mut CanvasState, pos: Pos2) -> Option<usize> {
+    hit_test_objects_where(canvas, pos, |object| !object.is_locked())
+}
+
+fn hit_test_objects_where(
+    canvas: &mut CanvasState,
+    pos: Pos2,
+    filter: impl Fn(&CanvasObject) -> bool,
+) -> Option<usize> {
+    canvas
+        .objects_in_rect(Rect::from_min_max(pos, pos))
+        .into_iter()
+        .filter(|&index| {
+            let object = &canvas.objects[index];
+            canvas.is_object_visible(index) && filter(object) && object.hit_test(pos)
+        })
+        .max_by_key(|&index| canvas.z_order_key(index))
+}
+
+// 将半径为 radius 的圆细分为多边形所需的线段数，使多边形与圆的最大距离不超过 tolerance
+pub fn curve_segments(radius: f32, tolerance: f32) -> usize {
+    const MIN_SEGMENTS: usize = 16;
+    const MAX_SEGMENTS: usize = 4096;
+    if radius <= tolerance || tolerance <= 0.0 {
+        return MIN_SEGMENTS;
+    }
+    // 弦与圆弧的最大距离为 r * (1 - cos(θ / 2))
+    let step = 2.0 * (1.0 - tolerance / radius).acos();
+    ((std::f32::consts::TAU / step).ceil() as usize).clamp(MIN_SEGMENTS, MAX_SEGMENTS)
+}
+
+// 以变换后的形式绘制 add_contents 中添加的所有图形
+pub fn paint_transformed(
+    painter: &Painter,
+    transform: egui::emath::TSTransform,
+    add_contents: impl FnOnce(&Painter),
+) {
+    let start = painter.add(egui::Shape::Noop);
+    add_contents(painter);
+    let end = painter.add(egui::Shape::Noop);
+    painter.ctx().graphics_mut(|graphics| {
+        graphics
+            .entry(painter.layer_id())
+            .transform_range(start, end, transform);
+    });
+}
+
+// 吸取画布上某点的颜色：图片取对应像素，其他对象取其颜色，空白处取画布背景色
+pub fn pick_color_at(canvas: &mut CanvasState, pos: Pos2, background: Color32) -> Color32 {
+    let Some(index) = hit_test_objects(canvas, pos) else {
+        return background;
+    };
+    let color = match &canvas.objects[index] {
+        CanvasObject::Image(img) => {
+            let rect = img.local_rect();
+            let local = rotate_point(pos, rect.center(), -img.rot);
+            let uv = (local - rect.min) / rect.size();
+            let [width, height] = img.image_size;
+            let x = ((uv.x * width as f32) as u32).min(width.saturating_sub(1));
+            let y = ((uv.y * height as f32) as u32).min(height.saturating_sub(1));
+            let offset = (y as usize * width as usize + x as usize) * 4;
+            match img.image_data.get(offset..offset + 4) {
+                // 透明像素处显示的是背景
+                Some(&[_, _, _, 0]) | None => background,
+                Some(&[r, g, b, _]) => Color32::from_rgb(r, g, b),
+                Some(_) => unreachable!(),
+            }
+        }
+        CanvasObject::Text(text) => text.color,
+        CanvasObject::StickyNote(note) => note.color,
+        CanvasObject::Shape(shape) => shape.fill.unwrap_or(shape.color),
+        CanvasObject::Stroke(stroke) => stroke.color,
+    };
+    // 荧光笔等半透明颜色只取色相，画笔颜色始终不透明
+    let [r, g, b, _] = color.to_srgba_unmultiplied();
+    Color32::from_rgb(r, g, b)
+}
+
+// 各通道与起点颜色之差都不超过此值的像素属于填充区域
+const FILL_COLOR_TOLERANCE: u8 = 48;
+
+// 在紧密排列的 RGBA8 画面中填充 seed 周围颜色相近的区域
+// 返回以 color 绘制的区域图像及其左上角的像素坐标，区域延伸到画面边缘 (未封闭) 时返回 None
+pub fn flood_fill(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    seed: [u32; 2],
+    color: Color32,
+) -> Option<(image::RgbaImage, [u32; 2])> {
+    let (width, height) = (width as usize, height as usize);
+    let [seed_x, seed_y] = seed.map(|v| v as usize);
+    if seed_x >= width || seed_y >= height || pixels.len() < width * height * 4 {
+        return None;
+    }
+
+    let pixel = |i: usize| &pixels[i * 4..i * 4 + 4];
+    let seed_color = pixel(seed_y * width + seed_x).to_vec();
+    let similar = |i: usize| {
+        pixel(i)
+            .iter()
+            .zip(&seed_color)
+            .all(|(a, b)| a.abs_diff(*b) <= FILL_COLOR_TOLERANCE)
+    };
+
+    let mut filled = vec![false; width * height];
+    let mut stack = vec![seed_y * width + seed_x];
+    filled[seed_y * width + seed_x] = true;
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (seed_x, seed_y, seed_x, seed_y);
+    while let Some(i) = stack.pop() {
+        let (x, y) = (i % width, i / width);
+        // 区域延伸到画面边缘说明未封闭，例如点击了空白背景
+        if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+            return None;
+        }
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+        for neighbor in [i - 1, i + 1, i - width, i + width] {
+            if !filled[neighbor] && similar(neighbor) {
+                filled[neighbor] = true;
+                stack.push(neighbor);
+            }
+        }
+    }
+
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let region = image::RgbaImage::from_fn(
+        (max_x - min_x + 1) as u32,
+        (max_y - min_y + 1) as u32,
+        |x, y| {
+            let i = (min_y + y as usize) * width + min_x + x as usize;
+            if filled[i] {
+                image::Rgba([r, g, b, a])
+            } else {
+                image::Rgba([0, 0, 0, 0])
+            }
+        },
+    );
+    Some((region, [min_x as u32, min_y as u32]))
+}
+
+// 绕中心点旋转一个点（弧度）
+pub fn rotate_point(point: Pos2, center: Pos2, angle: f32) -> Pos2 {
+    let (sin, cos) = angle.sin_cos();
+    let dx = point.x - center.x;
+    let dy = point.y - center.y;
+    Pos2::new(
+        center.x + dx * cos - dy * sin,
+        center.y + dx * sin + dy * cos,
+    )
+}
+
+// 旋转后矩形的轴对齐包围盒
+pub fn rotated_rect_bounds(rect: Rect, angle: f32) -> Rect {
+    if angle.abs() <= 0.001 {
+        return rect;
+    }
+    let center = rect.center();
+    let corners = [
+        rect.left_top(),
+        rect.right_top(),
+        rect.right_bottom(),
+        rect.left_bottom(),
+    ]
+    .map(|corner| rotate_point(corner, center, angle));
+    Rect::from_points(&corners)
+}
+
+// 判断点是否命中形状：填充的形状按实心处理，未填充的形状只命中轮廓附近 tolerance 范围内的点
+pub fn point_in_shape(shape: &CanvasShape, pos: Pos2, tolerance: f32) -> bool {
+    // 转换到形状未旋转时的坐标系
+    let center = shape.local_bounding_box().center();
+    let pos = rotate_point(pos, center, -shape.rotation);
+    // 轮廓线宽为 2
+    let tolerance = tolerance + 1.0;
+    let near_outline = |points: &[Pos2]| {
+        (0..points.len()).any(|i| {
+            let a = points[i];
+            let b = points[(i + 1) % points.len()];
+            point_to_line_segment_distance(pos, a, b) <= tolerance
+        })
+    };
+
+    match shape.shape_type {
+        CanvasShapeType::Line => {
+            point_to_line_segment_distance(pos, shape.pos, shape.line_end()) <= tolerance
+        }
+        CanvasShapeType::Arrow => {
+            point_to_line_segment_distance(pos, shape.pos, shape.line_end()) <= tolerance
+                || shape.arrow_heads().into_iter().any(|(tip, wings)| {
+                    wings
+                        .into_iter()
+                        .any(|wing| point_to_line_segment_distance(pos, wing, tip) <= tolerance)
+                })
+        }
+        CanvasShapeType::Rectangle => {
+            let rect = Rect::from_min_size(shape.pos, egui::vec2(shape.size, shape.size));
+            if shape.fill.is_some() {
+                rect.expand(tolerance).contains(pos)
+            } else {
+                near_outline(&[
+                    rect.left_top(),
+                    rect.right_top(),
+                    rect.right_bottom(),
+                    rect.left_bottom(),
+                ])
+            }
+        }
+        CanvasShapeType::Triangle(kind) => {
+            let points = shape.triangle_points(kind);
+            (shape.fill.is_some() && point_in_triangle(pos, points)) || near_outline(&points)
+        }
+        CanvasShapeType::Circle => {
+            let radius = shape.size / 2.0;
+            let distance = pos.distance(shape.pos);
+            if shape.fill.is_some() {
+                distance <= radius + tolerance
+            } else {
+                (distance - radius).abs() <= tolerance
+            }
+        }
+        CanvasShapeType::Ellipse => {
+            let rect = Rect::from_two_pos(shape.pos, shape.end);
+            let radius = rect.size() / 2.0;
+            let offset = pos - rect.center();
+            // 归一化到单位圆后判断是否落在椭圆内部
+            let inside = radius.min_elem() > 0.0
+                && (offset.x / radius.x).powi(2) + (offset.y / radius.y).powi(2) <= 1.0;
+            // 轮廓多边形的误差远小于命中容差
+            (shape.fill.is_some() && inside) || near_outline(&shape.ellipse_points(tolerance / 4.0))
+        }
+        CanvasShapeType::Polygon { .. } | CanvasShapeType::Star { .. } => {
+            let points = shape.star_points();
+            // 多边形与星形均可从中心扇形三角化
+            let inside = (0..points.len()).any(|i| {
+                point_in_triangle(pos, [shape.pos, points[i], points[(i + 1) % points.len()]])
+            });
+            (shape.fill.is_some() && inside) || near_outline(&points)
+        }
+    }
+}
+
+// 判断点是否在三角形内（重心坐标法，边上的点也视为在内）
+pub fn point_in_triangle(p: Pos2, [a, b, c]: [Pos2; 3]) -> bool {
+    let v0 = c - a;
+    let v1 = b - a;
+    let v2 = p - a;
+    let denom = v0.x * v1.y - v1.x * v0.y;
+    if denom.abs() <= f32::EPSILON {
+        return false;
+    }
+    let u = (v2.x * v1.y - v1.x * v2.y) / denom;
+    let v = (v0.x * v2.y - v2.x * v0.y) / denom;
+    u >= 0.0 && v >= 0.0 && u + v <= 1.0
+}
 
 // 检查点是否与笔画相交（用于对象橡皮擦）
 #[cfg_attr(feature = "profiling", profiling::function)]
@@ -40,6 +328,100 @@ pub fn point_intersects_stroke(pos: Pos2, stroke: &CanvasStroke, eraser_size: f3
     false
 }
 
+// 擦除笔画上橡皮擦范围内的部分（用于像素橡皮擦），笔画在擦除处断开为多段，各段保留原有的宽度与颜色
+// 橡皮擦边缘的软边区域按距离收窄笔画宽度，宽度降到接近 0 时才删除该点
+// 橡皮擦未触及笔画时返回 None，整条笔画都被擦除时返回空列表
+#[cfg_attr(feature = "profiling", profiling::function)]
+pub fn erase_stroke_at(
+    stroke: &CanvasStroke,
+    pos: Pos2,
+    eraser_size: f32,
+) -> Option<Vec<CanvasStroke>> {
+    // 旋转是绘制时绕包围盒中心应用的，片段的中心不同，因此先把旋转烘焙进点坐标
+    let points: Vec<Pos2> = if stroke.rot.abs() > 0.001 {
+        let center = stroke.bounds.center();
+        stroke
+            .points
+            .iter()
+            .map(|&p| rotate_point(p, center, stroke.rot))
+            .collect()
+    } else {
+        stroke.points.clone()
+    };
+    // 荧光笔和虚线按固定宽度绘制，无法逐点收窄，仍然整点删除
+    let can_taper = !stroke.is_highlighter && stroke.line_style == LineStyle::Solid;
+    let eraser_radius = eraser_size / 2.0;
+    let core_radius = if can_taper {
+        eraser_radius * (1.0 - ERASER_SOFT_EDGE)
+    } else {
+        eraser_radius
+    };
+    let reach = |i: usize| eraser_radius + stroke.width.get(i) / 2.0;
+
+    let mut modified = false;
+    let widths: Vec<f32> = (0..points.len())
+        .map(|i| {
+            let width = stroke.width.get(i);
+            let dist = points[i].distance(pos);
+            if dist > reach(i) {
+                return width;
+            }
+            modified = true;
+            if !can_taper {
+                return 0.0;
+            }
+            // 从笔画边缘刚接触橡皮擦时的原宽度线性收窄到核心区域边界处的 0
+            let t = ((dist - core_radius) / (reach(i) - core_radius)).clamp(0.0, 1.0);
+            width * t
+        })
+        .collect();
+    let erased = |i: usize| widths[i] < MIN_ERASED_STROKE_WIDTH;
+    // 快速划过时两端点都可能在范围外，线段本身穿过橡皮擦核心时也要断开
+    let crosses = |i: usize| {
+        point_to_line_segment_distance(pos, points[i - 1], points[i])
+            <= core_radius + widths[i - 1].max(widths[i]) / 2.0
+    };
+    if !modified && !(1..points.len()).any(crosses) {
+        return None;
+    }
+
+    let mut pieces = Vec::new();
+    let mut current: Vec<usize> = Vec::new();
+    let mut finish_piece = |current: &mut Vec<usize>| {
+        // 只剩一个点的片段不再保留
+        if current.len() >= 2 {
+            pieces.push(
+                CanvasStroke {
+                    id: CanvasStroke::new_id(),
+                    points: current.iter().map(|&i| points[i]).collect(),
+                    width: current
+                        .iter()
+                        .map(|&i| widths[i])
+                        .collect::<Vec<_>>()
+                        .into(),
+                    rot: 0.0,
+                    bounds: Rect::NOTHING,
+                    ..stroke.clone()
+                }
+                .with_bounds(),
+            );
+        }
+        current.clear();
+    };
+    for i in 0..points.len() {
+        if erased(i) {
+            finish_piece(&mut current);
+            continue;
+        }
+        if !current.is_empty() && crosses(i) {
+            finish_piece(&mut current);
+        }
+        current.push(i);
+    }
+    finish_piece(&mut current);
+    Some(pieces)
+}
+
 // 计算点到线段的最短距离
 #[cfg_attr(feature = "profiling", profiling::function)]
 pub fn point_to_line_segment_distance(p: Pos2, a: Pos2, b: Pos2) -> f32 {
@@ -62,9 +444,11 @@ pub fn point_to_line_segment_distance(p: Pos2, a: Pos2, b: Pos2) -> f32 {
 pub fn calculate_dynamic_width(
     base_width: f32,
     mode: DynamicBrushWidthMode,
+    curve: &DynamicWidthCurve,
     point_index: usize,
     total_points: usize,
     speed: Option<f32>,
+    force: Option<Force>,
 ) -> StrokeWidth {
     let width = match mode {
         DynamicBrushWidthMode::Disabled => return StrokeWidth::Fixed(base_width),
@@ -72,10 +456,10 @@ pub fn calculate_dynamic_width(
         DynamicBrushWidthMode::BrushTip => {
             // 模拟笔锋：在笔画末尾逐渐缩小
             let progress = point_index as f32 / total_points.max(1) as f32;
-            // 在最后 30% 的笔画中逐渐缩小到 40% 的宽度
-            if progress > 0.7 {
-                let shrink_progress = (progress - 0.7) / 0.3; // 0.0 到 1.0
-                base_width * (1.0 - shrink_progress * 0.6) // 从 100% 缩小到 40%
+            // 从 taper_start 处开始逐渐缩小到 taper_end_multiplier 倍宽度
+            if progress > curve.taper_start && curve.taper_start < 1.0 {
+                let shrink_progress = (progress - curve.taper_start) / (1.0 - curve.taper_start); // 0.0 到 1.0
+                base_width * (1.0 - shrink_progress * (1.0 - curve.taper_end_multiplier))
             } else {
                 base_width
             }
@@ -84,11 +468,21 @@ pub fn calculate_dynamic_width(
         DynamicBrushWidthMode::SpeedBased => {
             // 基于速度：速度快时变细，速度慢时变粗
             if let Some(speed_val) = speed {
-                // 速度范围假设：0-500 像素/秒
-                // 速度越快，宽度越小（最小到 50%）
-                // 速度越慢，宽度越大（最大到 150%）
-                let normalized_speed = (speed_val / 500.0).min(1.0);
-                base_width * (1.5 - normalized_speed) // 从 150% 到 50%
+                // 速度达到 speed_normalization 时宽度最小，静止时最大
+                let normalized_speed = (speed_val / curve.speed_normalization.max(1.0)).min(1.0);
+                base_width
+                    * (curve.max_multiplier
+                        - normalized_speed * (curve.max_multiplier - curve.min_multiplier))
+            } else {
+                base_width
+            }
+        }
+
+        DynamicBrushWidthMode::Pressure => {
+            // 基于压感：中等压力时为原始宽度，从 20% 到 200%
+            if let Some(force) = force {
+                let pressure = force.normalized() as f32;
+                base_width * (pressure * 2.0).clamp(0.2, 2.0)
             } else {
                 base_width
             }
@@ -175,9 +569,11 @@ pub fn apply_point_interpolation_in_place(
     }
 }
 
+// 平滑笔画：重新采样，Chaikin 切角，最后按 window_size 个点做滑动平均
+// 偶数窗口向上取为奇数，窗口为 1 时不做滑动平均
 #[must_use]
 #[cfg_attr(feature = "profiling", profiling::function)]
-pub fn apply_stroke_smoothing(points: &[Pos2]) -> Vec<Pos2> {
+pub fn apply_stroke_smoothing(points: &[Pos2], window_size: usize) -> Vec<Pos2> {
     if points.len() < 3 {
         return points.to_vec();
     }
@@ -243,27 +639,81 @@ pub fn apply_stroke_smoothing(points: &[Pos2]) -> Vec<Pos2> {
     }
 
     // --------------------------------
-    // 3. Light moving-average cleanup
+    // 3. Moving-average cleanup
     // --------------------------------
+    // 窗口在两端收缩为对称的较小窗口，首尾点保持不动，笔画不会缩短
     let len = smoothed.len();
-    let mut final_points = Vec::with_capacity(len);
+    let radius = window_size / 2;
+    (0..len)
+        .map(|i| {
+            let r = radius.min(i).min(len - 1 - i);
+            let window = &smoothed[i - r..=i + r];
+            let sum = window.iter().fold(Vec2::ZERO, |sum, p| sum + p.to_vec2());
+            (sum / window.len() as f32).to_pos2()
+        })
+        .collect()
+}
 
-    if len > 0 {
-        final_points.push(smoothed[0]);
+// Catmull-Rom 样条平滑：在每对相邻点之间插入 segments 个点，曲线经过所有原始点
+// 动态宽度与点同步重新采样，保证宽度与点一一对应
+#[cfg_attr(feature = "profiling", profiling::function)]
+pub fn apply_catmull_rom_smoothing(
+    points: &[Pos2],
+    width: &StrokeWidth,
+    segments: usize,
+) -> (Vec<Pos2>, StrokeWidth) {
+    let n = points.len();
+    if n < 3 || segments == 0 {
+        return (points.to_vec(), width.clone());
     }
+    let widths = match width {
+        StrokeWidth::Dynamic(v) if v.len() == n => Some(v),
+        _ => None,
+    };
 
-    for i in 1..smoothed.len() - 1 {
-        final_points.push(Pos2 {
-            x: (smoothed[i - 1].x + smoothed[i].x + smoothed[i + 1].x) / 3.0,
-            y: (smoothed[i - 1].y + smoothed[i].y + smoothed[i + 1].y) / 3.0,
-        });
+    let mut smoothed_points = Vec::with_capacity((n - 1) * (segments + 1) + 1);
+    let mut smoothed_widths = Vec::with_capacity(smoothed_points.capacity());
+    for i in 0..n - 1 {
+        // 首尾处重复端点作为控制点
+        let p0 = points[i.saturating_sub(1)];
+        let p1 = points[i];
+        let p2 = points[i + 1];
+        let p3 = points[(i + 2).min(n - 1)];
+
+        smoothed_points.push(p1);
+        if let Some(w) = widths {
+            smoothed_widths.push(w[i]);
+        }
+        for j in 1..=segments {
+            let t = j as f32 / (segments + 1) as f32;
+            smoothed_points.push(catmull_rom_point(p0, p1, p2, p3, t));
+            if let Some(w) = widths {
+                smoothed_widths.push(w[i] + t * (w[i + 1] - w[i]));
+            }
+        }
     }
+    smoothed_points.push(points[n - 1]);
 
-    if len > 1 {
-        final_points.push(smoothed[len - 1]);
-    }
+    let width = match widths {
+        Some(w) => {
+            smoothed_widths.push(w[n - 1]);
+            StrokeWidth::Dynamic(smoothed_widths)
+        }
+        None => width.clone(),
+    };
+    (smoothed_points, width)
+}
 
-    final_points
+fn catmull_rom_point(p0: Pos2, p1: Pos2, p2: Pos2, p3: Pos2, t: f32) -> Pos2 {
+    let (p0, p1, p2, p3) = (p0.to_vec2(), p1.to_vec2(), p2.to_vec2(), p3.to_vec2());
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let v = 0.5
+        * (2.0 * p1
+            + (p2 - p0) * t
+            + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+            + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3);
+    v.to_pos2()
 }
 
 // 判断笔画是否近似一条直线
@@ -301,6 +751,51 @@ pub fn is_stroke_linear(points: &[Pos2], tolerance: f32) -> bool {
     true
 }
 
+// 用 Ramer–Douglas–Peucker 算法简化笔画，删除与简化后折线距离不超过 epsilon 的点
+// 首尾两点总是保留，动态宽度随保留的点一起保留
+#[cfg_attr(feature = "profiling", profiling::function)]
+pub fn simplify_stroke(
+    points: &[Pos2],
+    width: &StrokeWidth,
+    epsilon: f32,
+) -> (Vec<Pos2>, StrokeWidth) {
+    if points.len() < 3 || epsilon <= 0.0 {
+        return (points.to_vec(), width.clone());
+    }
+
+    let last = points.len() - 1;
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[last] = true;
+    // 用栈代替递归，长笔画也不会栈溢出
+    let mut ranges = vec![(0, last)];
+    while let Some((start, end)) = ranges.pop() {
+        let (mut farthest, mut max_distance) = (start, 0.0);
+        for i in start + 1..end {
+            let distance = point_to_line_segment_distance(points[i], points[start], points[end]);
+            if distance > max_distance {
+                farthest = i;
+                max_distance = distance;
+            }
+        }
+        if max_distance > epsilon {
+            keep[farthest] = true;
+            ranges.push((start, farthest));
+            ranges.push((farthest, end));
+        }
+    }
+
+    let kept = |i: &usize| keep[*i];
+    let simplified = (0..points.len()).filter(kept).map(|i| points[i]).collect();
+    let width = match width {
+        StrokeWidth::Fixed(w) => StrokeWidth::Fixed(*w),
+        StrokeWidth::Dynamic(widths) => {
+            StrokeWidth::Dynamic((0..points.len()).filter(kept).map(|i| widths[i]).collect())
+        }
+    };
+    (simplified, width)
+}
+
 // 拉直笔画
 #[cfg_attr(feature = "profiling", profiling::function)]
 pub fn straighten_stroke(points: &[Pos2], tolerance: f32) -> Vec<Pos2> {
@@ -315,6 +810,148 @@ pub fn straighten_stroke(points: &[Pos2], tolerance: f32) -> Vec<Pos2> {
     }
 }
 
+/// Strokes smaller than this are never recognized as shapes
+const MIN_RECOGNIZED_SHAPE_SIZE: f32 = 30.0;
+/// Number of points a stroke is resampled to before counting corners
+const SHAPE_RECOGNITION_SAMPLES: usize = 64;
+/// Turning angle above which a resampled point counts as part of a corner
+const CORNER_ANGLE_THRESHOLD: f32 = 50.0 * std::f32::consts::PI / 180.0;
+
+// 识别手绘笔画对应的形状：根据是否封闭、转角数量与长宽比判断
+#[cfg_attr(feature = "profiling", profiling::function)]
+pub fn recognize_shape(points: &[Pos2]) -> Option<CanvasShapeType> {
+    if points.len() < 5 {
+        return None;
+    }
+    let bbox = Rect::from_points(points);
+    let extent = bbox.width().max(bbox.height());
+    if extent < MIN_RECOGNIZED_SHAPE_SIZE {
+        return None;
+    }
+
+    let first = points[0];
+    let last = points[points.len() - 1];
+    // 起终点相距较远时视为开放笔画，只可能是直线
+    if first.distance(last) > extent * 0.25 {
+        let length = first.distance(last);
+        return is_stroke_linear(points, length * 0.05).then_some(CanvasShapeType::Line);
+    }
+
+    // 形状只支持等宽高，长宽比差异过大时不识别
+    let aspect_ratio = bbox.width() / bbox.height().max(1.0);
+    if !(0.5..=2.0).contains(&aspect_ratio) {
+        return None;
+    }
+
+    let mut closed = points.to_vec();
+    closed.push(first);
+    let samples = resample_polyline(&closed, SHAPE_RECOGNITION_SAMPLES + 1);
+    let samples = &samples[..samples.len() - 1];
+
+    match count_corners(samples) {
+        0 if is_roughly_round(samples) => Some(CanvasShapeType::Circle),
+        3 => Some(CanvasShapeType::Triangle(TriangleKind::Isosceles)),
+        4 => Some(CanvasShapeType::Rectangle),
+        _ => None,
+    }
+}
+
+// 用识别出的形状替换笔画，形状大小与位置贴合笔画的范围
+pub fn shape_from_stroke(stroke: &CanvasStroke, shape_type: CanvasShapeType) -> CanvasShape {
+    let bbox = Rect::from_points(&stroke.points);
+    let size = (bbox.width() + bbox.height()) / 2.0;
+    let (pos, end, size) = match shape_type {
+        CanvasShapeType::Line | CanvasShapeType::Arrow => {
+            let (start, end) = (stroke.points[0], stroke.points[stroke.points.len() - 1]);
+            (start, end, start.distance(end))
+        }
+        CanvasShapeType::Circle
+        | CanvasShapeType::Polygon { .. }
+        | CanvasShapeType::Star { .. } => (bbox.center(), bbox.center(), size),
+        CanvasShapeType::Ellipse => (bbox.min, bbox.max, bbox.min.distance(bbox.max)),
+        CanvasShapeType::Rectangle | CanvasShapeType::Triangle(_) => {
+            let pos = bbox.center() - Vec2::splat(size / 2.0);
+            (pos, pos, size)
+        }
+    };
+    CanvasShape {
+        shape_type,
+        pos,
+        end,
+        size,
+        color: stroke.color,
+        rotation: 0.0,
+        line_style: stroke.line_style,
+        fill: None,
+        arrow_heads: ArrowHeads::default(),
+        bounds: egui::Rect::NOTHING,
+        layer: stroke.layer,
+        group: stroke.group,
+        locked: stroke.locked,
+    }
+    .with_bounds()
+}
+
+// 沿折线等距重新采样 count 个点
+fn resample_polyline(points: &[Pos2], count: usize) -> Vec<Pos2> {
+    let total_length: f32 = points.windows(2).map(|w| w[0].distance(w[1])).sum();
+    if points.len() < 2 || count < 2 || total_length < f32::EPSILON {
+        return points.to_vec();
+    }
+
+    let step = total_length / (count - 1) as f32;
+    let mut result = vec![points[0]];
+    let mut segment_start = points[0];
+    let mut remaining = step;
+    for &point in &points[1..] {
+        let mut segment_length = segment_start.distance(point);
+        while segment_length >= remaining && result.len() < count - 1 {
+            segment_start = segment_start.lerp(point, remaining / segment_length);
+            result.push(segment_start);
+            segment_length -= remaining;
+            remaining = step;
+        }
+        remaining -= segment_length;
+        segment_start = point;
+    }
+    result.push(points[points.len() - 1]);
+    result
+}
+
+// 统计封闭折线上的转角数量，连续的高转角点视为同一个转角
+fn count_corners(samples: &[Pos2]) -> usize {
+    const WINDOW: usize = 3;
+    let n = samples.len();
+    if n <= WINDOW * 2 {
+        return 0;
+    }
+
+    let is_corner: Vec<bool> = (0..n)
+        .map(|i| {
+            let incoming = samples[i] - samples[(i + n - WINDOW) % n];
+            let outgoing = samples[(i + WINDOW) % n] - samples[i];
+            let turn = (outgoing.angle() - incoming.angle()).rem_euclid(std::f32::consts::TAU);
+            turn.min(std::f32::consts::TAU - turn) > CORNER_ANGLE_THRESHOLD
+        })
+        .collect();
+    (0..n)
+        .filter(|&i| is_corner[i] && !is_corner[(i + n - 1) % n])
+        .count()
+}
+
+// 各点到中心的距离是否大致相等
+fn is_roughly_round(samples: &[Pos2]) -> bool {
+    let count = samples.len() as f32;
+    let center = samples.iter().fold(Vec2::ZERO, |acc, p| acc + p.to_vec2()) / count;
+    let distances: Vec<f32> = samples
+        .iter()
+        .map(|p| p.distance(center.to_pos2()))
+        .collect();
+    let mean = distances.iter().sum::<f32>() / count;
+    let variance = distances.iter().map(|d| (d - mean).powi(2)).sum::<f32>() / count;
+    mean > f32::EPSILON && variance.sqrt() / mean < 0.15
+}
+
 // pub fn pca_linearity(points: &[Pos2]) -> Option<(f32, Pos2)> {
 //     if points.len() < 2 {
 //         return None;
@@ -408,6 +1045,499 @@ pub fn straighten_stroke(points: &[Pos2], tolerance: f32) -> Vec<Pos2> {
 //     vec![start, end]
 // }
 
+// 将线段拆分为虚线段
+pub fn dash_line_segments(a: Pos2, b: Pos2, dash_len: f32, gap_len: f32) -> Vec<[Pos2; 2]> {
+    dash_polyline(&[a, b], dash_len, gap_len)
+}
+
+// 将折线拆分为虚线段，虚实相位在各段之间连续，因此由大量短线段组成的笔画也能正确显示虚线
+// dash_len 为 0 时每段退化为一个点，用于绘制点线
+pub fn dash_polyline(points: &[Pos2], dash_len: f32, gap_len: f32) -> Vec<[Pos2; 2]> {
+    let mut dashes = Vec::new();
+    if points.len() < 2 || dash_len < 0.0 || gap_len < 0.0 || dash_len + gap_len <= 0.0 {
+        return dashes;
+    }
+
+    let mut drawing = true;
+    let mut remaining = dash_len; // 当前实线段或间隙的剩余长度
+    let mut dash_start = points[0];
+    for segment in points.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        let length = a.distance(b);
+        if length <= f32::EPSILON {
+            continue;
+        }
+        let dir = (b - a) / length;
+
+        let mut travelled = 0.0;
+        while length - travelled >= remaining {
+            travelled += remaining;
+            let point = a + dir * travelled;
+            if drawing {
+                dashes.push([dash_start, point]);
+            } else {
+                dash_start = point;
+            }
+            drawing = !drawing;
+            remaining = if drawing { dash_len } else { gap_len };
+        }
+        remaining -= length - travelled;
+    }
+
+    // 末尾未画完的实线段
+    if drawing {
+        dashes.push([dash_start, points[points.len() - 1]]);
+    }
+    dashes
+}
+
+// 按线型绘制折线，closed 为 true 时首尾相连
+pub fn paint_styled_line(
+    painter: &Painter,
+    points: &[Pos2],
+    stroke: Stroke,
+    style: LineStyle,
+    closed: bool,
+) {
+    if points.len() < 2 {
+        return;
+    }
+
+    let unit = stroke.width.max(2.0);
+    let dashed_path = || {
+        let mut path = points.to_vec();
+        if closed {
+            path.push(points[0]);
+        }
+        path
+    };
+    match style {
+        LineStyle::Solid => {
+            painter.add(if closed {
+                egui::Shape::closed_line(points.to_vec(), stroke)
+            } else {
+                egui::Shape::line(points.to_vec(), stroke)
+            });
+        }
+        LineStyle::Dashed => {
+            for dash in dash_polyline(&dashed_path(), unit * 4.0, unit * 2.5) {
+                painter.line_segment(dash, stroke);
+            }
+        }
+        LineStyle::Dotted => {
+            for [dot, _] in dash_polyline(&dashed_path(), 0.0, unit * 2.5) {
+                painter.circle_filled(dot, stroke.width / 2.0, stroke.color);
+            }
+        }
+    }
+}
+
+/// Width of the antialiasing fringe around variable-width strokes
+const RIBBON_FEATHER: f32 = 1.0;
+/// Longest miter at a joint, relative to the half width
+const RIBBON_MAX_MITER: f32 = 2.0;
+
+// 绘制可变宽度笔画：沿中心线两侧各偏移半个宽度构成填充网格，两端使用圆形笔帽
+#[cfg_attr(feature = "profiling", profiling::function)]
+pub fn paint_variable_width_stroke(
+    painter: &Painter,
+    points: &[Pos2],
+    widths: &[f32],
+    color: Color32,
+) {
+    paint_ribbon(painter, points, widths, color, None);
+}
+
+/// Draws a stroke filled with a repeating texture as a ribbon mesh; the texture is anchored to
+/// the canvas so that it lines up across strokes
+#[cfg_attr(feature = "profiling", profiling::function)]
+pub fn paint_textured_stroke(
+    painter: &Painter,
+    points: &[Pos2],
+    widths: &[f32],
+    color: Color32,
+    texture_id: egui::TextureId,
+) {
+    paint_ribbon(painter, points, widths, color, Some(texture_id));
+}
+
+fn paint_ribbon(
+    painter: &Painter,
+    points: &[Pos2],
+    widths: &[f32],
+    color: Color32,
+    texture_id: Option<egui::TextureId>,
+) {
+    let n = points.len();
+    if n == 0 || widths.len() != n {
+        return;
+    }
+    let mut mesh = match texture_id {
+        Some(id) => egui::Mesh::with_texture(id),
+        None => egui::Mesh::default(),
+    };
+    let uv = |p: Pos2| match texture_id {
+        Some(_) => (p.to_vec2() / brush_texture::TEXTURE_TILE_SIZE).to_pos2(),
+        None => egui::epaint::WHITE_UV,
+    };
+    let vertex = |mesh: &mut egui::Mesh, pos: Pos2, color: Color32| {
+        mesh.vertices.push(egui::epaint::Vertex {
+            pos,
+            uv: uv(pos),
+            color,
+        });
+    };
+    // 纹理笔画的笔帽也要带纹理，只能自己拼成扇形
+    let tolerance = painter
+        .ctx()
+        .options(|options| options.tessellation_options.bezier_tolerance);
+    let cap = |painter: &Painter, center: Pos2, radius: f32| {
+        let Some(id) = texture_id else {
+            painter.circle_filled(center, radius, color);
+            return;
+        };
+        let mut disc = egui::Mesh::with_texture(id);
+        let segments = curve_segments(radius, tolerance);
+        vertex(&mut disc, center, color);
+        for k in 0..segments {
+            let angle = k as f32 / segments as f32 * std::f32::consts::TAU;
+            vertex(&mut disc, center + Vec2::angled(angle) * radius, color);
+            disc.add_triangle(0, 1 + k as u32, 1 + ((k + 1) % segments) as u32);
+        }
+        painter.add(disc);
+    };
+    cap(painter, points[0], widths[0] / 2.0);
+    if n < 2 {
+        return;
+    }
+    cap(painter, points[n - 1], widths[n - 1] / 2.0);
+
+    let segment_normal = |a: Pos2, b: Pos2| (b - a).normalized().rot90();
+    let mut sharp_joints = Vec::new();
+    for i in 0..n {
+        let incoming = if i > 0 {
+            segment_normal(points[i - 1], points[i])
+        } else {
+            Vec2::ZERO
+        };
+        let outgoing = if i + 1 < n {
+            segment_normal(points[i], points[i + 1])
+        } else {
+            Vec2::ZERO
+        };
+        let normal = (incoming + outgoing).normalized();
+
+        // 斜接长度随转角增大，转角过尖时限制长度并用圆形补齐接缝
+        let reference = if outgoing == Vec2::ZERO {
+            incoming
+        } else {
+            outgoing
+        };
+        let cos = normal.dot(reference);
+        let miter = if cos > 1.0 / RIBBON_MAX_MITER {
+            1.0 / cos
+        } else {
+            RIBBON_MAX_MITER
+        };
+        if cos < 0.5 {
+            sharp_joints.push(i);
+        }
+
+        let half = widths[i] / 2.0 * miter;
+        let p = points[i];
+        let (inner, outer) = (normal * half, normal * (half + RIBBON_FEATHER));
+        vertex(&mut mesh, p + outer, Color32::TRANSPARENT);
+        vertex(&mut mesh, p + inner, color);
+        vertex(&mut mesh, p - inner, color);
+        vertex(&mut mesh, p - outer, Color32::TRANSPARENT);
+
+        if i > 0 {
+            let prev = 4 * (i as u32 - 1);
+            let curr = 4 * i as u32;
+            for k in 0..3 {
+                mesh.add_triangle(prev + k, prev + k + 1, curr + k + 1);
+                mesh.add_triangle(prev + k, curr + k + 1, curr + k);
+            }
+        }
+    }
+    painter.add(mesh);
+
+    for i in sharp_joints {
+        cap(painter, points[i], widths[i] / 2.0);
+    }
+}
+
+/// Grid lines thinner than this on screen are not drawn
+const MIN_GRID_SCREEN_SPACING: f32 = 4.0;
+
+// 绘制背景网格，只绘制与可见区域相交的网格线
+pub fn draw_grid(painter: &Painter, visible_rect: Rect, grid_size: f32, scale: f32) {
+    if grid_size * scale < MIN_GRID_SCREEN_SPACING {
+        return;
+    }
+    // 网格线在屏幕上保持 1 像素宽
+    let stroke = Stroke::new(1.0 / scale, Color32::from_gray(128).gamma_multiply(0.3));
+
+    let mut x = (visible_rect.min.x / grid_size).floor() * grid_size;
+    while x <= visible_rect.max.x {
+        painter.line_segment(
+            [
+                Pos2::new(x, visible_rect.min.y),
+                Pos2::new(x, visible_rect.max.y),
+            ],
+            stroke,
+        );
+        x += grid_size;
+    }
+    let mut y = (visible_rect.min.y / grid_size).floor() * grid_size;
+    while y <= visible_rect.max.y {
+        painter.line_segment(
+            [
+                Pos2::new(visible_rect.min.x, y),
+                Pos2::new(visible_rect.max.x, y),
+            ],
+            stroke,
+        );
+        y += grid_size;
+    }
+}
+
+/// Dots of the dot pattern are skipped below this on-screen spacing, there would be too many to draw
+const MIN_DOT_SCREEN_SPACING: f32 = 8.0;
+
+// 绘制背景图案，仅用于显示，不会产生可选中的对象
+pub fn draw_background_pattern(
+    painter: &Painter,
+    visible_rect: Rect,
+    pattern: BackgroundPattern,
+    spacing: f32,
+    color: Color32,
+    scale: f32,
+) {
+    if spacing * scale < MIN_GRID_SCREEN_SPACING {
+        return;
+    }
+    // 线条在屏幕上保持 1 像素宽
+    let stroke = Stroke::new(1.0 / scale, color);
+    let first_x = (visible_rect.min.x / spacing).floor() * spacing;
+    let first_y = (visible_rect.min.y / spacing).floor() * spacing;
+
+    match pattern {
+        BackgroundPattern::Blank => {}
+        BackgroundPattern::Grid => {
+            let mut x = first_x;
+            while x <= visible_rect.max.x {
+                painter.vline(x, visible_rect.y_range(), stroke);
+                x += spacing;
+            }
+            let mut y = first_y;
+            while y <= visible_rect.max.y {
+                painter.hline(visible_rect.x_range(), y, stroke);
+                y += spacing;
+            }
+        }
+        BackgroundPattern::Lines => {
+            let mut y = first_y;
+            while y <= visible_rect.max.y {
+                painter.hline(visible_rect.x_range(), y, stroke);
+                y += spacing;
+            }
+        }
+        BackgroundPattern::Dots => {
+            if spacing * scale < MIN_DOT_SCREEN_SPACING {
+                return;
+            }
+            let radius = 1.5 / scale;
+            let mut y = first_y;
+            while y <= visible_rect.max.y {
+                let mut x = first_x;
+                while x <= visible_rect.max.x {
+                    painter.circle_filled(Pos2::new(x, y), radius, color);
+                    x += spacing;
+                }
+                y += spacing;
+            }
+        }
+    }
+}
+
+pub fn snap_to_grid(pos: Pos2, grid_size: f32) -> Pos2 {
+    Pos2::new(
+        (pos.x / grid_size).round() * grid_size,
+        (pos.y / grid_size).round() * grid_size,
+    )
+}
+
+// 计算拖动对象时本次应移动的距离，启用网格时使对象左上角吸附到网格交点
+// total_delta 为指针自拖动开始的总位移，applied_delta 为已应用到对象上的位移
+pub fn snapped_move_delta(
+    object: &CanvasObject,
+    total_delta: Vec2,
+    applied_delta: Vec2,
+    grid_size: Option<f32>,
+) -> Vec2 {
+    let Some(grid_size) = grid_size else {
+        return total_delta - applied_delta;
+    };
+    let origin = object.bounding_box().min - applied_delta;
+    snap_to_grid(origin + total_delta, grid_size) - origin - applied_delta
+}
+
+// 将移动后的包围盒 rect 的边缘或中线吸附到附近其他可见对象的边缘或中线
+// 返回还需追加的位移，以及每个方向上命中的参考线
+pub fn snap_to_alignment_guides(
+    canvas: &CanvasState,
+    moving: &[usize],
+    rect: Rect,
+    tolerance: f32,
+) -> (Vec2, Vec<[Pos2; 2]>) {
+    let others: Vec<Rect> = (0..canvas.objects.len())
+        .filter(|index| !moving.contains(index) && canvas.is_object_visible(*index))
+        .map(|index| canvas.objects[index].bounding_box())
+        .collect();
+
+    // 在一个方向上寻找距离最近的对齐位置，返回偏移量与所对齐的对象
+    let best_match = |edges: [f32; 3], other_edges: &dyn Fn(&Rect) -> [f32; 3]| {
+        let mut best: Option<(f32, Rect)> = None;
+        for other in &others {
+            for target in other_edges(other) {
+                for edge in edges {
+                    let offset = target - edge;
+                    if offset.abs() <= tolerance
+                        && best.is_none_or(|(best_offset, _)| offset.abs() < best_offset.abs())
+                    {
+                        best = Some((offset, *other));
+                    }
+                }
+            }
+        }
+        best
+    };
+    let x_edges = |r: &Rect| [r.min.x, r.center().x, r.max.x];
+    let y_edges = |r: &Rect| [r.min.y, r.center().y, r.max.y];
+
+    let x_match = best_match(x_edges(&rect), &x_edges);
+    let y_match = best_match(y_edges(&rect), &y_edges);
+    let offset = Vec2::new(
+        x_match.map_or(0.0, |(dx, _)| dx),
+        y_match.map_or(0.0, |(dy, _)| dy),
+    );
+    let moved = rect.translate(offset);
+
+    // 参考线位于对齐的那条边或中线上，贯穿两个对象
+    let aligned = |edges: [f32; 3], other_edges: [f32; 3]| {
+        edges.into_iter().find(|edge| {
+            other_edges
+                .iter()
+                .any(|target| (target - edge).abs() < 0.01)
+        })
+    };
+    let mut guides = Vec::new();
+    if let Some((_, other)) = x_match
+        && let Some(x) = aligned(x_edges(&moved), x_edges(&other))
+    {
+        let y_min = moved.min.y.min(other.min.y);
+        let y_max = moved.max.y.max(other.max.y);
+        guides.push([Pos2::new(x, y_min), Pos2::new(x, y_max)]);
+    }
+    if let Some((_, other)) = y_match
+        && let Some(y) = aligned(y_edges(&moved), y_edges(&other))
+    {
+        let x_min = moved.min.x.min(other.min.x);
+        let x_max = moved.max.x.max(other.max.x);
+        guides.push([Pos2::new(x_min, y), Pos2::new(x_max, y)]);
+    }
+    (offset, guides)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    Left,
+    HorizontalCenter,
+    Right,
+    Top,
+    VerticalCenter,
+    Bottom,
+}
+
+// 计算将对象按包围盒对齐到整体包围盒一侧或中线所需的位移
+pub fn align_objects(
+    objects: &ChunkedObjects,
+    indices: &[usize],
+    alignment: Alignment,
+) -> Vec<(usize, Vec2)> {
+    let bounds: Vec<(usize, Rect)> = indices
+        .iter()
+        .filter_map(|&index| Some((index, objects.get(index)?.bounding_box())))
+        .collect();
+    let Some(union) = bounds.iter().map(|(_, rect)| *rect).reduce(Rect::union) else {
+        return Vec::new();
+    };
+
+    bounds
+        .into_iter()
+        .map(|(index, rect)| {
+            let delta = match alignment {
+                Alignment::Left => Vec2::new(union.min.x - rect.min.x, 0.0),
+                Alignment::HorizontalCenter => Vec2::new(union.center().x - rect.center().x, 0.0),
+                Alignment::Right => Vec2::new(union.max.x - rect.max.x, 0.0),
+                Alignment::Top => Vec2::new(0.0, union.min.y - rect.min.y),
+                Alignment::VerticalCenter => Vec2::new(0.0, union.center().y - rect.center().y),
+                Alignment::Bottom => Vec2::new(0.0, union.max.y - rect.max.y),
+            };
+            (index, delta)
+        })
+        .filter(|(_, delta)| *delta != Vec2::ZERO)
+        .collect()
+}
+
+// 计算使对象之间间距相等所需的位移，两端的对象保持不动
+pub fn distribute_objects(
+    objects: &ChunkedObjects,
+    indices: &[usize],
+    horizontal: bool,
+) -> Vec<(usize, Vec2)> {
+    let axis = |v: Vec2| if horizontal { v.x } else { v.y };
+    let mut bounds: Vec<(usize, Rect)> = indices
+        .iter()
+        .filter_map(|&index| Some((index, objects.get(index)?.bounding_box())))
+        .collect();
+    if bounds.len() < 3 {
+        return Vec::new();
+    }
+    bounds.sort_by(|(_, a), (_, b)| {
+        axis(a.center().to_vec2()).total_cmp(&axis(b.center().to_vec2()))
+    });
+
+    let start = axis(bounds[0].1.min.to_vec2());
+    let end = axis(bounds[bounds.len() - 1].1.max.to_vec2());
+    let total_size: f32 = bounds.iter().map(|(_, rect)| axis(rect.size())).sum();
+    let gap = (end - start - total_size) / (bounds.len() - 1) as f32;
+
+    let mut cursor = start;
+    let mut moves = Vec::new();
+    for (index, rect) in bounds {
+        let offset = cursor - axis(rect.min.to_vec2());
+        if offset != 0.0 {
+            let delta = if horizontal {
+                Vec2::new(offset, 0.0)
+            } else {
+                Vec2::new(0.0, offset)
+            };
+            moves.push((index, delta));
+        }
+        cursor += axis(rect.size()) + gap;
+    }
+    moves
+}
+
+// 在指针处绘制表示画笔大小的圆环，黑白双层以便在任意背景上可见
+pub fn draw_cursor_ring(painter: &Painter, pos: Pos2, size: f32) {
+    let radius = size / 2.0;
+    painter.circle_stroke(pos, radius + 1.0, Stroke::new(1.0, Color32::BLACK));
+    painter.circle_stroke(pos, radius, Stroke::new(1.0, Color32::WHITE));
+}
+
 pub fn draw_size_preview(painter: &Painter, pos: Pos2, size: f32) {
     const SIZE_PREVIEW_BORDER_WIDTH: f32 = 2.0;
     let radius = size / 2.0;
@@ -419,6 +1549,38 @@ pub fn draw_size_preview(painter: &Painter, pos: Pos2, size: f32) {
     );
 }
 
+/// Decodes the frames of the GIF at `path` together with how long each is shown, scaled down to
+/// fit `max_size`; decoding stops at [`ImageAnimation::frame_limit`], the returned flag tells
+/// whether later frames were dropped
+pub fn decode_gif_frames(
+    path: &std::path::Path,
+    max_size: u32,
+) -> image::ImageResult<(Vec<(DynamicImage, std::time::Duration)>, bool)> {
+    use image::{AnimationDecoder, ImageDecoder};
+
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let decoder = image::codecs::gif::GifDecoder::new(file)?;
+    // 按缩小后的尺寸计算能保留多少帧，与 resize_image_for_texture 的缩放一致
+    let (width, height) = decoder.dimensions();
+    let scale = (max_size as f32 / width.max(height).max(1) as f32).min(1.0);
+    let limit = ImageAnimation::frame_limit([
+        (width as f32 * scale) as u32,
+        (height as f32 * scale) as u32,
+    ]);
+
+    let mut frames = Vec::new();
+    for frame in decoder.into_frames() {
+        if frames.len() == limit {
+            return Ok((frames, true));
+        }
+        let frame = frame?;
+        let delay = std::time::Duration::from(frame.delay());
+        let image = DynamicImage::ImageRgba8(frame.into_buffer());
+        frames.push((resize_image_for_texture(image, max_size), delay));
+    }
+    Ok((frames, false))
+}
+
 // 将图像调整大小以适应最大纹理大小限制
 // 最大纹理大小通常为 2048x2048，如果图像超过此限制，将其缩放以适应
 pub fn resize_image_for_texture(image: DynamicImage, max_texture_size: u32) -> DynamicImage {
@@ -464,6 +1626,44 @@ pub fn get_default_canvas_color() -> Color32 {
     Color32::from_rgb(15, 38, 30)
 }
 
+/// Canvas color presets: label, canvas color and a brush color that reads well on it
+pub const CANVAS_PRESETS: [(&str, Color32, Color32); 4] = [
+    ("黑板", Color32::from_rgb(15, 38, 30), Color32::WHITE),
+    ("白板", Color32::from_rgb(250, 250, 250), Color32::BLACK),
+    (
+        "深色",
+        Color32::from_rgb(32, 32, 36),
+        Color32::from_rgb(230, 230, 230),
+    ),
+    (
+        "浅色",
+        Color32::from_rgb(236, 232, 222),
+        Color32::from_rgb(40, 40, 40),
+    ),
+];
+
+/// Brush colors with a lower contrast ratio against the canvas than this are hard to see
+pub const MIN_INK_CONTRAST: f32 = 3.0;
+
+/// Relative luminance of `color` as defined by WCAG, 0 for black and 1 for white
+pub fn relative_luminance(color: Color32) -> f32 {
+    let channel = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(color.r()) + 0.7152 * channel(color.g()) + 0.0722 * channel(color.b())
+}
+
+/// WCAG contrast ratio between two opaque colors, from 1 (identical) to 21 (black on white)
+pub fn contrast_ratio(a: Color32, b: Color32) -> f32 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
 // 绘制调整句柄
 #[cfg_attr(feature = "profiling", profiling::function)]
 pub fn draw_resize_handles(painter: &egui::Painter, bbox: Rect) {
@@ -594,13 +1794,25 @@ pub fn rasterize_text(
             face.outline_glyph(glyph_id, &mut builder);
 
             for points in builder.strokes {
-                strokes.push(CanvasStroke {
-                    points,
-                    width: StrokeWidth::Fixed(1.0),
-                    color: text.color,
-                    base_width: text.font_size,
-                    rot: 0.0,
-                });
+                strokes.push(
+                    CanvasStroke {
+                        id: CanvasStroke::new_id(),
+                        points,
+                        width: StrokeWidth::Fixed(1.0),
+                        color: text.color,
+                        base_width: text.font_size,
+                        rot: 0.0,
+                        is_highlighter: false,
+                        line_style: LineStyle::Solid,
+                        texture: None,
+                        style: BrushStyle::Marker,
+                        bounds: egui::Rect::NOTHING,
+                        layer: text.layer,
+                        group: text.group,
+                        locked: text.locked,
+                    }
+                    .with_bounds(),
+                );
             }
 
             cursor_x += face.glyph_hor_advance(glyph_id).unwrap_or(0) as f32 * scale;
@@ -670,3 +1882,71 @@ impl OutlineBuilder for StrokeBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 沿水平线带有 ±0.4 抖动的手绘笔画，使用固定种子保证结果可复现
+    fn noisy_line(count: usize) -> Vec<Pos2> {
+        let mut seed: u32 = 12345;
+        (0..count)
+            .map(|i| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let jitter = (seed >> 8) as f32 / (1 << 24) as f32 * 0.8 - 0.4;
+                Pos2::new(i as f32 * 2.0, 50.0 + jitter)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn simplify_stroke_keeps_endpoints() {
+        let points = noisy_line(200);
+        let (simplified, _) = simplify_stroke(&points, &StrokeWidth::Fixed(3.0), 1.0);
+        assert_eq!(simplified.first(), points.first());
+        assert_eq!(simplified.last(), points.last());
+    }
+
+    #[test]
+    fn simplify_stroke_drops_points_on_noisy_input() {
+        let points = noisy_line(200);
+        let (simplified, width) = simplify_stroke(&points, &StrokeWidth::Fixed(3.0), 1.0);
+        assert!(
+            simplified.len() * 4 < points.len(),
+            "{} of {} points kept",
+            simplified.len(),
+            points.len()
+        );
+        assert!(matches!(width, StrokeWidth::Fixed(w) if w == 3.0));
+    }
+
+    #[test]
+    fn simplify_stroke_keeps_corners() {
+        let mut points: Vec<Pos2> = (0..=50).map(|i| Pos2::new(i as f32, 0.0)).collect();
+        points.extend((1..=50).map(|i| Pos2::new(50.0, i as f32)));
+        let (simplified, _) = simplify_stroke(&points, &StrokeWidth::Fixed(3.0), 1.0);
+        assert_eq!(
+            simplified,
+            vec![
+                Pos2::new(0.0, 0.0),
+                Pos2::new(50.0, 0.0),
+                Pos2::new(50.0, 50.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn simplify_stroke_keeps_dynamic_widths_aligned() {
+        let points = noisy_line(200);
+        // 每个点的宽度等于其下标，便于对照保留下来的点
+        let widths: Vec<f32> = (0..points.len()).map(|i| i as f32).collect();
+        let (simplified, width) = simplify_stroke(&points, &StrokeWidth::Dynamic(widths), 1.0);
+        let StrokeWidth::Dynamic(widths) = width else {
+            panic!("dynamic widths became fixed");
+        };
+        assert_eq!(widths.len(), simplified.len());
+        for (point, width) in simplified.iter().zip(&widths) {
+            assert_eq!(*point, points[*width as usize]);
+        }
+    }
+}
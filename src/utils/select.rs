@@ -0,0 +1,246 @@
+use egui::{Pos2, Rect, Vec2};
+
+use crate::state::{AppState, CanvasObject, CanvasObjectOps, PointerInteraction, PointerState};
+
+/// Smallest crop of an image, as a fraction of its width and height
+const MIN_CROP_SIZE: f32 = 0.02;
+
+/// Dragged objects snap to other objects' edges and centers within this many screen pixels
+const ALIGNMENT_SNAP_DISTANCE: f32 = 6.0;
+
+// 开始拖动：按住选中对象的手柄时变换该对象，按住对象时移动该对象（属于多选时移动整组），
+// 在空白处开始拖动时框选
+pub fn select_drag_start(state: &mut AppState, id: u64, pos: Pos2) {
+    // 裁剪图片时拖动选择要保留的区域
+    if let Some((index, _)) = state.cropping_image {
+        if let Some(CanvasObject::Image(image)) = state.canvas.objects.get(index) {
+            let start = image.texture_coords_at(pos);
+            state.pointers.insert(
+                id,
+                PointerState {
+                    id,
+                    pos,
+                    interaction: PointerInteraction::Cropping { start },
+                },
+            );
+        }
+        return;
+    }
+
+    let handle = state
+        .selected_object_index
+        .and_then(|index| state.canvas.objects.get(index))
+        .and_then(|object| {
+            super::get_transform_handle_at_pos(object.bounding_box(), pos)
+                .map(|handle| (handle, object.get_transform()))
+        });
+
+    let interaction = if let Some((handle, transform)) = handle {
+        PointerInteraction::Selecting {
+            drag_start: pos,
+            dragged_handle: Some(handle),
+            drag_original_transform: Some(transform),
+            drag_accumulated_delta: Vec2::ZERO,
+            drag_origin: pos,
+        }
+    } else if let Some(index) = super::hit_test_selectable_objects(&mut state.canvas, pos) {
+        if !state.is_selected(index) {
+            state.select_object(index);
+        }
+        PointerInteraction::Selecting {
+            drag_start: pos,
+            dragged_handle: None,
+            drag_original_transform: None,
+            drag_accumulated_delta: Vec2::ZERO,
+            drag_origin: pos,
+        }
+    } else {
+        PointerInteraction::Marquee { start: pos }
+    };
+
+    state.pointers.insert(
+        id,
+        PointerState {
+            id,
+            pos,
+            interaction,
+        },
+    );
+}
+
+// 拖动中：变换选中对象，或将选中的对象整体移动
+pub fn select_drag_move(state: &mut AppState, id: u64, pos: Pos2, keep_aspect_ratio: bool) {
+    let snap_grid = state.snap_grid();
+    let Some(pointer) = state.pointers.get_mut(&id) else {
+        return;
+    };
+    pointer.pos = pos;
+    if let PointerInteraction::Cropping { start } = pointer.interaction {
+        if let Some((index, crop)) = &mut state.cropping_image
+            && let Some(CanvasObject::Image(image)) = state.canvas.objects.get(*index)
+        {
+            let rect = Rect::from_two_pos(start, image.texture_coords_at(pos));
+            if rect.width() >= MIN_CROP_SIZE && rect.height() >= MIN_CROP_SIZE {
+                *crop = rect;
+            }
+        }
+        return;
+    }
+    let PointerInteraction::Selecting {
+        drag_start,
+        dragged_handle,
+        drag_accumulated_delta,
+        drag_origin,
+        ..
+    } = &mut pointer.interaction
+    else {
+        return;
+    };
+
+    if let Some(handle) = *dragged_handle {
+        if let Some(object) = state
+            .selected_object_index
+            .and_then(|index| state.canvas.objects.get_mut(index))
+        {
+            object.transform(
+                handle,
+                pos - *drag_start,
+                *drag_start,
+                pos,
+                keep_aspect_ratio,
+            );
+        }
+    } else {
+        let indices: Vec<usize> = state
+            .selected_object_index
+            .iter()
+            .chain(&state.selected_objects)
+            .copied()
+            .filter(|&index| index < state.canvas.objects.len())
+            .collect();
+        // 多选时按第一个对象吸附到网格，整组移动相同的距离以保持相对位置
+        if let Some(&first) = indices.first() {
+            let mut delta = super::snapped_move_delta(
+                &state.canvas.objects[first],
+                pos - *drag_origin,
+                *drag_accumulated_delta,
+                snap_grid,
+            );
+            // 未吸附网格时，靠近其他对象的边缘或中线则与其对齐并显示参考线
+            state.alignment_guides.clear();
+            if snap_grid.is_none()
+                && let Some(rect) = indices
+                    .iter()
+                    .map(|&index| state.canvas.objects[index].bounding_box())
+                    .reduce(Rect::union)
+            {
+                let (offset, guides) = super::snap_to_alignment_guides(
+                    &state.canvas,
+                    &indices,
+                    rect.translate(delta),
+                    ALIGNMENT_SNAP_DISTANCE / state.viewport.scale,
+                );
+                delta += offset;
+                state.alignment_guides = guides;
+            }
+            for index in indices {
+                CanvasObject::move_object(&mut state.canvas.objects[index], delta);
+            }
+            *drag_accumulated_delta += delta;
+        }
+    }
+
+    *drag_start = pos;
+}
+
+// 结束拖动：保存移动或变换到历史记录，框选时选中框内的对象
+pub fn select_drag_end(state: &mut AppState, id: u64) {
+    let Some(pointer) = state.pointers.remove(&id) else {
+        return;
+    };
+    state.alignment_guides.clear();
+    match pointer.interaction {
+        PointerInteraction::Selecting {
+            drag_accumulated_delta,
+            drag_original_transform,
+            ..
+        } => {
+            if drag_accumulated_delta != Vec2::ZERO {
+                let moved = match state.selected_object_index {
+                    Some(index) => vec![index],
+                    None => state.selected_objects.clone(),
+                };
+                for index in moved {
+                    if let Some(object) = state.canvas.objects.get(index) {
+                        state.collab.broadcast_move(
+                            state.current_page,
+                            object,
+                            drag_accumulated_delta,
+                        );
+                    }
+                }
+                if let Some(index) = state.selected_object_index {
+                    state.history.save_move_object(
+                        index,
+                        -drag_accumulated_delta,
+                        drag_accumulated_delta,
+                    );
+                } else if !state.selected_objects.is_empty() {
+                    let moves = state
+                        .selected_objects
+                        .iter()
+                        .map(|&index| (index, drag_accumulated_delta))
+                        .collect();
+                    state.history.save_move_objects(moves);
+                }
+            }
+            if let Some(original) = drag_original_transform
+                && let Some(index) = state.selected_object_index
+                && let Some(object) = state.canvas.objects.get(index)
+            {
+                let new_transform = object.get_transform();
+                // 缩放或旋转后的笔画无法用位移表示，其他成员删除原笔画后按新的形状重新添加
+                if let CanvasObject::Stroke(stroke) = object {
+                    state.collab.broadcast_removal(state.current_page, object);
+                    state.collab.broadcast_stroke(state.current_page, stroke);
+                }
+                state
+                    .history
+                    .save_transform_object(index, original, new_transform);
+            }
+        }
+        PointerInteraction::Marquee { start } => {
+            select_objects_in_rect(state, Rect::from_two_pos(start, pointer.pos));
+        }
+        _ => {}
+    }
+}
+
+// 选中包围盒完全位于矩形内的可见且未锁定的对象
+pub fn select_objects_in_rect(state: &mut AppState, rect: Rect) {
+    let indices: Vec<usize> = state
+        .canvas
+        .objects_in_rect(rect)
+        .into_iter()
+        .filter(|&index| {
+            state.canvas.is_object_visible(index)
+                && !state.canvas.objects[index].is_locked()
+                && rect.contains_rect(state.canvas.objects[index].bounding_box())
+        })
+        .collect();
+
+    // 框中组合的任一对象时选中整个组合
+    let mut indices: Vec<usize> = indices
+        .into_iter()
+        .flat_map(|index| state.canvas.group_members(index))
+        .collect();
+    indices.sort_unstable();
+    indices.dedup();
+
+    state.clear_selection();
+    if indices.len() == 1 {
+        state.selected_object_index = indices.pop();
+    } else {
+        state.selected_objects = indices;
+    }
+}
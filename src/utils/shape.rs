@@ -0,0 +1,105 @@
+use egui::{Color32, Pos2, Vec2};
+
+use crate::state::{
+    AppState, CanvasObject, CanvasShape, CanvasShapeType, PointerInteraction, PointerState,
+};
+
+/// Lines and arrows snap to multiples of this angle while Shift is held or angle snapping is on
+const LINE_SNAP_ANGLE: f32 = std::f32::consts::PI / 12.0;
+
+/// Drags shorter than this are treated as accidental and don't insert a shape
+const MIN_DRAWN_SHAPE_SIZE: f32 = 5.0;
+
+// 使用插入形状时的线型与填充设置创建位于两点之间的形状
+pub fn new_shape(
+    state: &AppState,
+    shape_type: CanvasShapeType,
+    start: Pos2,
+    end: Pos2,
+) -> CanvasShape {
+    let mut shape = CanvasShape {
+        shape_type,
+        pos: start,
+        end,
+        size: 0.0,
+        color: Color32::WHITE,
+        rotation: 0.0,
+        line_style: state.new_shape_line_style,
+        fill: None,
+        arrow_heads: state.new_arrow_heads,
+        bounds: egui::Rect::NOTHING,
+        layer: state.canvas.active_layer,
+        group: None,
+        locked: false,
+    };
+    shape.place_between(start, end);
+    if state.new_shape_fill && shape.is_closed() {
+        shape.fill = Some(state.new_shape_fill_color);
+    }
+    shape
+}
+
+pub fn shape_drag_start(state: &mut AppState, pointer_id: u64, pos: Pos2) {
+    let Some(shape_type) = state.pending_shape else {
+        return;
+    };
+    let shape = new_shape(state, shape_type, pos, pos);
+    state.pointers.insert(
+        pointer_id,
+        PointerState {
+            id: pointer_id,
+            pos,
+            interaction: PointerInteraction::PlacingShape { start: pos, shape },
+        },
+    );
+}
+
+pub fn shape_drag_move(state: &mut AppState, pointer_id: u64, pos: Pos2) {
+    let constrain = state.modifiers.shift_key();
+    let snap_angle = constrain || state.persistent.snap_line_angles;
+    let Some(pointer) = state.pointers.get_mut(&pointer_id) else {
+        return;
+    };
+    pointer.pos = pos;
+    let PointerInteraction::PlacingShape { start, shape } = &mut pointer.interaction else {
+        return;
+    };
+
+    let mut end = pos;
+    let delta = pos - *start;
+    match shape.shape_type {
+        CanvasShapeType::Line | CanvasShapeType::Arrow if snap_angle => {
+            let angle = (delta.angle() / LINE_SNAP_ANGLE).round() * LINE_SNAP_ANGLE;
+            end = *start + Vec2::angled(angle) * delta.length();
+        }
+        // 按住 Shift 绘制正圆
+        CanvasShapeType::Ellipse if constrain => {
+            let side = delta.x.abs().max(delta.y.abs());
+            end = *start + Vec2::new(side.copysign(delta.x), side.copysign(delta.y));
+        }
+        _ => {}
+    }
+
+    shape.place_between(*start, end);
+}
+
+pub fn shape_drag_end(state: &mut AppState, pointer_id: u64) {
+    let Some(pointer) = state.pointers.remove(&pointer_id) else {
+        return;
+    };
+    let PointerInteraction::PlacingShape { shape, .. } = pointer.interaction else {
+        return;
+    };
+    if shape.size < MIN_DRAWN_SHAPE_SIZE {
+        return;
+    }
+
+    let index = state.canvas.objects.len();
+    state
+        .history
+        .save_add_object(index, CanvasObject::Shape(shape.clone()));
+    state.canvas.objects.push(CanvasObject::Shape(shape));
+    if !state.persistent.keep_insertion_window_open {
+        state.pending_shape = None;
+    }
+}
@@ -1,22 +1,95 @@
 use std::time::Instant;
 
-use egui::Pos2;
+use egui::{Color32, Pos2};
+use winit::event::Force;
 
 use crate::state::{
-    ActiveStroke, AppState, CanvasObject, CanvasStroke, DynamicBrushWidthMode, PointerInteraction,
-    PointerState, StrokeWidth,
+    ActiveStroke, AppState, BrushStyle, CanvasObject, CanvasStroke, CanvasTool,
+    DynamicBrushWidthMode, LineStyle, PointerInteraction, PointerState, SmoothingMode,
+    StrokeTexture, StrokeWidth,
 };
 
+/// Opacity of highlighter strokes, so the content underneath stays readable
+pub const HIGHLIGHTER_ALPHA: u8 = 80;
+
+/// Points inserted between each pair of input points by Catmull-Rom smoothing
+const CATMULL_ROM_SEGMENTS: usize = 4;
+
+pub fn highlighter_color(color: Color32) -> Color32 {
+    let [r, g, b, _] = color.to_srgba_unmultiplied();
+    Color32::from_rgba_unmultiplied(r, g, b, HIGHLIGHTER_ALPHA)
+}
+
+/// Ink opacity of pencil strokes
+const PENCIL_OPACITY: f32 = 0.8;
+/// Width of pencil strokes relative to the brush width
+const PENCIL_WIDTH_SCALE: f32 = 0.4;
+const MIN_PENCIL_WIDTH: f32 = 1.0;
+
+/// Builds a brush stroke from the current brush settings, adjusted to the brush style
+pub fn new_brush_stroke(
+    state: &AppState,
+    points: Vec<Pos2>,
+    width: StrokeWidth,
+    color: Color32,
+) -> CanvasStroke {
+    let mut stroke = CanvasStroke {
+        id: CanvasStroke::new_id(),
+        points,
+        width,
+        color,
+        base_width: state.brush_width,
+        rot: 0.0,
+        is_highlighter: false,
+        line_style: state.brush_line_style,
+        texture: state.brush_texture.clone(),
+        style: state.brush_style,
+        bounds: egui::Rect::NOTHING,
+        layer: state.canvas.active_layer,
+        group: None,
+        locked: false,
+    };
+    match state.brush_style {
+        BrushStyle::Marker => {}
+        BrushStyle::Chalk => {
+            // 粉笔不随压感变化，颗粒本身是半透明的
+            stroke.width = StrokeWidth::Fixed(stroke.base_width);
+            stroke.texture = None;
+        }
+        BrushStyle::Pencil => {
+            let thin = |w: f32| (w * PENCIL_WIDTH_SCALE).max(MIN_PENCIL_WIDTH);
+            stroke.base_width = thin(stroke.base_width);
+            stroke.width = match stroke.width {
+                StrokeWidth::Fixed(w) => StrokeWidth::Fixed(thin(w)),
+                StrokeWidth::Dynamic(widths) => {
+                    StrokeWidth::Dynamic(widths.into_iter().map(thin).collect())
+                }
+            };
+            stroke.color = stroke.color.gamma_multiply(PENCIL_OPACITY);
+            stroke.texture = Some(StrokeTexture::Grain);
+        }
+    }
+    stroke.with_bounds()
+}
+
 #[cfg_attr(feature = "profiling", profiling::function)]
-pub fn brush_stroke_start(state: &mut AppState, pointer_id: u64, pos: Pos2) {
+pub fn brush_stroke_start(state: &mut AppState, pointer_id: u64, pos: Pos2, force: Option<Force>) {
     let start_time = Instant::now();
-    let width = super::calculate_dynamic_width(
-        state.brush_width,
-        state.dynamic_brush_width_mode,
-        0,
-        1,
-        None,
-    );
+    // 荧光笔始终使用固定宽度
+    let is_highlighter = state.current_tool == CanvasTool::Highlighter;
+    let width = if is_highlighter {
+        StrokeWidth::Fixed(state.highlighter_width)
+    } else {
+        super::calculate_dynamic_width(
+            state.brush_width,
+            state.dynamic_brush_width_mode,
+            &state.persistent.dynamic_width_curve,
+            0,
+            1,
+            None,
+            force,
+        )
+    };
     state.pointers.insert(
         pointer_id,
         PointerState {
@@ -27,8 +100,10 @@ pub fn brush_stroke_start(state: &mut AppState, pointer_id: u64, pos: Pos2) {
                     points: vec![pos],
                     width,
                     times: vec![0.0],
+                    forces: vec![force],
                     start_time,
                     last_movement_time: start_time,
+                    is_highlighter,
                 },
             },
         },
@@ -40,6 +115,7 @@ pub fn brush_stroke_add_point(
     state: &mut AppState,
     pointer_id: u64,
     pos: Pos2,
+    force: Option<Force>,
     apply_straightening: bool,
 ) {
     let Some(pointer) = state.pointers.get_mut(&pointer_id) else {
@@ -52,6 +128,24 @@ pub fn brush_stroke_add_point(
 
     let current_time = active_stroke.start_time.elapsed().as_secs_f64();
 
+    // 按住 Shift 时约束为从起点到当前位置的直线
+    if state.modifiers.shift_key() {
+        active_stroke.points.truncate(1);
+        active_stroke.times.truncate(1);
+        active_stroke.forces.truncate(1);
+        if let StrokeWidth::Dynamic(v) = &mut active_stroke.width
+            && let Some(&first) = v.first()
+        {
+            v.truncate(1);
+            v.push(first);
+        }
+        active_stroke.points.push(pos);
+        active_stroke.times.push(current_time);
+        active_stroke.forces.push(force);
+        active_stroke.last_movement_time = Instant::now();
+        return;
+    }
+
     if apply_straightening && state.persistent.stroke_straightening {
         let time_since_last_movement = active_stroke.last_movement_time.elapsed().as_secs_f32();
         if time_since_last_movement > 0.5 {
@@ -80,7 +174,9 @@ pub fn brush_stroke_add_point(
         }
     }
 
-    if active_stroke.points.is_empty() || active_stroke.points.last().unwrap().distance(pos) > 1.0 {
+    if active_stroke.points.is_empty()
+        || active_stroke.points.last().unwrap().distance(pos) > state.min_point_distance
+    {
         let speed = if !active_stroke.points.is_empty() && !active_stroke.times.is_empty() {
             let last_time = active_stroke.times.last().unwrap();
             let time_delta = ((current_time - last_time) as f32).max(0.001);
@@ -92,14 +188,19 @@ pub fn brush_stroke_add_point(
 
         active_stroke.points.push(pos);
         active_stroke.times.push(current_time);
+        active_stroke.forces.push(force);
 
-        if state.dynamic_brush_width_mode != DynamicBrushWidthMode::Disabled {
+        if !active_stroke.is_highlighter
+            && state.dynamic_brush_width_mode != DynamicBrushWidthMode::Disabled
+        {
             let stroke_width = super::calculate_dynamic_width(
                 state.brush_width,
                 state.dynamic_brush_width_mode,
+                &state.persistent.dynamic_width_curve,
                 active_stroke.points.len() - 1,
                 active_stroke.points.len(),
                 speed,
+                force,
             );
             active_stroke.width.push(stroke_width.first());
         }
@@ -137,28 +238,87 @@ pub fn brush_stroke_end(state: &mut AppState, pointer_id: u64) {
         unreachable!()
     };
 
-    let mut final_points = if state.persistent.stroke_smoothing {
-        super::apply_stroke_smoothing(&active_stroke.points)
-    } else {
-        active_stroke.points
+    // 先去掉密集采样产生的冗余点，再平滑；容差按屏幕像素计，放大书写时保留更多细节
+    let (points, width) = super::simplify_stroke(
+        &active_stroke.points,
+        &active_stroke.width,
+        state.persistent.stroke_simplification_epsilon / state.viewport.scale,
+    );
+    let (mut final_points, width) = match state.persistent.smoothing_mode {
+        SmoothingMode::None => (points, width),
+        SmoothingMode::MovingAverage => (
+            super::apply_stroke_smoothing(&points, state.smoothing_strength),
+            width,
+        ),
+        SmoothingMode::CatmullRom => {
+            super::apply_catmull_rom_smoothing(&points, &width, CATMULL_ROM_SEGMENTS)
+        }
     };
 
     let width = super::apply_point_interpolation_in_place(
         &mut final_points,
-        &active_stroke.width,
+        &width,
         state.persistent.interpolation_frequency,
     );
 
-    let new_stroke = CanvasStroke {
-        points: final_points,
-        width,
-        color: state.brush_color,
-        base_width: state.brush_width,
-        rot: 0.0,
+    let new_stroke = if active_stroke.is_highlighter {
+        CanvasStroke {
+            id: CanvasStroke::new_id(),
+            points: final_points,
+            width,
+            color: highlighter_color(state.highlighter_color),
+            base_width: state.highlighter_width,
+            rot: 0.0,
+            is_highlighter: true,
+            line_style: LineStyle::Solid,
+            texture: None,
+            style: BrushStyle::Marker,
+            bounds: egui::Rect::NOTHING,
+            layer: state.canvas.active_layer,
+            group: None,
+            locked: false,
+        }
+        .with_bounds()
+    } else {
+        new_brush_stroke(state, final_points, width, state.ink_color())
     };
+    // 对称模式下同时提交镜像出的笔画，它们是各自独立的对象
+    let strokes: Vec<CanvasStroke> = std::iter::once(new_stroke.clone())
+        .chain(
+            state
+                .symmetry
+                .mirror(&new_stroke.points, state.symmetry_center)
+                .into_iter()
+                .map(|points| {
+                    CanvasStroke {
+                        id: CanvasStroke::new_id(),
+                        points,
+                        ..new_stroke.clone()
+                    }
+                    .with_bounds()
+                }),
+        )
+        .collect();
+    for stroke in &strokes {
+        state.collab.broadcast_stroke(state.current_page, stroke);
+        if let Some(recorder) = &mut state.session_recorder {
+            recorder.note_drawn_stroke(stroke, &active_stroke.times);
+        }
+    }
+    // 只记录实际画过的颜色，避免拖动取色器时记下中间色
+    if !new_stroke.is_highlighter {
+        state.remember_recent_color(state.brush_color);
+    }
     let index = state.canvas.objects.len();
-    state
-        .history
-        .save_add_object(index, CanvasObject::Stroke(new_stroke.clone()));
-    state.canvas.objects.push(CanvasObject::Stroke(new_stroke));
+    if state.persistent.shape_recognition && !new_stroke.is_highlighter {
+        state.pending_shape_recognition =
+            super::recognize_shape(&new_stroke.points).map(|shape_type| (index, shape_type));
+    }
+    let objects: Vec<CanvasObject> = strokes.into_iter().map(CanvasObject::Stroke).collect();
+    if objects.len() > 1 {
+        state.history.save_add_objects(index, objects.clone());
+    } else {
+        state.history.save_add_object(index, objects[0].clone());
+    }
+    state.canvas.objects.extend(objects);
 }
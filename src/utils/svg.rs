@@ -0,0 +1,475 @@
+use std::fmt::Write as _;
+use std::io::Cursor;
+use std::path::Path;
+
+use egui::{Color32, Pos2, Rect, Vec2};
+
+use crate::i18n::t;
+use crate::state::{
+    CanvasImage, CanvasObject, CanvasObjectOps, CanvasShape, CanvasShapeType, CanvasState,
+    CanvasStickyNote, CanvasStroke, CanvasText, LineStyle, STICKY_NOTE_PADDING,
+    STICKY_NOTE_ROUNDING, StrokeWidth,
+};
+
+/// Empty space around the objects in exported documents
+const SVG_MARGIN: f32 = 20.0;
+/// Outline width of shapes, matching how they are painted on the board
+const SHAPE_STROKE_WIDTH: f32 = 2.0;
+/// Line height of multi-line text, relative to the font size
+const TEXT_LINE_HEIGHT: f32 = 1.2;
+
+/// Writes the visible objects of `canvas` to `path` as an SVG document on a `background` colored sheet.
+/// Strokes become polylines or filled outlines, shapes and text use native SVG elements and images are embedded as PNG.
+pub fn export_svg(
+    canvas: &CanvasState,
+    background: Color32,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let objects: Vec<&CanvasObject> = canvas
+        .draw_order()
+        .into_iter()
+        .map(|i| &canvas.objects[i])
+        .collect();
+    std::fs::write(
+        path,
+        objects_to_svg(&objects, Some(background), SVG_MARGIN)?,
+    )?;
+    Ok(())
+}
+
+/// Writes only the objects at `indices` to `path`, cropped to their combined bounding box on a transparent background
+pub fn export_selection_svg(
+    canvas: &CanvasState,
+    indices: &[usize],
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let objects: Vec<&CanvasObject> = canvas
+        .draw_order()
+        .into_iter()
+        .filter(|i| indices.contains(i))
+        .map(|i| &canvas.objects[i])
+        .collect();
+    std::fs::write(path, objects_to_svg(&objects, None, 0.0)?)?;
+    Ok(())
+}
+
+/// Builds an SVG document of `objects`, painted in the given order.
+/// The document covers their bounding box grown by `margin`, translated so its top-left corner is the origin.
+pub fn objects_to_svg(
+    objects: &[&CanvasObject],
+    background: Option<Color32>,
+    margin: f32,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let bounds = objects
+        .iter()
+        .map(|obj| obj.bounding_box())
+        .reduce(|a, b| a.union(b))
+        .ok_or(t("没有可导出的对象"))?
+        .expand(margin);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}" width="{}" height="{}">"#,
+        num(bounds.width()),
+        num(bounds.height()),
+        num(bounds.width()),
+        num(bounds.height()),
+    )?;
+    if let Some(background) = background {
+        writeln!(
+            svg,
+            r#"<rect width="100%" height="100%" {}/>"#,
+            paint_attrs("fill", background),
+        )?;
+    }
+
+    // 对象保持画布坐标，整体平移使裁剪区域的左上角位于原点
+    writeln!(
+        svg,
+        r#"<g transform="translate({} {})">"#,
+        num(-bounds.min.x),
+        num(-bounds.min.y),
+    )?;
+    for obj in objects {
+        match obj {
+            CanvasObject::Stroke(stroke) => write_stroke(&mut svg, stroke)?,
+            CanvasObject::Shape(shape) => write_shape(&mut svg, shape)?,
+            CanvasObject::Text(text) => write_text(&mut svg, text)?,
+            CanvasObject::StickyNote(note) => write_sticky_note(&mut svg, note)?,
+            CanvasObject::Image(image) => write_image(&mut svg, image)?,
+        }
+    }
+    svg.push_str("</g>\n</svg>\n");
+    Ok(svg)
+}
+
+fn write_stroke(svg: &mut String, stroke: &CanvasStroke) -> std::fmt::Result {
+    let center = stroke.bounds.center();
+    let points: Vec<Pos2> = stroke
+        .points
+        .iter()
+        .map(|&p| super::rotate_point(p, center, stroke.rot))
+        .collect();
+    let Some(&first) = points.first() else {
+        return Ok(());
+    };
+
+    if points.len() == 1 {
+        return writeln!(
+            svg,
+            r#"<circle cx="{}" cy="{}" r="{}" {}/>"#,
+            num(first.x),
+            num(first.y),
+            num(stroke.width.first() / 2.0),
+            paint_attrs("fill", stroke.color),
+        );
+    }
+
+    // 与画布上的绘制方式一致：荧光笔和虚线为等宽折线，可变宽度笔画为填充轮廓
+    match &stroke.width {
+        StrokeWidth::Dynamic(widths)
+            if !stroke.is_highlighter
+                && stroke.line_style == LineStyle::Solid
+                && widths.len() == points.len() =>
+        {
+            let mut outline = ribbon_side(&points, widths, 1.0);
+            outline.extend(ribbon_side(&points, widths, -1.0).into_iter().rev());
+            let last = points.len() - 1;
+            writeln!(svg, r#"<g {}>"#, paint_attrs("fill", stroke.color))?;
+            writeln!(svg, r#"<polygon points="{}"/>"#, points_attr(&outline))?;
+            for i in [0, last] {
+                writeln!(
+                    svg,
+                    r#"<circle cx="{}" cy="{}" r="{}"/>"#,
+                    num(points[i].x),
+                    num(points[i].y),
+                    num(widths[i] / 2.0),
+                )?;
+            }
+            writeln!(svg, "</g>")
+        }
+        _ => {
+            let width = match &stroke.width {
+                StrokeWidth::Dynamic(_) if !stroke.is_highlighter => stroke.base_width,
+                _ => stroke.width.first(),
+            };
+            writeln!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round" {}{}/>"#,
+                points_attr(&points),
+                num(width),
+                paint_attrs("stroke", stroke.color),
+                dash_attrs(stroke.line_style, width),
+            )
+        }
+    }
+}
+
+// 可变宽度笔画一侧的轮廓点，`side` 为 1 或 -1
+fn ribbon_side(points: &[Pos2], widths: &[f32], side: f32) -> Vec<Pos2> {
+    let segment_normal = |a: Pos2, b: Pos2| (b - a).normalized().rot90();
+    (0..points.len())
+        .map(|i| {
+            let incoming = if i > 0 {
+                segment_normal(points[i - 1], points[i])
+            } else {
+                Vec2::ZERO
+            };
+            let outgoing = if i + 1 < points.len() {
+                segment_normal(points[i], points[i + 1])
+            } else {
+                Vec2::ZERO
+            };
+            let normal = (incoming + outgoing).normalized();
+            // 转角处按斜接长度外扩，过尖时限制为两倍
+            let reference = if outgoing == Vec2::ZERO {
+                incoming
+            } else {
+                outgoing
+            };
+            let miter = 1.0 / normal.dot(reference).max(0.5);
+            let offset = normal * widths[i] / 2.0 * miter;
+            if offset.is_finite() {
+                points[i] + offset * side
+            } else {
+                points[i]
+            }
+        })
+        .collect()
+}
+
+fn write_shape(svg: &mut String, shape: &CanvasShape) -> std::fmt::Result {
+    let center = shape.local_bounding_box().center();
+    let transform = rotate_attr(shape.rotation, center);
+    let outline = format!(
+        r#"stroke-width="{}" stroke-linejoin="round" {}{}"#,
+        num(SHAPE_STROKE_WIDTH),
+        paint_attrs("stroke", shape.color),
+        dash_attrs(shape.line_style, SHAPE_STROKE_WIDTH),
+    );
+    let fill = match shape.fill {
+        Some(fill) if shape.is_closed() => paint_attrs("fill", fill),
+        _ => r#"fill="none""#.to_string(),
+    };
+
+    match shape.shape_type {
+        CanvasShapeType::Line => writeln!(
+            svg,
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" {outline}{transform}/>"#,
+            num(shape.pos.x),
+            num(shape.pos.y),
+            num(shape.line_end().x),
+            num(shape.line_end().y),
+        ),
+        CanvasShapeType::Arrow => {
+            writeln!(svg, r#"<g{transform}>"#)?;
+            writeln!(
+                svg,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" {outline}/>"#,
+                num(shape.pos.x),
+                num(shape.pos.y),
+                num(shape.line_end().x),
+                num(shape.line_end().y),
+            )?;
+            // 箭头头部始终为实线
+            for (tip, [left, right]) in shape.arrow_heads() {
+                writeln!(
+                    svg,
+                    r#"<polyline points="{}" fill="none" stroke-width="{}" stroke-linecap="round" {}/>"#,
+                    points_attr(&[left, tip, right]),
+                    num(SHAPE_STROKE_WIDTH),
+                    paint_attrs("stroke", shape.color),
+                )?;
+            }
+            writeln!(svg, "</g>")
+        }
+        CanvasShapeType::Rectangle => writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" {fill} {outline}{transform}/>"#,
+            num(shape.pos.x),
+            num(shape.pos.y),
+            num(shape.size),
+            num(shape.size),
+        ),
+        CanvasShapeType::Circle => writeln!(
+            svg,
+            r#"<circle cx="{}" cy="{}" r="{}" {fill} {outline}/>"#,
+            num(shape.pos.x),
+            num(shape.pos.y),
+            num(shape.size / 2.0),
+        ),
+        CanvasShapeType::Ellipse => {
+            let rect = Rect::from_two_pos(shape.pos, shape.end);
+            writeln!(
+                svg,
+                r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}" {fill} {outline}{transform}/>"#,
+                num(rect.center().x),
+                num(rect.center().y),
+                num(rect.width() / 2.0),
+                num(rect.height() / 2.0),
+            )
+        }
+        CanvasShapeType::Triangle(kind) => writeln!(
+            svg,
+            r#"<polygon points="{}" {fill} {outline}{transform}/>"#,
+            points_attr(&shape.triangle_points(kind)),
+        ),
+        CanvasShapeType::Polygon { .. } | CanvasShapeType::Star { .. } => writeln!(
+            svg,
+            r#"<polygon points="{}" {fill} {outline}{transform}/>"#,
+            points_attr(&shape.star_points()),
+        ),
+    }
+}
+
+fn write_text(svg: &mut String, text: &CanvasText) -> std::fmt::Result {
+    // 文字以左上角定位并绕其旋转，与画布上的绘制一致
+    writeln!(
+        svg,
+        r#"<text x="{}" y="{}" font-size="{}" font-family="sans-serif" {}{} xml:space="preserve">"#,
+        num(text.pos.x),
+        num(text.pos.y),
+        num(text.font_size),
+        paint_attrs("fill", text.color),
+        rotate_attr(text.rot, text.pos),
+    )?;
+    for (i, line) in text.text.lines().enumerate() {
+        let dy = if i == 0 { 1.0 } else { TEXT_LINE_HEIGHT };
+        writeln!(
+            svg,
+            r#"<tspan x="{}" dy="{}em">{}</tspan>"#,
+            num(text.pos.x),
+            num(dy),
+            escape_xml(line),
+        )?;
+    }
+    writeln!(svg, "</text>")
+}
+
+// SVG 文字不会自动换行，便签内只保留原有的换行
+fn write_sticky_note(svg: &mut String, note: &CanvasStickyNote) -> std::fmt::Result {
+    writeln!(
+        svg,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" rx="{}" {}/>"#,
+        num(note.pos.x),
+        num(note.pos.y),
+        num(note.size.x),
+        num(note.size.y),
+        num(STICKY_NOTE_ROUNDING),
+        paint_attrs("fill", note.color),
+    )?;
+    let x = note.pos.x + STICKY_NOTE_PADDING;
+    writeln!(
+        svg,
+        r#"<text x="{}" y="{}" font-size="{}" font-family="sans-serif" {} xml:space="preserve">"#,
+        num(x),
+        num(note.pos.y + STICKY_NOTE_PADDING),
+        num(note.font_size),
+        paint_attrs("fill", note.text_color),
+    )?;
+    for (i, line) in note.text.lines().enumerate() {
+        let dy = if i == 0 { 1.0 } else { TEXT_LINE_HEIGHT };
+        writeln!(
+            svg,
+            r#"<tspan x="{}" dy="{}em">{}</tspan>"#,
+            num(x),
+            num(dy),
+            escape_xml(line),
+        )?;
+    }
+    writeln!(svg, "</text>")
+}
+
+fn write_image(svg: &mut String, image: &CanvasImage) -> std::fmt::Result {
+    let [width, height] = image.image_size;
+    let Some(pixels) =
+        image::RgbaImage::from_raw(width, height, image.adjusted_pixels().into_owned())
+    else {
+        return Ok(());
+    };
+    // 只导出裁剪后显示的部分，crop_imm 会将范围限制在图片内
+    let crop = image.crop;
+    let pixels = image::imageops::crop_imm(
+        &pixels,
+        (crop.min.x * width as f32).round() as u32,
+        (crop.min.y * height as f32).round() as u32,
+        ((crop.width() * width as f32).round() as u32).max(1),
+        ((crop.height() * height as f32).round() as u32).max(1),
+    )
+    .to_image();
+
+    let mut png = Vec::new();
+    if let Err(err) = pixels.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png) {
+        eprintln!("failed to encode image for svg export: {err}");
+        return Ok(());
+    }
+
+    let rect = image.local_rect();
+    writeln!(
+        svg,
+        r#"<image x="{}" y="{}" width="{}" height="{}" opacity="{}" preserveAspectRatio="none"{} href="data:image/png;base64,{}"/>"#,
+        num(rect.min.x),
+        num(rect.min.y),
+        num(rect.width()),
+        num(rect.height()),
+        num(image.opacity as f32 / 255.0),
+        rotate_attr(image.rot, rect.center()),
+        base64_encode(&png),
+    )
+}
+
+// 颜色属性，半透明时附加对应的 opacity 属性
+fn paint_attrs(name: &str, color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let mut attrs = format!(r##"{name}="#{r:02x}{g:02x}{b:02x}""##);
+    if a < u8::MAX {
+        let _ = write!(attrs, r#" {name}-opacity="{}""#, num(a as f32 / 255.0));
+    }
+    attrs
+}
+
+// 与 paint_styled_line 相同的虚线和点线间距
+fn dash_attrs(style: LineStyle, width: f32) -> String {
+    let unit = width.max(2.0);
+    match style {
+        LineStyle::Solid => String::new(),
+        LineStyle::Dashed => format!(
+            r#" stroke-dasharray="{} {}""#,
+            num(unit * 4.0),
+            num(unit * 2.5)
+        ),
+        LineStyle::Dotted => format!(
+            r#" stroke-dasharray="0 {}" stroke-linecap="round""#,
+            num(unit * 2.5)
+        ),
+    }
+}
+
+fn rotate_attr(angle: f32, center: Pos2) -> String {
+    if angle.abs() > 0.001 {
+        format!(
+            r#" transform="rotate({} {} {})""#,
+            num(angle.to_degrees()),
+            num(center.x),
+            num(center.y)
+        )
+    } else {
+        String::new()
+    }
+}
+
+fn points_attr(points: &[Pos2]) -> String {
+    points
+        .iter()
+        .map(|p| format!("{},{}", num(p.x), num(p.y)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// 保留两位小数并去掉多余的零，缩小文件体积
+fn num(value: f32) -> String {
+    let text = format!("{value:.2}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - i * 6)) & 0x3f;
+                encoded.push(ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
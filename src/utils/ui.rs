@@ -1,4 +1,6 @@
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use egui::{Color32, Context, FontDefinitions, Pos2, Visuals};
 use egui_notify::Toasts;
@@ -6,10 +8,30 @@ use winit::window::{Fullscreen, Window, WindowLevel};
 
 use crate::{
     assets,
-    state::{AppState, CanvasState, PageState, ThemeMode, WindowMode},
+    i18n::{t, tf},
+    net::{collab::CollabUpdate, transfer::TransferEvent},
+    state::{
+        AppState, BackgroundImage, CanvasImage, CanvasObject, CanvasState, CanvasStickyNote,
+        CanvasStroke, CanvasText, CanvasTool, ClipboardItem, FULL_IMAGE_CROP, GroupId, History,
+        ImageAdjustments, ImageAnimation, LayerId, PageState, PersistentState, Presentation,
+        STICKY_NOTE_COLOR, STICKY_NOTE_SIZE, StrokeId, StrokeWidth, ThemeMode, WindowMode,
+        autosave::{AUTOSAVE_INTERVAL, Autosaver},
+        board,
+        recording::{RECORDING_FILE_EXT, SessionPlayer, SessionRecorder},
+    },
     utils,
 };
 
+/// Applies the curve tolerance and antialiasing width from the settings to egui's tessellator
+pub fn apply_tessellation_quality(ctx: &Context, persistent: &PersistentState) {
+    ctx.options_mut(|options| {
+        let tessellation = &mut options.tessellation_options;
+        tessellation.bezier_tolerance = persistent.curve_tolerance;
+        tessellation.feathering = persistent.feathering_size > 0.0;
+        tessellation.feathering_size_in_pixels = persistent.feathering_size;
+    });
+}
+
 pub fn apply_theme_mode_and_canvas_color(
     ctx: &Context,
     theme_mode: ThemeMode,
@@ -49,25 +71,18 @@ pub fn apply_window_mode(state: &mut AppState, window: &Arc<Window>) {
         }
         WindowMode::ExclusiveFullscreen => {
             // 全屏
-            // 使用选中的视频模式
-            if let Some(selected_index) = state.selected_video_mode_index {
-                if selected_index < state.fullscreen_video_modes.len() {
-                    if let Some(mode) = state.fullscreen_video_modes.get(selected_index) {
-                        window.set_fullscreen(Some(Fullscreen::Exclusive(mode.clone())));
-                        return;
-                    }
+            // 使用选中的视频模式，否则回退到第一个可用的视频模式
+            let mode = state
+                .selected_video_mode_index
+                .and_then(|index| state.fullscreen_video_modes.get(index))
+                .or_else(|| state.fullscreen_video_modes.first());
+            match mode {
+                Some(mode) => window.set_fullscreen(Some(Fullscreen::Exclusive(mode.clone()))),
+                // 没有可用的视频模式（例如设置来自另一台设备）时使用无边框全屏
+                None => {
+                    window.set_fullscreen(Some(Fullscreen::Borderless(window.current_monitor())))
                 }
             }
-
-            // 回退到第一个可用的视频模式
-            window.set_fullscreen(Some(Fullscreen::Exclusive(
-                state
-                    .fullscreen_video_modes
-                    .first()
-                    .expect("no video mode available")
-                    .clone(),
-            )));
-
             window.set_window_level(WindowLevel::AlwaysOnTop);
         }
         WindowMode::BorderlessFullscreen => {
@@ -86,8 +101,11 @@ pub enum PageAction {
 }
 
 pub fn clear_interaction_state(state: &mut AppState) {
-    state.selected_object_index = None;
+    state.clear_selection();
     state.pointers.clear();
+    state.alignment_guides.clear();
+    state.pending_shape = None;
+    state.context_menu = None;
 }
 
 pub fn switch_to_page_state(state: &mut AppState, page_index: usize) {
@@ -112,27 +130,1129 @@ pub fn add_new_page_state(state: &mut AppState) {
     clear_interaction_state(state);
 }
 
-pub fn load_canvas_from_file(state: &mut AppState) {
-    match CanvasState::load_from_file_with_dialog() {
-        Ok(canvas) => {
-            add_new_page_state(state);
-            state.canvas = canvas;
+pub fn start_presentation(state: &mut AppState, window: &Arc<Window>) {
+    clear_interaction_state(state);
+    state.presentation = Some(Presentation {
+        page_shown_at: Instant::now(),
+    });
+    // 窗口化时临时切换到无边框全屏，退出演示后恢复
+    if state.persistent.window_mode == WindowMode::Windowed {
+        window.set_fullscreen(Some(Fullscreen::Borderless(window.current_monitor())));
+    }
+}
+
+pub fn stop_presentation(state: &mut AppState, window: &Arc<Window>) {
+    state.presentation = None;
+    if state.persistent.window_mode == WindowMode::Windowed {
+        window.set_fullscreen(None);
+    }
+}
+
+/// Shows page `page_index` during a presentation and restarts the auto-advance timer
+pub fn present_page(state: &mut AppState, page_index: usize) {
+    if page_index >= state.pages.len() {
+        return;
+    }
+    switch_to_page_state(state, page_index);
+    if let Some(presentation) = &mut state.presentation {
+        presentation.page_shown_at = Instant::now();
+    }
+}
+
+pub fn update_presentation(state: &mut AppState, ctx: &Context) {
+    let Some(presentation) = state.presentation else {
+        return;
+    };
+    let interval = Duration::from_secs(state.persistent.slideshow_interval_secs.into());
+    if interval.is_zero() || state.pages.len() < 2 {
+        return;
+    }
+    let remaining = interval.saturating_sub(presentation.page_shown_at.elapsed());
+    if remaining.is_zero() {
+        // 自动翻页到最后一页后从头循环播放
+        present_page(state, (state.current_page + 1) % state.pages.len());
+        ctx.request_repaint_after(interval);
+    } else {
+        // 翻页之间没有输入时不重绘，到时间后唤醒一次
+        ctx.request_repaint_after(remaining);
+    }
+}
+
+pub fn load_canvas_from_file(state: &mut AppState, ctx: &Context) {
+    match board::load_pages_with_dialog(ctx) {
+        Ok((canvases, current_page)) => {
+            // 加载的页面追加在已有页面之后，不覆盖当前的工作
+            let first_new = state.pages.len();
+            for canvas in canvases {
+                add_new_page_state(state);
+                state.canvas = canvas;
+            }
+            switch_to_page_state(state, first_new + current_page);
             state.show_welcome_window = false;
-            state.toasts.success("成功加载画布!");
+            state.toasts.success(t("成功加载画布!"));
         }
         Err(err) => {
-            state.toasts.error(format!("画布加载失败: {}!", err));
+            state.toasts.error(tf("画布加载失败: {}!", &[&err]));
         }
     };
 }
 
+/// Returns the widget the user is currently dragging or typing into, which groups the edits
+/// it makes into a single undo step
+pub fn current_interaction(ctx: &Context) -> Option<egui::Id> {
+    ctx.dragged_id()
+        .or_else(|| ctx.memory(|memory| memory.focused()))
+}
+
+pub fn undo(state: &mut AppState) {
+    state.clear_selection(); // prevent selecting phantom object
+    let before = collab_strokes(state);
+    if state.history.undo(&mut state.canvas) {
+        broadcast_stroke_changes(state, before);
+        state.toasts.success(t("成功撤销操作!"));
+    } else {
+        state.toasts.error(t("无法撤销，没有更多历史记录!"));
+    }
+}
+
+pub fn redo(state: &mut AppState) {
+    state.clear_selection(); // prevent selecting phantom object
+    let before = collab_strokes(state);
+    if state.history.redo(&mut state.canvas) {
+        broadcast_stroke_changes(state, before);
+        state.toasts.success(t("成功重做操作!"));
+    } else {
+        state.toasts.error(t("无法重做，没有更多历史记录!"));
+    }
+}
+
+// 粘贴和创建副本时相对原对象的偏移
+const PASTE_OFFSET: egui::Vec2 = egui::vec2(20.0, 20.0);
+
+fn selected_object(state: &AppState) -> Option<&CanvasObject> {
+    state
+        .selected_object_index
+        .and_then(|index| state.canvas.objects.get(index))
+}
+
+// 将对象副本偏移后添加到画布并选中
+fn add_object_copy(state: &mut AppState, mut object: CanvasObject, offset: egui::Vec2) {
+    CanvasObject::move_object(&mut object, offset);
+    object.set_layer(state.canvas.active_layer);
+    // 副本不属于原对象的组合
+    object.set_group(None);
+    if let CanvasObject::Stroke(stroke) = &mut object {
+        stroke.id = CanvasStroke::new_id();
+        state.collab.broadcast_stroke(state.current_page, stroke);
+    }
+    let index = state.canvas.objects.len();
+    state.history.save_add_object(index, object.clone());
+    state.canvas.objects.push(object);
+    state.selected_object_index = Some(index);
+}
+
+pub fn copy_selected_object(state: &mut AppState) {
+    if let Some(object) = selected_object(state).cloned() {
+        state.clipboard = Some(ClipboardItem {
+            object,
+            paste_count: 0,
+        });
+        state.toasts.success(t("对象已复制到剪贴板!"));
+    }
+}
+
+pub fn delete_selected_object(state: &mut AppState) {
+    if !state.selected_objects.is_empty() {
+        delete_selected_objects(state);
+        return;
+    }
+    let Some(index) = state
+        .selected_object_index
+        .filter(|&index| index < state.canvas.objects.len())
+    else {
+        return;
+    };
+    let object = state.canvas.objects.remove(index);
+    state.collab.broadcast_removal(state.current_page, &object);
+    state.history.save_remove_object(index, object);
+    state.selected_object_index = None;
+    state.toasts.success(t("对象已删除!"));
+}
+
+// 删除多选的对象，作为一条历史记录保存
+fn delete_selected_objects(state: &mut AppState) {
+    let mut indices: Vec<usize> = state
+        .selected_objects
+        .iter()
+        .copied()
+        .filter(|&index| index < state.canvas.objects.len())
+        .collect();
+    indices.sort_unstable();
+    indices.dedup();
+    // 从后往前删除，前面的下标保持不变
+    let mut objects: Vec<(usize, CanvasObject)> = indices
+        .into_iter()
+        .rev()
+        .map(|index| (index, state.canvas.objects.remove(index)))
+        .collect();
+    objects.reverse();
+    for (_, object) in &objects {
+        state.collab.broadcast_removal(state.current_page, object);
+    }
+    state.history.save_remove_objects(objects);
+    state.clear_selection();
+    state.toasts.success(t("对象已删除!"));
+}
+
+// 将多选的对象组合为一组，原有的组合被合并
+pub fn group_selected_objects(state: &mut AppState) {
+    if state.selected_objects.len() < 2 {
+        return;
+    }
+    let group = Some(state.canvas.next_group_id());
+    set_selected_objects_group(state, group);
+    state.toasts.success(t("已组合!"));
+}
+
+pub fn ungroup_selected_objects(state: &mut AppState) {
+    set_selected_objects_group(state, None);
+    state.toasts.success(t("已取消组合!"));
+}
+
+fn set_selected_objects_group(state: &mut AppState, group: Option<GroupId>) {
+    let changes: Vec<_> = state
+        .selected_objects
+        .iter()
+        .filter_map(|&index| {
+            let object = state.canvas.objects.get_mut(index)?;
+            let old_group = object.group();
+            object.set_group(group);
+            (old_group != group).then_some((index, old_group, group))
+        })
+        .collect();
+    if !changes.is_empty() {
+        state.history.save_change_groups(changes);
+    }
+}
+
+pub fn bring_selected_to_front(state: &mut AppState) {
+    if move_selected_objects_to_end(state, true) {
+        state.toasts.success(t("对象已移至顶部!"));
+    }
+}
+
+pub fn send_selected_to_back(state: &mut AppState) {
+    if move_selected_objects_to_end(state, false) {
+        state.toasts.success(t("对象已移至底部!"));
+    }
+}
+
+// 将选中的对象整体移到最上层或最下层，它们之间的叠放顺序不变；已在该处时返回 false
+fn move_selected_objects_to_end(state: &mut AppState, to_front: bool) -> bool {
+    let len = state.canvas.objects.len();
+    let (selected, others): (Vec<usize>, Vec<usize>) =
+        (0..len).partition(|&index| state.is_selected(index));
+    let count = selected.len();
+    let order: Vec<usize> = if to_front {
+        others.into_iter().chain(selected).collect()
+    } else {
+        selected.into_iter().chain(others).collect()
+    };
+    if count == 0 || order.iter().copied().eq(0..len) {
+        return false;
+    }
+    state.canvas.reorder_objects(&order);
+    state.history.save_reorder_objects(order);
+
+    // 选中的对象位于新顺序的开头或末尾
+    let start = if to_front { len - count } else { 0 };
+    state.clear_selection();
+    if count == 1 {
+        state.selected_object_index = Some(start);
+    } else {
+        state.selected_objects = (start..start + count).collect();
+    }
+    true
+}
+
+/// Locks the selected objects so they can no longer be selected, moved or erased
+pub fn lock_selected_objects(state: &mut AppState) {
+    let indices: Vec<usize> = state
+        .selected_object_index
+        .into_iter()
+        .chain(state.selected_objects.iter().copied())
+        .collect();
+    if set_objects_locked(state, &indices, true) {
+        state.clear_selection();
+        state.toasts.success(t("已锁定!"));
+    }
+}
+
+/// Unlocks the object at `index` together with the rest of its group
+pub fn unlock_object(state: &mut AppState, index: usize) {
+    let indices = state.canvas.group_members(index);
+    if set_objects_locked(state, &indices, false) {
+        state.toasts.success(t("已解锁!"));
+    }
+}
+
+fn set_objects_locked(state: &mut AppState, indices: &[usize], locked: bool) -> bool {
+    let changes: Vec<_> = indices
+        .iter()
+        .filter_map(|&index| {
+            let object = state.canvas.objects.get_mut(index)?;
+            let changed = object.is_locked() != locked;
+            object.set_locked(locked);
+            changed.then_some((index, locked))
+        })
+        .collect();
+    if changes.is_empty() {
+        return false;
+    }
+    state.history.save_change_locks(changes);
+    true
+}
+
+pub fn move_selected_object_to_layer(state: &mut AppState, layer: LayerId) {
+    let Some(index) = state
+        .selected_object_index
+        .filter(|&index| index < state.canvas.objects.len())
+    else {
+        return;
+    };
+    let old_object = state.canvas.objects[index].clone();
+    if old_object.layer() == layer {
+        return;
+    }
+    let mut new_object = old_object.clone();
+    new_object.set_layer(layer);
+    state
+        .history
+        .save_replace_object(index, old_object, new_object.clone());
+    state.canvas.objects[index] = new_object;
+    // 目标图层隐藏时对象不再可见，不应保持选中
+    if !state.canvas.is_layer_visible(layer) {
+        state.selected_object_index = None;
+    }
+}
+
+// 对多选的对象应用位移，并作为一条历史记录保存
+pub fn move_selected_objects(state: &mut AppState, moves: Vec<(usize, egui::Vec2)>) {
+    if moves.is_empty() {
+        return;
+    }
+    for &(index, delta) in &moves {
+        CanvasObject::move_object(&mut state.canvas.objects[index], delta);
+        state
+            .collab
+            .broadcast_move(state.current_page, &state.canvas.objects[index], delta);
+    }
+    state.history.save_move_objects(moves);
+}
+
+// 按住 Shift 点击时将对象加入或移出多选，组合中的对象整体加入或移出
+pub fn toggle_object_selection(state: &mut AppState, index: usize) {
+    if let Some(selected) = state.selected_object_index.take() {
+        state.selected_objects = vec![selected];
+    }
+    let members = state.canvas.group_members(index);
+    if state.selected_objects.contains(&index) {
+        state.selected_objects.retain(|i| !members.contains(i));
+    } else {
+        for member in members {
+            if !state.selected_objects.contains(&member) {
+                state.selected_objects.push(member);
+            }
+        }
+    }
+    // 只剩一个对象时回到单选，以便使用变换手柄与对象操作
+    if state.selected_objects.len() == 1 {
+        state.selected_object_index = state.selected_objects.pop();
+    }
+}
+
+pub fn cut_selected_object(state: &mut AppState) {
+    let Some(index) = state
+        .selected_object_index
+        .filter(|&index| index < state.canvas.objects.len())
+    else {
+        return;
+    };
+    let object = state.canvas.objects.remove(index);
+    state.collab.broadcast_removal(state.current_page, &object);
+    state.history.save_remove_object(index, object.clone());
+    state.clipboard = Some(ClipboardItem {
+        object,
+        paste_count: 0,
+    });
+    state.selected_object_index = None;
+    state.toasts.success(t("对象已剪切!"));
+}
+
+pub fn paste_clipboard(state: &mut AppState, ctx: &Context) {
+    let Some(item) = &mut state.clipboard else {
+        paste_system_clipboard(state, ctx);
+        return;
+    };
+    // 连续粘贴时逐次偏移，避免副本相互重叠
+    item.paste_count += 1;
+    let offset = PASTE_OFFSET * item.paste_count as f32;
+    let object = item.object.clone();
+    add_object_copy(state, object, offset);
+}
+
+// 内部剪贴板为空时粘贴系统剪贴板中的图片或文本，图片放在视图中央，文本放在光标处
+fn paste_system_clipboard(state: &mut AppState, ctx: &Context) {
+    let mut clipboard = match arboard::Clipboard::new() {
+        Ok(clipboard) => clipboard,
+        Err(err) => {
+            state.toasts.error(tf("无法访问系统剪贴板: {}!", &[&err]));
+            return;
+        }
+    };
+    let center = state.viewport.to_canvas(ctx.content_rect().center());
+
+    if let Ok(data) = clipboard.get_image() {
+        let Some(rgba) = image::RgbaImage::from_raw(
+            data.width as u32,
+            data.height as u32,
+            data.bytes.into_owned(),
+        ) else {
+            state.toasts.error(t("剪贴板中的图片无效!"));
+            return;
+        };
+        let aspect_ratio = rgba.width() as f32 / rgba.height() as f32;
+        let size = egui::vec2(INSERTED_IMAGE_WIDTH, INSERTED_IMAGE_WIDTH / aspect_ratio);
+        insert_image(state, ctx, rgba.into(), center - size / 2.0);
+        state.toasts.success(t("已粘贴图片!"));
+    } else if let Ok(text) = clipboard.get_text()
+        && !text.trim().is_empty()
+    {
+        let content_rect = ctx.content_rect();
+        let pos = ctx
+            .input(|i| i.pointer.latest_pos())
+            .filter(|pos| content_rect.contains(*pos))
+            .map_or(center, |pos| state.viewport.to_canvas(pos));
+        let mut text = CanvasText {
+            // 统一换行符，多行文本按行绘制
+            text: text.replace("\r\n", "\n"),
+            pos,
+            color: state.new_text_color,
+            font_size: state.new_text_font_size,
+            rot: 0.0,
+            cached_size: None,
+            layer: state.canvas.active_layer,
+            group: None,
+            locked: false,
+        };
+        let painter = egui::Painter::new(ctx.clone(), egui::LayerId::background(), content_rect);
+        text.cached_size = Some(text.layout(&painter).size());
+        let index = state.canvas.objects.len();
+        state
+            .history
+            .save_add_object(index, CanvasObject::Text(text.clone()));
+        state.canvas.objects.push(CanvasObject::Text(text));
+        state.toasts.success(t("已粘贴文本!"));
+    }
+}
+
+/// Largest image side uploaded as a texture, bigger images are scaled down
+const MAX_TEXTURE_SIZE: u32 = 2048;
+/// Width that inserted images initially take up on the board
+const INSERTED_IMAGE_WIDTH: f32 = 300.0;
+
+/// Adds `img` as a new image object with its top-left corner at `pos`
+pub fn insert_image(state: &mut AppState, ctx: &Context, img: image::DynamicImage, pos: Pos2) {
+    insert_animated_image(state, ctx, vec![(img, Duration::ZERO)], pos);
+}
+
+/// Adds an image that cycles through `frames` (each with how long it's shown), a single frame gives a still image
+pub fn insert_animated_image(
+    state: &mut AppState,
+    ctx: &Context,
+    frames: Vec<(image::DynamicImage, Duration)>,
+    pos: Pos2,
+) {
+    // 如果图像太大，调整大小以适应纹理限制
+    let mut frames = frames.into_iter().map(|(img, delay)| {
+        let img = if img.width() > MAX_TEXTURE_SIZE || img.height() > MAX_TEXTURE_SIZE {
+            utils::resize_image_for_texture(img, MAX_TEXTURE_SIZE)
+        } else {
+            img
+        };
+        (img.to_rgba8(), delay)
+    });
+    let Some((img_rgba, first_delay)) = frames.next() else {
+        return;
+    };
+    let (width, height) = img_rgba.dimensions();
+    let aspect_ratio = width as f32 / height as f32;
+    let texture = ctx.load_texture(
+        "inserted_image",
+        egui::ColorImage::from_rgba_unmultiplied([width as usize, height as usize], &img_rgba),
+        egui::TextureOptions::LINEAR,
+    );
+    let image_data: Arc<[u8]> = img_rgba.into_raw().into();
+
+    let rest: Vec<(Arc<[u8]>, Duration)> = frames
+        .map(|(frame, delay)| (frame.into_raw().into(), delay))
+        .collect();
+    let animation = (!rest.is_empty()).then(|| {
+        let frames = std::iter::once((image_data.clone(), first_delay))
+            .chain(rest)
+            .collect();
+        ImageAnimation::new(ctx, frames, [width, height], ImageAdjustments::NONE)
+    });
+
+    let new_image = CanvasImage {
+        texture,
+        pos,
+        size: egui::vec2(INSERTED_IMAGE_WIDTH, INSERTED_IMAGE_WIDTH / aspect_ratio),
+        aspect_ratio,
+        marked_for_deletion: false,
+        rot: 0.0,
+        image_data,
+        image_size: [width, height],
+        crop: FULL_IMAGE_CROP,
+        adjustments: ImageAdjustments::NONE,
+        animation,
+        opacity: 255,
+        layer: state.canvas.active_layer,
+        group: None,
+        locked: false,
+    };
+    let index = state.canvas.objects.len();
+    state
+        .history
+        .save_add_object(index, CanvasObject::Image(new_image.clone()));
+    state.canvas.objects.push(CanvasObject::Image(new_image));
+}
+
+/// Adds an empty sticky note and selects it, so its text can be typed in the selection panel right away
+pub fn insert_sticky_note(state: &mut AppState) {
+    let note = CanvasStickyNote {
+        text: String::new(),
+        pos: state.new_object_pos(),
+        size: STICKY_NOTE_SIZE,
+        color: STICKY_NOTE_COLOR,
+        text_color: Color32::BLACK,
+        font_size: state.new_text_font_size,
+        layer: state.canvas.active_layer,
+        group: None,
+        locked: false,
+    };
+    let index = state.canvas.objects.len();
+    state
+        .history
+        .save_add_object(index, CanvasObject::StickyNote(note.clone()));
+    state.canvas.objects.push(CanvasObject::StickyNote(note));
+    state.current_tool = CanvasTool::Select;
+    state.select_object(index);
+}
+
+/// Starts editing the crop of the selected image
+pub fn start_image_crop(state: &mut AppState) {
+    if let Some(index) = state.selected_object_index
+        && let Some(CanvasObject::Image(image)) = state.canvas.objects.get(index)
+    {
+        state.cropping_image = Some((index, image.crop));
+    }
+}
+
+/// Applies the crop being edited to its image; the pixels are kept, so the crop can be undone or widened again later
+pub fn confirm_image_crop(state: &mut AppState) {
+    let Some((index, crop)) = state.cropping_image.take() else {
+        return;
+    };
+    let Some(CanvasObject::Image(image)) = state.canvas.objects.get(index) else {
+        return;
+    };
+    if image.crop == crop {
+        return;
+    }
+    let old_image = image.clone();
+    let mut new_image = old_image.clone();
+    new_image.set_crop(crop);
+    state.history.save_replace_object(
+        index,
+        CanvasObject::Image(old_image),
+        CanvasObject::Image(new_image.clone()),
+    );
+    state.canvas.objects[index] = CanvasObject::Image(new_image);
+}
+
+pub fn duplicate_selected_object(state: &mut AppState) {
+    if let Some(object) = selected_object(state).cloned() {
+        add_object_copy(state, object, PASTE_OFFSET);
+        state.toasts.success(t("对象已复制!"));
+    }
+}
+
+/// Fills the enclosed region around the canvas position `pos` in a captured frame of the canvas,
+/// inserting the filled area as an image
+pub fn apply_flood_fill(
+    state: &mut AppState,
+    ctx: &Context,
+    pos: Pos2,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) {
+    let pixels_per_point = ctx.pixels_per_point();
+    let seed = state.viewport.to_screen(pos).to_vec2() * pixels_per_point;
+    let Some((region, [x, y])) = utils::flood_fill(
+        pixels,
+        width,
+        height,
+        [seed.x as u32, seed.y as u32],
+        state.fill_color,
+    ) else {
+        state.toasts.warning(t("该区域未封闭, 无法填充!"));
+        return;
+    };
+
+    let (region_width, region_height) = region.dimensions();
+    let texture = ctx.load_texture(
+        "flood_fill",
+        egui::ColorImage::from_rgba_unmultiplied(
+            [region_width as usize, region_height as usize],
+            &region,
+        ),
+        egui::TextureOptions::LINEAR,
+    );
+    // 截取的像素坐标换算回画布坐标
+    let min = state
+        .viewport
+        .to_canvas(Pos2::new(x as f32, y as f32) / pixels_per_point);
+    let size = egui::vec2(region_width as f32, region_height as f32)
+        / pixels_per_point
+        / state.viewport.scale;
+    let image = CanvasImage {
+        texture,
+        pos: min,
+        size,
+        aspect_ratio: region_width as f32 / region_height as f32,
+        rot: 0.0,
+        marked_for_deletion: false,
+        image_data: region.into_raw().into(),
+        image_size: [region_width, region_height],
+        crop: FULL_IMAGE_CROP,
+        adjustments: ImageAdjustments::NONE,
+        animation: None,
+        opacity: 255,
+        layer: state.canvas.active_layer,
+        group: None,
+        locked: false,
+    };
+    let index = state.canvas.objects.len();
+    state
+        .history
+        .save_add_object(index, CanvasObject::Image(image.clone()));
+    state.canvas.objects.push(CanvasObject::Image(image));
+}
+
+/// Opens the image file at `path` and inserts it with its top-left corner at `pos`, returns whether it succeeded
+pub fn insert_image_file(
+    state: &mut AppState,
+    ctx: &Context,
+    path: &std::path::Path,
+    pos: Pos2,
+) -> bool {
+    // GIF 可能包含多帧，按动图插入
+    let is_gif = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+    if is_gif {
+        match utils::decode_gif_frames(path, MAX_TEXTURE_SIZE) {
+            Ok((frames, truncated)) => {
+                if truncated {
+                    state
+                        .toasts
+                        .warning(tf("动图过大，只保留了前 {} 帧!", &[&frames.len()]));
+                }
+                insert_animated_image(state, ctx, frames, pos);
+                return true;
+            }
+            Err(err) => {
+                state.toasts.error(tf("无法打开图片: {}!", &[&err]));
+                return false;
+            }
+        }
+    }
+
+    match image::open(path) {
+        Ok(img) => {
+            insert_image(state, ctx, img, pos);
+            true
+        }
+        Err(err) => {
+            state.toasts.error(tf("无法打开图片: {}!", &[&err]));
+            false
+        }
+    }
+}
+
+/// Places the image at `path` behind everything on the current page
+pub fn set_background_image(state: &mut AppState, ctx: &Context, path: &std::path::Path) {
+    match image::open(path) {
+        Ok(img) => {
+            let img = utils::resize_image_for_texture(img, MAX_TEXTURE_SIZE);
+            let fit = state
+                .canvas
+                .background_image
+                .as_ref()
+                .map(|background| background.fit)
+                .unwrap_or_default();
+            state.canvas.background_image = Some(BackgroundImage::new(ctx, img.to_rgba8(), fit));
+        }
+        Err(err) => {
+            state.toasts.error(tf("无法打开图片: {}!", &[&err]));
+        }
+    }
+}
+
 pub fn save_canvas_to_file(toasts: &mut Toasts, canvas: &CanvasState) {
     match canvas.save_to_file_with_dialog() {
         Ok(_) => {
-            toasts.success("成功保存画布!");
+            toasts.success(t("成功保存画布!"));
+        }
+        Err(err) => {
+            toasts.error(tf("画布保存失败: {}!", &[&err]));
+        }
+    }
+}
+
+pub fn save_all_pages_to_file(state: &mut AppState) {
+    let pages = all_pages(&state.canvas, &state.pages, state.current_page);
+    match board::save_board_with_dialog(&pages, state.current_page) {
+        Ok(_) => {
+            state.toasts.success(t("成功保存所有页面!"));
+        }
+        Err(err) => {
+            state.toasts.error(tf("画布保存失败: {}!", &[&err]));
+        }
+    }
+}
+
+pub fn export_canvas_to_svg(state: &mut AppState) {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter(t("SVG 矢量图"), &["svg"])
+        .set_file_name("canvas.svg")
+        .save_file()
+    else {
+        return;
+    };
+
+    match super::svg::export_svg(&state.canvas, state.persistent.canvas_color, &path) {
+        Ok(_) => {
+            state.toasts.success(t("成功导出为 SVG!"));
+        }
+        Err(err) => {
+            state.toasts.error(tf("画布导出失败: {}!", &[&err]));
+        }
+    }
+}
+
+pub fn export_selection_to_svg(state: &mut AppState) {
+    let indices: Vec<usize> = match state.selected_object_index {
+        Some(index) => vec![index],
+        None => state.selected_objects.clone(),
+    };
+    if indices.is_empty() {
+        state.toasts.error(t("未选中对象!"));
+        return;
+    }
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter(t("SVG 矢量图"), &["svg"])
+        .set_file_name("selection.svg")
+        .save_file()
+    else {
+        return;
+    };
+
+    match super::svg::export_selection_svg(&state.canvas, &indices, &path) {
+        Ok(_) => {
+            state.toasts.success(t("成功导出所选对象!"));
+        }
+        Err(err) => {
+            state.toasts.error(tf("导出失败: {}!", &[&err]));
         }
+    }
+}
+
+pub fn send_canvas_to_peer(state: &mut AppState) {
+    match state.canvas.to_bytes() {
+        Ok(bytes) => state.board_transfer.send(bytes),
         Err(err) => {
-            toasts.error(format!("画布保存失败: {}!", err));
+            state.toasts.error(tf("画布编码失败: {}!", &[&err]));
+        }
+    }
+}
+
+pub fn import_received_boards(state: &mut AppState, ctx: &Context) {
+    for event in state.board_transfer.poll() {
+        match event {
+            TransferEvent::Received { from, payload } => {
+                match CanvasState::from_bytes(&payload, ctx) {
+                    Ok(canvas) => {
+                        add_new_page_state(state);
+                        state.canvas = canvas;
+                        state.show_welcome_window = false;
+                        state.toasts.success(tf("已接收来自 {} 的画布!", &[&from]));
+                    }
+                    Err(err) => {
+                        state.toasts.error(tf("画布接收失败: {}!", &[&err]));
+                    }
+                }
+            }
+            TransferEvent::Sent(addr) => {
+                state.toasts.success(tf("成功发送画布至 {}!", &[&addr]));
+            }
+            TransferEvent::ListenFailed(err) | TransferEvent::Failed(err) => {
+                state.toasts.error(format!("{}!", err));
+            }
+            TransferEvent::Listening(_) => {}
+        }
+    }
+}
+
+// 远端添加的笔画不进入撤销历史, 以免撤销掉别人的笔画
+pub fn apply_collab_updates(state: &mut AppState) {
+    for update in state.collab.poll() {
+        match update {
+            CollabUpdate::StrokeAdded { page, stroke } => {
+                if let Some((canvas, _)) = collab_page(state, page)
+                    && collab_stroke_index(canvas, stroke.id).is_none()
+                {
+                    canvas.objects.push(CanvasObject::Stroke(stroke));
+                }
+            }
+            // 删除会改变其后对象的下标，撤销记录无法再对应当前的对象，与清空一样丢弃
+            CollabUpdate::StrokeRemoved { page, id } => {
+                let is_current_page = page == state.current_page;
+                let mut removed = false;
+                if let Some((canvas, history)) = collab_page(state, page)
+                    && let Some(index) = collab_stroke_index(canvas, id)
+                {
+                    canvas.objects.remove(index);
+                    history.clear();
+                    removed = true;
+                }
+                if removed && is_current_page {
+                    state.clear_selection();
+                }
+            }
+            CollabUpdate::StrokeMoved { page, id, delta } => {
+                if let Some((canvas, _)) = collab_page(state, page)
+                    && let Some(index) = collab_stroke_index(canvas, id)
+                {
+                    CanvasObject::move_object(&mut canvas.objects[index], delta);
+                }
+            }
+            // 远端清空同样不进入撤销历史，否则本地撤销只会在这一端恢复内容；
+            // 清空前的撤销记录已无法对应当前的对象，一并丢弃
+            CollabUpdate::CanvasCleared { page } => {
+                let is_current_page = page == state.current_page;
+                if let Some((canvas, history)) = collab_page(state, page) {
+                    canvas.objects.clear();
+                    history.clear();
+                }
+                if is_current_page {
+                    state.clear_selection();
+                }
+            }
+            CollabUpdate::Failed(err) => {
+                state.toasts.error(format!("{}!", err));
+            }
+            CollabUpdate::Disconnected => {
+                state.toasts.warning(t("与协作主机的连接已断开!"));
+            }
+        }
+    }
+}
+
+fn collab_stroke_index(canvas: &CanvasState, id: StrokeId) -> Option<usize> {
+    canvas
+        .objects
+        .iter()
+        .position(|object| matches!(object, CanvasObject::Stroke(stroke) if stroke.id == id))
+}
+
+// 协作时记下当前页的笔画，以便在撤销或重做后找出变化了的笔画
+fn collab_strokes(state: &AppState) -> Option<HashMap<StrokeId, CanvasStroke>> {
+    state.collab.role()?;
+    Some(
+        state
+            .canvas
+            .objects
+            .iter()
+            .filter_map(|object| match object {
+                CanvasObject::Stroke(stroke) => Some((stroke.id, stroke.clone())),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+// 把撤销或重做造成的笔画变化发给其他成员：整体平移的笔画发送移动，其他修改先删除再重新添加
+fn broadcast_stroke_changes(state: &AppState, before: Option<HashMap<StrokeId, CanvasStroke>>) {
+    let Some(mut before) = before else {
+        return;
+    };
+    let page = state.current_page;
+    for object in state.canvas.objects.iter() {
+        let CanvasObject::Stroke(stroke) = object else {
+            continue;
+        };
+        match before.remove(&stroke.id) {
+            None => state.collab.broadcast_stroke(page, stroke),
+            Some(old) => match stroke_translation(&old, stroke) {
+                Some(delta) if delta == egui::Vec2::ZERO => {}
+                Some(delta) => state.collab.broadcast_move(page, object, delta),
+                None => {
+                    state.collab.broadcast_removal(page, object);
+                    state.collab.broadcast_stroke(page, stroke);
+                }
+            },
+        }
+    }
+    for stroke in before.into_values() {
+        state
+            .collab
+            .broadcast_removal(page, &CanvasObject::Stroke(stroke));
+    }
+}
+
+// 笔画 new 与 old 只相差一次整体平移时返回平移量
+fn stroke_translation(old: &CanvasStroke, new: &CanvasStroke) -> Option<egui::Vec2> {
+    // 移动后再移回会留下浮点误差
+    const TOLERANCE: f32 = 1e-3;
+    let delta = *new.points.first()? - *old.points.first()?;
+    let same_points = old.points.len() == new.points.len()
+        && old
+            .points
+            .iter()
+            .zip(&new.points)
+            .all(|(&a, &b)| (b - a - delta).length() <= TOLERANCE);
+    let same_width = match (&old.width, &new.width) {
+        (StrokeWidth::Fixed(a), StrokeWidth::Fixed(b)) => a == b,
+        (StrokeWidth::Dynamic(a), StrokeWidth::Dynamic(b)) => a == b,
+        _ => false,
+    };
+    let same_look = old.color == new.color
+        && old.base_width == new.base_width
+        && old.rot == new.rot
+        && old.is_highlighter == new.is_highlighter
+        && old.line_style == new.line_style
+        && old.texture == new.texture
+        && old.style == new.style
+        && old.layer == new.layer
+        && old.group == new.group
+        && old.locked == new.locked;
+    (same_points && same_width && same_look).then_some(delta)
+}
+
+/// Most pages a remote member may add by drawing on a page this board does not have yet
+const MAX_COLLAB_PAGE_GROWTH: usize = 100;
+
+/// Canvas and history of page `page`; missing pages are appended so page numbers match the
+/// other members; `None` for implausible page numbers
+fn collab_page(state: &mut AppState, page: usize) -> Option<(&mut CanvasState, &mut History)> {
+    if page == state.current_page {
+        return Some((&mut state.canvas, &mut state.history));
+    }
+    if page >= state.pages.len() + MAX_COLLAB_PAGE_GROWTH {
+        return None;
+    }
+    if page >= state.pages.len() {
+        state.pages.resize_with(page + 1, PageState::default);
+    }
+    let page = &mut state.pages[page];
+    Some((&mut page.canvas, &mut page.history))
+}
+
+pub fn start_session_recording(state: &mut AppState) {
+    state.session_recorder = Some(SessionRecorder::start(
+        &mut state.canvas.objects,
+        state.current_tool,
+        state.persistent.canvas_color,
+    ));
+    state.toasts.info(t("开始录制!"));
+}
+
+pub fn stop_session_recording(state: &mut AppState) {
+    let Some(recorder) = state.session_recorder.take() else {
+        return;
+    };
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter(t("录制文件"), &[RECORDING_FILE_EXT])
+        .set_file_name(format!("session.{RECORDING_FILE_EXT}"))
+        .save_file()
+    else {
+        // 取消保存时继续录制
+        state.session_recorder = Some(recorder);
+        return;
+    };
+
+    match recorder.save_to_file(&path) {
+        Ok(_) => {
+            state.toasts.success(t("成功保存录制!"));
+        }
+        Err(err) => {
+            state.toasts.error(tf("录制保存失败: {}!", &[&err]));
+        }
+    }
+}
+
+pub fn open_session_replay(state: &mut AppState, ctx: &Context) {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter(t("录制文件"), &[RECORDING_FILE_EXT])
+        .pick_file()
+    else {
+        return;
+    };
+
+    match SessionPlayer::load_from_file(&path, ctx) {
+        Ok(mut player) => {
+            add_new_page_state(state);
+            player.page = state.current_page;
+            state.session_player = Some(player);
+            state.show_welcome_window = false;
+        }
+        Err(err) => {
+            state.toasts.error(tf("录制加载失败: {}!", &[&err]));
+        }
+    }
+}
+
+pub fn close_session_replay(state: &mut AppState, ctx: &Context) {
+    if state.session_player.take().is_some() {
+        apply_theme_mode_and_canvas_color(
+            ctx,
+            state.persistent.theme_mode,
+            state.persistent.canvas_color,
+        );
+    }
+}
+
+pub fn update_session_recording(state: &mut AppState, ctx: &Context) {
+    if let Some(recorder) = &mut state.session_recorder {
+        recorder.capture(
+            &mut state.canvas.objects,
+            state.current_tool,
+            state.persistent.canvas_color,
+        );
+    }
+
+    let Some(player) = &mut state.session_player else {
+        return;
+    };
+
+    // 回放页面不是当前页面时, 写入其保存的画布
+    let is_current_page = player.page == state.current_page;
+    let canvas = if is_current_page {
+        &mut state.canvas
+    } else if let Some(page) = state.pages.get_mut(player.page) {
+        &mut page.canvas
+    } else {
+        state.session_player = None;
+        return;
+    };
+
+    if player.tick(canvas) && is_current_page {
+        state.clear_selection();
+        if let Some(color) = player.canvas_color {
+            apply_theme_mode_and_canvas_color(ctx, state.persistent.theme_mode, color);
+        }
+    }
+
+    if !player.paused && !player.is_finished() {
+        ctx.request_repaint();
+    }
+}
+
+// 各页面历史记录的版本，用于判断是否有未自动保存的修改
+fn page_revisions(state: &AppState) -> Vec<u64> {
+    (0..state.pages.len())
+        .map(|i| {
+            if i == state.current_page {
+                state.history.revision()
+            } else {
+                state.pages[i].history.revision()
+            }
+        })
+        .collect()
+}
+
+// 按顺序列出所有页面的画布，当前页面的画布是 `canvas` 而不在 `pages` 中
+fn all_pages<'a>(
+    canvas: &'a CanvasState,
+    pages: &'a [PageState],
+    current_page: usize,
+) -> Vec<&'a CanvasState> {
+    (0..pages.len())
+        .map(|i| {
+            if i == current_page {
+                canvas
+            } else {
+                &pages[i].canvas
+            }
+        })
+        .collect()
+}
+
+pub fn update_autosave(state: &mut AppState, ctx: &Context) {
+    // 恢复提示处理前不覆盖上次运行留下的恢复文件
+    if state.autosave.pending_recovery {
+        return;
+    }
+    let revisions = page_revisions(state);
+    if !state.autosave.is_dirty(&revisions) {
+        return;
+    }
+    let remaining = AUTOSAVE_INTERVAL.saturating_sub(state.autosave.last_save.elapsed());
+    if !remaining.is_zero() {
+        // 空闲时没有重绘，到时间后唤醒一次以完成保存
+        ctx.request_repaint_after(remaining);
+        return;
+    }
+
+    write_autosave(state);
+}
+
+/// Writes every page to the recovery file right away
+pub fn write_autosave(state: &mut AppState) {
+    let revisions = page_revisions(state);
+    let pages = all_pages(&state.canvas, &state.pages, state.current_page);
+    if let Err(err) = state.autosave.save(&pages, state.current_page, revisions) {
+        eprintln!("autosave failed: {err}");
+        state.toasts.error(tf("自动保存失败: {}!", &[&err]));
+    }
+}
+
+pub fn restore_autosave(state: &mut AppState, ctx: &Context) {
+    match Autosaver::load(ctx) {
+        Ok((canvases, current_page)) => {
+            state.pages = canvases
+                .into_iter()
+                .map(|canvas| PageState {
+                    canvas,
+                    history: Default::default(),
+                })
+                .collect();
+            state.current_page = current_page;
+            state.canvas = std::mem::take(&mut state.pages[current_page].canvas);
+            state.history = std::mem::take(&mut state.pages[current_page].history);
+            clear_interaction_state(state);
+            state.show_welcome_window = false;
+            state.autosave.pending_recovery = false;
+            let revisions = page_revisions(state);
+            state.autosave.mark_saved(revisions);
+            state.toasts.success(t("成功恢复自动保存的画板!"));
+        }
+        Err(err) => {
+            state.autosave.discard();
+            state.toasts.error(tf("恢复自动保存失败: {}!", &[&err]));
         }
     }
 }
@@ -140,7 +1260,10 @@ pub fn save_canvas_to_file(toasts: &mut Toasts, canvas: &CanvasState) {
 pub fn setup_fonts(ctx: &mut Context) {
     let mut fonts = FontDefinitions::default();
 
-    let font_bytes = assets::font_bytes();
+    // 没有可用的 CJK 字体时使用 egui 的默认字体，中文将无法显示
+    let Some(font_bytes) = assets::font_bytes() else {
+        return;
+    };
     let font_name = "cjk_font";
     fonts.font_data.insert(
         font_name.to_owned(),